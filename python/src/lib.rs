@@ -0,0 +1,106 @@
+//! The `rustparser` Python module: the sentence splitter and line cleaning
+//! that ingestion runs, so text split in a notebook comes out the same as
+//! the sentences stored in the database.
+//!
+//! Built and installed into the current environment with `maturin develop
+//! --release` from this directory, or as a wheel with `maturin build`.
+//!
+//! ```python
+//! import rustparser
+//!
+//! line = rustparser.clean("“The cat sat down.” Then it slept all day.")
+//! rustparser.split_into_sentences(line)
+//! # ['The cat sat down.', 'Then it slept all day.']
+//! ```
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use rustparser::clean::{
+    normalize_invisible, normalize_unicode, CleaningPreset, InvisibleChars, UnicodeForm,
+};
+use rustparser::segment::SegmenterKind;
+use rustparser::{Segmenter, ValidityRules};
+
+/// Parses an option given as a string the way the command line does.
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> PyResult<T> {
+    value.parse().map_err(PyValueError::new_err)
+}
+
+/// Splits `text` into sentences, with the segmenter and validity rules of
+/// `rustparser ingest` and its defaults.
+#[pyfunction]
+#[pyo3(signature = (
+    text,
+    *,
+    segmenter = "rules",
+    min_words = 3,
+    min_chars = 10,
+    max_words = None,
+    max_chars = None,
+    max_digit_ratio = None,
+    reject_number_units = false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn split_into_sentences(
+    py: Python<'_>,
+    text: &str,
+    segmenter: &str,
+    min_words: usize,
+    min_chars: usize,
+    max_words: Option<usize>,
+    max_chars: Option<usize>,
+    max_digit_ratio: Option<f64>,
+    reject_number_units: bool,
+) -> PyResult<Vec<String>> {
+    let kind: SegmenterKind = parse(segmenter)?;
+    let rules = ValidityRules {
+        min_words,
+        min_chars,
+        max_words,
+        max_chars,
+        max_digit_ratio,
+        reject_number_units,
+    };
+    let segmenter = Segmenter::with_backend(rules, kind.backend());
+    Ok(py.allow_threads(|| segmenter.split(text)))
+}
+
+/// Cleans a line as ingestion does before splitting it: invisible
+/// characters, then Unicode normalization, then the cleaning preset's steps.
+#[pyfunction]
+#[pyo3(signature = (
+    text,
+    *,
+    preset = "strict",
+    invisible_chars = "normalize",
+    unicode_form = "none",
+    fold_quotes = false,
+))]
+fn clean(
+    py: Python<'_>,
+    text: &str,
+    preset: &str,
+    invisible_chars: &str,
+    unicode_form: &str,
+    fold_quotes: bool,
+) -> PyResult<String> {
+    let pipeline = parse::<CleaningPreset>(preset)?.pipeline();
+    let invisible: InvisibleChars = parse(invisible_chars)?;
+    let form: UnicodeForm = parse(unicode_form)?;
+    Ok(py.allow_threads(|| {
+        let mut line = normalize_invisible(text, invisible);
+        if form != UnicodeForm::None || fold_quotes {
+            line = normalize_unicode(&line, form, fold_quotes);
+        }
+        pipeline.apply(&line).trim().to_string()
+    }))
+}
+
+#[pymodule]
+#[pyo3(name = "rustparser")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(split_into_sentences, module)?)?;
+    module.add_function(wrap_pyfunction!(clean, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! Reading tar and zip archives member by member.
+//!
+//! Members are read on a background thread and handed over one at a time, so
+//! memory use is bounded by the largest member rather than the archive.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::sync::mpsc;
+
+use crate::decompress::decompress;
+use crate::format::InputFormat;
+use crate::source::{CorpusSource, Member};
+use crate::BoxError;
+
+/// How many bytes are checked for NULs when deciding whether a member is text.
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// A tar file, optionally gzip, zstd or bzip2 compressed.
+    Tar,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// Recognises archives by extension: `.tar`, `.tar.gz`, `.tgz`, `.tar.zst`,
+    /// `.tar.bz2` and `.zip`.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let lower = path.to_ascii_lowercase();
+        if lower.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if [".tar", ".tar.gz", ".tgz", ".tar.zst", ".tar.bz2"]
+            .iter()
+            .any(|ext| lower.ends_with(ext))
+        {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// A local archive whose text members are ingested as separate documents,
+/// each under its member name.
+pub struct ArchiveSource {
+    path: String,
+    kind: ArchiveKind,
+}
+
+impl ArchiveSource {
+    pub fn new(path: impl Into<String>, kind: ArchiveKind) -> Self {
+        ArchiveSource {
+            path: path.into(),
+            kind,
+        }
+    }
+}
+
+impl CorpusSource for ArchiveSource {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn path(&self) -> Option<&std::path::Path> {
+        Some(std::path::Path::new(&self.path))
+    }
+
+    fn open(&self) -> Result<Box<dyn std::io::BufRead + Send>, BoxError> {
+        Err(format!("{} is an archive and is read by member", self.path).into())
+    }
+
+    fn members(&self) -> Option<Box<dyn Iterator<Item = Result<Member, BoxError>> + Send>> {
+        // One member in flight keeps the reader just ahead of ingestion
+        let (tx, rx) = mpsc::sync_channel(1);
+        let path = self.path.clone();
+        let kind = self.kind;
+
+        std::thread::spawn(move || {
+            let result = match kind {
+                ArchiveKind::Tar => read_tar(&path, &tx),
+                ArchiveKind::Zip => read_zip(&path, &tx),
+            };
+            if let Err(e) = result {
+                let _ = tx.send(Err(format!("Error reading archive {}: {}", path, e).into()));
+            }
+        });
+
+        Some(Box::new(rx.into_iter()))
+    }
+}
+
+type MemberSender = mpsc::SyncSender<Result<Member, BoxError>>;
+
+fn read_tar(path: &str, tx: &MemberSender) -> Result<(), BoxError> {
+    let reader = decompress(Box::new(BufReader::new(File::open(path)?)))?;
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if !send_member(tx, name, &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_zip(path: &str, tx: &MemberSender) -> Result<(), BoxError> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+        let name = file.name()?.into_owned();
+        if !send_member(tx, name, &mut file)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a member and passes it on if it looks like text. Returns `false`
+/// once the receiving side has gone away.
+fn send_member(tx: &MemberSender, name: String, reader: &mut dyn Read) -> Result<bool, BoxError> {
+    if is_hidden(&name) {
+        return Ok(true);
+    }
+
+    let mut raw = Vec::new();
+    reader.read_to_end(&mut raw)?;
+    // Members may themselves be compressed (`book.txt.gz` inside a tar)
+    let mut data = Vec::new();
+    decompress(Box::new(std::io::Cursor::new(raw)))?.read_to_end(&mut data)?;
+
+    // Binary members are skipped unless a format knows how to read them.
+    // UTF-16 text is full of NULs too, but starts with a BOM.
+    if InputFormat::detect(&name) == InputFormat::Text
+        && encoding_rs::Encoding::for_bom(&data).is_none()
+        && data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0)
+    {
+        return Ok(true);
+    }
+    Ok(tx.send(Ok(Member { name, data })).is_ok())
+}
+
+/// Dotfiles and macOS resource forks (`__MACOSX/`) aren't corpus text.
+fn is_hidden(name: &str) -> bool {
+    name.split('/')
+        .any(|part| part.starts_with('.') || part == "__MACOSX")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn member_names(source: &ArchiveSource) -> Vec<(String, String)> {
+        source
+            .members()
+            .unwrap()
+            .map(|m| {
+                let m = m.unwrap();
+                (m.name, String::from_utf8(m.data).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_archives_by_extension() {
+        assert_eq!(
+            ArchiveKind::from_path("dump.tar.gz"),
+            Some(ArchiveKind::Tar)
+        );
+        assert_eq!(ArchiveKind::from_path("dump.TGZ"), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::from_path("books.zip"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::from_path("book.txt.gz"), None);
+    }
+
+    #[test]
+    fn reads_text_members_of_a_tar_gz() {
+        let path = std::env::temp_dir().join("rustparser_archive_test.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::fast(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, data) in [
+            ("books/one.txt", &b"The first book is short."[..]),
+            ("books/.DS_Store", &b"junk"[..]),
+            ("books/cover.png", &b"\x89PNG\0\0\0"[..]),
+            ("books/two.txt", &b"The second book is shorter."[..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let source = ArchiveSource::new(path.to_str().unwrap(), ArchiveKind::Tar);
+        assert_eq!(
+            member_names(&source),
+            vec![
+                (
+                    "books/one.txt".to_string(),
+                    "The first book is short.".to_string()
+                ),
+                (
+                    "books/two.txt".to_string(),
+                    "The second book is shorter.".to_string()
+                ),
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reads_text_members_of_a_zip() {
+        let path = std::env::temp_dir().join("rustparser_archive_test.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("letters/", options).unwrap();
+        writer.start_file("letters/a.txt", options).unwrap();
+        writer.write_all(b"Dear reader, hello.").unwrap();
+        writer
+            .start_file("__MACOSX/letters/._a.txt", options)
+            .unwrap();
+        writer.write_all(b"resource fork").unwrap();
+        writer.finish().unwrap();
+
+        let source = ArchiveSource::new(path.to_str().unwrap(), ArchiveKind::Zip);
+        assert_eq!(
+            member_names(&source),
+            vec![(
+                "letters/a.txt".to_string(),
+                "Dear reader, hello.".to_string()
+            )]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! Progress records that let an interrupted ingestion carry on where it
+//! stopped instead of starting over.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::BoxError;
+
+/// How far ingestion of one source got. Reading resumes after `line`, a line
+/// at which no paragraph was left open, with the state the source was in
+/// there; the first `written_after` sentences from the lines after it were
+/// already written and are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub line: usize,
+    pub written_after: usize,
+    /// Sentences written from the source so far.
+    pub sentences: usize,
+    /// Every sentence of the source was written.
+    pub done: bool,
+    pub doc_id: String,
+    pub paragraph_id: String,
+    pub paragraph_index: usize,
+    pub sentence_index: usize,
+    pub in_paragraph: bool,
+    pub previous_indent: usize,
+}
+
+/// A JSON file recording the [`Progress`] of every source of a run, by
+/// name. It's rewritten as a whole after each batch, through a temporary
+/// file so a crash mid-write leaves the previous version. Shared by every
+/// worker of the run.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    sources: Mutex<BTreeMap<String, Progress>>,
+}
+
+impl Checkpoint {
+    /// Starts a new record at `path`, replacing any there once it's saved.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Checkpoint {
+            path: path.into(),
+            sources: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Reads the record at `path` to resume from, or starts a new one if
+    /// there is none.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, BoxError> {
+        let path = path.into();
+        let sources = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error reading checkpoint {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(format!("Error reading checkpoint {}: {}", path.display(), e).into())
+            }
+        };
+        Ok(Checkpoint {
+            path,
+            sources: Mutex::new(sources),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The recorded progress of `source`, if it was started.
+    pub fn progress(&self, source: &str) -> Option<Progress> {
+        self.sources
+            .lock()
+            .expect("checkpoint poisoned")
+            .get(source)
+            .cloned()
+    }
+
+    /// Records `progress` for `source` and writes the file.
+    pub fn save(&self, source: &str, progress: Progress) -> Result<(), BoxError> {
+        let mut sources = self.sources.lock().expect("checkpoint poisoned");
+        sources.insert(source.to_string(), progress);
+        let json = serde_json::to_string_pretty(&*sources)?;
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, json)
+            .and_then(|_| std::fs::rename(&temporary, &self.path))
+            .map_err(|e| format!("Error writing checkpoint {}: {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_progress() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let checkpoint = Checkpoint::new(&path);
+        let progress = Progress {
+            line: 40,
+            written_after: 2,
+            sentences: 75,
+            ..Progress::default()
+        };
+        checkpoint.save("a.txt", progress.clone()).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.progress("a.txt"), Some(progress));
+        assert_eq!(loaded.progress("b.txt"), None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(Checkpoint::load(&path).unwrap().progress("a.txt").is_none());
+    }
+}
@@ -0,0 +1,782 @@
+//! Line normalization that runs before segmentation, and tidying of the
+//! sentences that come out of it.
+
+use regex::Regex;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::BoxError;
+
+/// How invisible characters (soft hyphens, zero-width spaces, byte order marks)
+/// are treated before a line is cleaned and split.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvisibleChars {
+    /// Leave them in place for the cleaning regex to deal with.
+    Keep,
+    /// Remove all of them.
+    Strip,
+    /// Remove soft hyphens, joiners and BOMs, but turn zero-width spaces into
+    /// ordinary spaces so the words they separate stay apart.
+    Normalize,
+}
+
+impl FromStr for InvisibleChars {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "keep" => Ok(InvisibleChars::Keep),
+            "strip" => Ok(InvisibleChars::Strip),
+            "normalize" => Ok(InvisibleChars::Normalize),
+            other => Err(format!(
+                "invalid value '{}' (expected keep, strip or normalize)",
+                other
+            )),
+        }
+    }
+}
+
+/// Applies `mode` to the invisible characters in `text`.
+pub fn normalize_invisible(text: &str, mode: InvisibleChars) -> String {
+    if mode == InvisibleChars::Keep {
+        return text.to_string();
+    }
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // Zero-width space, as one space for a run of them and none
+            // next to whitespace already there
+            '\u{200B}' if mode == InvisibleChars::Normalize => {
+                let spaced = normalized.ends_with(char::is_whitespace)
+                    || chars.peek().is_some_and(|next| next.is_whitespace());
+                if !spaced {
+                    normalized.push(' ');
+                }
+            }
+            // Soft hyphen, zero-width space, non-joiner, joiner, word joiner, BOM
+            '\u{00AD}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => {}
+            _ => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Unicode normalization form applied to lines before cleaning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnicodeForm {
+    /// Leave text as decoded.
+    #[default]
+    None,
+    /// Canonical composition, so "e" followed by a combining accent and a
+    /// precomposed "é" are stored the same way.
+    Nfc,
+    /// Compatibility composition, which also turns full-width letters,
+    /// ligatures and superscripts into their plain forms.
+    Nfkc,
+}
+
+impl FromStr for UnicodeForm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(UnicodeForm::None),
+            "nfc" => Ok(UnicodeForm::Nfc),
+            "nfkc" => Ok(UnicodeForm::Nfkc),
+            other => Err(format!(
+                "invalid normalization form '{}' (expected none, nfc or nfkc)",
+                other
+            )),
+        }
+    }
+}
+
+/// Applies `form` to `text`. With `fold_quotes`, curly quotes and
+/// apostrophes also become their straight ASCII forms.
+pub fn normalize_unicode(text: &str, form: UnicodeForm, fold_quotes: bool) -> String {
+    let text: String = match form {
+        UnicodeForm::None => text.to_string(),
+        UnicodeForm::Nfc => text.nfc().collect(),
+        UnicodeForm::Nfkc => text.nfkc().collect(),
+    };
+    if !fold_quotes {
+        return text;
+    }
+    text.chars()
+        .map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => '\'',
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => '"',
+            c => c,
+        })
+        .collect()
+}
+
+/// Pattern of characters removed from every line unless configured otherwise.
+pub const DEFAULT_CLEANING_REGEX: &str = r"[^a-zA-Z0-9\s.!?]";
+
+/// One step of a [`CleaningPipeline`].
+#[derive(Debug, Clone)]
+pub enum CleaningStep {
+    /// Resolves HTML entities such as `&amp;`, `&quot;` and `&#8217;` into
+    /// the characters they stand for.
+    DecodeEntities,
+    /// Removes control characters; tabs become spaces.
+    StripControl,
+    /// Turns every run of whitespace into a single space.
+    CollapseWhitespace,
+    /// Replaces every match of `regex` with `replacement`, which may refer to
+    /// capture groups as `$1` or `${name}`.
+    Replace { regex: Regex, replacement: String },
+}
+
+impl CleaningStep {
+    /// A step removing every match of `pattern`.
+    pub fn remove(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(CleaningStep::Replace {
+            regex: Regex::new(pattern)?,
+            replacement: String::new(),
+        })
+    }
+
+    /// Builds a step from its name in a config file: `decode_entities`,
+    /// `strip_control`, `collapse_whitespace`, or `replace`, which needs a
+    /// `pattern`.
+    pub fn from_config(
+        name: &str,
+        pattern: Option<&str>,
+        replacement: &str,
+    ) -> Result<Self, BoxError> {
+        match (name, pattern) {
+            ("decode_entities", None) => Ok(CleaningStep::DecodeEntities),
+            ("strip_control", None) => Ok(CleaningStep::StripControl),
+            ("collapse_whitespace", None) => Ok(CleaningStep::CollapseWhitespace),
+            ("replace", Some(pattern)) => Ok(CleaningStep::Replace {
+                regex: Regex::new(pattern)?,
+                replacement: replacement.to_string(),
+            }),
+            ("replace", None) => Err("cleaning step 'replace' needs a pattern".into()),
+            ("decode_entities" | "strip_control" | "collapse_whitespace", Some(_)) => {
+                Err(format!("cleaning step '{}' takes no pattern", name).into())
+            }
+            (other, _) => Err(format!(
+                "invalid cleaning step '{}' (expected decode_entities, strip_control, collapse_whitespace or replace)",
+                other
+            )
+            .into()),
+        }
+    }
+
+    fn apply(&self, text: String) -> String {
+        match self {
+            CleaningStep::DecodeEntities => {
+                if text.contains('&') {
+                    html_escape::decode_html_entities(&text).into_owned()
+                } else {
+                    text
+                }
+            }
+            CleaningStep::StripControl => text
+                .chars()
+                .filter_map(|c| match c {
+                    '\t' => Some(' '),
+                    c if c.is_control() => None,
+                    c => Some(c),
+                })
+                .collect(),
+            CleaningStep::CollapseWhitespace => {
+                let mut collapsed = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if !c.is_whitespace() {
+                        collapsed.push(c);
+                    } else if !collapsed.ends_with(' ') {
+                        collapsed.push(' ');
+                    }
+                }
+                collapsed
+            }
+            CleaningStep::Replace { regex, replacement } => {
+                regex.replace_all(&text, replacement.as_str()).into_owned()
+            }
+        }
+    }
+}
+
+/// The cleaning steps run on every line, in order. The default is
+/// [`CleaningPreset::Strict`], which removes [`DEFAULT_CLEANING_REGEX`].
+#[derive(Debug, Clone)]
+pub struct CleaningPipeline {
+    steps: Vec<CleaningStep>,
+}
+
+impl CleaningPipeline {
+    pub fn new(steps: Vec<CleaningStep>) -> Self {
+        CleaningPipeline { steps }
+    }
+
+    pub fn steps(&self) -> &[CleaningStep] {
+        &self.steps
+    }
+
+    /// Runs every step over `text`.
+    pub fn apply(&self, text: &str) -> String {
+        self.steps
+            .iter()
+            .fold(text.to_string(), |text, step| step.apply(text))
+    }
+
+    /// Like [`CleaningPipeline::apply`], but leaves every occurrence of the
+    /// `kept` strings as it is, such as redaction placeholders.
+    pub fn apply_keeping(&self, text: &str, kept: &[&str]) -> String {
+        let mut cleaned = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some((start, token)) = kept
+            .iter()
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| rest.find(token).map(|start| (start, *token)))
+            .min()
+        {
+            cleaned.push_str(&self.apply(&rest[..start]));
+            cleaned.push_str(token);
+            rest = &rest[start + token.len()..];
+        }
+        cleaned.push_str(&self.apply(rest));
+        cleaned
+    }
+}
+
+impl Default for CleaningPipeline {
+    fn default() -> Self {
+        CleaningPreset::Strict.pipeline()
+    }
+}
+
+/// Ready-made cleaning pipelines for common kinds of corpus. Each starts by
+/// decoding HTML entities, so they are filtered as the characters they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleaningPreset {
+    /// Only ASCII letters, digits and `.!?` survive; the default.
+    Strict,
+    /// Also keeps apostrophes, quotes, commas, hyphens, brackets and `;:`,
+    /// after turning curly quotes straight, so "don't" stays intact.
+    PunctuationPreserving,
+    /// Keeps letters, marks, digits and punctuation in any script, including
+    /// accented letters, and tidies control characters and whitespace.
+    Unicode,
+}
+
+impl CleaningPreset {
+    pub fn pipeline(self) -> CleaningPipeline {
+        let step = |pattern: &str, replacement: &str| CleaningStep::Replace {
+            regex: Regex::new(pattern).expect("valid regex"),
+            replacement: replacement.to_string(),
+        };
+        CleaningPipeline::new(match self {
+            CleaningPreset::Strict => vec![
+                CleaningStep::DecodeEntities,
+                step(DEFAULT_CLEANING_REGEX, ""),
+            ],
+            CleaningPreset::PunctuationPreserving => vec![
+                CleaningStep::DecodeEntities,
+                step("[\u{2018}\u{2019}]", "'"),
+                step("[\u{201C}\u{201D}]", "\""),
+                step(r#"[^a-zA-Z0-9\s.,!?;:'"()\-]"#, ""),
+            ],
+            CleaningPreset::Unicode => vec![
+                CleaningStep::DecodeEntities,
+                CleaningStep::StripControl,
+                step(r"[^\p{L}\p{M}\p{N}\p{P}\s]", ""),
+                CleaningStep::CollapseWhitespace,
+            ],
+        })
+    }
+}
+
+impl FromStr for CleaningPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" => Ok(CleaningPreset::Strict),
+            "punctuation-preserving" | "punctuation" => Ok(CleaningPreset::PunctuationPreserving),
+            "unicode" => Ok(CleaningPreset::Unicode),
+            other => Err(format!(
+                "invalid cleaning preset '{}' (expected strict, punctuation-preserving or unicode)",
+                other
+            )),
+        }
+    }
+}
+
+/// Normalizes invisible characters, then runs `pipeline`.
+pub fn clean_line(line: &str, pipeline: &CleaningPipeline, invisible: InvisibleChars) -> String {
+    let cleaned = normalize_invisible(line, invisible);
+    pipeline.apply(&cleaned).trim().to_string()
+}
+
+/// How far past the last match [`Alignment`] looks for a character, which is
+/// as much text as one cleaning step is expected to delete in a row.
+const ALIGNMENT_WINDOW: usize = 256;
+
+/// Maps cleaned text back to the text it was cleaned from, so a sentence can
+/// be stored with its original wording.
+#[derive(Debug)]
+pub struct Alignment {
+    /// For every byte of the cleaned text, the span of the raw text its
+    /// character was matched with.
+    spans: Vec<(usize, usize)>,
+}
+
+impl Alignment {
+    /// Cleaning mostly deletes characters, so each character of `cleaned` is
+    /// matched with the next equal one in `raw`, any whitespace matching any
+    /// other. One that isn't found nearby, such as text put in by a
+    /// replacement, is matched with nothing.
+    pub fn new(raw: &str, cleaned: &str) -> Self {
+        let mut spans = Vec::with_capacity(cleaned.len() + 1);
+        let mut cursor = 0;
+        for c in cleaned.chars() {
+            let found = raw[cursor..]
+                .char_indices()
+                .take(ALIGNMENT_WINDOW)
+                .find(|&(_, r)| r == c || (r.is_whitespace() && c.is_whitespace()));
+            let span = match found {
+                Some((offset, r)) => {
+                    let start = cursor + offset;
+                    cursor = start + r.len_utf8();
+                    (start, cursor)
+                }
+                None => (cursor, cursor),
+            };
+            spans.extend(std::iter::repeat_n(span, c.len_utf8()));
+        }
+        spans.push((cursor, cursor));
+        Alignment { spans }
+    }
+
+    /// The span of `raw` that `cleaned[range]` came from, widened to the
+    /// whole words at either end so quotes and letters the cleaning removed
+    /// there are kept.
+    pub fn raw_range(&self, raw: &str, range: Range<usize>) -> Range<usize> {
+        let Range { mut start, mut end } = self.raw_span(range);
+        while let Some(c) = raw[..start].chars().next_back() {
+            if c.is_whitespace() {
+                break;
+            }
+            start -= c.len_utf8();
+        }
+        while let Some(c) = raw[end..].chars().next() {
+            if c.is_whitespace() {
+                break;
+            }
+            end += c.len_utf8();
+        }
+        start..end
+    }
+
+    /// The span of the raw text that `cleaned[range]` was matched with,
+    /// without widening it.
+    pub fn raw_span(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.spans[range.start].0;
+        let end = match range.end.checked_sub(1) {
+            Some(last) if !range.is_empty() => self.spans[last].1,
+            _ => start,
+        };
+        start..end
+    }
+}
+
+/// Shortens runs of the same punctuation character ("!!!!!", "---") to at most
+/// `max_run` characters. Runs on stored sentences only, after splitting, so
+/// boundary detection still sees the original text.
+pub fn collapse_punctuation(sentence: &str, max_run: usize) -> String {
+    let max_run = max_run.max(1);
+    let mut collapsed = String::with_capacity(sentence.len());
+    let mut previous = None;
+    let mut run = 0;
+
+    for c in sentence.chars() {
+        if previous == Some(c) && c.is_ascii_punctuation() {
+            run += 1;
+        } else {
+            run = 1;
+        }
+        previous = Some(c);
+        if run <= max_run {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Rejoins words that a line break split with a hyphen ("inter-" "national").
+/// Runs on raw lines, before cleaning removes the hyphen.
+///
+/// Without a dictionary every split followed by a lower-case letter is
+/// merged. With one, a split is only kept as a hyphenated compound
+/// ("well-" "known") when the dictionary knows the hyphenated form or both
+/// halves but not the merged word.
+#[derive(Debug, Clone, Default)]
+pub struct Dehyphenator {
+    dictionary: Option<HashSet<String>>,
+}
+
+impl Dehyphenator {
+    /// Uses `words` to tell broken words from compounds.
+    pub fn with_dictionary<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let dictionary = self.dictionary.get_or_insert_with(HashSet::new);
+        for word in words {
+            let word = word.as_ref().trim();
+            if !word.is_empty() {
+                dictionary.insert(word.to_lowercase());
+            }
+        }
+        self
+    }
+
+    /// Reads the dictionary from `path`, one word per line. Blank lines and
+    /// lines starting with `#` are skipped.
+    pub fn with_dictionary_file(self, path: &str) -> Result<Self, BoxError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading hyphenation dictionary {}: {}", path, e))?;
+        Ok(self.with_dictionary(
+            contents
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#')),
+        ))
+    }
+
+    /// If `line` ends in a hyphenated fragment that `next` continues, moves
+    /// the rejoined word onto `line`, or onto `next` when its tail is all
+    /// `next` holds. Capitalised continuations ("Anglo-" "Saxon") keep their
+    /// hyphen.
+    pub fn rejoin(&self, line: &mut String, next: &mut String) {
+        let Some(before) = line.trim_end().strip_suffix('-') else {
+            return;
+        };
+        let head_len: usize = before
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphabetic())
+            .map(char::len_utf8)
+            .sum();
+        let rest = next.trim_start();
+        let tail_len: usize = rest
+            .chars()
+            .take_while(|c| c.is_alphabetic())
+            .map(char::len_utf8)
+            .sum();
+        if head_len == 0 || tail_len == 0 {
+            return;
+        }
+
+        let (prefix, head) = before.split_at(before.len() - head_len);
+        let (tail, remainder) = rest.split_at(tail_len);
+        let word = if self.is_broken_word(head, tail) {
+            format!("{}{}", head, tail)
+        } else {
+            format!("{}-{}", head, tail)
+        };
+        // Neither line may end up blank, which would end the paragraph
+        let (new_line, new_next) = if !remainder.trim().is_empty() {
+            (
+                format!("{}{}", prefix, word),
+                remainder.trim_start().to_string(),
+            )
+        } else if !prefix.trim().is_empty() {
+            (
+                prefix.trim_end().to_string(),
+                format!("{}{}", word, remainder),
+            )
+        } else {
+            return;
+        };
+        *line = new_line;
+        *next = new_next;
+    }
+
+    fn is_broken_word(&self, head: &str, tail: &str) -> bool {
+        if tail.starts_with(char::is_uppercase) {
+            return false;
+        }
+        let Some(dictionary) = &self.dictionary else {
+            return true;
+        };
+        let (head, tail) = (head.to_lowercase(), tail.to_lowercase());
+        if dictionary.contains(&format!("{}{}", head, tail)) {
+            return true;
+        }
+        let compound = dictionary.contains(&format!("{}-{}", head, tail))
+            || (dictionary.contains(&head) && dictionary.contains(&tail));
+        !compound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::segment::{split_into_sentences, ValidityRules};
+    use std::io::{BufRead, BufReader, Cursor};
+
+    fn cleaning_regex() -> CleaningPipeline {
+        CleaningPipeline::default()
+    }
+
+    fn word_counts(sentences: &[String]) -> Vec<usize> {
+        sentences
+            .iter()
+            .map(|s| s.split_whitespace().count())
+            .collect()
+    }
+
+    #[test]
+    fn strips_bom_at_file_start() {
+        let input =
+            "\u{FEFF}The first line starts with a byte order mark.\nThe second line does not.\n";
+        let re = cleaning_regex();
+
+        let lines: Vec<String> = BufReader::new(Cursor::new(input))
+            .lines()
+            .map(|l| clean_line(&l.unwrap(), &re, InvisibleChars::Normalize))
+            .collect();
+
+        assert_eq!(lines[0], "The first line starts with a byte order mark.");
+        assert_eq!(lines[1], "The second line does not.");
+        assert_eq!(
+            word_counts(&split_into_sentences(&lines[0], &ValidityRules::default())),
+            vec![9]
+        );
+    }
+
+    #[test]
+    fn zero_width_spaces_separate_words_when_normalized() {
+        let line = "The quick\u{200B}brown fox jumps. It lands\u{200B}\u{200B}softly on the grass.";
+        let cleaned = clean_line(line, &cleaning_regex(), InvisibleChars::Normalize);
+        let sentences = split_into_sentences(&cleaned, &ValidityRules::default());
+
+        assert_eq!(
+            sentences,
+            vec![
+                "The quick brown fox jumps.",
+                "It lands softly on the grass."
+            ]
+        );
+        assert_eq!(word_counts(&sentences), vec![5, 6]);
+        assert_eq!(
+            normalize_invisible(
+                "soft \u{200B}landing\u{200B} here",
+                InvisibleChars::Normalize
+            ),
+            "soft landing here"
+        );
+    }
+
+    #[test]
+    fn zero_width_spaces_are_removed_when_stripped() {
+        let line = "The quick\u{200B}brown fox jumps over the dog.";
+        let cleaned = clean_line(line, &cleaning_regex(), InvisibleChars::Strip);
+
+        assert_eq!(cleaned, "The quickbrown fox jumps over the dog.");
+        assert_eq!(cleaned.split_whitespace().count(), 7);
+    }
+
+    #[test]
+    fn soft_hyphens_are_removed() {
+        let line = "An inter\u{00AD}national team met on Monday.";
+
+        for mode in [InvisibleChars::Strip, InvisibleChars::Normalize] {
+            let cleaned = clean_line(line, &cleaning_regex(), mode);
+            assert_eq!(cleaned, "An international team met on Monday.");
+        }
+    }
+
+    #[test]
+    fn keep_leaves_text_untouched() {
+        let line = "\u{FEFF}soft\u{00AD}hyphen and zero\u{200B}width";
+        assert_eq!(normalize_invisible(line, InvisibleChars::Keep), line);
+    }
+
+    #[test]
+    fn applies_cleaning_regexes_in_order() {
+        let regexes = CleaningPipeline::new(vec![
+            CleaningStep::remove(r"https?://\S+").unwrap(),
+            CleaningStep::remove(DEFAULT_CLEANING_REGEX).unwrap(),
+        ]);
+        let cleaned = clean_line(
+            "See https://example.com/a?b for details.",
+            &regexes,
+            InvisibleChars::Normalize,
+        );
+        assert_eq!(cleaned, "See  for details.");
+    }
+
+    #[test]
+    fn normalizes_unicode_forms_and_quotes() {
+        let decomposed = "Cafe\u{301} \u{FF21}\u{FF22}\u{FF23} \u{FB01}ne";
+        assert_eq!(
+            normalize_unicode(decomposed, UnicodeForm::Nfc, false),
+            "Caf\u{E9} \u{FF21}\u{FF22}\u{FF23} \u{FB01}ne"
+        );
+        assert_eq!(
+            normalize_unicode(decomposed, UnicodeForm::Nfkc, false),
+            "Caf\u{E9} ABC fine"
+        );
+        assert_eq!(
+            normalize_unicode("\u{201C}It\u{2019}s\u{201D}", UnicodeForm::None, true),
+            "\"It's\""
+        );
+        assert_eq!("NFKC".parse(), Ok(UnicodeForm::Nfkc));
+        assert!("nfd".parse::<UnicodeForm>().is_err());
+    }
+
+    #[test]
+    fn runs_configured_steps_in_order() {
+        let pipeline = CleaningPipeline::new(vec![
+            CleaningStep::from_config("strip_control", None, "").unwrap(),
+            CleaningStep::from_config("replace", Some(r"(\d+)%"), "$1 percent").unwrap(),
+            CleaningStep::from_config("collapse_whitespace", None, "").unwrap(),
+        ]);
+        assert_eq!(
+            clean_line("Up\t\u{7}  40%  today.", &pipeline, InvisibleChars::Keep),
+            "Up 40 percent today."
+        );
+
+        assert!(CleaningStep::from_config("replace", None, "").is_err());
+        assert!(CleaningStep::from_config("strip_control", Some("x"), "").is_err());
+        assert!(CleaningStep::from_config("lowercase", None, "").is_err());
+    }
+
+    #[test]
+    fn aligns_cleaned_sentences_with_raw_text() {
+        let raw = "\u{201C}Don\u{2019}t go,\u{201D} she said. The caf\u{E9} was closed.";
+        let cleaned = clean_line(raw, &cleaning_regex(), InvisibleChars::Keep);
+        assert_eq!(cleaned, "Dont go she said. The caf was closed.");
+
+        let alignment = Alignment::new(raw, &cleaned);
+        let first = alignment.raw_range(raw, 0..17);
+        assert_eq!(&raw[first], "\u{201C}Don\u{2019}t go,\u{201D} she said.");
+        let second = alignment.raw_range(raw, 18..cleaned.len());
+        assert_eq!(&raw[second], "The caf\u{E9} was closed.");
+    }
+
+    #[test]
+    fn decodes_entities_before_filtering() {
+        let line = "Fish &amp; chips &quot;to go&quot; &#8212; Joe&#8217;s best.";
+        assert_eq!(
+            clean_line(line, &CleaningPipeline::default(), InvisibleChars::Keep),
+            "Fish  chips to go  Joes best."
+        );
+        assert_eq!(
+            clean_line(
+                line,
+                &CleaningPreset::PunctuationPreserving.pipeline(),
+                InvisibleChars::Keep
+            ),
+            "Fish  chips \"to go\"  Joe's best."
+        );
+    }
+
+    #[test]
+    fn presets_keep_what_they_promise() {
+        let line = "\u{201C}Don\u{2019}t\u{201D}, said Ren\u{E9}e \u{2014} twice; \u{2605}";
+        let clean =
+            |preset: CleaningPreset| clean_line(line, &preset.pipeline(), InvisibleChars::Keep);
+        assert_eq!(clean(CleaningPreset::Strict), "Dont said Rene  twice");
+        assert_eq!(
+            clean(CleaningPreset::PunctuationPreserving),
+            "\"Don't\", said Rene  twice;"
+        );
+        assert_eq!(
+            clean(CleaningPreset::Unicode),
+            "\u{201C}Don\u{2019}t\u{201D}, said Ren\u{E9}e \u{2014} twice;"
+        );
+        assert_eq!(
+            "punctuation-preserving".parse(),
+            Ok(CleaningPreset::PunctuationPreserving)
+        );
+    }
+
+    #[test]
+    fn collapses_repeated_punctuation() {
+        assert_eq!(collapse_punctuation("Wow!!!!!", 1), "Wow!");
+        assert_eq!(collapse_punctuation("Wait... what??", 1), "Wait. what?");
+        assert_eq!(collapse_punctuation("Wait..... what", 3), "Wait... what");
+        assert_eq!(
+            collapse_punctuation("No change here.", 1),
+            "No change here."
+        );
+    }
+
+    #[test]
+    fn splitting_sees_original_punctuation_before_collapse() {
+        let rules = ValidityRules::default();
+        let sentences: Vec<String> =
+            split_into_sentences("This is amazing!!!!! We won the game.", &rules)
+                .iter()
+                .map(|s| collapse_punctuation(s, 1))
+                .collect();
+
+        assert_eq!(sentences, vec!["This is amazing!", "We won the game."]);
+    }
+
+    fn rejoined(dehyphenator: &Dehyphenator, line: &str, next: &str) -> (String, String) {
+        let (mut line, mut next) = (line.to_string(), next.to_string());
+        dehyphenator.rejoin(&mut line, &mut next);
+        (line, next)
+    }
+
+    #[test]
+    fn rejoins_words_split_at_line_breaks() {
+        let plain = Dehyphenator::default();
+        assert_eq!(
+            rejoined(&plain, "an inter-", "national team met."),
+            ("an international".to_string(), "team met.".to_string())
+        );
+        assert_eq!(
+            rejoined(&plain, "the Anglo-", "Saxon Chronicle"),
+            ("the Anglo-Saxon".to_string(), "Chronicle".to_string())
+        );
+        // Only the tail on the next line: the word moves down instead
+        assert_eq!(
+            rejoined(&plain, "an inter-", "national"),
+            ("an".to_string(), "international".to_string())
+        );
+        // Dashes and numbers are not words
+        assert_eq!(
+            rejoined(&plain, "pages 10-", "12 and"),
+            ("pages 10-".to_string(), "12 and".to_string())
+        );
+    }
+
+    #[test]
+    fn dictionary_keeps_compounds() {
+        let dehyphenator =
+            Dehyphenator::default().with_dictionary(["international", "well", "known", "today"]);
+        assert_eq!(
+            rejoined(&dehyphenator, "a well-", "known fact"),
+            ("a well-known".to_string(), "fact".to_string())
+        );
+        assert_eq!(
+            rejoined(&dehyphenator, "an inter-", "national team"),
+            ("an international".to_string(), "team".to_string())
+        );
+        assert_eq!(
+            rejoined(&dehyphenator, "until to-", "day, at least"),
+            ("until today".to_string(), ", at least".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_invisible_chars_mode() {
+        assert_eq!("Strip".parse(), Ok(InvisibleChars::Strip));
+        assert_eq!("normalize".parse(), Ok(InvisibleChars::Normalize));
+        assert!("remove".parse::<InvisibleChars>().is_err());
+    }
+}
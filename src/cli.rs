@@ -0,0 +1,917 @@
+use clap::{Args, Parser, Subcommand};
+use mongodb::bson::DateTime;
+
+use rustparser::clean::{CleaningPreset, InvisibleChars, UnicodeForm};
+use rustparser::collocate::Measure;
+use rustparser::decompress::Compression;
+use rustparser::dedup::NearDuplicateAction;
+use rustparser::encoding::TextEncoding;
+use rustparser::format::{InputFormat, XmlSelector};
+use rustparser::freq::{NGRAMS_COLLECTION, WORD_FREQ_COLLECTION};
+use rustparser::logging::{LogFilter, LogFormat};
+use rustparser::redact::PiiKind;
+use rustparser::segment::SegmenterKind;
+use rustparser::sink::{Numbering, Partition, SinkKind, TeePolicy, TextIndex};
+use rustparser::tfidf::TFIDF_COLLECTION;
+use rustparser::token::Tokenizer;
+use std::str::FromStr;
+
+/// Splits text corpora into sentences and loads them into MongoDB.
+///
+/// Every flag can also be set through the environment variable named in its
+/// help text (or a `.env` file), and some through `parser.toml`. Flags given
+/// on the command line win over the environment, which wins over the file.
+#[derive(Debug, Parser)]
+#[command(name = "rustparser", version, about)]
+pub struct Cli {
+    /// MongoDB connection string
+    #[arg(long, env = "MONGODB_URI", global = true, hide_env_values = true)]
+    pub mongodb_uri: Option<String>,
+
+    /// TOML config file [default: parser.toml, if present]
+    #[arg(long, env = "PARSER_CONFIG", global = true)]
+    pub config: Option<String>,
+
+    /// MongoDB database name [default: coca_like_db]
+    #[arg(long, env = "MONGODB_DATABASE", global = true)]
+    pub database: Option<String>,
+
+    /// MongoDB collection name [default: corpus]
+    #[arg(long, env = "MONGODB_COLLECTION", global = true)]
+    pub collection: Option<String>,
+
+    /// Log more: -v for each batch written, -vv for everything
+    #[arg(long, short, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Log levels, for everything and by module, such as
+    /// info,rustparser::sink=debug; overrides --verbose and --quiet
+    /// [default: info]
+    #[arg(long, env = "LOG_LEVEL", global = true)]
+    pub log_level: Option<LogFilter>,
+
+    /// How log lines are written: text, or json with a field per value for
+    /// log collectors
+    #[arg(long, env = "LOG_FORMAT", global = true, default_value = "text")]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Split a text file into sentences and store them
+    Ingest(Box<IngestArgs>),
+    /// Keep ingesting the files dropped into a directory as they arrive,
+    /// until interrupted
+    Watch(Box<WatchArgs>),
+    /// Serve segmentation, ingestion and search as a JSON API over HTTP
+    Serve(ServeArgs),
+    /// Full-text search over the stored sentences
+    #[command(alias = "query")]
+    Search(SearchArgs),
+    /// Print corpus statistics
+    Stats(StatsArgs),
+    /// Write the stored sentences to a file, as text, JSON lines, CSV or TSV
+    Export(ExportArgs),
+    /// Delete the sentences of a source file, or those ingested before a
+    /// time, with what was derived from them
+    Purge(PurgeArgs),
+    /// Upgrade sentences stored by older versions to the current schema
+    Migrate,
+    /// Rebuild the table of word frequencies, overall and by genre
+    WordFreq(WordFreqArgs),
+    /// Rebuild the table of n-gram frequencies, overall and by genre
+    Ngrams(NgramsArgs),
+    /// Words seen near a word more often than by chance, with MI, t-score
+    /// and log-likelihood
+    Collocates(CollocatesArgs),
+    /// Rebuild the TF-IDF weights of the words of each source file
+    Tfidf(TfidfArgs),
+    /// Keyword-in-context lines for the sentences matching a term
+    Kwic(KwicArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct IngestArgs {
+    /// Text file, directory, glob such as `corpus/**/*.txt`, http(s) URL,
+    /// `s3://` or `gs://` bucket prefix, or `-` for stdin. gzip, zstd and
+    /// bzip2 input is decompressed on the fly
+    #[arg(env = "INPUT_FILE_PATH", required_unless_present = "input_flag")]
+    input: Option<String>,
+
+    /// Same as the positional INPUT
+    #[arg(long = "input", value_name = "INPUT")]
+    input_flag: Option<String>,
+
+    /// Input format: text, html, pdf, epub, docx, markdown, csv, tsv, jsonl or xml
+    /// [default: from each file's extension]
+    #[arg(long, env = "INPUT_FORMAT")]
+    pub format: Option<InputFormat>,
+
+    /// Also ingest DOCX headers, footers, footnotes and endnotes
+    #[arg(long, env = "DOCX_EXTRAS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub docx_extras: bool,
+
+    /// Drop Markdown code blocks instead of ingesting their lines
+    #[arg(long, env = "MARKDOWN_SKIP_CODE", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub markdown_skip_code: bool,
+
+    /// CSV/TSV column or JSONL field holding the text; the others are stored as fields
+    #[arg(long, env = "TEXT_FIELD", default_value = "text")]
+    pub text_field: String,
+
+    /// XML elements holding the text, e.g. `p`, `s` or `div[@type='chapter']/p`
+    #[arg(long, env = "XML_TEXT", default_value = "p")]
+    pub xml_text: XmlSelector,
+
+    /// XML metadata field as name=selector, e.g. `author=titleStmt/author` or
+    /// `genre=text@type`; repeat for several. Looked up on the enclosing
+    /// elements first, then anywhere in the document
+    #[arg(long = "xml-meta", env = "XML_META", value_name = "NAME=SELECTOR")]
+    pub xml_metadata: Vec<String>,
+
+    /// Character encoding of text input, e.g. utf-8, latin1 or windows-1252;
+    /// `auto` honours BOMs and guesses for anything that isn't UTF-8
+    #[arg(long, env = "INPUT_ENCODING", default_value = "auto")]
+    pub encoding: TextEncoding,
+
+    /// fileName recorded for sentences read from stdin
+    #[arg(long, env = "STDIN_NAME", default_value = "stdin")]
+    pub stdin_name: String,
+
+    /// Where to write sentences, several separated by commas: mongodb,
+    /// sqlite, postgres, elasticsearch, kafka, file, stdout, jsonl, csv, tsv,
+    /// parquet or null, which throws them away [default: sqlite with --sqlite,
+    /// postgres with --postgres-url, elasticsearch with --elasticsearch-url,
+    /// kafka with --kafka-url, null with --bench, otherwise mongodb]
+    #[arg(long, env = "SINK", value_delimiter = ',')]
+    pub sink: Vec<SinkKind>,
+
+    /// Which of several sinks have to keep working for ingestion to go on:
+    /// all, first (the others are copies, left out if they fail) or any
+    #[arg(long, env = "TEE_POLICY", default_value = "all")]
+    pub tee_policy: TeePolicy,
+
+    /// Field the MongoDB text index covers: text, the cleaned sentence, or
+    /// raw, the original wording stored as rawText
+    #[arg(long, env = "TEXT_INDEX", default_value = "text")]
+    pub text_index: TextIndex,
+
+    /// Create a unique MongoDB index over textHash, so each sentence is
+    /// stored at most once; one already stored is reported as a failed write
+    #[arg(long, env = "UNIQUE_HASH", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub unique_hash: bool,
+
+    /// Skip sentences whose textHash is already in the MongoDB collection
+    /// instead of storing them again, counting them as duplicates rather
+    /// than failures; implies --unique-hash
+    #[arg(long, env = "DEDUP", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub dedup: bool,
+
+    /// Drop sentences repeated within the run before they're written, with
+    /// a Bloom filter sized for about this many distinct sentences (1.8
+    /// bytes each); about one in a thousand new sentences is wrongly dropped
+    #[arg(long, env = "DEDUP_IN_MEMORY", value_name = "SENTENCES")]
+    pub dedup_in_memory: Option<usize>,
+
+    /// Store a MinHash signature of each sentence as minHash, and flag
+    /// (with nearDuplicateOf) or skip sentences nearly the same as one
+    /// stored earlier in the run
+    #[arg(long, env = "NEAR_DUPLICATES")]
+    pub near_duplicates: Option<NearDuplicateAction>,
+
+    /// Estimated share of character shingles two sentences have in common
+    /// for one to count as a near duplicate of the other
+    #[arg(long, env = "NEAR_DUPLICATE_THRESHOLD", default_value_t = 0.8, value_parser = parse_threshold)]
+    pub near_duplicate_threshold: f64,
+
+    /// Attempts at each MongoDB, Elasticsearch or Kafka batch, and at each
+    /// read of a URL input, before giving up, retrying after connection
+    /// losses and other transient errors; 1 never retries
+    #[arg(long, env = "MAX_RETRIES", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_retries: u32,
+
+    /// Wait before the first retry of a batch or read, in milliseconds; it
+    /// doubles with each further attempt, up to 30 seconds
+    #[arg(long, env = "RETRY_BASE_MS", default_value_t = 500)]
+    pub retry_base_ms: u64,
+
+    /// Output file for the file sink, one sentence per line, or the jsonl,
+    /// csv, tsv or parquet sink
+    #[arg(long, env = "OUTPUT_FILE_PATH")]
+    pub output: Option<String>,
+
+    /// Write a file per source file or genre (file or genre) into the
+    /// --output directory, for the file, jsonl, csv, tsv and parquet sinks
+    #[arg(long, env = "PARTITION_BY")]
+    pub partition_by: Option<Partition>,
+
+    /// Number the sentences the file and stdout sinks write, before a tab:
+    /// none, sequence (from 1 in each output file) or line (the source line
+    /// each starts on)
+    #[arg(long, env = "SENTENCE_NUMBERING", default_value = "none")]
+    pub numbering: Numbering,
+
+    /// Compress the output of the file, jsonl, csv and tsv sinks with gzip,
+    /// zstd or bzip2 [default: as the file's extension says, such as
+    /// .jsonl.gz], or the pages of the parquet sink with gzip or zstd
+    #[arg(long, env = "COMPRESS")]
+    pub compress: Option<Compression>,
+
+    /// Comma-separated fields the csv, tsv and parquet sinks write, in order
+    #[arg(
+        long,
+        env = "COLUMNS",
+        value_delimiter = ',',
+        default_value = "fileName,lineNumber,text"
+    )]
+    pub columns: Vec<String>,
+
+    /// Also write each sentence document, with its metadata, as a line of
+    /// JSON to this file, alongside the sink; the jsonl sink writes here
+    /// instead of --output when it's given
+    #[arg(long, env = "JSONL_OUTPUT")]
+    pub jsonl: Option<String>,
+
+    /// SQLite database for the sqlite sink
+    #[arg(long)]
+    pub sqlite: Option<String>,
+
+    /// PostgreSQL connection string for the postgres sink, which writes
+    /// through psql
+    #[arg(long, env = "POSTGRES_URL", hide_env_values = true)]
+    pub postgres_url: Option<String>,
+
+    /// Table the postgres sink writes to
+    #[arg(long, env = "POSTGRES_TABLE", default_value = "sentences")]
+    pub postgres_table: String,
+
+    /// Text search configuration the postgres sink indexes sentences with
+    #[arg(long, env = "POSTGRES_TEXT_SEARCH", default_value = "english")]
+    pub postgres_text_search: String,
+
+    /// Elasticsearch or OpenSearch cluster URL for the elasticsearch sink
+    #[arg(long, env = "ELASTICSEARCH_URL")]
+    pub elasticsearch_url: Option<String>,
+
+    /// Index the elasticsearch sink writes to
+    #[arg(long, env = "ELASTICSEARCH_INDEX", default_value = "corpus")]
+    pub elasticsearch_index: String,
+
+    /// API key the elasticsearch sink authenticates with
+    #[arg(long, env = "ELASTICSEARCH_API_KEY", hide_env_values = true)]
+    pub elasticsearch_api_key: Option<String>,
+
+    /// JSON index template, with its settings and mappings, to install
+    /// under the index's name instead of the default mapping
+    #[arg(long, env = "ELASTICSEARCH_TEMPLATE")]
+    pub elasticsearch_template: Option<String>,
+
+    /// URL of the Kafka REST proxy the kafka sink publishes through
+    #[arg(long, env = "KAFKA_URL")]
+    pub kafka_url: Option<String>,
+
+    /// Topic the kafka sink publishes sentences to, keyed by docId
+    #[arg(long, env = "KAFKA_TOPIC", default_value = "sentences")]
+    pub kafka_topic: String,
+
+    /// Files to process concurrently when the input names several
+    #[arg(long, env = "WORKERS", default_value_t = 1)]
+    pub workers: usize,
+
+    /// Sentences per insert batch [default: 1000]
+    #[arg(long, env = "BATCH_SIZE")]
+    pub batch_size: Option<usize>,
+
+    /// Batches queued for the writer task before segmentation waits for it
+    #[arg(long, env = "WRITE_QUEUE", default_value_t = 4)]
+    pub write_queue: usize,
+
+    /// Batches written at once, each over its own connection, while
+    /// segmentation goes on; sinks that write to a single file take one
+    #[arg(long, env = "INSERTS_IN_FLIGHT", default_value_t = 4)]
+    pub inserts_in_flight: usize,
+
+    /// Write a batch before it's full once its oldest sentence has waited
+    /// this many milliseconds, for input that arrives slowly such as stdin
+    #[arg(long, env = "FLUSH_INTERVAL_MS")]
+    pub flush_interval_ms: Option<u64>,
+
+    /// Pattern removed from every line; repeat to apply several in order.
+    /// Replaces --cleaning-preset and the config file's cleaning steps
+    /// [default: [^a-zA-Z0-9\s.!?]]
+    #[arg(long = "cleaning-regex", env = "CLEANING_REGEX")]
+    pub cleaning_regexes: Vec<String>,
+
+    /// Tag each sentence with its language as lang
+    #[arg(long, env = "DETECT_LANGUAGE", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub detect_language: bool,
+
+    /// Keep only sentences in these languages, as comma-separated ISO 639-3
+    /// codes such as eng,deu; implies --detect-language. Sentences too short
+    /// to identify are kept
+    #[arg(long, env = "LANGUAGES", value_delimiter = ',')]
+    pub languages: Vec<String>,
+
+    /// Personal information replaced with placeholders before cleaning, as a
+    /// comma-separated list of email, phone and url
+    #[arg(long, env = "REDACT", value_delimiter = ',')]
+    pub redact: Vec<PiiKind>,
+
+    /// Ready-made cleaning: strict, punctuation-preserving to keep
+    /// apostrophes and commas, or unicode to also keep accented letters
+    #[arg(long, env = "CLEANING_PRESET")]
+    pub cleaning_preset: Option<CleaningPreset>,
+
+    /// Unicode normalization before cleaning: none, nfc, or nfkc to also fold
+    /// full-width characters and ligatures [default: none]
+    #[arg(long, env = "UNICODE_FORM")]
+    pub unicode_form: Option<UnicodeForm>,
+
+    /// Turn curly quotes and apostrophes into straight ones before cleaning
+    #[arg(long, env = "FOLD_QUOTES", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub fold_quotes: bool,
+
+    /// Sentence boundary detection: the built-in rules, unicode for the
+    /// UAX #29 algorithm, or cjk for the rules plus Chinese and Japanese
+    /// terminators [default: rules]
+    #[arg(long, env = "SEGMENTER")]
+    pub segmenter: Option<SegmenterKind>,
+
+    /// Extra abbreviations for the rules segmenter, one per line, such as
+    /// "approx." or "z.B."; added to the built-in English list
+    #[arg(long, env = "ABBREV_FILE")]
+    pub abbrev_file: Option<String>,
+
+    /// SRX (Segmentation Rules eXchange) file to segment with instead of the
+    /// built-in backends
+    #[arg(long, env = "SRX_FILE")]
+    pub srx_file: Option<String>,
+
+    /// Language code the SRX file's rules are picked for [default: en]
+    #[arg(long, env = "SRX_LANGUAGE")]
+    pub srx_language: Option<String>,
+
+    /// Minimum number of words in a sentence [default: 3]
+    #[arg(long, env = "MIN_WORDS")]
+    pub min_words: Option<usize>,
+
+    /// Minimum number of characters in a sentence [default: 10]
+    #[arg(long, env = "MIN_CHARS")]
+    pub min_chars: Option<usize>,
+
+    /// Drop sentences with more words than this
+    #[arg(long, env = "MAX_WORDS")]
+    pub max_words: Option<usize>,
+
+    /// Drop sentences with more characters than this
+    #[arg(long, env = "MAX_CHARS")]
+    pub max_chars: Option<usize>,
+
+    /// Drop sentences in which more than this share (0-1) of the characters
+    /// are digits
+    #[arg(long, env = "MAX_DIGIT_RATIO")]
+    pub max_digit_ratio: Option<f64>,
+
+    /// Treatment of soft hyphens, zero-width spaces and BOMs: keep, strip or normalize
+    #[arg(long, env = "INVISIBLE_CHARS", default_value = "normalize")]
+    pub invisible_chars: InvisibleChars,
+
+    /// Count lines first so progress and positionRatio are accurate
+    #[arg(long, env = "TWO_PASS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub two_pass: bool,
+
+    /// Most bytes of a line read at once, so files without newlines such as
+    /// single-line dumps are segmented in pieces of this size instead of
+    /// read whole; JSON Lines records longer than this are left out
+    #[arg(long, env = "MAX_LINE_BYTES", default_value_t = 1024 * 1024, value_parser = clap::value_parser!(u64).range(1024..))]
+    pub max_line_bytes: u64,
+
+    /// JSON file recording how far each source got, rewritten after every
+    /// batch
+    #[arg(long, env = "CHECKPOINT_FILE")]
+    pub checkpoint: Option<String>,
+
+    /// Continue from the --checkpoint file: finished sources are skipped and
+    /// the others carry on after their last recorded line. Output files are
+    /// added to rather than replaced, so can't be compressed or parquet
+    #[arg(long, env = "RESUME", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub resume: bool,
+
+    /// Ingest files again even when the manifest of ingested files (the
+    /// MongoDB `files` collection) has them as unchanged
+    #[arg(long, env = "FORCE", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub force: bool,
+
+    /// Delete the sentences stored earlier from each file (by fileName)
+    /// before storing its new ones; needs the mongodb or sqlite sink
+    #[arg(long, env = "REPLACE", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub replace: bool,
+
+    /// Run the whole pipeline without touching MongoDB: write the sentences
+    /// to --output, or print them, and report how many each file gave
+    #[arg(long, env = "DRY_RUN", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false, conflicts_with_all = ["sink", "sqlite", "postgres_url", "elasticsearch_url", "kafka_url", "replace"])]
+    pub dry_run: bool,
+
+    /// Time the pipeline: report lines, sentences and megabytes per second
+    /// and the time spent reading, cleaning, segmenting and writing. The
+    /// sentences are thrown away unless --sink or a sink's URL is given, to
+    /// time a destination too
+    #[arg(long, env = "BENCH", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false, conflicts_with = "dry_run")]
+    pub bench: bool,
+
+    /// Process only this share (0-1) of each file's lines, picked at random
+    /// but the same ones on every run, to preview segmentation and the
+    /// stored fields of a huge corpus; files sampled aren't recorded as
+    /// ingested
+    #[arg(long, env = "SAMPLE", value_parser = parse_fraction, conflicts_with_all = ["checkpoint", "resume"])]
+    pub sample: Option<f64>,
+
+    /// Process only the first this many lines of each file, or of its
+    /// sampled lines with --sample; files cut short aren't recorded as
+    /// ingested
+    #[arg(long, env = "LIMIT", conflicts_with_all = ["checkpoint", "resume"])]
+    pub limit: Option<usize>,
+
+    /// Write what was done with each file, and the run's totals, to this
+    /// JSON file: lines read, bytes, sentences kept, fragments rejected by
+    /// each validity rule and the time taken
+    #[arg(long, env = "REPORT_FILE")]
+    pub report: Option<String>,
+
+    /// Print neither the status line nor the messages about each file, only
+    /// warnings, errors and the totals; the same as --log-level warn
+    #[arg(long, short, env = "QUIET", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub quiet: bool,
+
+    /// Serve Prometheus metrics of the run at /metrics on this address, such
+    /// as 0.0.0.0:9898: sentences, batches inserted and their latency,
+    /// retries and the write queue's depth
+    #[arg(long, env = "METRICS_LISTEN")]
+    pub metrics_listen: Option<String>,
+
+    /// Reject number+unit fragments ("42 kg") like bare numbers; =false
+    /// overrides a config file that sets it
+    #[arg(long, env = "REJECT_NUMBER_UNITS", value_parser = clap::builder::BoolishValueParser::new(), num_args = 0..=1, default_missing_value = "true")]
+    pub reject_number_units: Option<bool>,
+
+    /// Collapse runs of the same punctuation to at most this many characters
+    #[arg(long, env = "MAX_PUNCTUATION_RUN")]
+    pub max_punctuation_run: Option<usize>,
+
+    /// Split every line on its own instead of letting sentences run on across
+    /// line breaks until a blank line
+    #[arg(long, env = "KEEP_LINES", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub keep_lines: bool,
+
+    /// Leave words hyphenated at line breaks ("inter-" "national") split
+    #[arg(long, env = "KEEP_HYPHENS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub keep_hyphens: bool,
+
+    /// Word list, one per line, used to tell words broken at a line break
+    /// from hyphenated compounds such as "well-known"
+    #[arg(long, env = "HYPHENATION_DICT")]
+    pub hyphenation_dict: Option<String>,
+
+    /// Regex with named groups that pulls metadata off the front of each line
+    #[arg(long, env = "LINE_METADATA_REGEX")]
+    pub line_metadata_regex: Option<String>,
+
+    /// Capture group of --line-metadata-regex holding the text to split
+    #[arg(long, env = "LINE_TEXT_GROUP", default_value = "text")]
+    pub line_text_group: String,
+
+    /// Template file names are read as, such as `{genre}_{year:int}_{id}.txt`;
+    /// each {field} becomes a field of every sentence from the file, and
+    /// {field:int} a number
+    #[arg(long, env = "FILE_NAME_TEMPLATE")]
+    pub file_name_template: Option<String>,
+
+    /// Drop YAML front matter and Project Gutenberg headers and license
+    /// text, storing the title, author and year they give as fields
+    #[arg(long, env = "STRIP_HEADERS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub strip_headers: bool,
+
+    /// How sentences are split for tokenCount: unicode word boundaries,
+    /// dropping punctuation, or whitespace
+    #[arg(long, env = "TOKENIZER", default_value = "unicode")]
+    pub tokenizer: Tokenizer,
+
+    /// Also store each sentence's tokens as an array
+    #[arg(long, env = "STORE_TOKENS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub store_tokens: bool,
+
+    /// Store each token's lemma as a lemmas array, looked up in a table of
+    /// irregular English forms
+    #[arg(long, env = "LEMMAS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub lemmas: bool,
+
+    /// Tab-separated file of forms and their lemmas, added to the built-in
+    /// ones; implies --lemmas
+    #[arg(long, env = "LEMMA_TABLE")]
+    pub lemma_table: Option<String>,
+
+    /// Store each token's part of speech as a pos array of Universal
+    /// Dependencies tags, from the built-in English lexicon and word endings
+    #[arg(long, env = "POS", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub pos: bool,
+
+    /// Tab-separated file of words and their tags, added to the built-in
+    /// lexicon; implies --pos
+    #[arg(long, env = "POS_LEXICON")]
+    pub pos_lexicon: Option<String>,
+
+    /// Tagging service to send the sentences' tokens to instead, a batch at
+    /// a time, as JSON {"sentences": [[...], ...]}, answering with an array
+    /// of each one's tags; implies --pos
+    #[arg(long, env = "POS_URL")]
+    pub pos_url: Option<String>,
+
+    /// Store each sentence's Flesch-Kincaid grade, reading ease, average
+    /// word length and digit, uppercase and symbol ratios under quality
+    #[arg(long, env = "QUALITY_SCORES", value_parser = clap::builder::BoolishValueParser::new(), default_value_t = false)]
+    pub quality_scores: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SearchArgs {
+    /// Words, "quoted phrases" and -excluded words to search for
+    pub text: String,
+
+    /// Maximum number of sentences to print
+    #[arg(long, default_value_t = 20)]
+    pub limit: i64,
+
+    /// Sentences of the same document to print before and after each
+    /// match, for its context; as `before` and `after` arrays with json
+    #[arg(long, default_value_t = 0, conflicts_with = "sqlite")]
+    pub context: i32,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    /// Search the full-text index of a database written by the sqlite
+    /// sink instead of MongoDB; of the filters only --file applies, as it
+    /// stores no genre, year or ingestion time
+    #[arg(long, conflicts_with_all = ["genre", "year_from", "year_to", "ingested_after", "ingested_before"])]
+    pub sqlite: Option<String>,
+
+    /// Output: table, with the score, source and text of each sentence, or
+    /// json, a document per line
+    #[arg(long, default_value = "table")]
+    pub format: SearchFormat,
+}
+
+/// Which stored sentences a command looks at.
+#[derive(Debug, Args)]
+pub struct FilterArgs {
+    /// Only sentences with this genre
+    #[arg(long)]
+    pub genre: Option<String>,
+
+    /// Only sentences from this year or later
+    #[arg(long)]
+    pub year_from: Option<i32>,
+
+    /// Only sentences from this year or earlier
+    #[arg(long)]
+    pub year_to: Option<i32>,
+
+    /// Only sentences from files matching this name, where * matches
+    /// anything
+    #[arg(long)]
+    pub file: Option<String>,
+
+    /// Only sentences ingested on or after this date, as YYYY-MM-DD or an
+    /// RFC 3339 time
+    #[arg(long, value_parser = parse_date)]
+    pub ingested_after: Option<DateTime>,
+
+    /// Only sentences ingested before this date, as YYYY-MM-DD or an RFC
+    /// 3339 time
+    #[arg(long, value_parser = parse_date)]
+    pub ingested_before: Option<DateTime>,
+}
+
+/// `s` as a time, taking a bare date as its midnight UTC.
+pub fn parse_date(s: &str) -> Result<DateTime, String> {
+    let time = if s.len() == 10 {
+        format!("{}T00:00:00Z", s)
+    } else {
+        s.to_string()
+    };
+    DateTime::parse_rfc3339_str(&time).map_err(|_| {
+        format!(
+            "invalid date '{}' (expected YYYY-MM-DD or an RFC 3339 time)",
+            s
+        )
+    })
+}
+
+/// `s` as a share from 0 to 1.
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("invalid fraction '{}' (expected 0 to 1)", s)),
+    }
+}
+
+/// A share of more than 0, so it takes something in common, and up to 1.
+fn parse_threshold(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(share) if share > 0.0 && share <= 1.0 => Ok(share),
+        _ => Err(format!(
+            "invalid threshold '{}' (expected more than 0, up to 1)",
+            s
+        )),
+    }
+}
+
+/// How `search` prints matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for SearchFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(SearchFormat::Table),
+            "json" => Ok(SearchFormat::Json),
+            other => Err(format!(
+                "invalid search output '{}' (expected table or json)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct StatsArgs {
+    /// Also break the counts down by source file
+    #[arg(long)]
+    pub by_file: bool,
+
+    /// Also break the counts down by genre
+    #[arg(long)]
+    pub by_genre: bool,
+
+    /// Field of the sentences holding their genre
+    #[arg(long, default_value = "genre")]
+    pub genre_field: String,
+}
+
+#[derive(Debug, Args)]
+pub struct KwicArgs {
+    /// Word or phrase to search for
+    pub term: String,
+
+    /// Characters of context either side of the keyword
+    #[arg(long, default_value_t = 40)]
+    pub width: usize,
+
+    /// Sentences before and after each match to draw context from
+    #[arg(long, default_value_t = 1)]
+    pub context: i32,
+
+    /// Maximum number of lines
+    #[arg(long, default_value_t = 50)]
+    pub limit: i64,
+
+    /// File to write the lines to instead of standard output
+    #[arg(long)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// File to write the sentences to
+    #[arg(long)]
+    pub output: String,
+
+    /// Output: text, a sentence per line, jsonl, csv or tsv [default: from
+    /// the output's extension, otherwise text]
+    #[arg(long)]
+    pub format: Option<ExportFormat>,
+
+    /// Comma-separated fields written by jsonl, csv and tsv [default: all
+    /// but _id for jsonl, fileName,lineNumber,text for csv and tsv]
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<String>,
+
+    /// Compress the output with gzip, zstd or bzip2 [default: as its
+    /// extension says, such as .csv.gz]
+    #[arg(long)]
+    pub compress: Option<Compression>,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, env = "LISTEN_ADDR", default_value = "127.0.0.1:8080")]
+    pub listen: String,
+
+    /// Store and search sentences in this SQLite database instead of MongoDB
+    #[arg(long)]
+    pub sqlite: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct PurgeArgs {
+    /// Delete the sentences of files matching this name, where * matches
+    /// anything
+    #[arg(long, required_unless_present = "before")]
+    pub file: Option<String>,
+
+    /// Delete the sentences ingested before this date, as YYYY-MM-DD or an
+    /// RFC 3339 time
+    #[arg(long, value_parser = parse_date)]
+    pub before: Option<DateTime>,
+
+    /// Collection of TF-IDF weights to delete the files' weights from
+    #[arg(long, default_value = TFIDF_COLLECTION)]
+    pub tfidf_collection: String,
+}
+
+/// How `export` writes sentences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Text,
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+impl ExportFormat {
+    /// The format a file named `path` is in, going by its extension, past
+    /// any compression one.
+    pub fn from_path(path: &str) -> Self {
+        let mut path = std::path::Path::new(path);
+        if Compression::from_path(path).is_some() {
+            path = path.file_stem().map_or(path, std::path::Path::new);
+        }
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" | "json" => ExportFormat::Jsonl,
+            "csv" => ExportFormat::Csv,
+            "tsv" => ExportFormat::Tsv,
+            _ => ExportFormat::Text,
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(ExportFormat::Text),
+            "jsonl" | "ndjson" => Ok(ExportFormat::Jsonl),
+            "csv" => Ok(ExportFormat::Csv),
+            "tsv" => Ok(ExportFormat::Tsv),
+            other => Err(format!(
+                "invalid export format '{}' (expected text, jsonl, csv or tsv)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct WordFreqArgs {
+    /// Collection to write the frequencies to, replacing its contents
+    #[arg(long, default_value = WORD_FREQ_COLLECTION)]
+    pub collection: String,
+
+    /// Field of the sentences holding their genre
+    #[arg(long, default_value = "genre")]
+    pub genre_field: String,
+
+    #[command(flatten)]
+    pub counting: CountArgs,
+
+    /// Count the lemmas stored by ingest --lemmas instead of the words
+    #[arg(long)]
+    pub lemmas: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NgramsArgs {
+    /// Collection to write the n-grams to, replacing its contents
+    #[arg(long, default_value = NGRAMS_COLLECTION)]
+    pub collection: String,
+
+    /// Shortest n-grams counted, 2 for bigrams
+    #[arg(long, default_value_t = 2)]
+    pub min_n: usize,
+
+    /// Longest n-grams counted, up to 5
+    #[arg(long, default_value_t = 3)]
+    pub max_n: usize,
+
+    /// Leave out n-grams seen fewer times than this
+    #[arg(long, default_value_t = 1)]
+    pub min_count: u64,
+
+    /// Field of the sentences holding their genre
+    #[arg(long, default_value = "genre")]
+    pub genre_field: String,
+
+    #[command(flatten)]
+    pub counting: CountArgs,
+}
+
+/// How sentences are counted into frequency tables.
+#[derive(Debug, Args)]
+pub struct CountArgs {
+    /// How sentences are split into words: unicode or whitespace
+    #[arg(long, default_value = "unicode")]
+    pub tokenizer: Tokenizer,
+
+    /// Leave out the built-in stopwords of these languages, a
+    /// comma-separated list of ISO 639-3 codes: eng, deu, fra or spa. A
+    /// sentence's own list is used when its lang is stored
+    #[arg(long, value_delimiter = ',')]
+    pub stopwords: Vec<String>,
+
+    /// File of further stopwords, one per line, left out in every language
+    #[arg(long)]
+    pub stopword_file: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct TfidfArgs {
+    /// Collection to write the weights to, replacing its contents
+    #[arg(long, default_value = TFIDF_COLLECTION)]
+    pub collection: String,
+
+    /// Keep only this many of the weightiest words of each file
+    #[arg(long)]
+    pub top: Option<usize>,
+
+    #[command(flatten)]
+    pub counting: CountArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CollocatesArgs {
+    /// Word to find the collocates of
+    pub word: String,
+
+    /// Words either side of it that count as near it
+    #[arg(long, default_value_t = 4)]
+    pub window: usize,
+
+    /// Leave out words seen near it fewer times than this
+    #[arg(long, default_value_t = 3)]
+    pub min_count: u64,
+
+    /// Measure to rank collocates by: ll, mi or t
+    #[arg(long, default_value = "ll")]
+    pub measure: Measure,
+
+    /// Maximum number of collocates to print
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// How sentences are split into words: unicode or whitespace
+    #[arg(long, default_value = "unicode")]
+    pub tokenizer: Tokenizer,
+}
+
+impl IngestArgs {
+    pub fn input(&self) -> &str {
+        self.input_flag
+            .as_deref()
+            .or(self.input.as_deref())
+            .expect("clap requires an input")
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Ingests the files dropped into the directory given as INPUT, with
+    /// the options of `ingest`
+    #[command(flatten)]
+    pub ingest: IngestArgs,
+
+    /// Move each file here once it's ingested, under the same relative path
+    #[arg(long, value_name = "DIR")]
+    pub archive: Option<String>,
+
+    /// Milliseconds between looks at the directory; a file is ingested once
+    /// it's stayed the same for this long
+    #[arg(long, default_value_t = 2000)]
+    pub poll_interval_ms: u64,
+}
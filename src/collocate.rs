@@ -0,0 +1,208 @@
+//! Collocates of a word: the words seen near it more often than chance
+//! would have them, scored with the usual association measures.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::token::Tokenizer;
+
+/// An association measure collocates can be ranked by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Measure {
+    /// Log-likelihood, which favours frequent collocates that are reliably
+    /// attracted.
+    #[default]
+    LogLikelihood,
+    /// Mutual information, which favours rare, exclusive ones.
+    Mi,
+    /// The t-score, which favours frequent ones.
+    TScore,
+}
+
+impl FromStr for Measure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ll" | "log-likelihood" => Ok(Measure::LogLikelihood),
+            "mi" => Ok(Measure::Mi),
+            "t" | "t-score" => Ok(Measure::TScore),
+            other => Err(format!(
+                "invalid association measure '{}' (expected ll, mi or t)",
+                other
+            )),
+        }
+    }
+}
+
+/// How strongly one word is attracted to the node word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Association {
+    pub word: String,
+    /// Times it was seen within the window of the node.
+    pub observed: u64,
+    /// Times it was seen in the corpus.
+    pub frequency: u64,
+    /// Times it would be seen within the window by chance.
+    pub expected: f64,
+    pub mi: f64,
+    pub t_score: f64,
+    pub log_likelihood: f64,
+}
+
+impl Association {
+    pub fn score(&self, measure: Measure) -> f64 {
+        match measure {
+            Measure::LogLikelihood => self.log_likelihood,
+            Measure::Mi => self.mi,
+            Measure::TScore => self.t_score,
+        }
+    }
+}
+
+/// Co-occurrence counts for one node word, within `window` words either
+/// side of it in the same sentence.
+#[derive(Debug, Clone)]
+pub struct Collocates {
+    node: String,
+    window: usize,
+    tokenizer: Tokenizer,
+    /// Times the node was seen.
+    node_count: u64,
+    /// Words seen in its windows, with how often.
+    cooccurrences: HashMap<String, u64>,
+    frequencies: HashMap<String, u64>,
+    tokens: u64,
+}
+
+impl Collocates {
+    /// Counts the collocates of `node`, compared lowercased.
+    pub fn new(node: &str, window: usize, tokenizer: Tokenizer) -> Self {
+        Collocates {
+            node: node.to_lowercase(),
+            window: window.max(1),
+            tokenizer,
+            node_count: 0,
+            cooccurrences: HashMap::new(),
+            frequencies: HashMap::new(),
+            tokens: 0,
+        }
+    }
+
+    pub fn add_sentence(&mut self, sentence: &str) {
+        let words: Vec<String> = self
+            .tokenizer
+            .tokens(sentence)
+            .into_iter()
+            .map(str::to_lowercase)
+            .collect();
+        for (i, word) in words.iter().enumerate() {
+            *self.frequencies.entry(word.clone()).or_default() += 1;
+            if *word != self.node {
+                continue;
+            }
+            self.node_count += 1;
+            let start = i.saturating_sub(self.window);
+            let end = (i + self.window + 1).min(words.len());
+            for (j, near) in words.iter().enumerate().take(end).skip(start) {
+                if j != i {
+                    *self.cooccurrences.entry(near.clone()).or_default() += 1;
+                }
+            }
+        }
+        self.tokens += words.len() as u64;
+    }
+
+    /// Times the node word was seen.
+    pub fn node_count(&self) -> u64 {
+        self.node_count
+    }
+
+    /// The words seen near the node at least `min_count` times, strongest
+    /// by `measure` first.
+    pub fn associations(&self, measure: Measure, min_count: u64) -> Vec<Association> {
+        let n = self.tokens as f64;
+        // Every word in a window around the node, counted once per window
+        let span = (self.node_count * 2 * self.window as u64) as f64;
+        let mut associations: Vec<_> = self
+            .cooccurrences
+            .iter()
+            .filter(|(_, &observed)| observed >= min_count)
+            .map(|(word, &observed)| {
+                let frequency = self.frequencies[word];
+                let o = observed as f64;
+                let expected = span * frequency as f64 / n;
+                Association {
+                    word: word.clone(),
+                    observed,
+                    frequency,
+                    expected,
+                    mi: (o / expected).log2(),
+                    t_score: (o - expected) / o.sqrt(),
+                    log_likelihood: log_likelihood(o, span, frequency as f64, n),
+                }
+            })
+            .collect();
+        associations.sort_by(|a, b| {
+            b.score(measure)
+                .total_cmp(&a.score(measure))
+                .then_with(|| a.word.cmp(&b.word))
+        });
+        associations
+    }
+}
+
+/// Dunning's log-likelihood ratio for `o` co-occurrences, given `rows`
+/// words in the node's windows and `columns` occurrences of the collocate
+/// among `n` words.
+fn log_likelihood(o: f64, rows: f64, columns: f64, n: f64) -> f64 {
+    let observed = [o, rows - o, columns - o, n - rows - columns + o];
+    let expected = [
+        rows * columns / n,
+        rows * (n - columns) / n,
+        (n - rows) * columns / n,
+        (n - rows) * (n - columns) / n,
+    ];
+    2.0 * observed
+        .iter()
+        .zip(expected)
+        .filter(|(o, e)| **o > 0.0 && *e > 0.0)
+        .map(|(o, e)| o * (o / e).ln())
+        .sum::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_words_near_the_node() {
+        let mut collocates = Collocates::new("Strong", 1, Tokenizer::Unicode);
+        for sentence in [
+            "Strong tea again.",
+            "She likes strong tea.",
+            "A strong wind blew.",
+            "Tea is hot and the wind is cold.",
+        ] {
+            collocates.add_sentence(sentence);
+        }
+        assert_eq!(collocates.node_count(), 3);
+
+        let associations = collocates.associations(Measure::LogLikelihood, 2);
+        assert_eq!(associations.len(), 1);
+        let tea = &associations[0];
+        assert_eq!(
+            (tea.word.as_str(), tea.observed, tea.frequency),
+            ("tea", 2, 3)
+        );
+        // 6 words in windows among 19, 3 of them "tea"
+        assert!((tea.expected - 18.0 / 19.0).abs() < 1e-9);
+        assert!((tea.mi - (2.0 * 19.0 / 18.0_f64).log2()).abs() < 1e-9);
+        assert!(tea.t_score > 0.0 && tea.log_likelihood > 0.0);
+
+        let by_mi = collocates.associations(Measure::Mi, 1);
+        assert_eq!(by_mi.len(), 4);
+        assert!(by_mi[0].mi >= by_mi[3].mi);
+        assert_eq!("t".parse(), Ok(Measure::TScore));
+    }
+}
@@ -0,0 +1,367 @@
+use mongodb::bson::Document;
+use mongodb::options::{
+    Acknowledgment, AuthMechanism, ClientOptions, Credential, Tls, TlsOptions, WriteConcern,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rustparser::schema::Schema;
+use rustparser::sink::Route;
+use rustparser::BoxError;
+
+/// Config file looked for in the working directory when `--config` isn't given.
+pub const DEFAULT_CONFIG_FILE: &str = "parser.toml";
+
+/// Pipeline settings read from a TOML file. Every field is optional; values
+/// from the command line or the environment take precedence over these.
+///
+/// ```toml
+/// batch_size = 1000
+/// flush_interval_ms = 5000
+///
+/// [mongodb]
+/// database = "coca_like_db"
+/// collection = "corpus"
+/// manifest_collection = "files"
+/// max_pool_size = 20
+/// connect_timeout_ms = 5000
+/// server_selection_timeout_ms = 10000
+/// write_concern = "majority"
+/// journal = true
+/// tls_ca_file = "ca.pem"
+/// # tls_certificate_key_file = "client.pem"
+/// username = "ingest"
+/// password_file = "/run/secrets/mongo-password"
+/// auth_source = "admin"
+/// # auth_mechanism = "MONGODB-X509"
+///
+/// [schema]
+/// rename = { text = "sentence", fileName = "source" }
+/// fields = { corpus = "news", year = 2021 }
+///
+/// [[routing]]
+/// path = "fiction/**"
+/// collection = "fiction"
+///
+/// [[routing]]
+/// field = "genre"
+/// equals = "news"
+/// fields = { register = "news" }
+///
+/// [cleaning]
+/// unicode_form = "nfc"
+/// redact = ["email", "phone", "url"]
+/// hyphenation_dict = "words.txt"
+/// max_punctuation_run = 3
+/// # Either a preset ("strict", "punctuation-preserving", "unicode") or steps
+/// # preset = "punctuation-preserving"
+///
+/// [[cleaning.steps]]
+/// step = "strip_control"
+///
+/// [[cleaning.steps]]
+/// step = "replace"
+/// pattern = 'https?://\S+'
+/// replacement = ""
+///
+/// [[cleaning.steps]]
+/// step = "replace"
+/// pattern = '[^a-zA-Z0-9\s.!?]'
+/// enabled = false
+///
+/// [validity]
+/// min_words = 3
+/// min_chars = 10
+/// max_words = 120
+/// max_digit_ratio = 0.5
+/// reject_number_units = true
+///
+/// [segmentation]
+/// backend = "rules"
+/// abbrev_file = "abbreviations.txt"
+/// # srx_file = "segment.srx"
+/// # srx_language = "en"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub batch_size: Option<usize>,
+    pub flush_interval_ms: Option<u64>,
+    pub mongodb: MongoConfig,
+    pub cleaning: CleaningConfig,
+    pub validity: ValidityConfig,
+    pub segmentation: SegmentationConfig,
+    pub schema: SchemaConfig,
+    /// Rules sending sentences to other MongoDB collections, tried in order.
+    pub routing: Vec<RouteConfig>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoConfig {
+    pub database: Option<String>,
+    pub collection: Option<String>,
+    /// Collection recording the files ingested; `files` when unset.
+    pub manifest_collection: Option<String>,
+    /// Connection tuning; each one set here wins over the connection
+    /// string's.
+    pub max_pool_size: Option<u32>,
+    pub min_pool_size: Option<u32>,
+    pub connect_timeout_ms: Option<u64>,
+    pub server_selection_timeout_ms: Option<u64>,
+    /// Acknowledgment writes wait for: `majority`, a number of nodes, or a
+    /// replica set tag.
+    pub write_concern: Option<Acknowledgment>,
+    /// Whether writes wait for the journal.
+    pub journal: Option<bool>,
+    /// How long writes wait for `write_concern` before failing.
+    pub write_timeout_ms: Option<u64>,
+    /// Connect over TLS; implied by any other `tls_` setting.
+    pub tls: Option<bool>,
+    /// Certificate authorities the server's certificate is checked against.
+    pub tls_ca_file: Option<PathBuf>,
+    /// Client certificate and key in one PEM file, for X.509 authentication.
+    pub tls_certificate_key_file: Option<PathBuf>,
+    /// Accept any server certificate. Only for development clusters.
+    pub tls_allow_invalid_certificates: Option<bool>,
+    /// Accept a server certificate for another host name. The driver's TLS
+    /// backend can't skip that check alone, so this accepts invalid
+    /// certificates as well.
+    pub tls_allow_invalid_hostnames: Option<bool>,
+    pub username: Option<String>,
+    /// File holding the password, so it's kept out of the connection string
+    /// and the environment. Trailing whitespace is ignored.
+    pub password_file: Option<PathBuf>,
+    /// Database the user is defined in.
+    pub auth_source: Option<String>,
+    /// `SCRAM-SHA-256`, `SCRAM-SHA-1` or `MONGODB-X509`.
+    pub auth_mechanism: Option<String>,
+}
+
+impl MongoConfig {
+    /// Overrides `options` parsed from the connection string with the
+    /// tuning, TLS and authentication settings set here.
+    pub fn apply(&self, options: &mut ClientOptions) -> Result<(), BoxError> {
+        self.apply_tls(options);
+        self.apply_credential(options)?;
+        let millis = |ms: Option<u64>| ms.map(Duration::from_millis);
+        options.max_pool_size = self.max_pool_size.or(options.max_pool_size);
+        options.min_pool_size = self.min_pool_size.or(options.min_pool_size);
+        options.connect_timeout = millis(self.connect_timeout_ms).or(options.connect_timeout);
+        options.server_selection_timeout =
+            millis(self.server_selection_timeout_ms).or(options.server_selection_timeout);
+        if self.write_concern.is_none() && self.journal.is_none() && self.write_timeout_ms.is_none()
+        {
+            return Ok(());
+        }
+        let concern = options
+            .write_concern
+            .get_or_insert_with(WriteConcern::default);
+        concern.w = self.write_concern.clone().or(concern.w.take());
+        concern.journal = self.journal.or(concern.journal);
+        concern.w_timeout = millis(self.write_timeout_ms).or(concern.w_timeout);
+        Ok(())
+    }
+
+    fn apply_tls(&self, options: &mut ClientOptions) {
+        let configured = self.tls_ca_file.is_some()
+            || self.tls_certificate_key_file.is_some()
+            || self.tls_allow_invalid_certificates.is_some()
+            || self.tls_allow_invalid_hostnames.is_some();
+        match self.tls {
+            Some(false) => {
+                options.tls = Some(Tls::Disabled);
+                return;
+            }
+            None if !configured => return,
+            _ => {}
+        }
+        let mut tls = match options.tls.take() {
+            Some(Tls::Enabled(tls)) => tls,
+            _ => TlsOptions::default(),
+        };
+        if self.tls_allow_invalid_hostnames == Some(true) {
+            log::warn!("tls_allow_invalid_hostnames also accepts invalid certificates");
+        }
+        let allow_invalid = self
+            .tls_allow_invalid_certificates
+            .into_iter()
+            .chain(self.tls_allow_invalid_hostnames)
+            .reduce(|a, b| a || b);
+        tls.allow_invalid_certificates = allow_invalid.or(tls.allow_invalid_certificates);
+        tls.ca_file_path = self.tls_ca_file.clone().or(tls.ca_file_path);
+        tls.cert_key_file_path = self
+            .tls_certificate_key_file
+            .clone()
+            .or(tls.cert_key_file_path);
+        options.tls = Some(Tls::Enabled(tls));
+    }
+
+    fn apply_credential(&self, options: &mut ClientOptions) -> Result<(), BoxError> {
+        if self.username.is_none()
+            && self.password_file.is_none()
+            && self.auth_source.is_none()
+            && self.auth_mechanism.is_none()
+        {
+            return Ok(());
+        }
+        let credential = options.credential.get_or_insert_with(Credential::default);
+        if let Some(path) = &self.password_file {
+            let password = std::fs::read_to_string(path)
+                .map_err(|e| format!("Error reading password file {}: {}", path.display(), e))?;
+            credential.password = Some(password.trim_end().to_string());
+        }
+        if let Some(mechanism) = &self.auth_mechanism {
+            let mechanism: AuthMechanism = mechanism
+                .to_ascii_uppercase()
+                .parse()
+                .map_err(|_| format!("unknown auth_mechanism '{}'", mechanism))?;
+            credential.mechanism = Some(mechanism);
+        }
+        credential.username = self.username.clone().or(credential.username.take());
+        credential.source = self.auth_source.clone().or(credential.source.take());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleaningConfig {
+    /// `strict`, `punctuation-preserving` or `unicode`; can't be combined
+    /// with `regexes` or `steps`.
+    pub preset: Option<String>,
+    /// Patterns removed from every line, applied in order. Shorthand for
+    /// `replace` steps with an empty replacement; can't be combined with
+    /// `steps`.
+    pub regexes: Option<Vec<String>>,
+    /// The cleaning pipeline, run on every line in order.
+    pub steps: Option<Vec<CleaningStepConfig>>,
+    /// `none`, `nfc` or `nfkc`.
+    pub unicode_form: Option<String>,
+    /// Kinds of personal information to redact: `email`, `phone`, `url`.
+    pub redact: Option<Vec<String>>,
+    /// Word list for telling line-break hyphenation from compounds.
+    pub hyphenation_dict: Option<String>,
+    /// Longest run of the same punctuation kept; longer ones are cut to it.
+    pub max_punctuation_run: Option<usize>,
+}
+
+/// One `[[cleaning.steps]]` entry.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CleaningStepConfig {
+    /// `strip_control`, `collapse_whitespace` or `replace`.
+    pub step: String,
+    /// Regex matched by a `replace` step.
+    pub pattern: Option<String>,
+    /// What a `replace` step puts in place of each match; `$1` and `${name}`
+    /// refer to capture groups.
+    #[serde(default)]
+    pub replacement: String,
+    /// Set to false to keep a step in the file but skip it.
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidityConfig {
+    pub min_words: Option<usize>,
+    pub min_chars: Option<usize>,
+    pub max_words: Option<usize>,
+    pub max_chars: Option<usize>,
+    pub max_digit_ratio: Option<f64>,
+    /// Whether number+unit fragments ("42 kg") count as bare numbers.
+    pub reject_number_units: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SegmentationConfig {
+    /// `rules`, `unicode` or `cjk`.
+    pub backend: Option<String>,
+    /// Extra abbreviations for the rules backend, one per line.
+    pub abbrev_file: Option<String>,
+    /// SRX rules to segment with in place of `backend`.
+    pub srx_file: Option<String>,
+    /// Language the SRX rules are picked for; `en` when unset.
+    pub srx_language: Option<String>,
+}
+
+/// How stored sentences are laid out in MongoDB.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaConfig {
+    /// New names of fields, by the name they have otherwise.
+    pub rename: HashMap<String, String>,
+    /// Fields added to every sentence.
+    pub fields: toml::Table,
+}
+
+impl SchemaConfig {
+    pub fn to_schema(&self) -> Result<Schema, BoxError> {
+        let fields: Document = mongodb::bson::to_document(&self.fields)
+            .map_err(|e| format!("invalid [schema] fields: {}", e))?;
+        Ok(Schema::new(self.rename.clone(), fields))
+    }
+}
+
+/// One `[[routing]]` rule; see [`Route`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteConfig {
+    /// Glob the file name has to match.
+    pub path: Option<String>,
+    /// Field that has to equal `equals`.
+    pub field: Option<String>,
+    pub equals: Option<String>,
+    /// Collection the sentences go to; the main one when unset.
+    pub collection: Option<String>,
+    /// Fields added to the sentences, such as a shard key.
+    pub fields: toml::Table,
+}
+
+impl RouteConfig {
+    pub fn to_route(&self) -> Result<Route, BoxError> {
+        let path = self
+            .path
+            .as_deref()
+            .map(glob::Pattern::new)
+            .transpose()
+            .map_err(|e| format!("invalid routing path: {}", e))?;
+        let field = match (&self.field, &self.equals) {
+            (Some(field), Some(value)) => Some((field.clone(), value.clone())),
+            (None, None) => None,
+            _ => return Err("a routing rule needs both field and equals".into()),
+        };
+        let fields = mongodb::bson::to_document(&self.fields)
+            .map_err(|e| format!("invalid routing fields: {}", e))?;
+        Ok(Route {
+            path,
+            field,
+            fields,
+        })
+    }
+}
+
+impl FileConfig {
+    /// Loads `path` if given, otherwise `parser.toml` when it exists. A missing
+    /// default file is not an error; a missing explicit one is.
+    pub fn load(path: Option<&str>) -> Result<Self, BoxError> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE,
+            None => return Ok(FileConfig::default()),
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading config file {}: {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("Error parsing {}: {}", path, e).into())
+    }
+}
@@ -0,0 +1,199 @@
+//! Transparent decompression of gzip, zstd and bzip2 input, and compression
+//! of output files.
+//!
+//! Compression of input is detected from the stream's magic bytes rather
+//! than the file extension, so it also works for stdin and URLs. Output is
+//! compressed as its extension says, unless told otherwise.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Compression formats recognised by [`decompress`] and written by
+/// [`create_output`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Identifies the format from the first bytes of a stream.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if header.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    /// The format a file named `path` is in, going by its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// The extension of files in the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Bzip2 => "bz2",
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "bzip2" | "bz2" => Ok(Compression::Bzip2),
+            other => Err(format!(
+                "invalid compression '{}' (expected gzip, zstd or bzip2)",
+                other
+            )),
+        }
+    }
+}
+
+/// Wraps `reader` in a streaming decoder if it holds compressed data, and
+/// returns it unchanged otherwise.
+pub fn decompress(mut reader: Box<dyn BufRead + Send>) -> std::io::Result<Box<dyn BufRead + Send>> {
+    // Peek without consuming so an uncompressed stream is passed on intact
+    let compression = Compression::detect(reader.fill_buf()?);
+
+    Ok(match compression {
+        // Multi-member so concatenated .gz files (and bgzip) read fully
+        Some(Compression::Gzip) => {
+            Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+        }
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Some(Compression::Bzip2) => {
+            Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))
+        }
+        None => reader,
+    })
+}
+
+/// Wraps `writer` in a streaming encoder for `compression`, if any. The
+/// compressed stream is finished when the writer is dropped.
+pub fn compress<W: Write + Send + 'static>(
+    writer: W,
+    compression: Option<Compression>,
+) -> std::io::Result<Box<dyn Write + Send>> {
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(flate2::write::GzEncoder::new(
+            writer,
+            flate2::Compression::default(),
+        )),
+        Some(Compression::Zstd) => Box::new(zstd::Encoder::new(writer, 0)?.auto_finish()),
+        Some(Compression::Bzip2) => Box::new(bzip2::write::BzEncoder::new(
+            writer,
+            bzip2::Compression::default(),
+        )),
+        None => Box::new(writer),
+    })
+}
+
+/// Creates the file at `path`, compressed with `compression`, or as its
+/// extension says when that's not given.
+pub fn create_output(
+    path: &str,
+    compression: Option<Compression>,
+) -> std::io::Result<Box<dyn Write + Send>> {
+    let file = BufWriter::new(File::create(path)?);
+    compress(file, compression.or(Compression::from_path(path)))
+}
+
+/// Opens the file at `path` to write on at its end, creating it if needed,
+/// as resuming a run does. Compressed files can't be added to this way.
+pub fn append_output(
+    path: &str,
+    compression: Option<Compression>,
+) -> std::io::Result<Box<dyn Write + Send>> {
+    if let Some(compression) = compression.or(Compression::from_path(path)) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("can't append to {} output", compression.extension()),
+        ));
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read, Write};
+
+    const TEXT: &str = "A compressed corpus line. Another sentence follows.\n";
+
+    fn roundtrip(compressed: Vec<u8>) -> String {
+        let mut text = String::new();
+        decompress(Box::new(Cursor::new(compressed)))
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn reads_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(roundtrip(encoder.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn reads_zstd() {
+        let compressed = zstd::encode_all(TEXT.as_bytes(), 3).unwrap();
+        assert_eq!(roundtrip(compressed), TEXT);
+    }
+
+    #[test]
+    fn reads_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(roundtrip(encoder.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn compressed_output_reads_back() {
+        for compression in [Compression::Gzip, Compression::Zstd, Compression::Bzip2] {
+            let path = std::env::temp_dir().join(format!(
+                "compress-{}.txt.{}",
+                std::process::id(),
+                compression.extension()
+            ));
+            let path = path.to_str().unwrap();
+            assert_eq!(Compression::from_path(path), Some(compression));
+            let mut output = create_output(path, None).unwrap();
+            output.write_all(TEXT.as_bytes()).unwrap();
+            output.flush().unwrap();
+            drop(output);
+            assert_eq!(roundtrip(std::fs::read(path).unwrap()), TEXT);
+            std::fs::remove_file(path).unwrap();
+        }
+        assert_eq!(Compression::from_path("corpus.jsonl"), None);
+    }
+
+    #[test]
+    fn passes_plain_text_through() {
+        assert_eq!(roundtrip(TEXT.as_bytes().to_vec()), TEXT);
+        assert_eq!(roundtrip(Vec::new()), "");
+    }
+}
@@ -0,0 +1,265 @@
+//! Duplicate detection within one ingestion run.
+//!
+//! [`SeenFilter`] drops exact repeats using a fixed amount of memory.
+//! [`NearDuplicates`] compares MinHash signatures of sentences to find ones
+//! that are nearly identical, such as boilerplate and syndicated text, which
+//! exact hashing misses.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Share of new sentences a full [`SeenFilter`] wrongly takes for repeats.
+const BLOOM_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// A Bloom filter over the `textHash`es of sentences stored so far in a run.
+/// It never misses a repeat, but sized for `capacity` sentences it mistakes
+/// about one new sentence in a thousand for one, more once it holds more
+/// than that. Workers share it without locking.
+#[derive(Debug)]
+pub struct SeenFilter {
+    bits: Vec<AtomicU64>,
+    hashes: u64,
+}
+
+impl SeenFilter {
+    /// A filter for about `capacity` distinct sentences, taking roughly 1.8
+    /// bytes for each.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-capacity * BLOOM_FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil() as usize;
+        let words = bits.div_ceil(64).max(1);
+        let hashes = ((words * 64) as f64 / capacity * ln2).round().max(1.0) as u64;
+        SeenFilter {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            hashes,
+        }
+    }
+
+    /// The memory taken by the filter, in bytes.
+    pub fn size(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Adds a sentence by its hex `textHash`, returning whether it was
+    /// probably there already.
+    pub fn insert(&self, text_hash: &str) -> bool {
+        // The hash is already uniform, so two of its words serve as the two
+        // hashes every probe is derived from
+        let word = |range: std::ops::Range<usize>| {
+            text_hash
+                .get(range)
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                .unwrap_or_else(|| fnv1a(text_hash.as_bytes()))
+        };
+        let (h1, h2) = (word(0..16), word(16..32) | 1);
+        let len = self.bits.len() as u64 * 64;
+        let mut present = true;
+        for i in 0..self.hashes {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % len;
+            let mask = 1 << (bit % 64);
+            let previous = self.bits[(bit / 64) as usize].fetch_or(mask, Ordering::Relaxed);
+            present &= previous & mask != 0;
+        }
+        present
+    }
+}
+
+/// Characters per shingle compared between sentences.
+const SHINGLE_CHARS: usize = 5;
+
+/// Hash functions per signature.
+const SIGNATURE_LEN: usize = 64;
+
+/// Signature values hashed together into one locality-sensitive bucket.
+/// Sentences sharing any bucket are compared in full.
+const BAND_ROWS: usize = 4;
+
+/// FNV-1a, which unlike the standard library's hasher gives the same
+/// values in every build, so stored signatures stay comparable.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The SplitMix64 finalizer, turning one hash into an unrelated one.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// The MinHash signature of `text`: for each of [`SIGNATURE_LEN`] hash
+/// functions, the smallest hash of its lowercased character shingles. The
+/// share of equal values between two signatures estimates how much of their
+/// shingles the texts have in common.
+pub fn min_hash(text: &str) -> Vec<u32> {
+    let chars: Vec<char> = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .chars()
+        .collect();
+    let mut signature = vec![u32::MAX; SIGNATURE_LEN];
+    let mut shingle = String::new();
+    for window in chars.windows(SHINGLE_CHARS.min(chars.len()).max(1)) {
+        shingle.clear();
+        shingle.extend(window);
+        let base = fnv1a(shingle.as_bytes());
+        for (i, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(mix(base ^ mix(i as u64 + 1)) as u32);
+        }
+    }
+    signature
+}
+
+/// The estimated Jaccard similarity of the texts two signatures came from.
+pub fn similarity(a: &[u32], b: &[u32]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    equal as f64 / a.len().max(1) as f64
+}
+
+/// What happens to a sentence found to be a near duplicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NearDuplicateAction {
+    /// Store it with `nearDuplicateOf`, the `textHash` of the sentence it
+    /// resembles.
+    Flag,
+    /// Leave it out.
+    Skip,
+}
+
+impl FromStr for NearDuplicateAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "flag" => Ok(NearDuplicateAction::Flag),
+            "skip" => Ok(NearDuplicateAction::Skip),
+            other => Err(format!(
+                "invalid near-duplicate action '{}' (expected flag or skip)",
+                other
+            )),
+        }
+    }
+}
+
+/// The sentences seen so far in a run, indexed by bands of their MinHash
+/// signatures. Only sentences that weren't duplicates themselves are kept,
+/// at a few hundred bytes each. Shared by every worker of the run.
+#[derive(Debug)]
+pub struct NearDuplicates {
+    pub action: NearDuplicateAction,
+    /// Estimated similarity from which a sentence counts as a duplicate.
+    pub threshold: f64,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// Signature and `textHash` of each kept sentence.
+    sentences: Vec<(Vec<u32>, String)>,
+    /// For each band, the sentences with each bucket of it.
+    bands: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+impl NearDuplicates {
+    pub fn new(action: NearDuplicateAction, threshold: f64) -> Self {
+        NearDuplicates {
+            action,
+            threshold,
+            seen: Mutex::new(Seen {
+                sentences: Vec::new(),
+                bands: vec![HashMap::new(); SIGNATURE_LEN / BAND_ROWS],
+            }),
+        }
+    }
+
+    /// Looks for an earlier sentence at least [`NearDuplicates::threshold`]
+    /// similar to the one with `signature`, returning its `text_hash`. When
+    /// there is none the sentence is remembered under `text_hash`.
+    pub fn check(&self, signature: &[u32], text_hash: &str) -> Option<String> {
+        let buckets: Vec<u64> = signature
+            .chunks(BAND_ROWS)
+            .map(|band| {
+                let bytes: Vec<u8> = band.iter().flat_map(|v| v.to_le_bytes()).collect();
+                fnv1a(&bytes)
+            })
+            .collect();
+
+        let mut seen = self.seen.lock().expect("near-duplicate index poisoned");
+        let found = buckets
+            .iter()
+            .zip(&seen.bands)
+            .filter_map(|(bucket, band)| band.get(bucket))
+            .flatten()
+            .map(|&index| &seen.sentences[index])
+            .find(|(other, _)| similarity(signature, other) >= self.threshold)
+            .map(|(_, hash)| hash.clone());
+        if found.is_none() {
+            let index = seen.sentences.len();
+            seen.sentences
+                .push((signature.to_vec(), text_hash.to_string()));
+            for (bucket, band) in buckets.into_iter().zip(&mut seen.bands) {
+                band.entry(bucket).or_default().push(index);
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seen_filter_catches_repeats() {
+        let filter = SeenFilter::new(10_000);
+        assert!(filter.size() < 20_000);
+        let hashes: Vec<String> = (0..10_000)
+            .map(|i| crate::ingest::text_hash(&format!("Sentence number {}.", i)))
+            .collect();
+        let false_positives = hashes.iter().filter(|hash| filter.insert(hash)).count();
+        assert!(false_positives < 50, "{}", false_positives);
+        assert!(hashes.iter().all(|hash| filter.insert(hash)));
+    }
+
+    #[test]
+    fn similar_sentences_have_similar_signatures() {
+        let a = min_hash("Shares of the company rose 5 percent on Monday after the report.");
+        let b = min_hash("Shares of the company rose 6 percent on Tuesday after the report.");
+        let c = min_hash("The weather in the mountains was cold and wet all week long.");
+        assert_eq!(
+            a,
+            min_hash("shares of the  company rose 5 percent on Monday after the report.")
+        );
+        assert!(similarity(&a, &b) > 0.5, "{}", similarity(&a, &b));
+        assert!(similarity(&a, &c) < 0.2, "{}", similarity(&a, &c));
+    }
+
+    #[test]
+    fn finds_near_duplicates_seen_earlier() {
+        let near = NearDuplicates::new(NearDuplicateAction::Skip, 0.5);
+        let original = "Shares of the company rose 5 percent on Monday after the report.";
+        assert_eq!(near.check(&min_hash(original), "a"), None);
+        assert_eq!(
+            near.check(
+                &min_hash("The weather was cold and wet all week long."),
+                "b"
+            ),
+            None
+        );
+        assert_eq!(
+            near.check(
+                &min_hash("Shares of the company rose 6 percent on Tuesday after the report."),
+                "c"
+            ),
+            Some("a".to_string())
+        );
+        assert_eq!("SKIP".parse(), Ok(NearDuplicateAction::Skip));
+    }
+}
@@ -0,0 +1,133 @@
+//! Character encoding detection and transcoding to UTF-8.
+//!
+//! Input is decoded after decompression, so the rest of the pipeline only
+//! ever sees UTF-8 without a byte order mark.
+
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{BufRead, BufReader, Cursor, Read};
+use std::str::FromStr;
+
+/// How many bytes are looked at when guessing a stream's encoding.
+const SAMPLE_LEN: usize = 64 * 1024;
+
+/// The character encoding of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Honour a byte order mark, keep valid UTF-8 as it is, and otherwise
+    /// guess from the first 64 KiB, as a browser does for unlabelled pages.
+    Auto,
+    /// Any WHATWG label, such as `latin1`, `windows-1252` or `shift_jis`.
+    Fixed(&'static Encoding),
+}
+
+impl FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(TextEncoding::Auto);
+        }
+        Encoding::for_label(s.as_bytes())
+            .map(TextEncoding::Fixed)
+            .ok_or_else(|| format!("unknown encoding '{}'", s))
+    }
+}
+
+/// Guesses the encoding of `sample`, the start of a stream. `complete` says
+/// whether the sample is the whole stream.
+pub fn detect(sample: &[u8], complete: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        // A character cut off by the end of the sample is still UTF-8
+        Err(e) if e.error_len().is_none() && !complete => return UTF_8,
+        Err(_) => {}
+    }
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(sample, complete);
+    detector.guess(None, Utf8Detection::Allow)
+}
+
+/// Wraps `reader` in a decoder producing UTF-8, minus any byte order mark.
+/// Bytes that aren't valid in the encoding become U+FFFD rather than read
+/// errors.
+pub fn decode_reader(
+    mut reader: Box<dyn BufRead + Send>,
+    encoding: TextEncoding,
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    let encoding = match encoding {
+        TextEncoding::Fixed(encoding) => encoding,
+        TextEncoding::Auto => {
+            let mut sample = Vec::with_capacity(SAMPLE_LEN);
+            (&mut reader)
+                .take(SAMPLE_LEN as u64)
+                .read_to_end(&mut sample)?;
+            let encoding = detect(&sample, sample.len() < SAMPLE_LEN);
+            reader = Box::new(BufReader::new(Cursor::new(sample).chain(reader)));
+            encoding
+        }
+    };
+
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(Some(encoding))
+        .bom_override(true)
+        .strip_bom(true)
+        .build(reader);
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+/// Decodes a whole document held in memory to UTF-8.
+pub fn decode_bytes(data: &[u8], encoding: TextEncoding) -> String {
+    let encoding = match encoding {
+        TextEncoding::Fixed(encoding) => encoding,
+        TextEncoding::Auto => detect(data, true),
+    };
+    // A BOM overrides the chosen encoding, as it does for streams
+    let (text, _, _) = encoding.decode(data);
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(data: &[u8], encoding: TextEncoding) -> String {
+        let mut text = String::new();
+        decode_reader(Box::new(Cursor::new(data.to_vec())), encoding)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn detects_windows_1252() {
+        let latin = b"Caf\xe9 cr\xe8me, na\xefve fa\xe7ade and \x93smart quotes\x94 everywhere.";
+        let expected = "Café crème, naïve façade and \u{201c}smart quotes\u{201d} everywhere.";
+        assert_eq!(decode_all(latin, TextEncoding::Auto), expected);
+        assert_eq!(decode_bytes(latin, TextEncoding::Auto), expected);
+    }
+
+    #[test]
+    fn strips_byte_order_marks() {
+        assert_eq!(
+            decode_all(b"\xef\xbb\xbfHello.", TextEncoding::Auto),
+            "Hello."
+        );
+        let utf16 = b"\xff\xfeH\0i\0.\0";
+        assert_eq!(decode_all(utf16, TextEncoding::Auto), "Hi.");
+        assert_eq!(decode_bytes(utf16, TextEncoding::Auto), "Hi.");
+    }
+
+    #[test]
+    fn forced_encoding_wins_over_detection() {
+        let encoding: TextEncoding = "latin1".parse().unwrap();
+        // Valid UTF-8 for "é", read as two Latin-1 characters
+        assert_eq!(decode_all("é".as_bytes(), encoding), "Ã©");
+        assert!("klingon".parse::<TextEncoding>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Errors of an ingestion run, with the file, line and stage they happened
+//! at.
+//!
+//! A line that can't be parsed is left out, one that can't be tagged is
+//! stored untagged, and both are counted in the run's [`ErrorSummary`]; a
+//! source that can't be read on, or a sink that can't be written to, stops
+//! the run.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::BoxError;
+
+/// What was being done with a source when it failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Read,
+    Parse,
+    Extract,
+    Tag,
+    Checkpoint,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Read => "reading",
+            Stage::Parse => "parsing",
+            Stage::Extract => "extracting text",
+            Stage::Tag => "tagging",
+            Stage::Checkpoint => "saving the checkpoint",
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IngestError {
+    /// A line that failed but doesn't stop the run.
+    #[error("{file}:{line}: error {stage}: {source}")]
+    Line {
+        file: String,
+        line: usize,
+        stage: Stage,
+        source: BoxError,
+    },
+    /// A source that can't be read on, at `line` if it got that far.
+    #[error("{file}{}: error {stage}: {source}", .line.map(|line| format!(":{}", line)).unwrap_or_default())]
+    Source {
+        file: String,
+        line: Option<usize>,
+        stage: Stage,
+        source: BoxError,
+    },
+    /// A batch the sink failed to write.
+    #[error("error writing sentences of {file}: {source}")]
+    Sink { file: String, source: BoxError },
+    /// The run was asked to stop while `file` was being ingested; what was
+    /// read of it is written, and its progress saved if checkpointing.
+    #[error("interrupted while ingesting {file}")]
+    Interrupted { file: String },
+    #[error("an ingestion worker failed: {0}")]
+    Worker(#[from] tokio::task::JoinError),
+}
+
+impl IngestError {
+    /// Whether the run can go on past the error, leaving out what failed.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, IngestError::Line { .. })
+    }
+
+    /// Whether it's worth going on to other sources after the error, as it
+    /// was with one source and not with where sentences go.
+    pub fn is_per_source(&self) -> bool {
+        matches!(self, IngestError::Line { .. } | IngestError::Source { .. })
+    }
+
+    /// `error` from ingesting `file`, given the stage it failed at unless it
+    /// already has one.
+    pub fn in_source(file: &str, stage: Stage, error: BoxError) -> Self {
+        match error.downcast::<IngestError>() {
+            Ok(error) => *error,
+            Err(error) => IngestError::Source {
+                file: file.to_string(),
+                line: None,
+                stage,
+                source: error,
+            },
+        }
+    }
+}
+
+/// The recoverable errors of a run, counted by stage, with the first of
+/// each kept as an example. Clones count into the same summary.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorSummary {
+    failed: Arc<Mutex<BTreeMap<Stage, (usize, String)>>>,
+}
+
+impl ErrorSummary {
+    /// Counts `error` if it's a [line](IngestError::Line) that failed.
+    pub fn record(&self, error: &IngestError) {
+        if let IngestError::Line { stage, .. } = error {
+            let mut failed = self.failed.lock().expect("error summary poisoned");
+            failed
+                .entry(*stage)
+                .or_insert_with(|| (0, error.to_string()))
+                .0 += 1;
+        }
+    }
+
+    /// Lines that failed in all.
+    pub fn total(&self) -> usize {
+        let failed = self.failed.lock().expect("error summary poisoned");
+        failed.values().map(|(count, _)| count).sum()
+    }
+
+    /// A line per stage that had errors, such as `3 lines failed parsing,
+    /// the first: a.jsonl:4: error parsing: expected value`.
+    pub fn summary(&self) -> Vec<String> {
+        let failed = self.failed.lock().expect("error summary poisoned");
+        failed
+            .iter()
+            .map(|(stage, (count, first))| {
+                format!("{} lines failed {}, the first: {}", count, stage, first)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_context_and_summarizes_failed_lines() {
+        let line = |line| IngestError::Line {
+            file: "a.jsonl".to_string(),
+            line,
+            stage: Stage::Parse,
+            source: "expected value".into(),
+        };
+        let summary = ErrorSummary::default();
+        summary.record(&line(4));
+        summary.record(&line(9));
+        assert_eq!(summary.total(), 2);
+        assert_eq!(
+            summary.summary(),
+            vec!["2 lines failed parsing, the first: a.jsonl:4: error parsing: expected value"]
+        );
+
+        let fatal = IngestError::in_source("a.jsonl", Stage::Read, "disk gone".into());
+        assert!(!fatal.is_recoverable() && fatal.is_per_source());
+        assert_eq!(fatal.to_string(), "a.jsonl: error reading: disk gone");
+        summary.record(&fatal);
+        assert_eq!(summary.total(), 2);
+
+        let boxed: BoxError = line(7).into();
+        let unwrapped = IngestError::in_source("b.txt", Stage::Read, boxed);
+        assert!(unwrapped.is_recoverable());
+    }
+}
@@ -0,0 +1,60 @@
+//! Delimited tables with one record per row.
+
+use super::Block;
+use crate::BoxError;
+
+/// Returns one block per row holding the `text_column` cell, with every other
+/// non-empty cell stored under its header name. The first row must be a
+/// header.
+pub fn extract_csv(data: &[u8], delimiter: u8, text_column: &str) -> Result<Vec<Block>, BoxError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(data);
+
+    let headers = reader.headers()?.clone();
+    let text_index = headers
+        .iter()
+        .position(|header| header == text_column)
+        .ok_or_else(|| format!("no '{}' column in the header row", text_column))?;
+
+    let mut blocks = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let mut block = Block::new(record.get(text_index).unwrap_or_default());
+        for (index, (header, value)) in headers.iter().zip(record.iter()).enumerate() {
+            if index != text_index && !header.is_empty() && !value.is_empty() {
+                block.metadata.insert(header, value);
+            }
+        }
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn splits_text_column_from_metadata() {
+        let tsv = "id\tspeaker\ttext\tdate\n\
+                   1\tAlice\tHello there. How are you?\t2021-01-01\n\
+                   2\t\t\"Quoted, with a tab\tinside.\"\t2021-01-02\n";
+        let blocks = extract_csv(tsv.as_bytes(), b'\t', "text").unwrap();
+
+        assert_eq!(blocks[0].text, "Hello there. How are you?");
+        assert_eq!(
+            blocks[0].metadata,
+            doc! { "id": "1", "speaker": "Alice", "date": "2021-01-01" }
+        );
+        assert_eq!(blocks[1].text, "Quoted, with a tab\tinside.");
+        assert_eq!(blocks[1].metadata, doc! { "id": "2", "date": "2021-01-02" });
+    }
+
+    #[test]
+    fn requires_the_text_column() {
+        assert!(extract_csv(b"id,body\n1,hi\n", b',', "text").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Word `.docx` documents.
+
+use std::io::{Cursor, Read};
+
+use super::Block;
+use crate::BoxError;
+
+const WORD_NS: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const MARKUP_COMPATIBILITY_NS: &str = "http://schemas.openxmlformats.org/markup-compatibility/2006";
+
+/// Returns the paragraphs of the document body. With `include_extras`,
+/// headers, footers, footnotes and endnotes follow, each tagged with the
+/// `docxPart` it came from.
+pub fn extract_docx(data: &[u8], include_extras: bool) -> Result<Vec<Block>, BoxError> {
+    let mut document = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut blocks = paragraphs(&read_part(&mut document, "word/document.xml")?)?;
+
+    if include_extras {
+        let mut extras: Vec<String> = document
+            .file_names()
+            .filter_map(|name| name.ok().map(|name| name.into_owned()))
+            .filter(|name| is_extra_part(name))
+            .collect();
+        extras.sort();
+
+        for name in extras {
+            let part = name["word/".len()..name.len() - ".xml".len()].to_string();
+            for mut block in paragraphs(&read_part(&mut document, &name)?)? {
+                block.metadata.insert("docxPart", part.as_str());
+                blocks.push(block);
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+/// Header, footer and note parts, such as `word/footer1.xml`.
+fn is_extra_part(name: &str) -> bool {
+    let Some(part) = name.strip_prefix("word/") else {
+        return false;
+    };
+    part.ends_with(".xml")
+        && ["header", "footer", "footnotes", "endnotes"]
+            .iter()
+            .any(|prefix| part.starts_with(prefix))
+}
+
+fn read_part(
+    document: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+) -> Result<String, BoxError> {
+    let mut part = document
+        .by_name(name)
+        .map_err(|e| format!("DOCX part {}: {}", name, e))?;
+    let mut xml = String::new();
+    part.read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+/// Collects the text runs of every `w:p` paragraph in a WordprocessingML part.
+fn paragraphs(xml: &str) -> Result<Vec<Block>, BoxError> {
+    let xml = roxmltree::Document::parse(xml)?;
+    let mut blocks = Vec::new();
+
+    let paragraphs = xml
+        .descendants()
+        .filter(|n| n.has_tag_name((WORD_NS, "p")) && !is_fallback(*n));
+    for paragraph in paragraphs {
+        // Paragraphs nested in text boxes are visited on their own
+        let mut text = String::new();
+        for node in paragraph.descendants() {
+            if !belongs_to(node, paragraph) {
+                continue;
+            }
+            if node.has_tag_name((WORD_NS, "t")) {
+                text.push_str(node.text().unwrap_or_default());
+            } else if node.has_tag_name((WORD_NS, "tab"))
+                || node.has_tag_name((WORD_NS, "br"))
+                || node.has_tag_name((WORD_NS, "cr"))
+            {
+                text.push(' ');
+            }
+        }
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            blocks.push(Block::new(text));
+        }
+    }
+    Ok(blocks)
+}
+
+/// Whether `node` is part of `paragraph` itself rather than of a paragraph
+/// nested inside it.
+fn belongs_to(node: roxmltree::Node, paragraph: roxmltree::Node) -> bool {
+    node.ancestors()
+        .take_while(|ancestor| *ancestor != paragraph)
+        .all(|ancestor| !ancestor.has_tag_name((WORD_NS, "p")))
+}
+
+/// Whether `node` is inside the fallback copy Word writes next to drawings
+/// and text boxes, which would otherwise be read twice.
+fn is_fallback(node: roxmltree::Node) -> bool {
+    node.ancestors()
+        .any(|ancestor| ancestor.has_tag_name((MARKUP_COMPATIBILITY_NS, "Fallback")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn build_docx(parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, xml) in parts {
+            writer.start_file(*name, options).unwrap();
+            writer
+                .write_all(
+                    format!(
+                        r#"<w:document xmlns:w="{}" xmlns:mc="{}">{}</w:document>"#,
+                        WORD_NS, MARKUP_COMPATIBILITY_NS, xml
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_body_paragraphs_and_optional_extras() {
+        let docx = build_docx(&[
+            (
+                "word/document.xml",
+                "<w:body>\
+                   <w:p><w:r><w:t>The first </w:t></w:r><w:r><w:t>paragraph.</w:t></w:r></w:p>\
+                   <w:p><w:r><w:t>Second</w:t><w:tab/><w:t>one.</w:t></w:r></w:p>\
+                   <w:p><w:r><mc:AlternateContent><mc:Choice><w:p><w:r><w:t>Boxed text.</w:t></w:r></w:p></mc:Choice>\
+                   <mc:Fallback><w:p><w:r><w:t>Boxed text.</w:t></w:r></w:p></mc:Fallback></mc:AlternateContent></w:r></w:p>\
+                 </w:body>",
+            ),
+            (
+                "word/footer1.xml",
+                "<w:p><w:r><w:t>Page footer</w:t></w:r></w:p>",
+            ),
+        ]);
+
+        let body: Vec<_> = extract_docx(&docx, false)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.text)
+            .collect();
+        assert_eq!(body, ["The first paragraph.", "Second one.", "Boxed text."]);
+
+        let all = extract_docx(&docx, true).unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[3].text, "Page footer");
+        assert_eq!(all[3].metadata.get_str("docxPart").unwrap(), "footer1");
+        assert!(!all[0].metadata.contains_key("docxPart"));
+    }
+}
@@ -0,0 +1,178 @@
+//! EPUB books, read chapter by chapter in reading order.
+
+use mongodb::bson::Bson;
+use std::io::{Cursor, Read};
+
+use super::html::{extract_html, heading};
+use super::Block;
+use crate::BoxError;
+
+/// Returns the text of every chapter in the book's spine, in reading order.
+/// Each block carries the 1-based `chapterIndex` of its spine entry and,
+/// when the chapter has a heading or title, `chapterTitle`.
+pub fn extract_epub(data: &[u8]) -> Result<Vec<Block>, BoxError> {
+    let mut book = zip::ZipArchive::new(Cursor::new(data))?;
+
+    let container = read_entry(&mut book, "META-INF/container.xml")?;
+    let container = roxmltree::Document::parse(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|n| n.has_tag_name("rootfile"))
+        .and_then(|n| n.attribute("full-path"))
+        .ok_or("container.xml names no package document")?
+        .to_string();
+
+    let package = read_entry(&mut book, &opf_path)?;
+    let package = roxmltree::Document::parse(&package)?;
+    let base = opf_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+    let mut blocks = Vec::new();
+    let mut chapter_index = 0;
+    for itemref in package.descendants().filter(|n| n.has_tag_name("itemref")) {
+        // Non-linear items are covers, footnotes and the like
+        if itemref.attribute("linear") == Some("no") {
+            continue;
+        }
+        let Some(href) = itemref.attribute("idref").and_then(|id| {
+            package
+                .descendants()
+                .find(|n| n.has_tag_name("item") && n.attribute("id") == Some(id))
+                .and_then(|item| item.attribute("href"))
+        }) else {
+            continue;
+        };
+
+        let chapter = read_entry(&mut book, &resolve(base, href))?;
+        chapter_index += 1;
+        let title = heading(&chapter);
+        for mut block in extract_html(&chapter) {
+            block
+                .metadata
+                .insert("chapterIndex", Bson::Int32(chapter_index));
+            if let Some(title) = &title {
+                block.metadata.insert("chapterTitle", title.as_str());
+            }
+            blocks.push(block);
+        }
+    }
+    Ok(blocks)
+}
+
+fn read_entry(book: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String, BoxError> {
+    let mut entry = book
+        .by_name(name)
+        .map_err(|e| format!("EPUB entry {}: {}", name, e))?;
+    let mut contents = String::new();
+    entry.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Resolves a manifest href, which is relative to the package document and
+/// may be percent-encoded, to a path inside the zip.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or(href);
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    percent_decode(&parts.join("/"))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn build_epub(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_chapters_in_spine_order() {
+        let epub = build_epub(&[
+            ("mimetype", "application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#,
+            ),
+            (
+                "OEBPS/content.opf",
+                r#"<package xmlns="http://www.idpf.org/2007/opf">
+                     <manifest>
+                       <item id="cover" href="cover.xhtml"/>
+                       <item id="c1" href="text/chapter%201.xhtml"/>
+                       <item id="c2" href="text/two.xhtml"/>
+                     </manifest>
+                     <spine>
+                       <itemref idref="cover" linear="no"/>
+                       <itemref idref="c2"/>
+                       <itemref idref="c1"/>
+                     </spine>
+                   </package>"#,
+            ),
+            ("OEBPS/cover.xhtml", "<body><p>Cover page text.</p></body>"),
+            (
+                "OEBPS/text/chapter 1.xhtml",
+                "<body><h1>The End</h1><p>It was over.</p></body>",
+            ),
+            (
+                "OEBPS/text/two.xhtml",
+                "<html><head><title>Beginnings</title></head><body><p>It began.</p></body></html>",
+            ),
+        ]);
+
+        let blocks = extract_epub(&epub).unwrap();
+        let summary: Vec<_> = blocks
+            .iter()
+            .map(|b| {
+                (
+                    b.text.as_str(),
+                    b.metadata.get_i32("chapterIndex").unwrap(),
+                    b.metadata.get_str("chapterTitle").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("It began.", 1, "Beginnings"),
+                ("The End", 2, "The End"),
+                ("It was over.", 2, "The End"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Main-content extraction from HTML pages.
+
+use scraper::node::Node;
+use scraper::{ElementRef, Html, Selector};
+
+use super::Block;
+
+/// Elements whose contents are never corpus text.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "template", "head", "nav", "header", "footer", "aside", "form",
+    "button", "select", "iframe", "svg", "canvas", "menu",
+];
+
+/// ARIA roles marking page chrome rather than content.
+const SKIPPED_ROLES: &[&str] = &[
+    "navigation",
+    "banner",
+    "contentinfo",
+    "complementary",
+    "search",
+];
+
+/// Elements that start a new block of text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "main",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
+
+/// Blocks with more than this share of their text inside links are treated as
+/// menus or link lists and dropped.
+const MAX_LINK_DENSITY: f64 = 0.5;
+
+/// Returns the paragraphs of the page's main content. When the page marks it
+/// with `<main>`, `<article>` or `role="main"` only that part is read;
+/// otherwise the whole body is, minus navigation, headers, footers, scripts
+/// and link-heavy blocks.
+pub fn extract_html(html: &str) -> Vec<Block> {
+    let document = Html::parse_document(html);
+    let main = ["main", "article", r#"[role="main"]"#, "body"]
+        .iter()
+        .find_map(|selector| {
+            let selector = Selector::parse(selector).expect("valid selector");
+            document.select(&selector).next()
+        })
+        .unwrap_or_else(|| document.root_element());
+
+    let mut extractor = Extractor::default();
+    extractor.walk(main, false);
+    extractor.finish_block();
+    extractor.blocks
+}
+
+/// The page's first `h1`-`h3` heading, or its `<title>` if it has none.
+pub fn heading(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    ["h1, h2, h3", "title"].iter().find_map(|selector| {
+        let selector = Selector::parse(selector).expect("valid selector");
+        let element = document.select(&selector).next()?;
+        let text = element.text().collect::<Vec<_>>().join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+#[derive(Default)]
+struct Extractor {
+    blocks: Vec<Block>,
+    text: String,
+    link_chars: usize,
+}
+
+impl Extractor {
+    fn walk(&mut self, element: ElementRef, in_link: bool) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text, in_link),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    if is_skipped(child) {
+                        continue;
+                    }
+                    let name = child.value().name();
+                    let is_block = BLOCK_ELEMENTS.contains(&name);
+                    if is_block {
+                        self.finish_block();
+                    }
+                    self.walk(child, in_link || name == "a");
+                    if is_block {
+                        self.finish_block();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str, in_link: bool) {
+        // Collapse whitespace runs, including ones spanning inline elements
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !self.text.is_empty() && !self.text.ends_with(' ') {
+                    self.text.push(' ');
+                }
+            } else {
+                self.text.push(c);
+                if in_link {
+                    self.link_chars += 1;
+                }
+            }
+        }
+    }
+
+    fn finish_block(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let link_chars = std::mem::take(&mut self.link_chars);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let text_chars = text.chars().filter(|c| !c.is_whitespace()).count();
+        if link_chars as f64 > text_chars as f64 * MAX_LINK_DENSITY {
+            return;
+        }
+        self.blocks.push(Block::new(text));
+    }
+}
+
+fn is_skipped(element: ElementRef) -> bool {
+    let value = element.value();
+    SKIPPED_ELEMENTS.contains(&value.name())
+        || value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+        || value
+            .attr("role")
+            .is_some_and(|role| SKIPPED_ROLES.contains(&role))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(html: &str) -> Vec<String> {
+        extract_html(html).into_iter().map(|b| b.text).collect()
+    }
+
+    #[test]
+    fn keeps_paragraphs_and_drops_chrome() {
+        let html = r#"<html><head><title>Page</title><style>p { color: red }</style></head>
+            <body>
+              <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+              <div role="banner">Site banner</div>
+              <h1>The headline</h1>
+              <p>The first paragraph has <b>bold</b> and <a href="/x">a link</a> in it.</p>
+              <script>var tracking = "Not a sentence.";</script>
+              <p>A second
+                 paragraph.</p>
+              <footer>Copyright 2024</footer>
+            </body></html>"#;
+
+        assert_eq!(
+            texts(html),
+            [
+                "The headline",
+                "The first paragraph has bold and a link in it.",
+                "A second paragraph."
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_main_content() {
+        let html = "<body><div>Sidebar text outside main.</div>\
+                    <main><p>Only this is the article.</p></main></body>";
+        assert_eq!(texts(html), ["Only this is the article."]);
+    }
+
+    #[test]
+    fn drops_link_lists() {
+        let html = r#"<body><p>Real prose with <a href="/a">one link</a> in the middle of it.</p>
+            <div><a href="/1">Related one</a> | <a href="/2">Related two</a></div></body>"#;
+        assert_eq!(
+            texts(html),
+            ["Real prose with one link in the middle of it."]
+        );
+    }
+}
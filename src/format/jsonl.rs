@@ -0,0 +1,58 @@
+//! JSON Lines: one JSON object per line.
+
+use mongodb::bson::Bson;
+
+use super::Block;
+use crate::BoxError;
+
+/// Parses one JSONL record into a block holding its `text_field`, with every
+/// other field passed through as metadata. Values are read as MongoDB
+/// Extended JSON, so `{"$date": ...}` arrives as a date. Blank lines and
+/// records without the text field yield an empty block.
+pub fn parse_jsonl_line(line: &str, text_field: &str) -> Result<Block, BoxError> {
+    if line.trim().is_empty() {
+        return Ok(Block::default());
+    }
+    let serde_json::Value::Object(mut record) = serde_json::from_str(line)? else {
+        return Err("expected a JSON object".into());
+    };
+
+    let text = match record.remove(text_field) {
+        Some(serde_json::Value::String(text)) => text,
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(_) => return Err(format!("'{}' is not a string", text_field).into()),
+    };
+    let mut block = Block::new(text);
+    for (key, value) in record {
+        block.metadata.insert(key, Bson::try_from(value)?);
+    }
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn passes_metadata_through() {
+        let block = parse_jsonl_line(
+            r#"{"body": "It rained. Then it stopped.", "id": 7, "tags": ["weather"], "source": {"site": "news"}}"#,
+            "body",
+        )
+        .unwrap();
+        assert_eq!(block.text, "It rained. Then it stopped.");
+        assert_eq!(
+            block.metadata,
+            doc! { "id": 7, "tags": ["weather"], "source": { "site": "news" } }
+        );
+    }
+
+    #[test]
+    fn tolerates_blank_lines_and_missing_text() {
+        assert_eq!(parse_jsonl_line("  ", "text").unwrap(), Block::default());
+        assert_eq!(parse_jsonl_line(r#"{"id": 1}"#, "text").unwrap().text, "");
+        assert!(parse_jsonl_line("[1, 2]", "text").is_err());
+        assert!(parse_jsonl_line(r#"{"text": 5}"#, "text").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Markdown documents, reduced to the prose they contain.
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use super::Block;
+
+/// Returns the paragraphs, headings and list items of a Markdown document as
+/// plain text: link and emphasis syntax is dropped in favour of the text it
+/// wraps, inline code keeps its contents, and tables, images, raw HTML and
+/// front matter are left out. Code blocks become one block per line unless
+/// `skip_code` is set, in which case they are dropped too.
+pub fn extract_markdown(markdown: &str, skip_code: bool) -> Vec<Block> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut skip_depth = 0;
+    let mut in_code = false;
+
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(tag) => {
+                if is_skipped(&tag, skip_code) {
+                    skip_depth += 1;
+                } else if skip_depth == 0 && starts_block(&TagEnd::from(tag.clone())) {
+                    finish_block(&mut blocks, &mut text);
+                    in_code = matches!(tag, Tag::CodeBlock(_));
+                }
+            }
+            Event::End(tag) => {
+                if is_skipped_end(tag, skip_code) {
+                    skip_depth -= 1;
+                } else if skip_depth == 0 && starts_block(&tag) {
+                    finish_block(&mut blocks, &mut text);
+                    in_code = false;
+                }
+            }
+            _ if skip_depth > 0 => {}
+            Event::Text(content) if in_code => {
+                for line in content.lines() {
+                    text.push_str(line);
+                    finish_block(&mut blocks, &mut text);
+                }
+            }
+            Event::Text(content) | Event::Code(content) => text.push_str(&content),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    finish_block(&mut blocks, &mut text);
+    blocks
+}
+
+fn is_skipped(tag: &Tag, skip_code: bool) -> bool {
+    matches!(
+        tag,
+        Tag::Table(_) | Tag::Image { .. } | Tag::HtmlBlock | Tag::MetadataBlock(_)
+    ) || (skip_code && matches!(tag, Tag::CodeBlock(_)))
+}
+
+fn is_skipped_end(tag: TagEnd, skip_code: bool) -> bool {
+    matches!(
+        tag,
+        TagEnd::Table | TagEnd::Image | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)
+    ) || (skip_code && tag == TagEnd::CodeBlock)
+}
+
+fn starts_block(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::Item
+            | TagEnd::BlockQuote(_)
+            | TagEnd::CodeBlock
+            | TagEnd::FootnoteDefinition
+    )
+}
+
+fn finish_block(blocks: &mut Vec<Block>, text: &mut String) {
+    let block = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    text.clear();
+    if !block.is_empty() {
+        blocks.push(Block::new(block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const README: &str = "\
+# Getting *started*
+
+Read [the guide](https://example.com/guide) and run `cargo build`
+before anything else.
+
+| Flag | Meaning |
+|------|---------|
+| -v   | verbose |
+
+![diagram](arch.png)
+
+- First item.
+- Second item.
+
+```rust
+fn main() {}
+```
+";
+
+    fn texts(markdown: &str, skip_code: bool) -> Vec<String> {
+        extract_markdown(markdown, skip_code)
+            .into_iter()
+            .map(|b| b.text)
+            .collect()
+    }
+
+    #[test]
+    fn strips_markdown_syntax() {
+        assert_eq!(
+            texts(README, false),
+            [
+                "Getting started",
+                "Read the guide and run cargo build before anything else.",
+                "First item.",
+                "Second item.",
+                "fn main() {}",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_skip_code_blocks() {
+        assert_eq!(texts(README, true).last().unwrap(), "Second item.");
+    }
+}
@@ -0,0 +1,273 @@
+//! Document formats that need to be turned into plain text before cleaning.
+//!
+//! Plain text and JSON Lines are streamed line by line through [`parse_line`].
+//! Every other format is read whole and broken into [`Block`]s, which the
+//! ingestion loop then treats like lines.
+//! Adding a format means a module here, an [`InputFormat`] to select it, and
+//! a case in [`extract_blocks`].
+
+mod csv;
+mod docx;
+mod epub;
+mod html;
+mod jsonl;
+mod markdown;
+mod pdf;
+mod xml;
+
+use mongodb::bson::Document;
+use std::str::FromStr;
+
+use crate::BoxError;
+
+pub use self::csv::extract_csv;
+pub use docx::extract_docx;
+pub use epub::extract_epub;
+pub use html::extract_html;
+pub use jsonl::parse_jsonl_line;
+pub use markdown::extract_markdown;
+pub use pdf::extract_pdf;
+pub use xml::{extract_xml, XmlSelector};
+
+/// A run of text the extractor considers one unit, usually a paragraph,
+/// with any fields it learned about where the text came from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Block {
+    pub text: String,
+    pub metadata: Document,
+    /// Where the text starts in the source, for blocks that are a line of it
+    /// taken verbatim.
+    pub offset: Option<SourceOffset>,
+    /// The line of the source it comes from, for blocks read a line at a
+    /// time; the pieces of a line too long to read at once share it.
+    pub line: Option<usize>,
+    /// Bytes of the source it was read from, line break included, for
+    /// blocks read a line at a time.
+    pub source_bytes: Option<usize>,
+}
+
+impl Block {
+    pub fn new(text: impl Into<String>) -> Self {
+        Block {
+            text: text.into(),
+            metadata: Document::new(),
+            offset: None,
+            line: None,
+            source_bytes: None,
+        }
+    }
+}
+
+/// A position in a source as decoded to UTF-8, in bytes and in characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceOffset {
+    pub byte: usize,
+    pub char: usize,
+}
+
+impl SourceOffset {
+    /// The position just past `text`, starting here.
+    pub fn advance(self, text: &str) -> Self {
+        SourceOffset {
+            byte: self.byte + text.len(),
+            char: self.char + text.chars().count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Text,
+    /// Web pages; markup, scripts and navigation are dropped and only the main
+    /// content is kept.
+    Html,
+    /// PDFs; paragraphs are rebuilt across line and page breaks and tagged
+    /// with `pageNumber`.
+    Pdf,
+    /// EPUB books; chapters are read in spine order and tagged with
+    /// `chapterIndex` and `chapterTitle`.
+    Epub,
+    /// Word documents; body paragraphs, plus headers, footers and notes when
+    /// [`FormatOptions::docx_extras`] is set.
+    Docx,
+    /// Markdown; syntax is removed and tables, images and raw HTML dropped.
+    Markdown,
+    /// Comma-separated rows; one column is split and the rest become fields.
+    Csv,
+    /// Like [`InputFormat::Csv`], separated by tabs.
+    Tsv,
+    /// One JSON object per line; one field is split and the rest become fields.
+    Jsonl,
+    /// XML such as TEI; text and metadata are picked out with
+    /// [`XmlSelector`]s.
+    Xml,
+}
+
+impl InputFormat {
+    /// Guesses the format from a file name, looking past a compression
+    /// extension. Anything unrecognised is treated as plain text.
+    pub fn detect(name: &str) -> Self {
+        let lower = name.to_ascii_lowercase();
+        let lower = [".gz", ".zst", ".bz2"]
+            .iter()
+            .find_map(|ext| lower.strip_suffix(ext))
+            .unwrap_or(&lower);
+        if lower.ends_with(".html") || lower.ends_with(".htm") || lower.ends_with(".xhtml") {
+            InputFormat::Html
+        } else if lower.ends_with(".pdf") {
+            InputFormat::Pdf
+        } else if lower.ends_with(".epub") {
+            InputFormat::Epub
+        } else if lower.ends_with(".docx") {
+            InputFormat::Docx
+        } else if lower.ends_with(".md") || lower.ends_with(".markdown") {
+            InputFormat::Markdown
+        } else if lower.ends_with(".csv") {
+            InputFormat::Csv
+        } else if lower.ends_with(".tsv") || lower.ends_with(".tab") {
+            InputFormat::Tsv
+        } else if lower.ends_with(".jsonl") || lower.ends_with(".ndjson") {
+            InputFormat::Jsonl
+        } else if lower.ends_with(".xml") || lower.ends_with(".tei") {
+            InputFormat::Xml
+        } else {
+            InputFormat::Text
+        }
+    }
+
+    /// Whether the format is a binary container that does its own text
+    /// decoding, as opposed to text in some character encoding.
+    pub fn is_binary(self) -> bool {
+        matches!(
+            self,
+            InputFormat::Pdf | InputFormat::Epub | InputFormat::Docx
+        )
+    }
+
+    /// Whether the format can be read a line at a time with [`parse_line`]
+    /// instead of being loaded whole.
+    pub fn streams_lines(self) -> bool {
+        matches!(self, InputFormat::Text | InputFormat::Jsonl)
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Ok(InputFormat::Text),
+            "html" => Ok(InputFormat::Html),
+            "pdf" => Ok(InputFormat::Pdf),
+            "epub" => Ok(InputFormat::Epub),
+            "docx" => Ok(InputFormat::Docx),
+            "markdown" | "md" => Ok(InputFormat::Markdown),
+            "csv" => Ok(InputFormat::Csv),
+            "tsv" => Ok(InputFormat::Tsv),
+            "jsonl" | "ndjson" => Ok(InputFormat::Jsonl),
+            "xml" | "tei" => Ok(InputFormat::Xml),
+            other => Err(format!(
+                "invalid format '{}' (expected text, html, pdf, epub, docx, markdown, csv, tsv, jsonl or xml)",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings for the individual extractors.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Also read DOCX headers, footers, footnotes and endnotes.
+    pub docx_extras: bool,
+    /// Drop Markdown code blocks instead of keeping their lines.
+    pub markdown_skip_code: bool,
+    /// CSV/TSV column or JSONL field holding the text to split.
+    pub text_field: String,
+    /// XML elements whose text is split.
+    pub xml_text: XmlSelector,
+    /// Fields added to every XML block, by name.
+    pub xml_metadata: Vec<(String, XmlSelector)>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            docx_extras: false,
+            markdown_skip_code: false,
+            text_field: "text".to_string(),
+            xml_text: "p".parse().expect("valid selector"),
+            xml_metadata: Vec::new(),
+        }
+    }
+}
+
+/// Turns one line of a [line-streamed](InputFormat::streams_lines) format into
+/// a block.
+pub fn parse_line(
+    format: InputFormat,
+    line: String,
+    options: &FormatOptions,
+) -> Result<Block, BoxError> {
+    match format {
+        InputFormat::Jsonl => parse_jsonl_line(&line, &options.text_field),
+        _ => Ok(Block::new(line)),
+    }
+}
+
+/// Extracts the text blocks of a whole document in `format`.
+pub fn extract_blocks(
+    format: InputFormat,
+    data: &[u8],
+    options: &FormatOptions,
+) -> Result<Vec<Block>, BoxError> {
+    Ok(match format {
+        InputFormat::Text => String::from_utf8_lossy(data)
+            .lines()
+            .map(Block::new)
+            .collect(),
+        InputFormat::Html => extract_html(&String::from_utf8_lossy(data)),
+        InputFormat::Pdf => extract_pdf(data)?,
+        InputFormat::Epub => extract_epub(data)?,
+        InputFormat::Docx => extract_docx(data, options.docx_extras)?,
+        InputFormat::Markdown => {
+            extract_markdown(&String::from_utf8_lossy(data), options.markdown_skip_code)
+        }
+        InputFormat::Csv => extract_csv(data, b',', &options.text_field)?,
+        InputFormat::Tsv => extract_csv(data, b'\t', &options.text_field)?,
+        InputFormat::Xml => extract_xml(
+            &String::from_utf8_lossy(data),
+            &options.xml_text,
+            &options.xml_metadata,
+        )?,
+        InputFormat::Jsonl => String::from_utf8_lossy(data)
+            .lines()
+            .map(|line| parse_line(format, line.to_string(), options))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_from_name() {
+        assert_eq!(InputFormat::detect("page.HTML"), InputFormat::Html);
+        assert_eq!(InputFormat::detect("crawl/page.htm.gz"), InputFormat::Html);
+        assert_eq!(InputFormat::detect("paper.pdf"), InputFormat::Pdf);
+        assert_eq!(InputFormat::detect("novel.epub"), InputFormat::Epub);
+        assert_eq!(InputFormat::detect("report.docx"), InputFormat::Docx);
+        assert_eq!(InputFormat::detect("README.md"), InputFormat::Markdown);
+        assert_eq!(InputFormat::detect("talks.tsv"), InputFormat::Tsv);
+        assert_eq!(InputFormat::detect("crawl.jsonl.zst"), InputFormat::Jsonl);
+        assert_eq!(InputFormat::detect("A00.xml"), InputFormat::Xml);
+        assert_eq!(InputFormat::detect("notes.txt"), InputFormat::Text);
+        assert_eq!(InputFormat::detect("stdin"), InputFormat::Text);
+    }
+
+    #[test]
+    fn parses_format() {
+        assert_eq!("HTML".parse(), Ok(InputFormat::Html));
+        assert!("rtf".parse::<InputFormat>().is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Text extraction from PDFs.
+
+use mongodb::bson::doc;
+
+use super::Block;
+use crate::BoxError;
+
+/// Returns the paragraphs of a PDF, each tagged with the `pageNumber` it
+/// starts on.
+pub fn extract_pdf(data: &[u8]) -> Result<Vec<Block>, BoxError> {
+    let pages = pdf_extract::extract_text_from_mem_by_pages(data)?;
+    Ok(paragraphs(&pages))
+}
+
+/// Rebuilds paragraphs from the lines of each page. PDF text breaks lines
+/// wherever the layout did, so lines are joined until a blank line, and
+/// words hyphenated across a line break are put back together. A paragraph
+/// that doesn't end a sentence when its page does carries on to the next
+/// page; lines holding only a page number are dropped.
+fn paragraphs(pages: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph = String::new();
+    let mut start_page = 1;
+
+    let mut finish = |paragraph: &mut String, page: usize| {
+        if !paragraph.is_empty() {
+            let mut block = Block::new(std::mem::take(paragraph));
+            block.metadata = doc! { "pageNumber": page as i32 };
+            blocks.push(block);
+        }
+    };
+
+    for (index, page) in pages.iter().enumerate() {
+        let page_number = index + 1;
+        for line in page.lines().map(str::trim) {
+            if line.is_empty() {
+                finish(&mut paragraph, start_page);
+                continue;
+            }
+            if line.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            if paragraph.is_empty() {
+                start_page = page_number;
+            }
+            join_line(&mut paragraph, line);
+        }
+        if paragraph.ends_with(['.', '!', '?', ':']) {
+            finish(&mut paragraph, start_page);
+        }
+    }
+    finish(&mut paragraph, start_page);
+    blocks
+}
+
+/// Appends `line` to `paragraph`. A trailing hyphen after a letter is
+/// dropped when the next line continues in lower case ("exam-" "ple"), and
+/// kept without a space otherwise ("Anglo-" "Saxon").
+fn join_line(paragraph: &mut String, line: &str) {
+    let hyphenated = paragraph
+        .strip_suffix('-')
+        .and_then(|rest| rest.chars().last())
+        .is_some_and(char::is_alphabetic);
+    if hyphenated {
+        if line.starts_with(char::is_lowercase) {
+            paragraph.pop();
+        }
+    } else if !paragraph.is_empty() {
+        paragraph.push(' ');
+    }
+    paragraph.push_str(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(pages: &[&str]) -> Vec<String> {
+        pages.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn joins_lines_and_dehyphenates() {
+        let blocks = paragraphs(&pages(&[
+            "The results of the exam-\nination were mixed for\nAnglo-\nSaxon texts.\n\nA new paragraph.\n",
+        ]));
+        assert_eq!(
+            blocks,
+            [
+                Block {
+                    text: "The results of the examination were mixed for Anglo-Saxon texts."
+                        .to_string(),
+                    metadata: doc! { "pageNumber": 1 },
+                    offset: None,
+                    line: None,
+                    source_bytes: None,
+                },
+                Block {
+                    text: "A new paragraph.".to_string(),
+                    metadata: doc! { "pageNumber": 1 },
+                    offset: None,
+                    line: None,
+                    source_bytes: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn carries_unfinished_paragraphs_across_pages() {
+        let blocks = paragraphs(&pages(&[
+            "First page ends.\n\nThis sentence runs over\n12\n",
+            "onto the second page.\n\nAnother one.\n",
+        ]));
+        let texts: Vec<_> = blocks.iter().map(|b| b.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "First page ends.",
+                "This sentence runs over onto the second page.",
+                "Another one."
+            ]
+        );
+        let page_numbers: Vec<_> = blocks
+            .iter()
+            .map(|b| b.metadata.get_i32("pageNumber").unwrap())
+            .collect();
+        assert_eq!(page_numbers, [1, 1, 2]);
+    }
+}
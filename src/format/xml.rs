@@ -0,0 +1,244 @@
+//! XML corpora such as TEI and the BNC, read through simple path selectors.
+
+use mongodb::bson::Document;
+use std::str::FromStr;
+
+use super::Block;
+use crate::BoxError;
+
+/// A path of element names, each optionally filtered on an attribute, that
+/// may end in an attribute to read instead of the element's text:
+///
+/// - `p` — every `<p>`
+/// - `titleStmt/author` — `<author>` directly inside `<titleStmt>`
+/// - `div[@type='chapter']/head` — headings of chapter divisions
+/// - `text@type` or `text/@type` — the `type` attribute of `<text>`
+///
+/// Names are matched without their namespace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlSelector {
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    name: String,
+    filter: Option<(String, String)>,
+}
+
+impl FromStr for XmlSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid XML selector '{}'", s);
+        let (path, attribute) = match s.rsplit_once('@') {
+            // An '@' inside a final [...] filter isn't an attribute to read
+            Some((path, attribute)) if !attribute.contains(']') => (
+                path.strip_suffix('/').unwrap_or(path),
+                Some(attribute.to_string()),
+            ),
+            _ => (s, None),
+        };
+
+        let mut steps = Vec::new();
+        for step in path.split('/').filter(|step| !step.is_empty()) {
+            let (name, filter) = match step.split_once('[') {
+                Some((name, filter)) => {
+                    let filter = filter
+                        .strip_suffix(']')
+                        .and_then(|f| f.strip_prefix('@'))
+                        .and_then(|f| f.split_once('='))
+                        .ok_or_else(invalid)?;
+                    let value = filter.1.trim_matches(|c| c == '\'' || c == '"');
+                    (name, Some((filter.0.to_string(), value.to_string())))
+                }
+                None => (step, None),
+            };
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            steps.push(Step {
+                name: name.to_string(),
+                filter,
+            });
+        }
+        if steps.is_empty() {
+            return Err(invalid());
+        }
+        Ok(XmlSelector { steps, attribute })
+    }
+}
+
+impl XmlSelector {
+    /// Whether `node` is the last step's element, with the earlier steps
+    /// matching its ancestors in turn.
+    fn matches(&self, node: roxmltree::Node) -> bool {
+        let mut current = Some(node);
+        for step in self.steps.iter().rev() {
+            match current {
+                Some(element) if element.is_element() && step.matches(element) => {
+                    current = element.parent_element();
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// The selected attribute of `node`, or its text.
+    fn value(&self, node: roxmltree::Node) -> Option<String> {
+        match &self.attribute {
+            Some(attribute) => attribute_value(node, attribute).map(str::to_string),
+            None => Some(text_content(node)).filter(|text| !text.is_empty()),
+        }
+    }
+}
+
+impl Step {
+    fn matches(&self, element: roxmltree::Node) -> bool {
+        element.tag_name().name() == self.name
+            && self.filter.as_ref().is_none_or(|(attribute, value)| {
+                attribute_value(element, attribute) == Some(value.as_str())
+            })
+    }
+}
+
+/// Returns the text of every element matching `text`. Each `metadata`
+/// selector is looked up on the element and its ancestors first, so
+/// attributes of an enclosing `<div>` apply to the paragraphs inside it, and
+/// otherwise anywhere in the document, which picks up header fields such as
+/// the author.
+pub fn extract_xml(
+    xml: &str,
+    text: &XmlSelector,
+    metadata: &[(String, XmlSelector)],
+) -> Result<Vec<Block>, BoxError> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    let document = roxmltree::Document::parse_with_options(xml, options)?;
+
+    let document_fields: Vec<Option<String>> = metadata
+        .iter()
+        .map(|(_, selector)| {
+            document
+                .descendants()
+                .filter(|node| selector.matches(*node))
+                .find_map(|node| selector.value(node))
+        })
+        .collect();
+
+    let mut blocks = Vec::new();
+    for node in document.descendants().filter(|node| text.matches(*node)) {
+        // Matches nested in another match are already part of its text
+        if node
+            .ancestors()
+            .skip(1)
+            .any(|ancestor| text.matches(ancestor))
+        {
+            continue;
+        }
+
+        let mut fields = Document::new();
+        for ((name, selector), document_value) in metadata.iter().zip(&document_fields) {
+            let value = node
+                .ancestors()
+                .filter(|ancestor| selector.matches(*ancestor))
+                .find_map(|ancestor| selector.value(ancestor))
+                .or_else(|| document_value.clone());
+            if let Some(value) = value {
+                fields.insert(name, value);
+            }
+        }
+        blocks.push(Block {
+            text: text_content(node),
+            metadata: fields,
+            offset: None,
+            line: None,
+            source_bytes: None,
+        });
+    }
+    Ok(blocks)
+}
+
+fn attribute_value<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value())
+}
+
+fn text_content(node: roxmltree::Node) -> String {
+    let text: Vec<&str> = node
+        .descendants()
+        .filter_map(|n| n.text().filter(|_| n.is_text()))
+        .collect();
+    text.concat()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    const TEI: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE TEI SYSTEM "tei.dtd">
+<TEI xmlns="http://www.tei-c.org/ns/1.0">
+  <teiHeader><fileDesc><titleStmt>
+    <title>Collected Letters</title><author>A. Writer</author>
+  </titleStmt></fileDesc></teiHeader>
+  <text type="letters"><body>
+    <div type="letter" n="1"><p>Dear friend,
+      I am <hi>well</hi>.</p></div>
+    <div type="letter" n="2"><p>It rained again today.</p></div>
+  </body></text>
+</TEI>"#;
+
+    fn selector(s: &str) -> XmlSelector {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn reads_text_with_ancestor_and_header_metadata() {
+        let metadata = [
+            ("author".to_string(), selector("titleStmt/author")),
+            ("genre".to_string(), selector("text@type")),
+            ("letter".to_string(), selector("div[@type='letter']/@n")),
+        ];
+        let blocks = extract_xml(TEI, &selector("p"), &metadata).unwrap();
+
+        assert_eq!(
+            blocks,
+            [
+                Block {
+                    text: "Dear friend, I am well.".to_string(),
+                    metadata: doc! { "author": "A. Writer", "genre": "letters", "letter": "1" },
+                    offset: None,
+                    line: None,
+                    source_bytes: None,
+                },
+                Block {
+                    text: "It rained again today.".to_string(),
+                    metadata: doc! { "author": "A. Writer", "genre": "letters", "letter": "2" },
+                    offset: None,
+                    line: None,
+                    source_bytes: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_selectors() {
+        assert!("".parse::<XmlSelector>().is_err());
+        assert!("div[type='x']".parse::<XmlSelector>().is_err());
+        assert_eq!(
+            selector("div[@type='a']/p").steps[0].filter,
+            Some(("type".to_string(), "a".to_string()))
+        );
+    }
+}
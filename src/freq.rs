@@ -0,0 +1,388 @@
+//! Frequency lists built from the stored sentences: how often each word
+//! and each n-gram occurs in the corpus and in each genre, ranked, as COCA
+//! publishes them.
+
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::{FindOptions, IndexOptions};
+use mongodb::{Collection, IndexModel};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use crate::stopword::Stopwords;
+use crate::token::Tokenizer;
+use crate::BoxError;
+
+/// Collection the word frequencies are kept in, next to the sentences.
+pub const WORD_FREQ_COLLECTION: &str = "word_freq";
+
+/// Collection the n-gram frequencies are kept in.
+pub const NGRAMS_COLLECTION: &str = "ngrams";
+
+/// The longest n-grams counted.
+pub const MAX_NGRAM: usize = 5;
+
+/// Documents inserted at a time when a table is stored.
+const INSERT_CHUNK: usize = 10_000;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Counts {
+    total: u64,
+    genres: BTreeMap<String, u64>,
+}
+
+/// Counts of terms, overall and by genre.
+#[derive(Debug, Clone, Default)]
+pub struct FrequencyTable {
+    counts: HashMap<String, Counts>,
+    tokens: u64,
+    stopwords: Stopwords,
+}
+
+impl FrequencyTable {
+    pub fn new() -> Self {
+        FrequencyTable::default()
+    }
+
+    /// Leaves `stopwords` out when counting words.
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    /// Counts one occurrence of `term`, in `genre` if it's known.
+    pub fn add(&mut self, term: &str, genre: Option<&str>) {
+        let counts = match self.counts.get_mut(term) {
+            Some(counts) => counts,
+            None => self.counts.entry(term.to_string()).or_default(),
+        };
+        counts.total += 1;
+        if let Some(genre) = genre {
+            *counts.genres.entry(genre.to_string()).or_default() += 1;
+        }
+        self.tokens += 1;
+    }
+
+    /// Counts lowercased `words` of a sentence in `lang`, other than
+    /// stopwords.
+    pub fn add_words<'a>(
+        &mut self,
+        words: impl IntoIterator<Item = &'a str>,
+        genre: Option<&str>,
+        lang: Option<&str>,
+    ) {
+        for word in words {
+            if !self.stopwords.contains(word, lang) {
+                self.add(word, genre);
+            }
+        }
+    }
+
+    /// Counts the words of `sentence`, lowercased.
+    pub fn add_sentence(
+        &mut self,
+        sentence: &str,
+        tokenizer: Tokenizer,
+        genre: Option<&str>,
+        lang: Option<&str>,
+    ) {
+        let words = lowercase_words(sentence, tokenizer);
+        self.add_words(words.iter().map(String::as_str), genre, lang);
+    }
+
+    /// How many different terms were counted.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// How many occurrences were counted in all.
+    pub fn tokens(&self) -> u64 {
+        self.tokens
+    }
+
+    /// A document per term, holding it as `key` with its `rank`, `count` and
+    /// `genres` counts, most frequent first. Terms counted as often are
+    /// ranked alphabetically.
+    pub fn ranked(&self, key: &str) -> Vec<Document> {
+        self.ranked_at_least(key, 1)
+    }
+
+    /// [`ranked`](Self::ranked), leaving out terms counted fewer than
+    /// `min_count` times.
+    pub fn ranked_at_least(&self, key: &str, min_count: u64) -> Vec<Document> {
+        let mut terms: Vec<_> = self
+            .counts
+            .iter()
+            .filter(|(_, counts)| counts.total >= min_count)
+            .collect();
+        terms.sort_by(|(a, x), (b, y)| y.total.cmp(&x.total).then_with(|| a.cmp(b)));
+        terms
+            .into_iter()
+            .enumerate()
+            .map(|(i, (term, counts))| {
+                let genres: Document = counts
+                    .genres
+                    .iter()
+                    .map(|(genre, count)| (genre.clone(), (*count as i64).into()))
+                    .collect();
+                doc! {
+                    key: term,
+                    "rank": i as i64 + 1,
+                    "count": counts.total as i64,
+                    "genres": genres,
+                }
+            })
+            .collect()
+    }
+}
+
+fn lowercase_words(sentence: &str, tokenizer: Tokenizer) -> Vec<String> {
+    tokenizer
+        .tokens(sentence)
+        .into_iter()
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Counts of the n-grams of each length in `sizes`. N-grams don't run from
+/// one sentence into the next.
+#[derive(Debug, Clone)]
+pub struct NgramCounts {
+    sizes: RangeInclusive<usize>,
+    tables: Vec<FrequencyTable>,
+    stopwords: Stopwords,
+}
+
+impl NgramCounts {
+    /// Counts n-grams as long as `sizes`, which has to lie within
+    /// 2..=[`MAX_NGRAM`].
+    pub fn new(sizes: RangeInclusive<usize>) -> Result<Self, BoxError> {
+        if *sizes.start() < 2 || *sizes.end() > MAX_NGRAM || sizes.is_empty() {
+            return Err(format!(
+                "n-gram lengths {}-{} aren't within 2-{}",
+                sizes.start(),
+                sizes.end(),
+                MAX_NGRAM
+            )
+            .into());
+        }
+        let tables = sizes.clone().map(|_| FrequencyTable::new()).collect();
+        Ok(NgramCounts {
+            sizes,
+            tables,
+            stopwords: Stopwords::default(),
+        })
+    }
+
+    /// Leaves out n-grams made up of nothing but `stopwords`, such as "of
+    /// the", keeping ones like "cup of tea".
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    /// Counts the n-grams of the lowercased words of `sentence`, in `lang`.
+    pub fn add_sentence(
+        &mut self,
+        sentence: &str,
+        tokenizer: Tokenizer,
+        genre: Option<&str>,
+        lang: Option<&str>,
+    ) {
+        let words = lowercase_words(sentence, tokenizer);
+        for (n, table) in self.sizes.clone().zip(&mut self.tables) {
+            for ngram in words.windows(n) {
+                if self.stopwords.is_empty()
+                    || !ngram.iter().all(|word| self.stopwords.contains(word, lang))
+                {
+                    table.add(&ngram.join(" "), genre);
+                }
+            }
+        }
+    }
+
+    /// How many n-grams were counted in all.
+    pub fn tokens(&self) -> u64 {
+        self.tables.iter().map(FrequencyTable::tokens).sum()
+    }
+
+    /// A document per n-gram counted at least `min_count` times, holding it
+    /// as `ngram` with its length `n`, and its `rank` among n-grams as long.
+    pub fn ranked(&self, min_count: u64) -> Vec<Document> {
+        let mut documents = Vec::new();
+        for (n, table) in self.sizes.clone().zip(&self.tables) {
+            documents.extend(table.ranked_at_least("ngram", min_count).into_iter().map(
+                |mut document| {
+                    document.insert("n", n as i32);
+                    document
+                },
+            ));
+        }
+        documents
+    }
+}
+
+/// Calls `count` with every sentence in `sentences`, holding only `fields`.
+pub async fn scan(
+    sentences: &Collection<Document>,
+    fields: &[&str],
+    mut count: impl FnMut(&Document),
+) -> Result<(), BoxError> {
+    let projection: Document = fields
+        .iter()
+        .map(|&field| (field.to_string(), 1.into()))
+        .collect();
+    let options = FindOptions::builder().projection(projection).build();
+    let mut cursor = sentences.find(None, options).await?;
+    while let Some(sentence) = cursor.try_next().await? {
+        count(&sentence);
+    }
+    Ok(())
+}
+
+/// Replaces the contents of `collection` with `table`, ranked, with the
+/// words as `word`. Returns how many documents were written.
+pub async fn store_words(
+    table: &FrequencyTable,
+    collection: &Collection<Document>,
+) -> Result<usize, BoxError> {
+    let documents = table.ranked("word");
+    replace(
+        collection,
+        &documents,
+        doc! { "word": 1 },
+        doc! { "rank": 1 },
+    )
+    .await?;
+    Ok(documents.len())
+}
+
+/// Replaces the contents of `collection` with the n-grams of `counts`
+/// counted at least `min_count` times. Returns how many were written.
+pub async fn store_ngrams(
+    counts: &NgramCounts,
+    collection: &Collection<Document>,
+    min_count: u64,
+) -> Result<usize, BoxError> {
+    let documents = counts.ranked(min_count);
+    let order = doc! { "n": 1, "rank": 1 };
+    replace(collection, &documents, doc! { "ngram": 1 }, order).await?;
+    Ok(documents.len())
+}
+
+/// Empties `collection` and writes `documents` to it, indexed uniquely by
+/// `key` and for listing by `order`.
+pub(crate) async fn replace(
+    collection: &Collection<Document>,
+    documents: &[Document],
+    key: Document,
+    order: Document,
+) -> Result<(), BoxError> {
+    collection.drop(None).await?;
+    let unique = IndexModel::builder()
+        .keys(key)
+        .options(IndexOptions::builder().unique(true).build())
+        .build();
+    let order = IndexModel::builder().keys(order).build();
+    collection.create_indexes([unique, order], None).await?;
+
+    for chunk in documents.chunks(INSERT_CHUNK) {
+        collection.insert_many(chunk, None).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_words_by_count() {
+        let mut table = FrequencyTable::new();
+        table.add_sentence(
+            "The cat saw the dog.",
+            Tokenizer::Unicode,
+            Some("fic"),
+            None,
+        );
+        table.add_sentence("A dog barked.", Tokenizer::Unicode, Some("news"), None);
+        table.add_sentence("The end.", Tokenizer::Unicode, None, None);
+        assert_eq!(table.tokens(), 10);
+        assert_eq!(table.len(), 7);
+
+        let ranked = table.ranked("word");
+        assert_eq!(
+            ranked[0],
+            doc! { "word": "the", "rank": 1_i64, "count": 3_i64, "genres": { "fic": 2_i64 } }
+        );
+        assert_eq!(
+            ranked[1],
+            doc! {
+                "word": "dog",
+                "rank": 2_i64,
+                "count": 2_i64,
+                "genres": { "fic": 1_i64, "news": 1_i64 },
+            }
+        );
+        let words: Vec<_> = ranked[2..]
+            .iter()
+            .map(|d| d.get_str("word").unwrap())
+            .collect();
+        assert_eq!(words, ["a", "barked", "cat", "end", "saw"]);
+    }
+
+    #[test]
+    fn counts_ngrams_within_sentences() {
+        let mut counts = NgramCounts::new(2..=3).unwrap();
+        counts.add_sentence("Out of the blue.", Tokenizer::Unicode, None, None);
+        counts.add_sentence("Out of the woods!", Tokenizer::Unicode, Some("fic"), None);
+        assert_eq!(counts.tokens(), 10);
+
+        let ranked: Vec<_> = counts
+            .ranked(2)
+            .iter()
+            .map(|d| {
+                (
+                    d.get_str("ngram").unwrap().to_string(),
+                    d.get_i32("n").unwrap(),
+                    d.get_i64("rank").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                ("of the".to_string(), 2, 1),
+                ("out of".to_string(), 2, 2),
+                ("out of the".to_string(), 3, 1),
+            ]
+        );
+        assert!(NgramCounts::new(1..=2).is_err());
+
+        let mut stopwords = Stopwords::new();
+        stopwords.add_language("eng").unwrap();
+        let mut counts = NgramCounts::new(2..=3).unwrap().with_stopwords(stopwords);
+        counts.add_sentence("A cup of the tea.", Tokenizer::Unicode, None, Some("eng"));
+        let ngrams: Vec<_> = counts
+            .ranked(1)
+            .iter()
+            .map(|d| d.get_str("ngram").unwrap().to_string())
+            .collect();
+        assert_eq!(
+            ngrams,
+            [
+                "a cup",
+                "cup of",
+                "the tea",
+                "a cup of",
+                "cup of the",
+                "of the tea"
+            ]
+        );
+        assert!(NgramCounts::new(2..=6).is_err());
+    }
+}
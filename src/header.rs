@@ -0,0 +1,302 @@
+//! Document headers that aren't part of the text: YAML front matter, and
+//! the Project Gutenberg boilerplate around a book.
+//!
+//! [`DocumentHeaders`] blanks them out of a document's blocks, so line
+//! numbers still count them, and adds the `title`, `author` and `year` they
+//! give to every block after them.
+
+use mongodb::bson::Document;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::LazyLock;
+
+use crate::error::IngestError;
+use crate::format::Block;
+
+/// Lines looked through for the start of a Gutenberg book before deciding
+/// a mention of Project Gutenberg isn't its header.
+const GUTENBERG_HEADER_LINES: usize = 500;
+
+/// Lines of front matter read before deciding a leading `---` doesn't
+/// open any.
+const FRONT_MATTER_LINES: usize = 100;
+
+static GUTENBERG_START: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*\*{3}\s*START OF (THE|THIS) PROJECT GUTENBERG").expect("valid regex")
+});
+
+static GUTENBERG_END: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^\s*(\*{3}\s*END OF (THE|THIS) PROJECT GUTENBERG|END OF (THE )?PROJECT GUTENBERG'?S?)")
+        .expect("valid regex")
+});
+
+static YEAR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(1[0-9]{3}|20[0-9]{2})\b").expect("valid regex"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Nothing read yet.
+    Start,
+    Body,
+    /// Past the end of a Gutenberg book; everything is boilerplate.
+    Footer,
+}
+
+/// Wraps the blocks of one document, see the [module docs](self).
+pub struct DocumentHeaders<I> {
+    blocks: I,
+    /// Blocks read ahead while looking for the end of a header.
+    pending: VecDeque<Result<Block, IngestError>>,
+    metadata: Document,
+    state: State,
+    gutenberg: bool,
+}
+
+impl<I> DocumentHeaders<I>
+where
+    I: Iterator<Item = Result<Block, IngestError>>,
+{
+    /// Strips the headers of `blocks`; unless `enabled`, they're passed
+    /// through as they are.
+    pub fn new(blocks: I, enabled: bool) -> Self {
+        DocumentHeaders {
+            blocks,
+            pending: VecDeque::new(),
+            metadata: Document::new(),
+            state: if enabled { State::Start } else { State::Body },
+            gutenberg: false,
+        }
+    }
+
+    /// Reads ahead to the first line with text, and past a header starting
+    /// there.
+    fn read_header(&mut self) {
+        let first = loop {
+            match self.blocks.next() {
+                Some(Ok(block)) if block.text.trim().is_empty() => {
+                    self.pending.push_back(Ok(block))
+                }
+                Some(Ok(block)) => break block,
+                Some(error) => return self.pending.push_back(error),
+                None => return,
+            }
+        };
+        let skipped = self.pending.len();
+        let text = first.text.trim().to_string();
+        self.pending.push_back(Ok(first));
+
+        if text == "---" {
+            let end = self.read_until(FRONT_MATTER_LINES, |line| {
+                matches!(line.trim(), "---" | "...")
+            });
+            if let Some(end) = end {
+                self.metadata = front_matter(self.lines(skipped + 1..end));
+                self.blank(skipped..=end);
+            }
+        } else if text.to_lowercase().contains("project gutenberg") {
+            let start = self.read_until(GUTENBERG_HEADER_LINES, |line| {
+                GUTENBERG_START.is_match(line)
+            });
+            if let Some(start) = start {
+                self.metadata = gutenberg_header(self.lines(skipped..start));
+                self.blank(skipped..=start);
+                self.gutenberg = true;
+            }
+        }
+    }
+
+    /// Reads ahead up to `limit` more lines, returning the index in
+    /// `pending` of the first that `ends` accepts.
+    fn read_until(&mut self, limit: usize, ends: impl Fn(&str) -> bool) -> Option<usize> {
+        for _ in 0..limit {
+            let block = self.blocks.next()?;
+            let found = matches!(&block, Ok(block) if ends(&block.text));
+            let failed = block.is_err();
+            self.pending.push_back(block);
+            if found {
+                return Some(self.pending.len() - 1);
+            }
+            if failed {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn lines(&self, range: std::ops::Range<usize>) -> impl Iterator<Item = &str> {
+        self.pending
+            .range(range)
+            .filter_map(|block| block.as_ref().ok())
+            .map(|block| block.text.as_str())
+    }
+
+    fn blank(&mut self, range: std::ops::RangeInclusive<usize>) {
+        for block in self.pending.range_mut(range).flatten() {
+            *block = Block::new("");
+        }
+    }
+}
+
+impl<I> Iterator for DocumentHeaders<I>
+where
+    I: Iterator<Item = Result<Block, IngestError>>,
+{
+    type Item = Result<Block, IngestError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::Start {
+            self.read_header();
+            self.state = State::Body;
+        }
+        let mut block = match self.pending.pop_front().or_else(|| self.blocks.next())? {
+            Ok(block) => block,
+            error => return Some(error),
+        };
+        if self.gutenberg && self.state == State::Body && GUTENBERG_END.is_match(&block.text) {
+            self.state = State::Footer;
+        }
+        if self.state == State::Footer {
+            return Some(Ok(Block::new("")));
+        }
+        if !block.text.trim().is_empty() {
+            for (key, value) in &self.metadata {
+                if !block.metadata.contains_key(key) {
+                    block.metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Some(Ok(block))
+    }
+}
+
+/// The first year in `text`.
+fn year(text: &str) -> Option<i32> {
+    YEAR.find(text)?.as_str().parse().ok()
+}
+
+/// The `title`, `author` and `year` of simple `key: value` front matter;
+/// `date` gives the year when there's no `year`.
+fn front_matter<'a>(lines: impl Iterator<Item = &'a str>) -> Document {
+    let mut metadata = Document::new();
+    let mut date_year = None;
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+        if value.is_empty() {
+            continue;
+        }
+        match key.trim().to_lowercase().as_str() {
+            "title" => {
+                metadata.insert("title", value);
+            }
+            "author" => {
+                metadata.insert("author", value);
+            }
+            "year" => {
+                if let Some(year) = year(value) {
+                    metadata.insert("year", year);
+                }
+            }
+            "date" => date_year = year(value),
+            _ => {}
+        }
+    }
+    if let (Some(year), false) = (date_year, metadata.contains_key("year")) {
+        metadata.insert("year", year);
+    }
+    metadata
+}
+
+/// The `title`, `author` and `year` of a Gutenberg header: the year of
+/// original publication if it's given, otherwise of the release.
+fn gutenberg_header<'a>(lines: impl Iterator<Item = &'a str>) -> Document {
+    let mut metadata = Document::new();
+    let (mut published, mut released) = (None, None);
+    for line in lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "title" if !value.is_empty() => {
+                metadata.insert("title", value);
+            }
+            "author" if !value.is_empty() => {
+                metadata.insert("author", value);
+            }
+            "original publication" => published = year(value),
+            "release date" | "release date of original ebook" => {
+                released = released.or(year(value))
+            }
+            _ => {}
+        }
+    }
+    if let Some(year) = published.or(released) {
+        metadata.insert("year", year);
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    fn strip(text: &str) -> Vec<Block> {
+        DocumentHeaders::new(text.lines().map(|line| Ok(Block::new(line))), true)
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn strips_gutenberg_boilerplate() {
+        let book = "The Project Gutenberg eBook of Moby Dick, by Herman Melville\n\
+                    \n\
+                    Title: Moby Dick\n\
+                    Author: Herman Melville\n\
+                    Release date: July 1, 2001 [eBook #2701]\n\
+                    \n\
+                    *** START OF THE PROJECT GUTENBERG EBOOK MOBY DICK ***\n\
+                    Call me Ishmael.\n\
+                    \n\
+                    *** END OF THE PROJECT GUTENBERG EBOOK MOBY DICK ***\n\
+                    Section 1. General Terms of Use";
+        let blocks = strip(book);
+        assert_eq!(blocks.len(), 11);
+        let text: Vec<_> = blocks
+            .iter()
+            .map(|b| b.text.as_str())
+            .filter(|t| !t.is_empty())
+            .collect();
+        assert_eq!(text, ["Call me Ishmael."]);
+        assert_eq!(
+            blocks[7].metadata,
+            doc! { "title": "Moby Dick", "author": "Herman Melville", "year": 2001 }
+        );
+    }
+
+    #[test]
+    fn reads_front_matter() {
+        let blocks = strip("---\ntitle: \"On Rivers\"\ndate: 1998-04-02\n---\nRivers run.");
+        assert_eq!(blocks.len(), 5);
+        assert!(blocks[..4].iter().all(|b| b.text.is_empty()));
+        assert_eq!(blocks[4].text, "Rivers run.");
+        assert_eq!(
+            blocks[4].metadata,
+            doc! { "title": "On Rivers", "year": 1998 }
+        );
+    }
+
+    #[test]
+    fn leaves_other_documents_alone() {
+        let text = "---\nA rule, then text that never ends the front matter.";
+        let blocks = strip(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].text, "---");
+        assert!(blocks[1].metadata.is_empty());
+        let mention = strip("I read it on Project Gutenberg.\nThen I slept.");
+        assert_eq!(mention[0].text, "I read it on Project Gutenberg.");
+    }
+}
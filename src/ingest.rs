@@ -0,0 +1,2924 @@
+//! The line-by-line ingestion loop.
+
+use futures::{Stream, StreamExt};
+use mongodb::bson::{doc, DateTime, Document};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ulid::Ulid;
+
+use crate::bench::{Phase, PhaseTimes};
+use crate::checkpoint::{Checkpoint, Progress};
+use crate::clean::{
+    collapse_punctuation, normalize_invisible, normalize_unicode, Alignment, CleaningPipeline,
+    Dehyphenator, InvisibleChars, UnicodeForm,
+};
+use crate::decompress::decompress;
+use crate::dedup::{fnv1a, min_hash, mix, NearDuplicateAction, NearDuplicates, SeenFilter};
+use crate::encoding::{decode_bytes, decode_reader, TextEncoding};
+use crate::error::{ErrorSummary, IngestError, Stage};
+use crate::format::{extract_blocks, parse_line, Block, FormatOptions, InputFormat, SourceOffset};
+use crate::header::DocumentHeaders;
+use crate::language::LanguageFilter;
+use crate::lemma::Lemmatizer;
+use crate::logging;
+use crate::manifest::Manifest;
+use crate::metrics::METRICS;
+use crate::pos::PosTagger;
+use crate::quality::Quality;
+use crate::redact::Redactor;
+use crate::report::{FileReport, FileReports};
+use crate::schema::SCHEMA_VERSION;
+use crate::segment::{Rejection, Rejections};
+use crate::shutdown::Shutdown;
+use crate::sink::SentenceSink;
+use crate::source::{CorpusSource, Member};
+use crate::status::StatusLine;
+use crate::token::Tokenizer;
+use crate::{BoxError, Segmenter};
+
+/// Pulls structured metadata off the front of each line, as in
+/// "[2021-03-01][user42] the actual text". Every named group except
+/// `text_group` becomes a document field; `text_group` is what gets split.
+pub struct LineMetadata {
+    regex: Regex,
+    text_group: String,
+}
+
+impl LineMetadata {
+    pub fn new(pattern: &str, text_group: &str) -> Result<Self, BoxError> {
+        let regex = Regex::new(pattern)?;
+        if !regex.capture_names().any(|name| name == Some(text_group)) {
+            return Err(format!(
+                "line metadata regex has no capture group named '{}'",
+                text_group
+            )
+            .into());
+        }
+        Ok(LineMetadata {
+            regex,
+            text_group: text_group.to_string(),
+        })
+    }
+
+    /// Returns the text to split and the extracted fields. Lines that don't
+    /// match are passed through whole, without metadata.
+    pub fn extract<'a>(&self, line: &'a str) -> (&'a str, Document) {
+        let Some(captures) = self.regex.captures(line) else {
+            return (line, Document::new());
+        };
+
+        let mut metadata = Document::new();
+        for name in self.regex.capture_names().flatten() {
+            if name == self.text_group {
+                continue;
+            }
+            if let Some(value) = captures.name(name) {
+                metadata.insert(name, value.as_str());
+            }
+        }
+        let text = captures.name(&self.text_group).map_or("", |m| m.as_str());
+        (text, metadata)
+    }
+}
+
+/// Reads fields off file names laid out as COCA's are, with a template such
+/// as `{genre}_{year:int}_{id}.txt`. Each `{name}` matches some of the name
+/// and is stored as a string field; `{name:int}` is stored as a number.
+/// Everything else has to match as written.
+pub struct FileNameTemplate {
+    regex: Regex,
+    /// Fields stored as numbers.
+    numeric: Vec<String>,
+}
+
+impl FileNameTemplate {
+    pub fn new(template: &str) -> Result<Self, BoxError> {
+        let placeholder = Regex::new(r"\{(\w+)(?::(\w+))?\}").expect("valid regex");
+        let mut pattern = String::from("^");
+        let mut numeric = Vec::new();
+        let mut last = 0;
+        for captures in placeholder.captures_iter(template) {
+            let whole = captures.get(0).expect("match");
+            let name = &captures[1];
+            pattern.push_str(&regex::escape(&template[last..whole.start()]));
+            match captures.get(2).map(|kind| kind.as_str()) {
+                None => pattern.push_str(&format!("(?P<{}>.+?)", name)),
+                Some("int") => {
+                    pattern.push_str(&format!("(?P<{}>-?[0-9]+)", name));
+                    numeric.push(name.to_string());
+                }
+                Some(other) => {
+                    return Err(format!(
+                        "invalid field type '{}' in file name template (expected int)",
+                        other
+                    )
+                    .into())
+                }
+            }
+            last = whole.end();
+        }
+        pattern.push_str(&regex::escape(&template[last..]));
+        pattern.push('$');
+        let regex = Regex::new(&pattern)
+            .map_err(|e| format!("invalid file name template '{}': {}", template, e))?;
+        Ok(FileNameTemplate { regex, numeric })
+    }
+
+    /// The fields in the last component of `path`, or `None` if it doesn't
+    /// fit the template.
+    pub fn extract(&self, path: &str) -> Option<Document> {
+        let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let captures = self.regex.captures(name)?;
+        let mut fields = Document::new();
+        for name in self.regex.capture_names().flatten() {
+            let value = captures.name(name)?.as_str();
+            match value.parse::<i64>() {
+                Ok(number) if self.numeric.iter().any(|n| n == name) => fields.insert(name, number),
+                _ => fields.insert(name, value),
+            };
+        }
+        Some(fields)
+    }
+}
+
+/// Counts the lines in `reader` without decoding them, so the first pass of
+/// two-pass mode stays cheap even on very large files.
+pub fn count_lines<R: Read>(reader: R) -> std::io::Result<usize> {
+    let mut reader = BufReader::with_capacity(64 * 1024, reader);
+    let mut count = 0;
+    let mut last_byte = None;
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        count += buf.iter().filter(|&&b| b == b'\n').count();
+        last_byte = buf.last().copied();
+        let len = buf.len();
+        reader.consume(len);
+    }
+
+    // A final line without a trailing newline still counts
+    if last_byte.is_some_and(|b| b != b'\n') {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The hex SHA-256 of `sentence` lowercased and with its whitespace
+/// collapsed, stored as `textHash` so duplicates can be found across files.
+pub fn text_hash(sentence: &str) -> String {
+    let normalized = sentence
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Builds the document stored for a sentence. `total_lines` is only known in
+/// two-pass mode; without it the position within the file can't be computed
+/// and `positionRatio` is left out rather than guessed.
+pub fn sentence_document(
+    sentence: &str,
+    file_path: &str,
+    line_number: usize,
+    total_lines: Option<usize>,
+) -> Document {
+    let mut document = doc! {
+        "text": sentence,
+        "fileName": file_path,
+        "lineNumber": line_number as i32,
+        "wordCount": sentence.split_whitespace().count() as i32,
+        "textHash": text_hash(sentence)
+    };
+    if let Some(total) = total_lines.filter(|&t| t > 0) {
+        document.insert("positionRatio", line_number as f64 / total as f64);
+    }
+    document
+}
+
+/// Per-run settings for `ingest`.
+pub struct IngestOptions {
+    pub invisible_chars: InvisibleChars,
+    /// Unicode normalization applied before cleaning.
+    pub unicode_form: UnicodeForm,
+    /// Turn curly quotes and apostrophes into straight ones before cleaning.
+    pub fold_quotes: bool,
+    pub cleaning: CleaningPipeline,
+    /// Tag sentences with their language as `lang`, dropping unwanted ones.
+    pub language: Option<LanguageFilter>,
+    /// Replace emails, phone numbers and URLs with placeholders before
+    /// cleaning; the placeholders survive cleaning as they are.
+    pub redaction: Option<Redactor>,
+    /// Drop sentences already stored earlier in the run.
+    pub seen: Option<SeenFilter>,
+    /// Store each sentence's MinHash signature as `minHash` and flag or skip
+    /// ones nearly the same as a sentence stored before them.
+    pub near_duplicates: Option<NearDuplicates>,
+    pub segmenter: Segmenter,
+    /// Count each source's lines first so progress and `positionRatio` are
+    /// accurate. Costs an extra read of every source.
+    pub two_pass: bool,
+    /// Most bytes of a line held at once; longer lines of plain text are
+    /// split in pieces read as lines of their own, and longer records are
+    /// left out as failing to parse.
+    pub max_line_bytes: usize,
+    pub batch_size: usize,
+    /// Write a batch early once its oldest sentence has waited this long,
+    /// so slowly arriving input such as a pipe isn't held back until a
+    /// whole batch is full. Checked as each line arrives.
+    pub flush_interval: Option<Duration>,
+    /// Collapse repeated punctuation in stored sentences to this many characters.
+    pub max_punctuation_run: Option<usize>,
+    pub line_metadata: Option<LineMetadata>,
+    /// Fields read off each file's name and added to all its sentences.
+    pub file_name_template: Option<FileNameTemplate>,
+    /// Strip YAML front matter and Project Gutenberg boilerplate, storing
+    /// the title, author and year they give with every sentence.
+    pub strip_headers: bool,
+    /// Stored as `ingestedAt` with every sentence, telling runs apart.
+    pub ingested_at: DateTime,
+    /// Splits sentences for `tokenCount`.
+    pub tokenizer: Tokenizer,
+    /// Also store the tokens themselves as `tokens`.
+    pub store_tokens: bool,
+    /// Store the lemma of each token as `lemmas`.
+    pub lemmatizer: Option<Lemmatizer>,
+    /// Store a part-of-speech tag for each token as `pos`.
+    pub pos_tagger: Option<Arc<dyn PosTagger>>,
+    /// Store readability and quality scores as `quality`.
+    pub quality_scores: bool,
+    /// Let sentences in plain text run on across line breaks, as in books
+    /// wrapped at a fixed width. Blank lines still end a sentence. Off, every
+    /// line is split on its own. Ignored with `line_metadata`, whose lines are
+    /// separate records.
+    pub join_lines: bool,
+    /// Rejoin words hyphenated across joined lines; `None` leaves them split.
+    pub dehyphenation: Option<Dehyphenator>,
+    /// Format of every source; `None` picks one from each file name.
+    pub format: Option<InputFormat>,
+    pub format_options: FormatOptions,
+    /// Encoding of text input; binary formats such as PDF are left alone.
+    pub encoding: TextEncoding,
+    /// Where progress is recorded after every batch, and resumed from.
+    pub checkpoint: Option<Checkpoint>,
+    /// Files already ingested unchanged are skipped, and files ingested
+    /// completely are added.
+    pub manifest: Option<Manifest>,
+    /// Ingest files the manifest has as unchanged anyway.
+    pub force: bool,
+    /// Delete the sentences stored from a source earlier before storing
+    /// its new ones.
+    pub replace: bool,
+    /// Counts what's read and written for the status line.
+    pub status: StatusLine,
+    /// Counts the lines that failed without stopping the run.
+    pub errors: ErrorSummary,
+    /// Asks the run to stop before the next line, once what's read so far
+    /// is written.
+    pub shutdown: Shutdown,
+    /// Times the phases of the pipeline, for `--bench`.
+    pub phase_times: Option<PhaseTimes>,
+    /// Gets the report of each file ingested.
+    pub file_reports: FileReports,
+    /// Keep only this share of each source's lines, for a preview; the
+    /// others are read as blank lines.
+    pub sample: Option<f64>,
+    /// Stop reading each source after this many of the lines kept.
+    pub limit: Option<usize>,
+}
+
+impl IngestOptions {
+    /// Whether only part of each source is ingested, as a preview.
+    pub fn previews(&self) -> bool {
+        self.sample.is_some() || self.limit.is_some()
+    }
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        IngestOptions {
+            invisible_chars: InvisibleChars::Normalize,
+            unicode_form: UnicodeForm::None,
+            fold_quotes: false,
+            cleaning: CleaningPipeline::default(),
+            redaction: None,
+            language: None,
+            seen: None,
+            near_duplicates: None,
+            segmenter: Segmenter::default(),
+            two_pass: false,
+            max_line_bytes: 1024 * 1024,
+            batch_size: 1000,
+            flush_interval: None,
+            max_punctuation_run: None,
+            line_metadata: None,
+            file_name_template: None,
+            strip_headers: false,
+            ingested_at: DateTime::now(),
+            tokenizer: Tokenizer::default(),
+            store_tokens: false,
+            lemmatizer: None,
+            pos_tagger: None,
+            quality_scores: false,
+            join_lines: true,
+            dehyphenation: Some(Dehyphenator::default()),
+            format: None,
+            format_options: FormatOptions::default(),
+            encoding: TextEncoding::Auto,
+            checkpoint: None,
+            manifest: None,
+            force: false,
+            replace: false,
+            status: StatusLine::default(),
+            errors: ErrorSummary::default(),
+            shutdown: Shutdown::default(),
+            phase_times: None,
+            file_reports: FileReports::default(),
+            sample: None,
+            limit: None,
+        }
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+/// Reads `source` from the start, decompressing it if needed, and ingests it
+/// with [`ingest_reader`], counting its lines first in two-pass mode.
+pub async fn ingest(
+    source: &dyn CorpusSource,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    if let Some(members) = source.members() {
+        let mut sentence_count = 0;
+        for member in members {
+            let Member { name, data } = member?;
+            log::info!(file = name; "Ingesting {} from {}", name, source.name());
+            let format = options.format.unwrap_or_else(|| InputFormat::detect(&name));
+            sentence_count += if format.streams_lines() {
+                let total_lines = if options.two_pass {
+                    Some(count_lines(data.as_slice())?)
+                } else {
+                    None
+                };
+                let reader = decode_reader(Box::new(std::io::Cursor::new(data)), options.encoding)?;
+                ingest_read_ahead(reader, format, &name, total_lines, options, sink).await?
+            } else {
+                ingest_document(&data, format, &name, options, sink).await?
+            };
+        }
+        if let Some(size) = source.path().and_then(file_size) {
+            options.status.add_bytes(size);
+        }
+        return Ok(sentence_count);
+    }
+
+    let format = options
+        .format
+        .unwrap_or_else(|| InputFormat::detect(source.name()));
+    if !format.streams_lines() {
+        let mut data = Vec::new();
+        decompress(Box::new(options.status.counting(source.open()?)))?
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Error reading {}: {}", source.name(), e))?;
+        return ingest_document(&data, format, source.name(), options, sink).await;
+    }
+
+    let total_lines = if options.two_pass {
+        if !source.rereadable() {
+            return Err(format!("two-pass mode can't re-read {}", source.name()).into());
+        }
+        let total = count_lines(decompress(source.open()?)?)?;
+        log::info!(file = source.name(), lines = total; "Counted {} lines in {}", total, source.name());
+        Some(total)
+    } else {
+        None
+    };
+
+    let reader = Box::new(options.status.counting(source.open()?));
+    let reader = decode_reader(decompress(reader)?, options.encoding)?;
+    ingest_read_ahead(reader, format, source.name(), total_lines, options, sink).await
+}
+
+/// Extracts the blocks of a whole document and ingests them as lines. The
+/// block count is known up front, so two-pass mode costs nothing extra here.
+async fn ingest_document(
+    data: &[u8],
+    format: InputFormat,
+    file_path: &str,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    let decoded;
+    let data = if format.is_binary() {
+        data
+    } else {
+        decoded = decode_bytes(data, options.encoding);
+        decoded.as_bytes()
+    };
+    let blocks =
+        extract_blocks(format, data, &options.format_options).map_err(|e| IngestError::Source {
+            file: file_path.to_string(),
+            line: None,
+            stage: Stage::Extract,
+            source: e,
+        })?;
+    let total_lines = options.two_pass.then_some(blocks.len());
+    ingest_blocks(
+        blocks.into_iter().map(Ok),
+        Layout::Blocks,
+        file_path,
+        total_lines,
+        options,
+        sink,
+    )
+    .await
+}
+
+/// Ingests `sources` with one tokio task per sink in `sinks`; each task
+/// takes the next unprocessed source and segments and batches it on its own.
+/// Together with the thread reading each source ahead and a
+/// [`WriterSink`](crate::sink::WriterSink) behind the sinks, that makes a
+/// pipeline of reading, segmentation and writing stages.
+/// After the first error, or once the run is asked to stop, no new sources
+/// are started. Returns the total number of sentences processed.
+pub async fn ingest_all(
+    sources: Vec<Box<dyn CorpusSource>>,
+    options: Arc<IngestOptions>,
+    sinks: Vec<Box<dyn SentenceSink>>,
+) -> Result<usize, IngestError> {
+    let sources = Arc::new(sources);
+    let next = Arc::new(AtomicUsize::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+
+    let mut workers = Vec::with_capacity(sinks.len());
+    for mut sink in sinks {
+        let sources = Arc::clone(&sources);
+        let options = Arc::clone(&options);
+        let next = Arc::clone(&next);
+        let failed = Arc::clone(&failed);
+
+        workers.push(tokio::spawn(async move {
+            let mut sentence_count = 0;
+            while !failed.load(Ordering::Relaxed) && !options.shutdown.is_requested() {
+                let Some(source) = sources.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                    break;
+                };
+                match ingest_unless_unchanged(source.as_ref(), &options, sink.as_mut()).await {
+                    Ok(count) => sentence_count += count,
+                    Err(e) => {
+                        failed.store(true, Ordering::Relaxed);
+                        return Err(e);
+                    }
+                }
+            }
+            Ok::<_, IngestError>(sentence_count)
+        }));
+    }
+
+    let mut sentence_count = 0;
+    let mut first_error = None;
+    for worker in workers {
+        match worker.await? {
+            Ok(count) => sentence_count += count,
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+    // Sources never started when the run was asked to stop
+    if let Some(source) = sources.get(next.load(Ordering::Relaxed)) {
+        return Err(IngestError::Interrupted {
+            file: source.name().to_string(),
+        });
+    }
+    Ok(sentence_count)
+}
+
+/// Ingests `source` unless the manifest has it as unchanged, then records it
+/// there.
+async fn ingest_unless_unchanged(
+    source: &dyn CorpusSource,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, IngestError> {
+    ingest_recorded(source, options, sink)
+        .await
+        .map_err(|e| IngestError::in_source(source.name(), Stage::Read, e))
+}
+
+async fn ingest_recorded(
+    source: &dyn CorpusSource,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    // A preview doesn't count as ingesting the source
+    let manifest = options
+        .manifest
+        .as_ref()
+        .zip(source.path())
+        .filter(|_| !options.previews());
+    if let (Some((manifest, path)), false) = (manifest, options.force) {
+        if manifest.is_unchanged(path).await? {
+            log::info!(
+                file = source.name();
+                "Skipping {}, unchanged since it was ingested",
+                source.name()
+            );
+            options.status.add_bytes(file_size(path).unwrap_or(0));
+            return Ok(0);
+        }
+    }
+    log::info!(file = source.name(); "Ingesting {}", source.name());
+    let count = ingest(source, options, sink).await?;
+    if let Some((manifest, path)) = manifest {
+        manifest.record(path, count).await?;
+    }
+    METRICS.add_file();
+    Ok(count)
+}
+
+/// Cleans and splits every line of `reader`, handing the resulting documents
+/// to `sink` in batches. `total_lines`, when known, enables progress
+/// percentages and `positionRatio`. Returns the number of sentences processed.
+pub async fn ingest_reader<R: BufRead>(
+    reader: R,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    ingest_lines(
+        read_lines(reader, options.max_line_bytes),
+        InputFormat::Text,
+        file_path,
+        total_lines,
+        options,
+        sink,
+    )
+    .await
+}
+
+/// Ingests the `lines` of a [line-streamed](InputFormat::streams_lines)
+/// format, parsing each into a block as it goes.
+async fn ingest_lines(
+    lines: impl Iterator<Item = std::io::Result<Line>>,
+    format: InputFormat,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    let blocks = parse_lines(
+        lines,
+        format,
+        file_path,
+        options.max_line_bytes,
+        &options.format_options,
+    );
+    let layout = line_layout(format, options);
+    ingest_blocks(blocks, layout, file_path, total_lines, options, sink).await
+}
+
+/// Like [`ingest_lines`], but reads the lines of `reader` and parses them
+/// ahead on a thread of their own with [`read_ahead`].
+async fn ingest_read_ahead(
+    reader: Box<dyn BufRead + Send>,
+    format: InputFormat,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError> {
+    let blocks = read_ahead(reader, format, file_path, options);
+    let layout = line_layout(format, options);
+    ingest_block_stream(blocks, layout, file_path, total_lines, options, sink).await
+}
+
+/// How the lines of `format` group into paragraphs.
+fn line_layout(format: InputFormat, options: &IngestOptions) -> Layout {
+    // Records such as JSONL objects or metadata-prefixed lines stand alone
+    if format == InputFormat::Text && options.line_metadata.is_none() {
+        Layout::Lines {
+            join_lines: options.join_lines,
+        }
+    } else {
+        Layout::Blocks
+    }
+}
+
+/// Parses each of `lines` into a block, numbered with the line it's from.
+fn parse_lines<'a>(
+    lines: impl Iterator<Item = std::io::Result<Line>> + 'a,
+    format: InputFormat,
+    file_path: &'a str,
+    max_line_bytes: usize,
+    format_options: &'a FormatOptions,
+) -> impl Iterator<Item = Result<Block, IngestError>> + 'a {
+    // A record such as a JSONL object is only whole in one piece, so the
+    // rest of one cut short is dropped
+    let mut in_cut_record = false;
+    let lines = lines.filter(move |line| {
+        let cut_record = format != InputFormat::Text && line.as_ref().is_ok_and(|line| line.cut);
+        !std::mem::replace(&mut in_cut_record, cut_record)
+    });
+    // The line of the last piece read, for an error reading the next
+    let mut number = 0;
+    lines.map(move |line_result| {
+        let line = line_result.map_err(|e| IngestError::Source {
+            file: file_path.to_string(),
+            line: Some(number + 1),
+            stage: Stage::Read,
+            source: e.into(),
+        })?;
+        number = line.number;
+        let parse_error = |e| IngestError::Line {
+            file: file_path.to_string(),
+            line: line.number,
+            stage: Stage::Parse,
+            source: e,
+        };
+        if line.cut && format != InputFormat::Text {
+            return Err(parse_error(
+                format!("longer than {} bytes", max_line_bytes).into(),
+            ));
+        }
+        let (offset, len) = (line.offset, line.len);
+        let mut block = parse_line(format, line.text, format_options).map_err(parse_error)?;
+        // Only plain text lines are stored as they were read
+        if format == InputFormat::Text {
+            block.offset = Some(offset);
+        }
+        block.line = Some(line.number);
+        block.source_bytes = Some(len);
+        Ok(block)
+    })
+}
+
+/// Lines a source's reader thread gets ahead of its segmentation by.
+const READ_AHEAD_LINES: usize = 1024;
+/// The most bytes of lines of the longest length allowed the thread gets
+/// ahead by, so it holds fewer of them when they're allowed to be long.
+const READ_AHEAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// Reads the lines of `reader` with [`read_lines`], parses them and strips
+/// the document's headers on a blocking thread of its own, so reading,
+/// decompressing and decoding the next lines overlaps with segmenting the
+/// last ones without holding up the runtime's workers. The thread waits
+/// once it's [`READ_AHEAD_LINES`] ahead, or fewer if long lines would make
+/// more than [`READ_AHEAD_BYTES`], and stops when the blocks are dropped.
+fn read_ahead(
+    reader: Box<dyn BufRead + Send>,
+    format: InputFormat,
+    file_path: &str,
+    options: &IngestOptions,
+) -> impl Stream<Item = Result<Block, IngestError>> {
+    let max_bytes = options.max_line_bytes;
+    let ahead = (READ_AHEAD_BYTES / max_bytes.max(1)).clamp(1, READ_AHEAD_LINES);
+    let (sender, receiver) = tokio::sync::mpsc::channel(ahead);
+    let file_path = file_path.to_string();
+    let format_options = options.format_options.clone();
+    let strip_headers = options.strip_headers;
+    tokio::task::spawn_blocking(move || {
+        let lines = read_lines(reader, max_bytes);
+        let blocks = parse_lines(lines, format, &file_path, max_bytes, &format_options);
+        for block in DocumentHeaders::new(blocks, strip_headers) {
+            if sender.blocking_send(block).is_err() {
+                break;
+            }
+        }
+    });
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        let block = receiver.recv().await?;
+        Some((block, receiver))
+    })
+}
+
+/// A line as [`read_lines`] gives it.
+struct Line {
+    text: String,
+    /// The line of the source it's on, counted from 1.
+    number: usize,
+    /// Where it starts in the source.
+    offset: SourceOffset,
+    /// Bytes it takes up in the source, with its line break.
+    len: usize,
+    /// Whether it's the start of a line longer than the most read at once,
+    /// going on in the next.
+    cut: bool,
+}
+
+/// Like [`BufRead::lines`], but also gives where each line starts, and
+/// holds no more than `max_bytes` of a line at once: a longer one, such as
+/// a whole file without newlines, comes in pieces cut after the last
+/// whitespace that fits, or the last whole character if there's none.
+fn read_lines<R: BufRead>(
+    mut reader: R,
+    max_bytes: usize,
+) -> impl Iterator<Item = std::io::Result<Line>> {
+    let mut offset = SourceOffset::default();
+    let mut number = 0;
+    // What's left of a line after a piece of it was cut off, and whether
+    // there is any
+    let mut rest = Vec::new();
+    let mut continues = false;
+    std::iter::from_fn(move || {
+        let mut bytes = std::mem::take(&mut rest);
+        let ended = match read_until_newline(&mut reader, &mut bytes, max_bytes) {
+            Ok(ended) => ended,
+            Err(e) => return Some(Err(e)),
+        };
+        if bytes.is_empty() {
+            return None;
+        }
+        let cut = !ended && bytes.len() >= max_bytes;
+        if !std::mem::replace(&mut continues, cut) {
+            number += 1;
+        }
+        if cut {
+            rest = bytes.split_off(cut_point(&bytes));
+        }
+        let mut text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(e) => return Some(Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))),
+        };
+        let (start, len) = (offset, text.len());
+        offset = offset.advance(&text);
+        if text.ends_with('\n') {
+            text.pop();
+            if text.ends_with('\r') {
+                text.pop();
+            }
+        }
+        Some(Ok(Line {
+            text,
+            number,
+            offset: start,
+            len,
+            cut,
+        }))
+    })
+}
+
+/// Reads into `bytes` up to and including the next newline, but no further
+/// than `max_bytes` in all, and returns whether the newline was reached.
+fn read_until_newline<R: BufRead>(
+    reader: &mut R,
+    bytes: &mut Vec<u8>,
+    max_bytes: usize,
+) -> std::io::Result<bool> {
+    while bytes.len() < max_bytes {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            break;
+        }
+        let room = available.len().min(max_bytes - bytes.len());
+        let (taken, ended) = match available[..room].iter().position(|&b| b == b'\n') {
+            Some(newline) => (newline + 1, true),
+            None => (room, false),
+        };
+        bytes.extend_from_slice(&available[..taken]);
+        reader.consume(taken);
+        if ended {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Where to cut a piece off the front of an over-long line: just after its
+/// last whitespace, or else before its last character, which may be
+/// incomplete.
+fn cut_point(bytes: &[u8]) -> usize {
+    match bytes.iter().rposition(u8::is_ascii_whitespace) {
+        Some(space) if space > 0 => space + 1,
+        _ => bytes
+            .iter()
+            .rposition(|&b| b & 0xC0 != 0x80)
+            .filter(|&start| start > 0)
+            .unwrap_or(bytes.len()),
+    }
+}
+
+/// How the blocks given to [`ingest_blocks`] group into paragraphs, which
+/// are numbered from 0 in each file as `paragraphIndex` and identified by a
+/// ULID as `paragraphId`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Lines of plain text. A blank line, or a line indented deeper than the
+    /// one before it, starts a new paragraph. With `join_lines` a sentence
+    /// may run on over several lines and is recorded under the line it
+    /// starts on; see [`IngestOptions::join_lines`].
+    Lines { join_lines: bool },
+    /// Paragraphs or records such as JSONL objects, one per block.
+    Blocks,
+}
+
+/// Like [`ingest_reader`], but over extracted blocks; each block is numbered
+/// as a line unless it has the [line](Block::line) it came from, and its
+/// metadata is added to every sentence from it.
+pub async fn ingest_blocks<I>(
+    blocks: I,
+    layout: Layout,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError>
+where
+    I: Iterator<Item = Result<Block, IngestError>>,
+{
+    let mut headers = DocumentHeaders::new(blocks, options.strip_headers);
+    // Iterated through a closure, as rustc can't otherwise prove the future
+    // holding it is Send
+    let blocks = std::iter::from_fn(move || headers.next());
+    let blocks = futures::stream::iter(blocks);
+    ingest_block_stream(blocks, layout, file_path, total_lines, options, sink).await
+}
+
+/// Like [`ingest_blocks`], but over blocks that may have to be waited for,
+/// with their headers already stripped, in a `file` log span.
+async fn ingest_block_stream<S>(
+    blocks: S,
+    layout: Layout,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError>
+where
+    S: Stream<Item = Result<Block, IngestError>>,
+{
+    let file = ingest_file_blocks(blocks, layout, file_path, total_lines, options, sink);
+    logging::in_span("file", &[("file", file_path.into())], file).await
+}
+
+async fn ingest_file_blocks<S>(
+    blocks: S,
+    layout: Layout,
+    file_path: &str,
+    total_lines: Option<usize>,
+    options: &IngestOptions,
+    sink: &mut dyn SentenceSink,
+) -> Result<usize, BoxError>
+where
+    S: Stream<Item = Result<Block, IngestError>>,
+{
+    let started = Instant::now();
+    let mut batch = vec![];
+    let batch_size = options.batch_size;
+    // When the oldest sentence of the batch was added
+    let mut batch_started = Instant::now();
+    let mut sentence_count = 0;
+    let join_lines = layout == Layout::Lines { join_lines: true };
+    let mut paragraph = Paragraph::default();
+    let mut rejections = Rejections::default();
+    let mut position = Position::new(file_path);
+    let mut in_paragraph = false;
+    let mut previous_indent = 0;
+
+    let checkpoint = options.checkpoint.as_ref();
+    let resume = checkpoint.and_then(|checkpoint| checkpoint.progress(file_path));
+    // Sentences of the source produced so far, counting earlier runs, and
+    // how many of the next ones were written by the last of those
+    let mut produced = 0;
+    let mut skip = 0;
+    let mut resume_line = 0;
+    if let Some(progress) = &resume {
+        if progress.done {
+            log::info!("Skipping {}, already ingested", file_path);
+            return Ok(0);
+        }
+        log::info!(
+            line = progress.line;
+            "Resuming {} after line {}",
+            file_path, progress.line
+        );
+        position = Position::resume(progress)?;
+        in_paragraph = progress.in_paragraph;
+        previous_indent = progress.previous_indent;
+        produced = progress.sentences - progress.written_after;
+        skip = progress.written_after;
+        resume_line = progress.line;
+    }
+    if let Some(total) = total_lines {
+        options.status.add_total_lines(total as u64);
+    }
+    let file_metadata = match &options.file_name_template {
+        Some(template) => template.extract(file_path).unwrap_or_else(|| {
+            log::warn!("{} doesn't fit the file name template", file_path);
+            Document::new()
+        }),
+        None => Document::new(),
+    };
+    // A resumed source's sentences from before the interruption stay
+    if options.replace && resume.is_none() {
+        let removed = sink.remove_source(file_path).await?;
+        if removed > 0 {
+            log::info!(
+                removed;
+                "Removed {} sentences stored earlier from {}",
+                removed, file_path
+            );
+        }
+    }
+    // The last line after which no paragraph was left open
+    let mut boundary = (
+        position.progress(resume_line, in_paragraph, previous_indent),
+        produced,
+    );
+
+    let mut blocks = Box::pin(blocks.enumerate().peekable());
+    // Where the next line lies, once part of a word moved onto or off it
+    let mut next_pieces: Option<Pieces> = None;
+    // What the last paragraph is stored with once the input ends
+    let mut last_metadata = file_metadata.clone();
+    // Which lines a sample keeps depends on the source, and how many were
+    // kept on how many a limit lets through
+    let sample_seed = fnv1a(file_path.as_bytes());
+    let mut kept = 0;
+    let (mut lines_read, mut bytes_read) = (0, 0);
+    loop {
+        let read_started = Instant::now();
+        let Some((index, block)) = blocks.next().await else {
+            break;
+        };
+        add_time(options, Phase::Read, read_started);
+        if let Some(times) = &options.phase_times {
+            times.add_line();
+        }
+        if options.shutdown.is_requested() {
+            return stop_early(&mut batch, sink, options, file_path, &boundary, produced).await;
+        }
+        // Blocks read a line at a time know theirs, and the rest are one each
+        let line_number = match &block {
+            Ok(Block {
+                line: Some(line), ..
+            })
+            | Err(IngestError::Line { line, .. }) => *line,
+            _ => index + 1,
+        };
+        let new_line = line_number as u64 != lines_read;
+        lines_read = line_number as u64;
+        if new_line && total_lines.is_some() {
+            options.status.add_line();
+        }
+        if line_number <= resume_line {
+            continue;
+        }
+        let Block {
+            text: mut line,
+            metadata: block_metadata,
+            offset,
+            source_bytes,
+            ..
+        } = match block {
+            Ok(block) => block,
+            Err(e) if e.is_recoverable() => {
+                log::warn!(line = line_number; "{}", e);
+                options.errors.record(&e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // As read, before rejoining words across lines moves text between them
+        bytes_read += source_bytes.unwrap_or(line.len()) as u64;
+        // Lines left out of a sample end paragraphs like blank ones, so
+        // sentences aren't made up of lines that weren't next to each other
+        if options
+            .sample
+            .is_some_and(|share| !is_sampled(sample_seed, line_number, share))
+        {
+            line.clear();
+        } else if new_line {
+            if options.limit.is_some_and(|limit| kept >= limit) {
+                break;
+            }
+            kept += 1;
+        }
+        // Where the line lies in the source, counting a word rejoined with
+        // the line before
+        let mut pieces = offset.map(|offset| next_pieces.take().unwrap_or(Pieces::new(offset)));
+        let mut rejoined = false;
+        if join_lines && line.trim_end().ends_with('-') {
+            if let (Some(dehyphenator), Some((_, Ok(next)))) =
+                (&options.dehyphenation, blocks.as_mut().peek_mut().await)
+            {
+                let (line_before, next_before) = (line.clone(), next.text.clone());
+                dehyphenator.rejoin(&mut line, &mut next.text);
+                rejoined = next.text != next_before;
+                if let (true, Some(pieces), Some(next_offset)) =
+                    (rejoined, pieces.as_mut(), next.offset)
+                {
+                    next_pieces = Some(pieces.rejoin(
+                        (&line_before, &line),
+                        (&next_before, &next.text),
+                        next_offset,
+                    ));
+                }
+            }
+        }
+        // Metadata has to come off before cleaning strips its delimiters
+        let (text, mut metadata) = match &options.line_metadata {
+            Some(line_metadata) => line_metadata.extract(&line),
+            None => (line.as_str(), Document::new()),
+        };
+        for (key, value) in block_metadata {
+            metadata.entry(key).or_insert(value);
+        }
+        for (key, value) in &file_metadata {
+            if !metadata.contains_key(key) {
+                metadata.insert(key.clone(), value.clone());
+            }
+        }
+        if !line.trim().is_empty() {
+            last_metadata.clone_from(&metadata);
+        }
+
+        let (raw_line, cleaned_line) = timed(options, Phase::Clean, || clean(text, options));
+        let blank = cleaned_line.is_empty();
+        let source = pieces.filter(|_| !blank).map(|pieces| {
+            // The text may be only the end of the line, after its metadata
+            let text_start = text.as_ptr() as usize - line.as_ptr() as usize;
+            LineSource::new(pieces.starting_at(&line, text_start), text, &raw_line)
+        });
+
+        // Indentation has to be measured before cleaning trims it
+        let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+        let starts_paragraph = match layout {
+            Layout::Lines { .. } => blank || indent > previous_indent,
+            Layout::Blocks => true,
+        };
+        if !blank {
+            previous_indent = indent;
+        }
+
+        let segment_started = Instant::now();
+        let mut documents = Vec::new();
+        if in_paragraph && starts_paragraph {
+            if join_lines {
+                let sentences = paragraph.finish(&options.segmenter);
+                documents = sentence_documents(
+                    sentences,
+                    &metadata,
+                    file_path,
+                    total_lines,
+                    &mut position,
+                    options,
+                    &mut rejections,
+                );
+            }
+            position.next_paragraph();
+            in_paragraph = false;
+        }
+
+        // Split the cleaned line into sentences
+        if !blank {
+            in_paragraph = true;
+            let sentences = if join_lines {
+                paragraph.push_line(
+                    &cleaned_line,
+                    &raw_line,
+                    source,
+                    line_number,
+                    &options.segmenter,
+                )
+            } else {
+                let split = options.segmenter.candidates(&cleaned_line);
+                if split.is_empty() {
+                    rejections.record(Rejection::TooShort);
+                }
+                locate(&cleaned_line, &raw_line, split)
+                    .into_iter()
+                    .map(|(text, _, raw)| Sentence {
+                        text,
+                        source: source.as_ref().map(|source| {
+                            source.position(raw.start, false)..source.position(raw.end, true)
+                        }),
+                        raw: raw_line[raw].to_string(),
+                        line_number,
+                    })
+                    .collect()
+            };
+            documents.extend(sentence_documents(
+                sentences,
+                &metadata,
+                file_path,
+                total_lines,
+                &mut position,
+                options,
+                &mut rejections,
+            ));
+        }
+        add_time(options, Phase::Segment, segment_started);
+
+        for document in documents {
+            produced += 1;
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            if batch.is_empty() {
+                batch_started = Instant::now();
+            }
+            batch.push(document);
+            sentence_count += 1;
+
+            if batch.len() >= batch_size {
+                write_out(&mut batch, sink, options, file_path, &boundary, produced).await?;
+            }
+        }
+        let waited = |interval| batch_started.elapsed() >= interval;
+        if !batch.is_empty() && options.flush_interval.is_some_and(waited) {
+            write_out(&mut batch, sink, options, file_path, &boundary, produced).await?;
+        }
+
+        // A line that was rejoined with the next changed it, and one with
+        // pieces still to come isn't done, so reading can't start over from
+        // there
+        let continues = matches!(
+            blocks.as_mut().peek().await,
+            Some((_, Ok(next))) if next.line.is_some_and(|next| next == line_number)
+        );
+        if (!join_lines || paragraph.is_empty()) && !rejoined && !continues {
+            boundary = (
+                position.progress(line_number, in_paragraph, previous_indent),
+                produced,
+            );
+        }
+    }
+
+    // The last paragraph has no blank line after it
+    let documents = timed(options, Phase::Segment, || {
+        let sentences = paragraph.finish(&options.segmenter);
+        sentence_documents(
+            sentences,
+            &last_metadata,
+            file_path,
+            total_lines,
+            &mut position,
+            options,
+            &mut rejections,
+        )
+    });
+    for document in documents {
+        produced += 1;
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        batch.push(document);
+        sentence_count += 1;
+    }
+
+    rejections.too_short += paragraph.too_short;
+    if rejections.total() > 0 {
+        log::info!(
+            rejected = rejections.total();
+            "Rejected {} fragments in {}: {} too short, {} too long, {} mostly digits, {} in other languages, {} repeats, {} near duplicates",
+            rejections.total(),
+            file_path,
+            rejections.too_short,
+            rejections.too_long,
+            rejections.too_many_digits,
+            rejections.other_language,
+            rejections.duplicate,
+            rejections.near_duplicate
+        );
+    }
+
+    // Handle any remaining items in the batch
+    tag_batch(&mut batch, options, file_path).await;
+    let write_started = Instant::now();
+    if !batch.is_empty() {
+        write_batch(batch, sink, options, file_path).await?;
+    }
+    match checkpoint {
+        Some(checkpoint) => {
+            save_progress(checkpoint, sink, file_path, &boundary, produced, true).await?
+        }
+        None => sink.flush().await.map_err(|e| sink_error(file_path, e))?,
+    }
+    add_time(options, Phase::Write, write_started);
+
+    let report = FileReport {
+        file: file_path.to_string(),
+        lines: lines_read,
+        bytes: bytes_read,
+        sentences: sentence_count as u64,
+        rejections,
+        elapsed: started.elapsed(),
+    };
+    log::info!(
+        lines = report.lines, bytes = report.bytes,
+        sentences = report.sentences, rejected = rejections.total(),
+        millis = report.elapsed.as_millis() as u64;
+        "Finished {}: {} lines, {} bytes, {} sentences, {} fragments rejected, in {:.1}s",
+        file_path, report.lines, report.bytes, report.sentences, rejections.total(),
+        report.elapsed.as_secs_f64()
+    );
+    options.file_reports.add(report);
+
+    Ok(sentence_count)
+}
+
+/// Whether a sample keeping `share` of the lines of the source hashed to
+/// `seed` keeps line `line_number`; every run picks the same lines.
+fn is_sampled(seed: u64, line_number: usize, share: f64) -> bool {
+    let hash = mix(seed ^ mix(line_number as u64));
+    // The top 53 bits as a number from 0 to 1
+    ((hash >> 11) as f64 / (1u64 << 53) as f64) < share
+}
+
+/// Runs `f`, adding the time it takes to `phase` when the run is timed.
+fn timed<T>(options: &IngestOptions, phase: Phase, f: impl FnOnce() -> T) -> T {
+    match &options.phase_times {
+        Some(times) => times.time(phase, f),
+        None => f(),
+    }
+}
+
+/// Adds the time since `started` to `phase` when the run is timed.
+fn add_time(options: &IngestOptions, phase: Phase, started: Instant) {
+    if let Some(times) = &options.phase_times {
+        times.add(phase, started.elapsed());
+    }
+}
+
+/// Normalizes invisible characters and Unicode forms in `text`, then removes
+/// unwanted characters, giving the normalized line, which is kept as the raw
+/// text, and the cleaned one.
+fn clean(text: &str, options: &IngestOptions) -> (String, String) {
+    let mut raw_line = normalize_invisible(text, options.invisible_chars);
+    if options.unicode_form != UnicodeForm::None || options.fold_quotes {
+        raw_line = normalize_unicode(&raw_line, options.unicode_form, options.fold_quotes);
+    }
+    // Redacted text is never stored, not even as the raw text
+    let cleaned_line = match &options.redaction {
+        Some(redactor) => {
+            if let Cow::Owned(redacted) = redactor.redact(&raw_line) {
+                raw_line = redacted;
+            }
+            options
+                .cleaning
+                .apply_keeping(&raw_line, &redactor.placeholders())
+        }
+        None => options.cleaning.apply(&raw_line),
+    };
+    let cleaned_line = cleaned_line.trim().to_string();
+    (raw_line, cleaned_line)
+}
+
+/// `error` from writing the sentences of `file_path` to the sink.
+fn sink_error(file_path: &str, error: BoxError) -> IngestError {
+    IngestError::Sink {
+        file: file_path.to_string(),
+        source: error,
+    }
+}
+
+/// Hands `batch` over to `sink`, leaving an empty one of the same capacity
+/// in its place, and records the progress that completes.
+async fn write_out(
+    batch: &mut Vec<Document>,
+    sink: &mut dyn SentenceSink,
+    options: &IngestOptions,
+    file_path: &str,
+    boundary: &(Progress, usize),
+    produced: usize,
+) -> Result<(), BoxError> {
+    let mut full = std::mem::replace(batch, Vec::with_capacity(batch.capacity()));
+    tag_batch(&mut full, options, file_path).await;
+    let started = Instant::now();
+    write_batch(full, sink, options, file_path).await?;
+    add_time(options, Phase::Write, started);
+    match options.checkpoint.as_ref() {
+        Some(checkpoint) => {
+            save_progress(checkpoint, sink, file_path, boundary, produced, false).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Adds the `pos` tags of every sentence of `batch` if there's a tagger,
+/// asking it for the whole batch at once on a blocking thread, as it may
+/// be waiting on a tagging service. A batch that can't be tagged is stored
+/// without them, its sentences counted as failed.
+async fn tag_batch(batch: &mut [Document], options: &IngestOptions, file_path: &str) {
+    let Some(tagger) = options.pos_tagger.clone() else {
+        return;
+    };
+    if batch.is_empty() {
+        return;
+    }
+    let started = Instant::now();
+    let sentences: Vec<Vec<String>> = batch
+        .iter()
+        .map(|document| {
+            let text = document.get_str("text").unwrap_or_default();
+            options
+                .tokenizer
+                .tokens(text)
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        })
+        .collect();
+    let span = logging::Span::current();
+    let tagged = tokio::task::spawn_blocking(move || span.enter(|| tagger.tag_all(&sentences)))
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+    match tagged {
+        Ok(tags) => {
+            for (document, tags) in batch.iter_mut().zip(tags) {
+                document.insert("pos", tags);
+            }
+        }
+        Err(e) => {
+            let line_of = |document: &Document| document.get_i32("lineNumber").unwrap_or(0);
+            let first = line_of(&batch[0]);
+            log::warn!(line = first; "Error tagging {} sentences from line {}: {}", batch.len(), first, e);
+            for document in batch.iter() {
+                options.errors.record(&IngestError::Line {
+                    file: file_path.to_string(),
+                    line: line_of(document) as usize,
+                    stage: Stage::Tag,
+                    source: e.to_string().into(),
+                });
+            }
+        }
+    }
+    add_time(options, Phase::Segment, started);
+}
+
+/// Hands `batch` to `sink` in a `batch` log span, numbered among the run's
+/// batches as it's handed over.
+async fn write_batch(
+    batch: Vec<Document>,
+    sink: &mut dyn SentenceSink,
+    options: &IngestOptions,
+    file_path: &str,
+) -> Result<(), BoxError> {
+    let sentences = batch.len();
+    let number = options.status.add_batch();
+    let fields = [("batch", number.into()), ("sentences", sentences.into())];
+    logging::in_span("batch", &fields, async {
+        let started = Instant::now();
+        sink.write_batch(batch)
+            .await
+            .map_err(|e| sink_error(file_path, e))?;
+        options.status.add_sentences(sentences as u64);
+        METRICS.add_sentences(sentences as u64);
+        log::debug!(
+            millis = started.elapsed().as_millis() as u64;
+            "Wrote batch {} of {} sentences",
+            number, sentences
+        );
+        Ok(())
+    })
+    .await
+}
+
+/// Writes out what's batched of `file_path` when the run is asked to stop
+/// partway through it, and saves its progress up to the `boundary` line, so
+/// resuming reads on from there; the paragraph being read is left to then.
+async fn stop_early(
+    batch: &mut Vec<Document>,
+    sink: &mut dyn SentenceSink,
+    options: &IngestOptions,
+    file_path: &str,
+    boundary: &(Progress, usize),
+    produced: usize,
+) -> Result<usize, BoxError> {
+    if !batch.is_empty() {
+        write_out(batch, sink, options, file_path, boundary, produced).await?;
+    }
+    match options.checkpoint.as_ref() {
+        Some(checkpoint) => {
+            save_progress(checkpoint, sink, file_path, boundary, produced, false).await?
+        }
+        None => sink.flush().await.map_err(|e| sink_error(file_path, e))?,
+    }
+    Err(IngestError::Interrupted {
+        file: file_path.to_string(),
+    }
+    .into())
+}
+
+/// Flushes `sink` and records that the `produced` sentences of `file_path`
+/// are written, the ones after the `boundary` line included.
+async fn save_progress(
+    checkpoint: &Checkpoint,
+    sink: &mut dyn SentenceSink,
+    file_path: &str,
+    (progress, boundary_produced): &(Progress, usize),
+    produced: usize,
+    done: bool,
+) -> Result<(), BoxError> {
+    sink.flush().await.map_err(|e| sink_error(file_path, e))?;
+    checkpoint
+        .save(
+            file_path,
+            Progress {
+                written_after: produced - boundary_produced,
+                sentences: produced,
+                done,
+                ..progress.clone()
+            },
+        )
+        .map_err(|e| IngestError::in_source(file_path, Stage::Checkpoint, e).into())
+}
+
+/// A sentence ready to be stored.
+struct Sentence {
+    text: String,
+    /// The text it was cleaned from.
+    raw: String,
+    /// The line it starts on.
+    line_number: usize,
+    /// Where the raw text lies in the source, when the block was a line of it.
+    source: Option<Range<SourceOffset>>,
+}
+
+/// Where the parts of a line lie in the source, as the byte of the line
+/// each starts at and where that is in the source, each running on there
+/// up to the next. A line is one piece unless rejoining a word broken
+/// across a line break moved part of it in from the line before or after.
+#[derive(Debug, Clone, PartialEq)]
+struct Pieces(Vec<(usize, SourceOffset)>);
+
+impl Pieces {
+    fn new(offset: SourceOffset) -> Self {
+        Pieces(vec![(0, offset)])
+    }
+
+    /// Where byte `index` of `line` lies in the source; for the `end` of a
+    /// range, the position just past the byte before it.
+    fn at(&self, line: &str, index: usize, end: bool) -> SourceOffset {
+        let (start, offset) = self
+            .0
+            .iter()
+            .rev()
+            .find(|(start, _)| *start < index || *start == index && !(end && index > 0))
+            .unwrap_or(&self.0[0]);
+        offset.advance(&line[*start..index])
+    }
+
+    /// The pieces of `line` from byte `index` on.
+    fn starting_at(&self, line: &str, index: usize) -> Pieces {
+        let first = (0, self.at(line, index, false));
+        let rest = self.0.iter().filter(|(start, _)| *start > index);
+        Pieces(
+            std::iter::once(first)
+                .chain(rest.map(|(start, offset)| (start - index, *offset)))
+                .collect(),
+        )
+    }
+
+    /// Follows [`Dehyphenator::rejoin`](crate::clean::Dehyphenator::rejoin)
+    /// turning `line` into `line_after`, and the next line, found at
+    /// `next_offset`, from `next` into `next_after`: either the word's tail
+    /// moved up onto the end of `line`, or its head down onto the start of
+    /// the next. Returns the next line's pieces.
+    fn rejoin(
+        &mut self,
+        (line, line_after): (&str, &str),
+        (next, next_after): (&str, &str),
+        next_offset: SourceOffset,
+    ) -> Pieces {
+        let kept = common_prefix_len(line, line_after);
+        let moved_down = next_after.len() - common_suffix_len(next, next_after);
+        let mut next_pieces = Pieces(Vec::new());
+        if moved_down > 0 {
+            // The head, with its hyphen if it kept it, ended the line
+            let head = &next_after[..moved_down];
+            let trimmed = line.trim_end();
+            let end = match trimmed.ends_with(head) {
+                true => trimmed.len(),
+                false => trimmed.len() - '-'.len_utf8(),
+            };
+            next_pieces
+                .0
+                .push((0, self.at(line, end - head.len(), false)));
+        }
+        let stays = next.len() - (next_after.len() - moved_down);
+        next_pieces
+            .0
+            .push((moved_down, next_offset.advance(&next[..stays])));
+
+        self.0.retain(|(start, _)| *start == 0 || *start < kept);
+        if line_after.len() > kept {
+            let tail_start = next.len() - next.trim_start().len();
+            self.0
+                .push((kept, next_offset.advance(&next[..tail_start])));
+        }
+        next_pieces
+    }
+}
+
+/// Bytes at the start of `a` and `b` that are the same, in whole characters.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((index, _), _)| index)
+}
+
+/// Bytes at the end of `a` and `b` that are the same, in whole characters.
+fn common_suffix_len(a: &str, b: &str) -> usize {
+    a.chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum()
+}
+
+/// Maps positions in a line after normalization back to the line as it was
+/// read.
+struct LineSource {
+    /// Where the parts of `original` lie in the source.
+    pieces: Pieces,
+    original: String,
+    /// How the normalized line lines up with `original`; `None` when
+    /// normalization left it as it was.
+    alignment: Option<Alignment>,
+    /// Length of the normalized line.
+    len: usize,
+}
+
+impl LineSource {
+    fn new(pieces: Pieces, original: &str, normalized: &str) -> Self {
+        LineSource {
+            pieces,
+            original: original.to_string(),
+            alignment: (original != normalized).then(|| Alignment::new(original, normalized)),
+            len: normalized.len(),
+        }
+    }
+
+    /// Where byte `offset` of the normalized line came from; for the `end`
+    /// of a range, the position just past the character before it.
+    fn position(&self, offset: usize, end: bool) -> SourceOffset {
+        let offset = offset.min(self.len);
+        let offset = match &self.alignment {
+            Some(alignment) if end && offset > 0 => alignment.raw_span(offset - 1..offset).end,
+            Some(alignment) => alignment.raw_span(offset..offset).start,
+            None => offset,
+        };
+        self.pieces.at(&self.original, offset, end)
+    }
+}
+
+/// Identifies the paragraph being built and the next sentence stored from
+/// it. Every source gets a ULID as `docId` made from its file name, and
+/// every paragraph another as `paragraphId` made from that and its index,
+/// so ingesting a source again, replaced, changed or in watch mode, keeps
+/// the ids stored references point to. `sentenceIndex` numbers the stored
+/// sentences of a source from 0, so a sentence's neighbours are the ones
+/// with the same `docId` and the index next to it.
+struct Position {
+    doc_id: Ulid,
+    paragraph_id: Ulid,
+    paragraph_index: usize,
+    sentence_index: usize,
+}
+
+impl Position {
+    fn new(file_path: &str) -> Self {
+        let doc_id = stable_ulid(&[file_path.as_bytes()]);
+        Position {
+            doc_id,
+            paragraph_id: paragraph_id(doc_id, 0),
+            paragraph_index: 0,
+            sentence_index: 0,
+        }
+    }
+
+    fn next_paragraph(&mut self) {
+        self.paragraph_index += 1;
+        self.paragraph_id = paragraph_id(self.doc_id, self.paragraph_index);
+    }
+
+    /// Picks up where `progress` left off.
+    fn resume(progress: &Progress) -> Result<Self, BoxError> {
+        let ulid = |id: &str| {
+            Ulid::from_string(id).map_err(|e| format!("invalid id '{}' in checkpoint: {}", id, e))
+        };
+        Ok(Position {
+            doc_id: ulid(&progress.doc_id)?,
+            paragraph_id: ulid(&progress.paragraph_id)?,
+            paragraph_index: progress.paragraph_index,
+            sentence_index: progress.sentence_index,
+        })
+    }
+
+    /// The progress of a source read up to `line`, with nothing written yet.
+    fn progress(&self, line: usize, in_paragraph: bool, previous_indent: usize) -> Progress {
+        Progress {
+            line,
+            doc_id: self.doc_id.to_string(),
+            paragraph_id: self.paragraph_id.to_string(),
+            paragraph_index: self.paragraph_index,
+            sentence_index: self.sentence_index,
+            in_paragraph,
+            previous_indent,
+            ..Progress::default()
+        }
+    }
+}
+
+/// A ULID made from a hash of `parts`, the same whenever they are.
+fn stable_ulid(parts: &[&[u8]]) -> Ulid {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let digest = hasher.finalize();
+    Ulid(u128::from_be_bytes(
+        digest[..16].try_into().expect("16 bytes"),
+    ))
+}
+
+fn paragraph_id(doc_id: Ulid, index: usize) -> Ulid {
+    stable_ulid(&[&doc_id.0.to_be_bytes(), &(index as u64).to_le_bytes()])
+}
+
+/// Builds the documents for `sentences` of one paragraph, adding `metadata`
+/// to every one. Sentences past the validity maximums are counted in
+/// `rejections` instead.
+fn sentence_documents(
+    sentences: Vec<Sentence>,
+    metadata: &Document,
+    file_path: &str,
+    total_lines: Option<usize>,
+    position: &mut Position,
+    options: &IngestOptions,
+    rejections: &mut Rejections,
+) -> Vec<Document> {
+    let mut documents = Vec::with_capacity(sentences.len());
+    for Sentence {
+        text: sentence,
+        raw,
+        line_number,
+        source,
+    } in sentences
+    {
+        let sentence = match options.max_punctuation_run {
+            Some(max_run) => collapse_punctuation(&sentence, max_run),
+            None => sentence,
+        };
+        if sentence.is_empty() {
+            continue;
+        }
+        if let Err(rejection) = options.segmenter.check(&sentence) {
+            rejections.record(rejection);
+            continue;
+        }
+        // The raw text still has the accents and apostrophes that tell
+        // languages apart
+        let lang = options.language.as_ref().map(|filter| filter.detect(&raw));
+        if let (Some(filter), Some(lang)) = (&options.language, lang) {
+            if !filter.accepts(lang) {
+                rejections.record(Rejection::OtherLanguage);
+                continue;
+            }
+        }
+        let mut document = sentence_document(&sentence, file_path, line_number, total_lines);
+        if let Some(lang) = lang.flatten() {
+            document.insert("lang", lang.code());
+        }
+        if let Some(seen) = &options.seen {
+            if seen.insert(document.get_str("textHash").unwrap_or_default()) {
+                rejections.record(Rejection::Duplicate);
+                continue;
+            }
+        }
+        if let Some(near) = &options.near_duplicates {
+            let signature = min_hash(&sentence);
+            let text_hash = document.get_str("textHash").unwrap_or_default();
+            if let Some(original) = near.check(&signature, text_hash) {
+                if near.action == NearDuplicateAction::Skip {
+                    rejections.record(Rejection::NearDuplicate);
+                    continue;
+                }
+                document.insert("nearDuplicateOf", original);
+            }
+            let signature: Vec<i64> = signature.into_iter().map(i64::from).collect();
+            document.insert("minHash", signature);
+        }
+        let tokens = options.tokenizer.tokens(&sentence);
+        document.insert("tokenCount", tokens.len() as i32);
+        if let Some(lemmatizer) = &options.lemmatizer {
+            document.insert("lemmas", lemmatizer.lemmas(&tokens));
+        }
+        if options.quality_scores {
+            document.insert("quality", Quality::of(&sentence, &tokens).to_document());
+        }
+        if options.store_tokens {
+            document.insert("tokens", tokens);
+        }
+        document.insert("rawText", raw);
+        document.insert("docId", position.doc_id.to_string());
+        document.insert("paragraphId", position.paragraph_id.to_string());
+        document.insert("paragraphIndex", position.paragraph_index as i32);
+        document.insert("sentenceIndex", position.sentence_index as i32);
+        document.insert("ingestedAt", options.ingested_at);
+        document.insert("schemaVersion", SCHEMA_VERSION);
+        position.sentence_index += 1;
+        if let Some(Range { start, end }) = source {
+            document.insert("startOffset", start.byte as i64);
+            document.insert("endOffset", end.byte as i64);
+            document.insert("startCharOffset", start.char as i64);
+            document.insert("endCharOffset", end.char as i64);
+        }
+        for (key, value) in metadata {
+            document.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        documents.push(document);
+    }
+    documents
+}
+
+/// Pairs each of `sentences`, split from `cleaned`, with where it lies in
+/// `cleaned` and in `raw`, the text that was cleaned.
+fn locate(
+    cleaned: &str,
+    raw: &str,
+    sentences: Vec<String>,
+) -> Vec<(String, Range<usize>, Range<usize>)> {
+    let alignment = Alignment::new(raw, cleaned);
+    let mut cursor = 0;
+    sentences
+        .into_iter()
+        .map(|sentence| {
+            // A short trailing fragment merged onto the last sentence makes
+            // it differ from the text; it still starts at the cursor
+            let start = cleaned[cursor..]
+                .find(sentence.as_str())
+                .map_or(cursor, |found| cursor + found);
+            cursor = (start + sentence.len()).min(cleaned.len());
+            while !cleaned.is_char_boundary(cursor) {
+                cursor -= 1;
+            }
+            let raw_range = alignment.raw_range(raw, start..cursor);
+            (sentence, start..cursor, raw_range)
+        })
+        .collect()
+}
+
+/// What's kept of a paragraph after its complete sentences are taken is
+/// split as it stands once longer than this, rather than waiting any longer
+/// for a blank line or the end of a sentence, so memory stays bounded.
+const MAX_PARAGRAPH_LEN: usize = 64 * 1024;
+
+/// Cleaned text carried over from line to line until the segmenter can tell
+/// where its sentences end, with the raw text it came from and the offset at
+/// which each line starts.
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    raw: String,
+    line_starts: Vec<(usize, usize)>,
+    /// The lines making up `raw`, by where they start counting from the
+    /// start of the paragraph, including text already drained.
+    raw_lines: Vec<RawLine>,
+    /// How much has been drained from the front of `raw`.
+    raw_drained: usize,
+    /// Paragraphs left out because they were too short for a sentence.
+    too_short: usize,
+}
+
+/// A line added to a [`Paragraph`]'s raw text.
+struct RawLine {
+    start: usize,
+    /// Whitespace trimmed off the front of the normalized line.
+    lead: usize,
+    source: Option<LineSource>,
+}
+
+impl Paragraph {
+    /// Adds a line and returns the sentences now known to be complete, which
+    /// is all but the last one found; that one may continue on the next
+    /// line. A blank line ends the paragraph.
+    fn push_line(
+        &mut self,
+        line: &str,
+        raw: &str,
+        source: Option<LineSource>,
+        line_number: usize,
+        segmenter: &Segmenter,
+    ) -> Vec<Sentence> {
+        let line = line.trim();
+        if line.is_empty() {
+            return self.finish(segmenter);
+        }
+        if !self.text.is_empty() {
+            self.text.push(' ');
+            self.raw.push(' ');
+        }
+        self.line_starts.push((self.text.len(), line_number));
+        self.text.push_str(line);
+        self.raw_lines.push(RawLine {
+            start: self.raw_drained + self.raw.len(),
+            lead: raw.len() - raw.trim_start().len(),
+            source,
+        });
+        self.raw.push_str(raw.trim());
+
+        let mut sentences = locate(&self.text, &self.raw, segmenter.candidates(&self.text));
+        // Nothing valid yet: keep everything for the next line
+        let Some((_, last, last_raw)) = sentences.pop() else {
+            if self.text.len() > MAX_PARAGRAPH_LEN {
+                return self.finish(segmenter);
+            }
+            return Vec::new();
+        };
+        let mut complete: Vec<_> = sentences
+            .into_iter()
+            .map(|sentence| self.sentence(sentence))
+            .collect();
+
+        let start_line = self.line_at(last.start);
+        self.text.drain(..last.start);
+        self.raw.drain(..last_raw.start);
+        self.raw_drained += last_raw.start;
+        let first_kept = self
+            .raw_lines
+            .iter()
+            .rposition(|line| line.start <= self.raw_drained)
+            .unwrap_or(0);
+        self.raw_lines.drain(..first_kept);
+        self.line_starts.retain(|&(offset, _)| offset > last.start);
+        for (offset, _) in &mut self.line_starts {
+            *offset -= last.start;
+        }
+        self.line_starts.insert(0, (0, start_line));
+        if self.text.len() > MAX_PARAGRAPH_LEN {
+            complete.extend(self.finish(segmenter));
+        }
+        complete
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Splits whatever is left, at a blank line or the end of the input.
+    fn finish(&mut self, segmenter: &Segmenter) -> Vec<Sentence> {
+        let sentences: Vec<_> = locate(&self.text, &self.raw, segmenter.candidates(&self.text))
+            .into_iter()
+            .map(|sentence| self.sentence(sentence))
+            .collect();
+        if sentences.is_empty() && !self.text.is_empty() {
+            self.too_short += 1;
+        }
+        self.text.clear();
+        self.raw.clear();
+        self.line_starts.clear();
+        self.raw_lines.clear();
+        self.raw_drained = 0;
+        sentences
+    }
+
+    fn sentence(&self, (text, range, raw): (String, Range<usize>, Range<usize>)) -> Sentence {
+        Sentence {
+            text,
+            source: self
+                .source_at(raw.start, false)
+                .zip(self.source_at(raw.end, true))
+                .map(|(start, end)| start..end),
+            raw: self.raw[raw].to_string(),
+            line_number: self.line_at(range.start),
+        }
+    }
+
+    /// Where byte `offset` of `raw` came from in the source; see
+    /// [`LineSource::position`].
+    fn source_at(&self, offset: usize, end: bool) -> Option<SourceOffset> {
+        let offset = self.raw_drained + offset;
+        // The end of a range belongs to the line its last byte is on
+        let key = if end {
+            offset.saturating_sub(1)
+        } else {
+            offset
+        };
+        let line = self
+            .raw_lines
+            .iter()
+            .take_while(|line| line.start <= key)
+            .last()?;
+        let source = line.source.as_ref()?;
+        Some(source.position(line.lead + offset - line.start, end))
+    }
+
+    /// The line on which the byte at `offset` appeared.
+    fn line_at(&self, offset: usize) -> usize {
+        self.line_starts
+            .iter()
+            .take_while(|&&(start, _)| start <= offset)
+            .last()
+            .map_or(0, |&(_, line_number)| line_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::io::Cursor;
+
+    #[test]
+    fn counts_lines_with_and_without_trailing_newline() {
+        assert_eq!(count_lines(Cursor::new("one\ntwo\nthree\n")).unwrap(), 3);
+        assert_eq!(count_lines(Cursor::new("one\ntwo\nthree")).unwrap(), 3);
+        assert_eq!(count_lines(Cursor::new("")).unwrap(), 0);
+    }
+
+    #[test]
+    fn position_ratio_is_only_set_in_two_pass_mode() {
+        let input = "First line of the file.\nSecond line of the file.\nThird line of the file.\nLast line of the file.\n";
+        let total = count_lines(Cursor::new(input)).unwrap();
+
+        let two_pass = sentence_document("Third line of the file.", "f.txt", 3, Some(total));
+        assert_eq!(two_pass.get_f64("positionRatio").unwrap(), 0.75);
+
+        let last = sentence_document("Last line of the file.", "f.txt", 4, Some(total));
+        assert_eq!(last.get_f64("positionRatio").unwrap(), 1.0);
+
+        let single_pass = sentence_document("Third line of the file.", "f.txt", 3, None);
+        assert!(!single_pass.contains_key("positionRatio"));
+    }
+
+    #[test]
+    fn text_hash_ignores_case_and_spacing() {
+        let hash = text_hash("The cat sat down.");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(text_hash("the  cat sat\tDOWN."), hash);
+        assert_ne!(text_hash("The cat sat down!"), hash);
+        assert_eq!(
+            sentence_document("The cat sat down.", "f.txt", 1, None).get_str("textHash"),
+            Ok(hash.as_str())
+        );
+    }
+
+    /// Keeps every written document in memory.
+    #[derive(Default)]
+    struct CollectSink {
+        documents: Vec<Document>,
+        batch_sizes: Vec<usize>,
+    }
+
+    #[async_trait]
+    impl SentenceSink for CollectSink {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            self.batch_sizes.push(batch.len());
+            self.documents.extend(batch);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn extracts_bracketed_line_metadata() {
+        let input = "[2021-03-01][user42] The first post is short. It has two sentences.\n\
+                     [2021-03-02][user7] Another user wrote this one.\n\
+                     A line without any metadata at all.\n";
+        let options = IngestOptions {
+            line_metadata: Some(
+                LineMetadata::new(
+                    r"^\[(?P<date>[^\]]+)\]\[(?P<user>[^\]]+)\]\s*(?P<text>.*)$",
+                    "text",
+                )
+                .unwrap(),
+            ),
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+
+        ingest_reader(Cursor::new(input), "posts.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        let docs = &sink.documents;
+        assert_eq!(docs.len(), 4);
+        assert_eq!(docs[0].get_str("text").unwrap(), "The first post is short.");
+        assert_eq!(docs[1].get_str("text").unwrap(), "It has two sentences.");
+        for doc in &docs[..2] {
+            assert_eq!(doc.get_str("date").unwrap(), "2021-03-01");
+            assert_eq!(doc.get_str("user").unwrap(), "user42");
+        }
+        assert_eq!(
+            docs[2].get_str("text").unwrap(),
+            "Another user wrote this one."
+        );
+        assert_eq!(docs[2].get_str("user").unwrap(), "user7");
+        assert_eq!(
+            docs[3].get_str("text").unwrap(),
+            "A line without any metadata at all."
+        );
+        assert!(!docs[3].contains_key("date"));
+    }
+
+    #[test]
+    fn reads_fields_from_file_names() {
+        let template = FileNameTemplate::new("{genre}_{year:int}_{id}.txt").unwrap();
+        assert_eq!(
+            template.extract("corpus/acad/acad_1995_0042.txt"),
+            Some(doc! { "genre": "acad", "year": 1995_i64, "id": "0042" })
+        );
+        assert_eq!(template.extract("acad_nineties_0042.txt"), None);
+        assert_eq!(template.extract("acad_1995_0042.txt.bak"), None);
+        assert!(FileNameTemplate::new("{year:date}.txt").is_err());
+    }
+
+    #[test]
+    fn line_metadata_requires_text_group() {
+        assert!(LineMetadata::new(r"^\[(?P<date>[^\]]+)\]\s*(?P<body>.*)$", "text").is_err());
+    }
+
+    struct MemorySource {
+        name: String,
+        text: String,
+    }
+
+    impl CorpusSource for MemorySource {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn open(&self) -> Result<Box<dyn BufRead + Send>, BoxError> {
+            Ok(Box::new(Cursor::new(self.text.clone().into_bytes())))
+        }
+    }
+
+    /// Collects documents from every clone into one list.
+    #[derive(Clone, Default)]
+    struct SharedCollectSink {
+        documents: Arc<std::sync::Mutex<Vec<Document>>>,
+    }
+
+    #[async_trait]
+    impl SentenceSink for SharedCollectSink {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            self.documents.lock().unwrap().extend(batch);
+            Ok(())
+        }
+
+        fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+            Some(Box::new(self.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn two_pass_mode_counts_the_lines_of_a_source_first() {
+        let source = MemorySource {
+            name: "notes.txt".to_string(),
+            text: "The first line is here.\n\nThe middle line is here.\nThe last line is here."
+                .to_string(),
+        };
+        let options = IngestOptions {
+            two_pass: true,
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+
+        ingest(&source, &options, &mut sink).await.unwrap();
+
+        let ratio = |document: &Document| document.get_f64("positionRatio").unwrap();
+        let (first, last) = (&sink.documents[0], sink.documents.last().unwrap());
+        assert_eq!(ratio(first), 0.25);
+        assert_eq!(ratio(last), 1.0);
+        assert!(options.status.line().contains("4 / 4 lines (100%)"));
+    }
+
+    #[tokio::test]
+    async fn extracts_html_sources_by_extension() {
+        let source = MemorySource {
+            name: "page.html".to_string(),
+            text: "<body><nav>Home | About | Contact us</nav>\
+                   <p>The article starts here. It is short.</p></body>"
+                .to_string(),
+        };
+        let mut sink = CollectSink::default();
+
+        ingest(&source, &IngestOptions::default(), &mut sink)
+            .await
+            .unwrap();
+
+        let texts: Vec<_> = sink
+            .documents
+            .iter()
+            .map(|d| d.get_str("text").unwrap())
+            .collect();
+        assert_eq!(texts, ["The article starts here.", "It is short."]);
+    }
+
+    #[test]
+    fn cuts_over_long_lines_into_pieces() {
+        let pieces: Vec<_> = read_lines(Cursor::new("one two three\nfour\nnaïve"), 8)
+            .map(|line| line.unwrap())
+            .map(|line| (line.text, line.number, line.offset.byte, line.cut))
+            .collect();
+        assert_eq!(
+            pieces,
+            [
+                ("one two ".to_string(), 1, 0, true),
+                ("three".to_string(), 1, 8, false),
+                ("four".to_string(), 2, 14, false),
+                ("naïve".to_string(), 3, 19, false),
+            ]
+        );
+        // Without whitespace a piece ends before a character it would split
+        let pieces: Vec<_> = read_lines(Cursor::new("ééééé"), 5)
+            .map(|line| line.unwrap().text)
+            .collect();
+        assert_eq!(pieces, ["éé", "éé", "é"]);
+    }
+
+    #[tokio::test]
+    async fn segments_lines_without_newlines_in_bounded_pieces() {
+        let input = "It rained all day long. The sun came out at last. ".repeat(50);
+        let options = IngestOptions {
+            max_line_bytes: 64,
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+
+        let count = ingest_reader(Cursor::new(&input), "dump.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 100);
+        assert!(sink
+            .documents
+            .iter()
+            .step_by(2)
+            .all(|d| d.get_str("text").unwrap() == "It rained all day long."));
+    }
+
+    #[tokio::test]
+    async fn pieces_of_a_long_line_share_its_number() {
+        let input = format!(
+            "{}\nThe sun came out at last.\n",
+            "It rained all day long. ".repeat(10)
+        );
+        let options = IngestOptions {
+            max_line_bytes: 64,
+            two_pass: true,
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+
+        ingest_reader(
+            Cursor::new(&input),
+            "dump.txt",
+            Some(2),
+            &options,
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        let (last, pieces) = sink.documents.split_last().unwrap();
+        assert!(pieces.iter().all(|d| d.get_i32("lineNumber").unwrap() == 1));
+        assert_eq!(last.get_str("text").unwrap(), "The sun came out at last.");
+        assert_eq!(last.get_i32("lineNumber").unwrap(), 2);
+        assert_eq!(last.get_f64("positionRatio").unwrap(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn skips_and_counts_lines_that_fail_to_parse() {
+        let source = MemorySource {
+            name: "news.jsonl".to_string(),
+            text: "{\"text\": \"It rained all day long.\"}\n{\"text\": \"It\n\
+                   {\"text\": \"The sun came out at last.\"}\n"
+                .to_string(),
+        };
+        let options = IngestOptions::default();
+        let mut sink = CollectSink::default();
+
+        let count = ingest(&source, &options, &mut sink).await.unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(options.errors.total(), 1);
+        assert!(options.errors.summary()[0]
+            .starts_with("1 lines failed parsing, the first: news.jsonl:2: error parsing: "));
+    }
+
+    #[tokio::test]
+    async fn workers_share_the_sources() {
+        let sources: Vec<Box<dyn CorpusSource>> = (0..5)
+            .map(|i| {
+                Box::new(MemorySource {
+                    name: format!("file{}.txt", i),
+                    text: format!("This is file number {}. It has two sentences.\n", i),
+                }) as Box<dyn CorpusSource>
+            })
+            .collect();
+        let sink = SharedCollectSink::default();
+        let options = Arc::new(IngestOptions {
+            batch_size: 1,
+            ..IngestOptions::default()
+        });
+
+        let count = ingest_all(
+            sources,
+            options,
+            crate::sink::fan_out(Box::new(sink.clone()), 3),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count, 10);
+        let documents = sink.documents.lock().unwrap();
+        for i in 0..5 {
+            let name = format!("file{}.txt", i);
+            let from_file = documents
+                .iter()
+                .filter(|d| d.get_str("fileName").unwrap() == name)
+                .count();
+            assert_eq!(from_file, 2, "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_waiting_sentences_after_the_flush_interval() {
+        let input = "The first sentence arrives.\n\
+                     The second one comes later.\n\
+                     A third one comes last.\n";
+        for (flush_interval, batch_sizes) in
+            [(None, vec![3]), (Some(Duration::ZERO), vec![1, 1, 1])]
+        {
+            let mut sink = CollectSink::default();
+            let options = IngestOptions {
+                flush_interval,
+                join_lines: false,
+                ..IngestOptions::default()
+            };
+            ingest_reader(Cursor::new(input), "pipe", None, &options, &mut sink)
+                .await
+                .unwrap();
+            assert_eq!(sink.batch_sizes, batch_sizes);
+        }
+    }
+
+    #[tokio::test]
+    async fn times_the_phases_when_benchmarking() {
+        let input = "The first sentence arrives.\n\nThe second one comes later.\n";
+        let times = PhaseTimes::default();
+        let options = IngestOptions {
+            phase_times: Some(times.clone()),
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+        ingest_reader(Cursor::new(input), "pipe", None, &options, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(times.lines(), 3);
+        assert!(times.get(Phase::Segment) > Duration::ZERO);
+        assert!(times.get(Phase::Write) > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn samples_and_limits_the_lines_of_each_source() {
+        let input: String = (1..=200)
+            .map(|n| format!("This is sentence number {} of the file.\n", n))
+            .collect();
+        let lines_ingested = |sample, limit| {
+            let input = input.clone();
+            async move {
+                let options = IngestOptions {
+                    sample,
+                    limit,
+                    ..IngestOptions::default()
+                };
+                let mut sink = CollectSink::default();
+                ingest_reader(Cursor::new(input), "big.txt", None, &options, &mut sink)
+                    .await
+                    .unwrap();
+                sink.documents
+                    .iter()
+                    .map(|document| document.get_i32("lineNumber").unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let sampled = lines_ingested(Some(0.25), None).await;
+        assert!((30..=70).contains(&sampled.len()), "{}", sampled.len());
+        assert_eq!(lines_ingested(Some(0.25), None).await, sampled);
+
+        assert_eq!(
+            lines_ingested(None, Some(10)).await,
+            (1..=10).collect::<Vec<_>>()
+        );
+        let sampled_and_limited = lines_ingested(Some(0.25), Some(10)).await;
+        assert_eq!(sampled_and_limited, sampled[..10]);
+    }
+
+    #[tokio::test]
+    async fn reports_what_was_done_with_each_file() {
+        let input = "Short.\n\nThe first sen-\ntence arrives here.\n";
+        let options = IngestOptions::default();
+        let mut sink = CollectSink::default();
+        ingest_reader(Cursor::new(input), "a.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+        let files = options.file_reports.files();
+        assert_eq!(files.len(), 1);
+        let report = &files[0];
+        assert_eq!(
+            (
+                report.file.as_str(),
+                report.lines,
+                report.bytes,
+                report.sentences
+            ),
+            ("a.txt", 4, input.len() as u64, 1)
+        );
+        assert_eq!(report.rejections.too_short, 1);
+    }
+
+    #[tokio::test]
+    async fn joins_sentences_wrapped_across_lines() {
+        let input = "It was the best of times, it was the worst\n\
+                     of times. It was the age of wisdom,\n\
+                     it was the age of foolishness.\n\
+                     \n\
+                     A heading without a stop\n\
+                     \n\
+                     The next paragraph starts\n\
+                     here. And it ends here.\n";
+        let mut sink = CollectSink::default();
+
+        ingest_reader(
+            Cursor::new(input),
+            "book.txt",
+            None,
+            &IngestOptions::default(),
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        let sentences: Vec<_> = sink
+            .documents
+            .iter()
+            .map(|d| (d.get_str("text").unwrap(), d.get_i32("lineNumber").unwrap()))
+            .collect();
+        assert_eq!(
+            sentences,
+            [
+                ("It was the best of times it was the worst of times.", 1),
+                ("It was the age of wisdom it was the age of foolishness.", 2),
+                ("A heading without a stop", 5),
+                ("The next paragraph starts here.", 7),
+                ("And it ends here.", 8),
+            ]
+        );
+
+        let mut per_line = CollectSink::default();
+        let options = IngestOptions {
+            join_lines: false,
+            ..IngestOptions::default()
+        };
+        ingest_reader(
+            Cursor::new(input),
+            "book.txt",
+            None,
+            &options,
+            &mut per_line,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            per_line.documents[0].get_str("text").unwrap(),
+            "It was the best of times it was the worst"
+        );
+    }
+
+    #[tokio::test]
+    async fn numbers_paragraphs_at_blank_lines_and_indents() {
+        let input = "The first paragraph runs on\n\
+                     over two lines.\n\
+                     \n\
+                     \n    An indented line begins the second.\n\
+                     It carries on here.\n\
+                     \x20   The third one is indented too.\n";
+        for join_lines in [true, false] {
+            let options = IngestOptions {
+                join_lines,
+                ..IngestOptions::default()
+            };
+            let mut sink = CollectSink::default();
+            ingest_reader(Cursor::new(input), "book.txt", None, &options, &mut sink)
+                .await
+                .unwrap();
+
+            let paragraphs: Vec<_> = sink
+                .documents
+                .iter()
+                .map(|d| d.get_i32("paragraphIndex").unwrap())
+                .collect();
+            let expected: &[i32] = if join_lines {
+                &[0, 1, 1, 2]
+            } else {
+                &[0, 0, 1, 1, 2]
+            };
+            assert_eq!(paragraphs, expected, "join_lines: {}", join_lines);
+
+            // Sentences share their paragraph's ULID and are numbered
+            // through the file
+            let ids: Vec<_> = sink
+                .documents
+                .iter()
+                .map(|d| d.get_str("paragraphId").unwrap())
+                .collect();
+            for (i, document) in sink.documents.iter().enumerate() {
+                assert_eq!(document.get_i32("sentenceIndex").unwrap(), i as i32);
+                assert_eq!(document.get_i32("schemaVersion"), Ok(SCHEMA_VERSION));
+                assert!(document.get_i32("tokenCount").unwrap() > 0);
+                assert!(!document.contains_key("tokens"));
+                assert!(document.get_datetime("ingestedAt").is_ok());
+                assert_eq!(
+                    document.get_str("docId").unwrap(),
+                    sink.documents[0].get_str("docId").unwrap()
+                );
+                assert!(ulid::Ulid::from_string(ids[i]).is_ok());
+                if i > 0 {
+                    assert_eq!(ids[i] == ids[i - 1], paragraphs[i] == paragraphs[i - 1]);
+                }
+            }
+
+            // Ingesting the file again gives it the same ids, and another
+            // file others
+            let ids_of = |documents: &[Document]| -> Vec<(String, String)> {
+                documents
+                    .iter()
+                    .map(|d| {
+                        let id = |key| d.get_str(key).unwrap().to_string();
+                        (id("docId"), id("paragraphId"))
+                    })
+                    .collect()
+            };
+            for (name, same) in [("book.txt", true), ("other.txt", false)] {
+                let mut again = CollectSink::default();
+                ingest_reader(Cursor::new(input), name, None, &options, &mut again)
+                    .await
+                    .unwrap();
+                assert_eq!(ids_of(&again.documents) == ids_of(&sink.documents), same);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_raw_text_of_each_sentence() {
+        let input = "\u{201C}Don\u{2019}t,\u{201D} she said, \u{201C}it\u{2019}s\n\
+                     late.\u{201D} The caf\u{E9} had closed.\n";
+        let mut sink = CollectSink::default();
+        ingest_reader(
+            Cursor::new(input),
+            "book.txt",
+            None,
+            &IngestOptions::default(),
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        let sentences: Vec<_> = sink
+            .documents
+            .iter()
+            .map(|d| (d.get_str("text").unwrap(), d.get_str("rawText").unwrap()))
+            .collect();
+        assert_eq!(
+            sentences,
+            [
+                (
+                    "Dont she said its late.",
+                    "\u{201C}Don\u{2019}t,\u{201D} she said, \u{201C}it\u{2019}s late.\u{201D}"
+                ),
+                ("The caf had closed.", "The caf\u{E9} had closed."),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn records_source_offsets() {
+        let input =
+            "Caf\u{E9} owners met\r\n  on Monday. They agreed\u{200B} to\n\nclose early today.\n\
+                     \n\
+                     It rained now. And it con-\n\
+                     tinues on the next line nicely. The towns-\n\
+                     folk\n\
+                     stayed in all day.\n";
+        let mut sink = CollectSink::default();
+        ingest_reader(
+            Cursor::new(input),
+            "notes.txt",
+            None,
+            &IngestOptions::default(),
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        for document in &sink.documents {
+            let start = document.get_i64("startOffset").unwrap() as usize;
+            let end = document.get_i64("endOffset").unwrap() as usize;
+            let start_char = document.get_i64("startCharOffset").unwrap() as usize;
+            let end_char = document.get_i64("endCharOffset").unwrap() as usize;
+            assert_eq!(input[..start].chars().count(), start_char);
+            assert_eq!(input[..end].chars().count(), end_char);
+            // The zero-width space is a plain space in the raw text, and
+            // words broken across lines are rejoined
+            let source = input[start..end].replace('\u{200B}', "").replace("-\n", "");
+            assert_eq!(
+                source.split_whitespace().collect::<Vec<_>>(),
+                document
+                    .get_str("rawText")
+                    .unwrap()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+            );
+        }
+        let starts: Vec<_> = sink
+            .documents
+            .iter()
+            .map(|d| d.get_i64("startOffset").unwrap())
+            .collect();
+        assert_eq!(starts, [0, 20, 50, 70, 85, 129]);
+        let source = |index: usize| {
+            let document = &sink.documents[index];
+            let start = document.get_i64("startOffset").unwrap() as usize;
+            let end = document.get_i64("endOffset").unwrap() as usize;
+            &input[start..end]
+        };
+        assert_eq!(source(4), "And it con-\ntinues on the next line nicely.");
+        assert_eq!(source(5), "The towns-\nfolk\nstayed in all day.");
+    }
+
+    /// Fails every batch after the first `limit`.
+    struct FailingSink {
+        inner: CollectSink,
+        limit: usize,
+    }
+
+    #[async_trait]
+    impl SentenceSink for FailingSink {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            if self.limit == 0 {
+                return Err("connection lost".into());
+            }
+            self.limit -= 1;
+            self.inner.write_batch(batch).await
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_from_the_checkpoint() {
+        let input = "The first paragraph has one sentence. And then\n\
+                     another one that runs on. A third ends it.\n\
+                     \n\
+                     The second paragraph is long. It has four sentences.\n\
+                     The third of them is here. The last one ends it.\n\
+                     \n\
+                     A closing paragraph on its own line.\n";
+        let path = std::env::temp_dir().join(format!("resume-{}.json", std::process::id()));
+        let options = |checkpoint| IngestOptions {
+            batch_size: 2,
+            checkpoint: Some(checkpoint),
+            ..IngestOptions::default()
+        };
+        let fields = |documents: &[Document]| -> Vec<(String, i32, i32)> {
+            documents
+                .iter()
+                .map(|d| {
+                    (
+                        d.get_str("text").unwrap().to_string(),
+                        d.get_i32("paragraphIndex").unwrap(),
+                        d.get_i32("sentenceIndex").unwrap(),
+                    )
+                })
+                .collect()
+        };
+
+        let mut whole = CollectSink::default();
+        ingest_reader(
+            Cursor::new(input),
+            "a.txt",
+            None,
+            &IngestOptions::default(),
+            &mut whole,
+        )
+        .await
+        .unwrap();
+        assert_eq!(whole.documents.len(), 8);
+
+        // Two batches make it before the failure, then the rest on resuming
+        let mut failing = FailingSink {
+            inner: CollectSink::default(),
+            limit: 2,
+        };
+        let interrupted = options(Checkpoint::new(&path));
+        assert!(ingest_reader(
+            Cursor::new(input),
+            "a.txt",
+            None,
+            &interrupted,
+            &mut failing
+        )
+        .await
+        .is_err());
+        let mut resumed = CollectSink::default();
+        let resuming = options(Checkpoint::load(&path).unwrap());
+        let count = ingest_reader(Cursor::new(input), "a.txt", None, &resuming, &mut resumed)
+            .await
+            .unwrap();
+        assert_eq!(count, 4);
+
+        let mut documents = failing.inner.documents;
+        documents.extend(resumed.documents);
+        assert_eq!(fields(&documents), fields(&whole.documents));
+        assert!(documents
+            .iter()
+            .all(|d| d.get_str("docId") == documents[0].get_str("docId")));
+
+        // Nothing is left to do for a finished source
+        let finished = options(Checkpoint::load(&path).unwrap());
+        let mut again = CollectSink::default();
+        let count = ingest_reader(Cursor::new(input), "a.txt", None, &finished, &mut again)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Asks the run to stop once it has written a batch.
+    struct StoppingSink<S> {
+        inner: S,
+        shutdown: Shutdown,
+    }
+
+    #[async_trait]
+    impl<S: SentenceSink> SentenceSink for StoppingSink<S> {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            self.shutdown.request();
+            self.inner.write_batch(batch).await
+        }
+
+        async fn flush(&mut self) -> Result<(), BoxError> {
+            self.inner.flush().await
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_cleanly_and_resumes_when_asked_to_stop() {
+        let input = "One sentence here. Two sentences here.\n\
+                     \n\
+                     Three sentences here. Four sentences here.\n\
+                     \n\
+                     Five sentences here.\n";
+        let path = std::env::temp_dir().join(format!("shutdown-{}.json", std::process::id()));
+        let stopping = IngestOptions {
+            batch_size: 1,
+            checkpoint: Some(Checkpoint::new(&path)),
+            ..IngestOptions::default()
+        };
+        let mut sink = StoppingSink {
+            inner: CollectSink::default(),
+            shutdown: stopping.shutdown.clone(),
+        };
+        let error = ingest_reader(Cursor::new(input), "a.txt", None, &stopping, &mut sink)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<IngestError>(),
+            Some(IngestError::Interrupted { .. })
+        ));
+        // Reading stopped at the next line, with the first sentence written
+        assert_eq!(sink.inner.documents.len(), 1);
+
+        let resuming = IngestOptions {
+            checkpoint: Some(Checkpoint::load(&path).unwrap()),
+            ..IngestOptions::default()
+        };
+        let mut resumed = CollectSink::default();
+        let count = ingest_reader(Cursor::new(input), "a.txt", None, &resuming, &mut resumed)
+            .await
+            .unwrap();
+        assert_eq!(count, 4);
+        assert_eq!(
+            resumed.documents[0].get_str("text").unwrap(),
+            "Two sentences here."
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resumes_into_the_file_it_was_writing() {
+        use crate::decompress::{append_output, create_output};
+        use crate::sink::JsonlSink;
+
+        let sentences: Vec<_> = (1..=6)
+            .map(|n| format!("Sentence number {} is here.", n))
+            .collect();
+        let input = sentences.join("\n\n") + "\n";
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("resume-file-{}.json", std::process::id()));
+        let output = dir.join(format!("resume-file-{}.jsonl", std::process::id()));
+        let output = output.to_str().unwrap();
+        let stopping = IngestOptions {
+            batch_size: 2,
+            checkpoint: Some(Checkpoint::new(&path)),
+            ..IngestOptions::default()
+        };
+        let mut sink = StoppingSink {
+            inner: JsonlSink::new(create_output(output, None).unwrap()),
+            shutdown: stopping.shutdown.clone(),
+        };
+        let input_reader = || Cursor::new(input.clone());
+        assert!(
+            ingest_reader(input_reader(), "a.txt", None, &stopping, &mut sink)
+                .await
+                .is_err()
+        );
+        drop(sink);
+
+        let resuming = IngestOptions {
+            checkpoint: Some(Checkpoint::load(&path).unwrap()),
+            ..IngestOptions::default()
+        };
+        let mut sink = JsonlSink::new(append_output(output, None).unwrap());
+        ingest_reader(input_reader(), "a.txt", None, &resuming, &mut sink)
+            .await
+            .unwrap();
+        drop(sink);
+
+        let written: Vec<String> = std::fs::read_to_string(output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let document: serde_json::Value = serde_json::from_str(line).unwrap();
+                document["text"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(written, sentences);
+        assert!(append_output("a.jsonl.gz", None).is_err());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(output).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tags_a_batch_of_sentences_per_request() {
+        use crate::pos::HttpTagger;
+        use crate::sink::test_server::serve;
+
+        let (url, server) = serve(vec![
+            r#"{"tags": [["DET", "NOUN", "VERB", "ADV"], ["DET", "NOUN", "VERB", "ADP", "NOUN"]]}"#,
+            r#"[["DET", "NOUN", "VERB", "ADV"]]"#,
+        ]);
+        let options = IngestOptions {
+            batch_size: 2,
+            pos_tagger: Some(Arc::new(HttpTagger::new(url))),
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+        ingest_reader(
+            Cursor::new(
+                "The rain fell hard.\n\nThe town stayed in doors.\n\nThe sun shone later.\n",
+            ),
+            "a.txt",
+            None,
+            &options,
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        let tags: Vec<_> = sink
+            .documents
+            .iter()
+            .map(|d| d.get_array("pos").unwrap().len())
+            .collect();
+        assert_eq!(tags, [4, 5, 4]);
+        let received = server.join().unwrap();
+        assert_eq!(
+            received[0],
+            "POST / HTTP/1.1\n{\"sentences\":[[\"The\",\"rain\",\"fell\",\"hard\"],\
+             [\"The\",\"town\",\"stayed\",\"in\",\"doors\"]]}"
+        );
+    }
+
+    #[tokio::test]
+    async fn flags_or_skips_near_duplicates() {
+        let input = "Shares of the company rose 5 percent on Monday after the report.\n\
+                     The weather in the mountains was cold and wet all week.\n\
+                     Shares of the company rose 6 percent on Monday after the report.\n";
+        for action in [NearDuplicateAction::Flag, NearDuplicateAction::Skip] {
+            let options = IngestOptions {
+                near_duplicates: Some(NearDuplicates::new(action, 0.6)),
+                ..IngestOptions::default()
+            };
+            let mut sink = CollectSink::default();
+            ingest_reader(Cursor::new(input), "news.txt", None, &options, &mut sink)
+                .await
+                .unwrap();
+
+            let first = &sink.documents[0];
+            assert_eq!(first.get_array("minHash").unwrap().len(), 64);
+            match action {
+                NearDuplicateAction::Flag => {
+                    assert_eq!(sink.documents.len(), 3);
+                    assert_eq!(
+                        sink.documents[2].get_str("nearDuplicateOf"),
+                        first.get_str("textHash")
+                    );
+                    assert!(!sink.documents[1].contains_key("nearDuplicateOf"));
+                }
+                NearDuplicateAction::Skip => assert_eq!(sink.documents.len(), 2),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn redacts_before_cleaning_and_keeps_placeholders() {
+        let input = "Write to jane@example.org or visit https://example.org/a soon.\n";
+        let options = IngestOptions {
+            redaction: Some(Redactor::default()),
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+        ingest_reader(Cursor::new(input), "mail.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        let document = &sink.documents[0];
+        assert_eq!(
+            document.get_str("text").unwrap(),
+            "Write to <EMAIL> or visit <URL> soon."
+        );
+        assert_eq!(
+            document.get_str("rawText").unwrap(),
+            "Write to <EMAIL> or visit <URL> soon."
+        );
+    }
+
+    #[tokio::test]
+    async fn tags_languages_and_drops_unwanted_ones() {
+        let input = "The committee met on Monday to discuss the budget for next year.\n\
+                     Le comit\u{E9} s\u{2019}est r\u{E9}uni lundi pour discuter du budget de l\u{2019}ann\u{E9}e prochaine.\n";
+        let options = IngestOptions {
+            language: Some(LanguageFilter::from_codes(&["eng"]).unwrap()),
+            join_lines: false,
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+        ingest_reader(Cursor::new(input), "mixed.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        assert_eq!(sink.documents.len(), 1);
+        assert_eq!(sink.documents[0].get_str("lang").unwrap(), "eng");
+    }
+
+    #[tokio::test]
+    async fn rejoins_hyphenated_words_before_cleaning() {
+        let input = "The inter-\nnational team met in Lon-\ndon on a well-\nknown date.\n";
+        let options = IngestOptions {
+            dehyphenation: Some(Dehyphenator::default().with_dictionary(["well", "known"])),
+            ..IngestOptions::default()
+        };
+        let mut sink = CollectSink::default();
+        ingest_reader(Cursor::new(input), "book.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        // The default cleaning regex drops the compound's hyphen as usual
+        assert_eq!(sink.documents.len(), 1);
+        assert_eq!(
+            sink.documents[0].get_str("text").unwrap(),
+            "The international team met in London on a wellknown date."
+        );
+        assert_eq!(sink.documents[0].get_i32("lineNumber").unwrap(), 1);
+    }
+}
@@ -0,0 +1,74 @@
+//! Keyword-in-context lines: each match of a term with the text either side
+//! of it, aligned so the matches line up in a column.
+
+use std::ops::Range;
+
+/// Where `term` first occurs in `sentence` as whole words, ignoring case.
+/// When it doesn't, as with the other forms of a word a text index also
+/// matches, the first word starting with it is taken instead.
+pub fn find_keyword(sentence: &str, term: &str) -> Option<Range<usize>> {
+    let lower = sentence.to_lowercase();
+    let term = term.trim().to_lowercase();
+    // Lowercasing can change byte lengths; matches are only mapped back
+    // when it didn't
+    if term.is_empty() || lower.len() != sentence.len() {
+        return None;
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '\'';
+    let starts_word = |i: usize| !lower[..i].chars().next_back().is_some_and(is_word);
+    let occurrences = || {
+        lower
+            .match_indices(&term)
+            .map(|(i, _)| i)
+            .filter(|&i| starts_word(i))
+    };
+    let whole = occurrences().find(|&i| !lower[i + term.len()..].starts_with(is_word));
+    let start = whole.or_else(|| occurrences().next())?;
+    let end = lower[start..]
+        .find(|c: char| !is_word(c))
+        .map_or(lower.len(), |len| start + len)
+        .max(start + term.len());
+    Some(start..end)
+}
+
+/// `left`, `keyword` and `right` as one line, with `left` cut to its last
+/// `width` characters and padded to them, and `right` cut to `width`.
+pub fn concordance_line(left: &str, keyword: &str, right: &str, width: usize) -> String {
+    let left: String = collapse(left);
+    let right: String = collapse(right);
+    let left_chars = left.chars().count();
+    let left: String = left
+        .chars()
+        .skip(left_chars.saturating_sub(width))
+        .collect();
+    let right: String = right.chars().take(width).collect();
+    format!("{:>width$}  {}  {}", left, keyword, right, width = width)
+}
+
+/// `text` on one line, with runs of whitespace as single spaces.
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_aligns_keywords() {
+        let sentence = "Rivers run; the river runs fast.";
+        assert_eq!(find_keyword(sentence, "river"), Some(16..21));
+        assert_eq!(find_keyword(sentence, "RUN"), Some(7..10));
+        assert_eq!(find_keyword("It outran them.", "ran"), None);
+        assert_eq!(find_keyword("The runners left.", "run"), Some(4..11));
+
+        assert_eq!(
+            concordance_line("It rained.  The", "river", "rose all night long.", 10),
+            "ained. The  river  rose all n"
+        );
+        assert_eq!(
+            concordance_line("", "Rivers", "run.", 6),
+            "        Rivers  run."
+        );
+    }
+}
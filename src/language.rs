@@ -0,0 +1,76 @@
+//! Language identification of sentences, to tag them or keep only some
+//! languages.
+
+use std::collections::HashSet;
+use whatlang::Lang;
+
+use crate::BoxError;
+
+/// Tags each sentence with its language and drops those that are reliably
+/// in a language outside `languages`. Sentences too short to tell are kept
+/// untagged.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageFilter {
+    languages: HashSet<Lang>,
+}
+
+impl LanguageFilter {
+    /// Keeps only `languages`; none keeps everything and only tags.
+    pub fn new(languages: impl IntoIterator<Item = Lang>) -> Self {
+        LanguageFilter {
+            languages: languages.into_iter().collect(),
+        }
+    }
+
+    /// Like [`LanguageFilter::new`], from ISO 639-3 codes such as `eng`.
+    pub fn from_codes<S: AsRef<str>>(codes: &[S]) -> Result<Self, BoxError> {
+        let languages = codes
+            .iter()
+            .map(|code| {
+                let code = code.as_ref();
+                Lang::from_code(code.to_ascii_lowercase()).ok_or_else(|| {
+                    format!(
+                        "unknown language '{}' (expected an ISO 639-3 code such as eng)",
+                        code
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LanguageFilter::new(languages))
+    }
+
+    /// The language of `text`, when it can be told reliably.
+    pub fn detect(&self, text: &str) -> Option<Lang> {
+        whatlang::detect(text)
+            .filter(|info| info.is_reliable())
+            .map(|info| info.lang())
+    }
+
+    /// Whether a sentence detected as `lang` is kept.
+    pub fn accepts(&self, lang: Option<Lang>) -> bool {
+        self.languages.is_empty() || lang.is_none_or(|lang| self.languages.contains(&lang))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_target_languages_and_unclear_text() {
+        let english = LanguageFilter::from_codes(&["ENG"]).unwrap();
+        let french = english.detect(
+            "Le chat est assis sur le tapis et regarde les oiseaux qui chantent dans le jardin.",
+        );
+        assert_eq!(french, Some(Lang::Fra));
+        assert!(!english.accepts(french));
+
+        let lang =
+            english.detect("The committee met on Monday to discuss the budget for next year.");
+        assert_eq!(lang, Some(Lang::Eng));
+        assert!(english.accepts(lang));
+        assert!(english.accepts(None));
+        assert!(LanguageFilter::default().accepts(french));
+        assert!(LanguageFilter::from_codes(&["english"]).is_err());
+    }
+}
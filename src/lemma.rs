@@ -0,0 +1,98 @@
+//! Lemmas stored with each sentence as `lemmas`, parallel to its tokens, so
+//! frequencies and searches can count "ran" and "running" as "run".
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::BoxError;
+
+/// The irregular English forms known without a table.
+const ENGLISH: &str = include_str!("lemmas_en.tsv");
+
+/// Looks up the lemma of each word in a table of forms. Words it doesn't
+/// know are their own lemma, lowercased; regular inflections such as
+/// "walked" need a full list such as those published with most corpora.
+#[derive(Debug, Clone, Default)]
+pub struct Lemmatizer {
+    lemmas: HashMap<String, String>,
+}
+
+impl Lemmatizer {
+    /// The built-in table of irregular English verbs, plurals and
+    /// comparatives.
+    pub fn english() -> Self {
+        let mut lemmatizer = Lemmatizer::default();
+        lemmatizer
+            .extend(ENGLISH, "the built-in lemma table")
+            .expect("valid built-in lemma table");
+        lemmatizer
+    }
+
+    /// Adds the forms listed in the file at `path`, one `form<TAB>lemma`
+    /// per line, over the ones already known. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), BoxError> {
+        let path = path.as_ref();
+        let table = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading lemma table {}: {}", path.display(), e))?;
+        self.extend(&table, &path.display().to_string())
+    }
+
+    fn extend(&mut self, table: &str, name: &str) -> Result<(), BoxError> {
+        for (i, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split('\t').map(str::trim);
+            let (Some(form), Some(lemma), None) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(format!(
+                    "{} line {}: expected a form and its lemma separated by a tab",
+                    name,
+                    i + 1
+                )
+                .into());
+            };
+            self.lemmas
+                .insert(form.to_lowercase(), lemma.to_lowercase());
+        }
+        Ok(())
+    }
+
+    /// The lemma of `word`.
+    pub fn lemma(&self, word: &str) -> String {
+        let word = word.to_lowercase();
+        self.lemmas.get(&word).cloned().unwrap_or(word)
+    }
+
+    pub fn lemmas(&self, tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|token| self.lemma(token)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_forms_in_tables() {
+        let mut lemmatizer = Lemmatizer::english();
+        assert_eq!(
+            lemmatizer.lemmas(&["She", "ran", "with", "the", "children"]),
+            ["she", "run", "with", "the", "child"]
+        );
+        assert_eq!(lemmatizer.lemma("running"), "running");
+
+        let path = std::env::temp_dir().join(format!("lemmas-{}.tsv", std::process::id()));
+        std::fs::write(&path, "# regular forms\nrunning\trun\n\nWalked\twalk\n").unwrap();
+        lemmatizer.load(&path).unwrap();
+        assert_eq!(lemmatizer.lemmas(&["running", "walked"]), ["run", "walk"]);
+        std::fs::write(&path, "running run\n").unwrap();
+        let error = lemmatizer.load(&path).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("line 1: expected a form and its lemma separated by a tab"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+//! Sentence segmentation and corpus ingestion for COCA-style text corpora.
+//!
+//! [`segment`] is the standalone sentence splitter and [`clean`] holds the
+//! line normalization that runs before it, after any [`redact`]ion.
+//! [`ingest`] drives the full pipeline from a [`source`], through [`format`]
+//! extraction for documents that aren't plain text and the removal of their
+//! [`header`]s, into MongoDB or another [`sink`], optionally leaving out
+//! [`dedup`]licated sentences. A [`schema`] maps the stored fields onto an
+//! existing collection's, and each sentence's [`token`]s, with their
+//! [`lemma`]s and [`pos`] tags, are stored with it along with its [`quality`]
+//! scores.
+//!
+//! From what's stored, [`freq`] builds word and n-gram frequency lists,
+//! leaving out [`stopword`]s if asked, [`tfidf`] weighs the words of each
+//! file, [`collocate`] scores the words found near a given one, and
+//! [`kwic`] lines up the matches of a search in context.
+//!
+//! Everything but the segmentation core (segmentation, cleaning, tokens,
+//! lemmas, tags, stopwords and the analyses on them) is behind the default
+//! `ingest` feature; without it the crate builds for wasm32.
+
+#[cfg(feature = "ingest")]
+pub mod archive;
+#[cfg(feature = "ingest")]
+pub mod bench;
+#[cfg(feature = "ingest")]
+pub mod checkpoint;
+pub mod clean;
+pub mod collocate;
+#[cfg(feature = "ingest")]
+pub mod decompress;
+#[cfg(feature = "ingest")]
+pub mod dedup;
+#[cfg(feature = "ingest")]
+pub mod encoding;
+#[cfg(feature = "ingest")]
+pub mod error;
+#[cfg(feature = "ingest")]
+pub mod format;
+#[cfg(feature = "ingest")]
+pub mod freq;
+#[cfg(feature = "ingest")]
+pub mod header;
+#[cfg(feature = "ingest")]
+pub mod ingest;
+pub mod kwic;
+pub mod language;
+pub mod lemma;
+#[cfg(feature = "ingest")]
+pub mod logging;
+#[cfg(feature = "ingest")]
+pub mod manifest;
+#[cfg(feature = "ingest")]
+pub mod metrics;
+#[cfg(feature = "ingest")]
+pub mod object_store;
+pub mod pos;
+#[cfg(feature = "ingest")]
+pub mod quality;
+pub mod redact;
+#[cfg(feature = "ingest")]
+pub mod report;
+#[cfg(feature = "ingest")]
+pub mod schema;
+pub mod segment;
+#[cfg(feature = "ingest")]
+pub mod server;
+#[cfg(feature = "ingest")]
+pub mod shutdown;
+#[cfg(feature = "ingest")]
+pub mod sink;
+#[cfg(feature = "ingest")]
+pub mod source;
+#[cfg(feature = "ingest")]
+pub mod status;
+pub mod stopword;
+#[cfg(feature = "ingest")]
+pub mod tfidf;
+pub mod token;
+#[cfg(feature = "ingest")]
+pub mod watch;
+
+pub use segment::{Segmenter, ValidityRules};
+
+/// Error type used throughout the pipeline.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
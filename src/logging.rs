@@ -0,0 +1,323 @@
+//! Where the [`log`] records of a run go: stderr, as plain lines for people
+//! or as JSON objects for log systems, at levels picked per module.
+//!
+//! Ingesting a file runs in a `file` [`Span`], and writing each of its
+//! batches in a `batch` one inside it, so every record logged meanwhile,
+//! by the sink and its retries too, carries the `file` and the `batch`
+//! number and `sentences` as fields, which JSON output keeps as fields of
+//! their own for filtering on, with the names of the spans as `spans`.
+//!
+//! The spans are kept in a tokio task-local rather than by `tracing`, so
+//! that the crates logging through [`log`], mongodb's driver among them,
+//! go on being filtered and formatted the same way as the rest.
+
+use log::kv::{Key, Value, VisitSource};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::{Map, Value as Json};
+use std::future::Future;
+use std::str::FromStr;
+
+use crate::status;
+use crate::BoxError;
+
+/// How log records are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The message alone at info level, and with its level otherwise, plus
+    /// the module and fields at debug and trace.
+    #[default]
+    Text,
+    /// A JSON object per line with `time`, `level`, `target`, `message` and
+    /// the record's fields.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "invalid log format '{}' (expected text or json)",
+                other
+            )),
+        }
+    }
+}
+
+/// Which records are written: a level for everything, and levels for the
+/// modules named, as in `info,rustparser::sink=debug`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> Self {
+        LogFilter {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    /// The level for `target`, from the longest module naming it.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, |max, level| max.max(level))
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = LogFilter::new(LevelFilter::Info);
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (module, level) = match directive.split_once('=') {
+                Some((module, level)) => (Some(module), level),
+                None => (None, directive),
+            };
+            let level = level
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("invalid log level '{}' in '{}'", level, s))?;
+            match module {
+                Some(module) => filter.modules.push((module.to_string(), level)),
+                None => filter.default = level,
+            }
+        }
+        Ok(filter)
+    }
+}
+
+struct Logger {
+    filter: LogFilter,
+    format: LogFormat,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            status::print_above(&format_record(record, self.format, &Span::current()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The spans a task is in, as their names, outermost first, and the fields
+/// they add to the records logged in them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Span {
+    names: Vec<&'static str>,
+    fields: Map<String, Json>,
+}
+
+tokio::task_local! {
+    static SPAN: Span;
+}
+
+impl Span {
+    /// The spans the running task is in, none outside a task or span.
+    pub fn current() -> Span {
+        SPAN.try_with(Span::clone).unwrap_or_default()
+    }
+
+    /// Runs `future` in these spans, as a task taking over work from
+    /// another does.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        SPAN.scope(self, future).await
+    }
+
+    /// Runs `f` in these spans, as a blocking task doing another's work
+    /// does.
+    pub fn enter<T>(self, f: impl FnOnce() -> T) -> T {
+        SPAN.sync_scope(self, f)
+    }
+}
+
+/// Runs `future` in a span named `name` inside the ones the task is in,
+/// adding `fields` to the records logged meanwhile.
+pub async fn in_span<F: Future>(
+    name: &'static str,
+    fields: &[(&str, Json)],
+    future: F,
+) -> F::Output {
+    let mut span = Span::current();
+    span.names.push(name);
+    for (key, value) in fields {
+        span.fields.insert(key.to_string(), value.clone());
+    }
+    span.run(future).await
+}
+
+/// Sends the records of the run to stderr from now on.
+pub fn init(filter: LogFilter, format: LogFormat) -> Result<(), BoxError> {
+    log::set_max_level(filter.max_level());
+    log::set_boxed_logger(Box::new(Logger { filter, format }))?;
+    Ok(())
+}
+
+/// Gathers a record's fields as JSON.
+struct Fields(Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(number) = value.to_i64() {
+            Json::from(number)
+        } else if let Some(number) = value.to_f64() {
+            Json::from(number)
+        } else if let Some(flag) = value.to_bool() {
+            Json::from(flag)
+        } else {
+            Json::from(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// `record` as a line, with the fields of the `span` it was logged in
+/// unless it has its own of the same names.
+fn format_record(record: &Record, format: LogFormat, span: &Span) -> String {
+    let mut fields = Fields(span.fields.clone());
+    let _ = record.key_values().visit(&mut fields);
+    match format {
+        LogFormat::Json => {
+            let mut line = Map::new();
+            let time = mongodb::bson::DateTime::now()
+                .try_to_rfc3339_string()
+                .unwrap_or_default();
+            line.insert("time".to_string(), Json::from(time));
+            line.insert("level".to_string(), Json::from(record.level().as_str()));
+            line.insert("target".to_string(), Json::from(record.target()));
+            line.insert("message".to_string(), Json::from(record.args().to_string()));
+            if !span.names.is_empty() {
+                line.insert("spans".to_string(), Json::from(span.names.clone()));
+            }
+            line.extend(fields.0);
+            Json::Object(line).to_string()
+        }
+        LogFormat::Text => match record.level() {
+            Level::Info => record.args().to_string(),
+            Level::Warn => format!("Warning: {}", record.args()),
+            Level::Error => format!("Error: {}", record.args()),
+            level => {
+                let mut line = format!("{} {}: {}", level, record.target(), record.args());
+                for (key, value) in fields.0 {
+                    match value {
+                        Json::String(text) => line.push_str(&format!(" {}={}", key, text)),
+                        other => line.push_str(&format!(" {}={}", key, other)),
+                    }
+                }
+                line
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_levels_by_module() {
+        let filter: LogFilter = "warn,rustparser::sink=debug,rustparser::sink::mongo=error"
+            .parse()
+            .unwrap();
+        assert_eq!(filter.level("rustparser::ingest"), LevelFilter::Warn);
+        assert_eq!(filter.level("rustparser::sink::tee"), LevelFilter::Debug);
+        assert_eq!(filter.level("rustparser::sink::mongo"), LevelFilter::Error);
+        assert_eq!(filter.level("rustparser::sinks"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+        assert!("loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test]
+    fn formats_records_with_their_fields() {
+        let record = |level| {
+            format_record(
+                &Record::builder()
+                    .level(level)
+                    .target("rustparser::ingest")
+                    .args(format_args!("Wrote a batch"))
+                    .key_values(&[("file", "a.txt")])
+                    .build(),
+                LogFormat::Text,
+                &Span::default(),
+            )
+        };
+        assert_eq!(record(Level::Info), "Wrote a batch");
+        assert_eq!(record(Level::Warn), "Warning: Wrote a batch");
+        assert_eq!(
+            record(Level::Debug),
+            "DEBUG rustparser::ingest: Wrote a batch file=a.txt"
+        );
+
+        let json = format_record(
+            &Record::builder()
+                .level(Level::Debug)
+                .target("rustparser::ingest")
+                .args(format_args!("Wrote a batch"))
+                .key_values(&[("sentences", 1000)])
+                .build(),
+            LogFormat::Json,
+            &Span::default(),
+        );
+        let json: Json = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["level"], "DEBUG");
+        assert_eq!(json["message"], "Wrote a batch");
+        assert_eq!(json["sentences"], 1000);
+    }
+
+    #[tokio::test]
+    async fn adds_the_fields_of_the_spans_a_record_is_logged_in() {
+        let line = in_span("file", &[("file", Json::from("a.txt"))], async {
+            let span = in_span("batch", &[("batch", Json::from(3))], async {
+                Span::current()
+            })
+            .await;
+            // A task taking over the work logs in the same spans
+            let span = tokio::spawn(span.run(async { Span::current() }))
+                .await
+                .unwrap();
+            format_record(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("rustparser::sink::retry")
+                    .args(format_args!("Retrying"))
+                    .key_values(&[("batch", 4)])
+                    .build(),
+                LogFormat::Json,
+                &span,
+            )
+        })
+        .await;
+        let json: Json = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["spans"], serde_json::json!(["file", "batch"]));
+        assert_eq!(json["file"], "a.txt");
+        assert_eq!(json["batch"], 4);
+        assert_eq!(Span::current(), Span::default());
+    }
+}
@@ -1,186 +1,1710 @@
+mod cli;
+mod config;
+
+use clap::Parser;
+use cli::{
+    Cli, CollocatesArgs, Command, CountArgs, ExportArgs, ExportFormat, FilterArgs, IngestArgs,
+    KwicArgs, NgramsArgs, PurgeArgs, SearchArgs, SearchFormat, ServeArgs, StatsArgs, TfidfArgs,
+    WatchArgs, WordFreqArgs,
+};
+use config::{FileConfig, MongoConfig, RouteConfig};
 use dotenv::dotenv;
-use mongodb::bson::doc;
-use mongodb::options::ClientOptions;
-use mongodb::{Client, IndexModel};
-use regex::Regex;
+use futures::TryStreamExt;
+use log::LevelFilter;
+use mongodb::bson::{doc, Bson, DateTime, Document, Regex};
+use mongodb::options::{AggregateOptions, ClientOptions, FindOptions};
+use mongodb::{Client, Collection, Database};
+use rustparser::bench::PhaseTimes;
+use rustparser::checkpoint::Checkpoint;
+use rustparser::clean::{
+    CleaningPipeline, CleaningPreset, CleaningStep, Dehyphenator, UnicodeForm,
+};
+use rustparser::collocate::Collocates;
+use rustparser::decompress::{append_output, create_output, Compression};
+use rustparser::dedup::{NearDuplicates, SeenFilter};
+use rustparser::error::{ErrorSummary, IngestError};
+use rustparser::format::FormatOptions;
+use rustparser::freq::{
+    self, FrequencyTable, NgramCounts, NGRAMS_COLLECTION, WORD_FREQ_COLLECTION,
+};
+use rustparser::ingest::{
+    count_lines, ingest_all, ingest_reader, FileNameTemplate, IngestOptions, LineMetadata,
+};
+use rustparser::kwic::{concordance_line, find_keyword};
+use rustparser::language::LanguageFilter;
+use rustparser::lemma::Lemmatizer;
+use rustparser::logging::{self, LogFilter};
+use rustparser::manifest::{Manifest, MANIFEST_COLLECTION};
+use rustparser::metrics::METRICS;
+use rustparser::pos::{HttpTagger, LexiconTagger, PosTagger};
+use rustparser::redact::{PiiKind, Redactor};
+use rustparser::report::FileReports;
+use rustparser::schema::{migrate, Schema, MIGRATIONS, SCHEMA_VERSION};
+use rustparser::segment::srx::SrxBackend;
+use rustparser::segment::{CjkBackend, RuleBackend, SegmentationBackend, SegmenterKind};
+use rustparser::server::{self, Request, Response};
+use rustparser::shutdown::Shutdown;
+use rustparser::sink::{
+    fan_out, CsvSink, ElasticsearchSink, JsonlSink, KafkaSink, MongoSink, NullSink, Numbering,
+    ParquetSink, PartitionedSink, PostgresSink, RetryPolicy, RoutedSink, SentenceSink, SinkKind,
+    SqliteSink, Tally, TallySink, TeeSink, TextIndex, TextSink, WriteReport, WriterSink,
+};
+use rustparser::source::{expand_input, source_for, InputOptions};
+use rustparser::status::{StatusLine, Visibility};
+use rustparser::stopword::Stopwords;
+use rustparser::tfidf::{self, TermWeights};
+use rustparser::watch::DropDirectory;
+use rustparser::{BoxError, Segmenter, ValidityRules};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_DATABASE: &str = "coca_like_db";
+const DEFAULT_COLLECTION: &str = "corpus";
+/// Sentences `export` reads from the cursor before writing them out.
+const EXPORT_BATCH: usize = 1000;
+
+/// Where to find the corpus, merged from the command line, environment and
+/// config file.
+struct MongoTarget {
+    uri: Option<String>,
+    database: String,
+    collection: String,
+    config: MongoConfig,
+    schema: Schema,
+}
 
-fn is_sentence_boundary(text: &str, pos: usize) -> bool {
-    if pos == 0 || pos >= text.len() - 1 {
-        return false;
+impl MongoTarget {
+    fn resolve(cli: &Cli, config: &FileConfig) -> Result<Self, BoxError> {
+        Ok(MongoTarget {
+            uri: cli.mongodb_uri.clone(),
+            database: cli
+                .database
+                .clone()
+                .or_else(|| config.mongodb.database.clone())
+                .unwrap_or_else(|| DEFAULT_DATABASE.to_string()),
+            collection: cli
+                .collection
+                .clone()
+                .or_else(|| config.mongodb.collection.clone())
+                .unwrap_or_else(|| DEFAULT_COLLECTION.to_string()),
+            config: config.mongodb.clone(),
+            schema: config.schema.to_schema()?,
+        })
     }
+}
+
+async fn connect_database(target: &MongoTarget) -> Result<Database, BoxError> {
+    let mongodb_uri = target
+        .uri
+        .as_deref()
+        .ok_or("MONGODB_URI must be set (or pass --mongodb-uri)")?;
+    let mut client_options = ClientOptions::parse(mongodb_uri).await?;
+    target.config.apply(&mut client_options)?;
+    let client = Client::with_options(client_options)?;
 
-    let prev_char = text.chars().nth(pos - 1).unwrap();
-    let next_char = text.chars().nth(pos + 1).unwrap();
+    Ok(client.database(&target.database))
+}
+
+async fn connect(target: &MongoTarget) -> Result<Collection<Document>, BoxError> {
+    Ok(connect_database(target)
+        .await?
+        .collection(&target.collection))
+}
 
-    // Check if it's not part of an abbreviation, number, or ellipsis
-    if prev_char.is_alphabetic() && next_char.is_alphabetic() {
-        return false;
+/// How the flags ask for failed writes to be retried.
+fn retry_policy(args: &IngestArgs) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: args.max_retries,
+        base_delay: Duration::from_millis(args.retry_base_ms),
+        ..RetryPolicy::default()
     }
+}
 
-    if prev_char.is_numeric() && next_char.is_numeric() {
-        return false;
+/// A sink for MongoDB `collection`, set up as the flags ask and tallying
+/// documents left out in `report`.
+async fn mongo_sink(
+    args: &IngestArgs,
+    target: &MongoTarget,
+    collection: Collection<Document>,
+    report: &WriteReport,
+) -> Result<MongoSink, BoxError> {
+    let sink = MongoSink::new(collection, args.text_index, target.schema.clone())
+        .await?
+        .with_retry(retry_policy(args))
+        .with_report(report.clone());
+    Ok(if args.dedup {
+        sink.with_dedup().await?
+    } else if args.unique_hash {
+        sink.with_unique_hash().await?
+    } else {
+        sink
+    })
+}
+
+/// Opens the sinks picked with `--sink`, with the manifest of ingested
+/// files and the report of documents left out when one is MongoDB. Without
+/// it, `--sqlite`, `--postgres-url`, `--elasticsearch-url` and `--kafka-url`
+/// imply their sinks and MongoDB is the default; `--jsonl` adds the jsonl
+/// sink. Several sinks are written through a [`TeeSink`], each with a
+/// writer of its own so a slow one doesn't hold back the others.
+async fn open_sink(
+    args: &IngestArgs,
+    target: &MongoTarget,
+    routing: &[RouteConfig],
+) -> Result<(Box<dyn SentenceSink>, Option<Manifest>, Option<WriteReport>), BoxError> {
+    let mut kinds = args.sink.clone();
+    if kinds.is_empty() {
+        kinds.push(if args.dry_run {
+            match args.output {
+                Some(_) => SinkKind::File,
+                None => SinkKind::Stdout,
+            }
+        } else if args.sqlite.is_some() {
+            SinkKind::Sqlite
+        } else if args.postgres_url.is_some() {
+            SinkKind::Postgres
+        } else if args.elasticsearch_url.is_some() {
+            SinkKind::Elasticsearch
+        } else if args.kafka_url.is_some() {
+            SinkKind::Kafka
+        } else if args.bench {
+            SinkKind::Null
+        } else {
+            SinkKind::Mongo
+        });
+    }
+    if args.jsonl.is_some() && !kinds.contains(&SinkKind::Jsonl) {
+        kinds.push(SinkKind::Jsonl);
+    }
+    for (i, kind) in kinds.iter().enumerate() {
+        if kinds[..i].contains(kind) {
+            return Err(format!("the {} sink is given more than once", kind.name()).into());
+        }
+    }
+    let to_output: Vec<_> = kinds
+        .iter()
+        .filter(|kind| match kind {
+            SinkKind::File | SinkKind::Csv | SinkKind::Tsv | SinkKind::Parquet => true,
+            SinkKind::Jsonl => args.jsonl.is_none(),
+            _ => false,
+        })
+        .map(SinkKind::name)
+        .collect();
+    if to_output.len() > 1 {
+        return Err(format!(
+            "only one of the {} sinks can write to --output (the jsonl sink can use --jsonl <path>)",
+            to_output.join(", ")
+        )
+        .into());
     }
 
-    if next_char == '.' {
-        return false; // Likely an ellipsis
+    if let [kind] = kinds[..] {
+        return open_one_sink(kind, args, target, routing).await;
     }
+    let (mut sinks, mut manifest, mut report) = (Vec::new(), None, None);
+    for kind in kinds {
+        let (sink, sink_manifest, sink_report) = open_one_sink(kind, args, target, routing).await?;
+        manifest = manifest.or(sink_manifest);
+        report = report.or(sink_report);
+        let writer = WriterSink::spawn(sink, args.write_queue, args.inserts_in_flight);
+        sinks.push((
+            kind.name().to_string(),
+            Box::new(writer) as Box<dyn SentenceSink>,
+        ));
+    }
+    let tee = TeeSink::new(sinks).with_policy(args.tee_policy);
+    Ok((Box::new(tee), manifest, report))
+}
 
-    // Check if it's followed by a space and a capital letter or number
-    next_char.is_whitespace()
-        && text[pos + 1..]
-            .trim_start()
-            .chars()
-            .next()
-            .map_or(false, |c| c.is_uppercase() || c.is_numeric())
+/// Opens the sink `kind`, with the manifest and report for MongoDB. Mongo is
+/// only connected to when it's actually a destination, and only it takes
+/// the config file's routing rules.
+async fn open_one_sink(
+    kind: SinkKind,
+    args: &IngestArgs,
+    target: &MongoTarget,
+    routing: &[RouteConfig],
+) -> Result<(Box<dyn SentenceSink>, Option<Manifest>, Option<WriteReport>), BoxError> {
+    let sink: Box<dyn SentenceSink> = match kind {
+        SinkKind::Mongo => {
+            let database = connect_database(target).await?;
+            let report = WriteReport::default();
+            let collection = database.collection(&target.collection);
+            let sink = mongo_sink(args, target, collection, &report).await?;
+            let sink: Box<dyn SentenceSink> = if routing.is_empty() {
+                Box::new(sink)
+            } else {
+                let mut routed = RoutedSink::new(Box::new(sink));
+                // Routes to the same collection share its sink
+                let mut sinks = HashMap::from([(target.collection.clone(), 0)]);
+                for rule in routing {
+                    let name = rule.collection.as_ref().unwrap_or(&target.collection);
+                    let index = match sinks.get(name) {
+                        Some(&index) => index,
+                        None => {
+                            let collection = database.collection(name);
+                            let sink = mongo_sink(args, target, collection, &report).await?;
+                            let index = routed.add_sink(Box::new(sink));
+                            sinks.insert(name.clone(), index);
+                            index
+                        }
+                    };
+                    routed.route(rule.to_route()?, index);
+                }
+                Box::new(routed)
+            };
+            let manifest = open_manifest(&database, target).await?;
+            return Ok((sink, Some(manifest), Some(report)));
+        }
+        SinkKind::Sqlite => {
+            let path = args
+                .sqlite
+                .as_deref()
+                .ok_or("the sqlite sink needs --sqlite <path>")?;
+            Box::new(SqliteSink::open(path)?)
+        }
+        SinkKind::Postgres => {
+            let url = args
+                .postgres_url
+                .as_deref()
+                .ok_or("the postgres sink needs --postgres-url <url> (or POSTGRES_URL)")?;
+            Box::new(
+                PostgresSink::connect(url, &args.postgres_table, &args.postgres_text_search)
+                    .await?,
+            )
+        }
+        SinkKind::Elasticsearch => {
+            let url = args.elasticsearch_url.as_deref().ok_or(
+                "the elasticsearch sink needs --elasticsearch-url <url> (or ELASTICSEARCH_URL)",
+            )?;
+            let template = match &args.elasticsearch_template {
+                Some(path) => Some(
+                    std::fs::read_to_string(path)
+                        .map_err(|e| format!("Error reading {}: {}", path, e))?,
+                ),
+                None => None,
+            };
+            let sink = ElasticsearchSink::connect(
+                url,
+                &args.elasticsearch_index,
+                args.elasticsearch_api_key.as_deref(),
+                template.as_deref(),
+            )?;
+            Box::new(sink.with_retry(retry_policy(args)))
+        }
+        SinkKind::Kafka => {
+            let url = args
+                .kafka_url
+                .as_deref()
+                .ok_or("the kafka sink needs --kafka-url <url> (or KAFKA_URL)")?;
+            Box::new(KafkaSink::new(url, &args.kafka_topic).with_retry(retry_policy(args)))
+        }
+        SinkKind::Stdout => Box::new(TextSink::stdout().with_numbering(args.numbering)),
+        SinkKind::Null => Box::new(NullSink),
+        SinkKind::File | SinkKind::Jsonl | SinkKind::Csv | SinkKind::Tsv | SinkKind::Parquet => {
+            let mut extension = match kind {
+                SinkKind::File => "txt",
+                _ => kind.name(),
+            }
+            .to_string();
+            // Parquet compresses its pages, not the whole file
+            if let Some(compression) = args.compress.filter(|_| kind != SinkKind::Parquet) {
+                extension = format!("{}.{}", extension, compression.extension());
+            }
+            let output = match kind {
+                SinkKind::Jsonl => args.jsonl.as_ref().or(args.output.as_ref()),
+                _ => args.output.as_ref(),
+            };
+            let path = output.ok_or_else(|| {
+                format!(
+                    "the {} sink needs --output <path> (or OUTPUT_FILE_PATH)",
+                    kind.name()
+                )
+            })?;
+            match args.partition_by {
+                Some(partition) => {
+                    std::fs::create_dir_all(path)
+                        .map_err(|e| format!("Error creating {}: {}", path, e))?;
+                    let directory = std::path::PathBuf::from(path);
+                    let columns = args.columns.clone();
+                    let compression = args.compress;
+                    let numbering = args.numbering;
+                    let append = args.resume;
+                    Box::new(PartitionedSink::new(partition, move |key| {
+                        let path = directory.join(format!("{}.{}", key, extension));
+                        file_sink(
+                            kind,
+                            &path.to_string_lossy(),
+                            &columns,
+                            compression,
+                            numbering,
+                            append,
+                        )
+                    }))
+                }
+                None => file_sink(
+                    kind,
+                    path,
+                    &args.columns,
+                    args.compress,
+                    args.numbering,
+                    args.resume,
+                )?,
+            }
+        }
+    };
+    Ok((sink, None, None))
 }
 
-fn is_valid_sentence(sentence: &str) -> bool {
-    let trimmed = sentence.trim();
-    if trimmed.is_empty() {
-        return false;
+/// A `kind` of sink writing to the file at `path`, with `columns` for csv,
+/// tsv and parquet or `numbering` for plain text, compressed with
+/// `compression` or as the extension says. When resuming it `append`s to
+/// what the interrupted run wrote, which the checkpoint counts as done.
+fn file_sink(
+    kind: SinkKind,
+    path: &str,
+    columns: &[String],
+    compression: Option<Compression>,
+    numbering: Numbering,
+    append: bool,
+) -> Result<Box<dyn SentenceSink>, BoxError> {
+    if kind == SinkKind::Parquet {
+        if append {
+            return Err(format!("--resume can't add to the parquet file {}", path).into());
+        }
+        return Ok(Box::new(ParquetSink::create(
+            path,
+            columns.to_vec(),
+            compression,
+        )?));
     }
+    // What the interrupted run left, if anything
+    let existing = match append {
+        true => std::fs::metadata(path).map_or(0, |metadata| metadata.len()),
+        false => 0,
+    };
+    let written = match (numbering, existing) {
+        (Numbering::Sequence, 1..) => {
+            let file =
+                std::fs::File::open(path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+            count_lines(file)? as u64
+        }
+        _ => 0,
+    };
+    let output = match append {
+        true => append_output(path, compression)
+            .map_err(|e| format!("--resume can't add to {}: {}", path, e))?,
+        false => create_output(path, compression)
+            .map_err(|e| format!("Error creating {}: {}", path, e))?,
+    };
+    Ok(match kind {
+        SinkKind::Jsonl => Box::new(JsonlSink::new(output)),
+        SinkKind::Csv | SinkKind::Tsv => {
+            let delimiter = if kind == SinkKind::Csv { b',' } else { b'\t' };
+            let sink = CsvSink::new(output, columns.to_vec(), delimiter);
+            match existing {
+                0 => Box::new(sink),
+                _ => Box::new(sink.without_header()),
+            }
+        }
+        _ => Box::new(
+            TextSink::new(output)
+                .with_numbering(numbering)
+                .starting_after(written),
+        ),
+    })
+}
 
-    // A valid sentence should have at least 3 words and not be just a number
-    let word_count = trimmed.split_whitespace().count();
-    word_count >= 3 && !trimmed.parse::<f64>().is_ok() && trimmed.len() >= 10
+/// What `ingest` and `watch` run with, made from the flags and config file:
+/// the options, the sink, and the reports to print once done.
+struct PreparedIngest {
+    options: IngestOptions,
+    sink: Box<dyn SentenceSink>,
+    report: Option<WriteReport>,
+    tally: Option<Tally>,
 }
 
-fn split_into_sentences(text: &str) -> Vec<String> {
-    let mut sentences = Vec::new();
-    let mut current_sentence = String::new();
-    let mut chars = text.char_indices().peekable();
+/// Prints the summaries of the run, and writes the report of its files to
+/// --report if asked.
+fn print_reports(
+    args: &IngestArgs,
+    report: Option<&WriteReport>,
+    tally: Option<&Tally>,
+    errors: &ErrorSummary,
+    files: &FileReports,
+) {
+    if let Some(line) = files.summary() {
+        eprintln!("{}", line);
+    }
+    for line in errors.summary() {
+        eprintln!("{}", line);
+    }
+    for line in report.iter().flat_map(|report| report.summary()) {
+        eprintln!("{}", line);
+    }
+    for line in tally.iter().flat_map(|tally| tally.summary()) {
+        eprintln!("{}", line);
+    }
+    if let Some(path) = &args.report {
+        if let Err(e) = files.write(path) {
+            log::error!("{}", e);
+        }
+    }
+}
 
-    while let Some((i, c)) = chars.next() {
-        current_sentence.push(c);
+/// Starts answering `GET /metrics` on `address` in the background, for the
+/// rest of the run.
+async fn serve_metrics(address: &str) -> Result<(), BoxError> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Error listening on {}: {}", address, e))?;
+    log::info!(
+        "Serving metrics at http://{}/metrics",
+        listener.local_addr()?
+    );
+    tokio::spawn(server::serve(listener, |request: Request| async move {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/metrics") => Response::text(METRICS.render()),
+            _ => Response::error(404, "no such endpoint"),
+        }
+    }));
+    Ok(())
+}
 
-        if c == '.' && is_sentence_boundary(text, i) {
-            if is_valid_sentence(&current_sentence) {
-                sentences.push(current_sentence.trim().to_string());
-                current_sentence.clear();
+async fn prepare_ingest(
+    args: &IngestArgs,
+    target: &MongoTarget,
+    config: &FileConfig,
+) -> Result<PreparedIngest, BoxError> {
+    if let Some(address) = &args.metrics_listen {
+        serve_metrics(address).await?;
+    }
+    let defaults = ValidityRules::default();
+    let validity_rules = ValidityRules {
+        min_words: args
+            .min_words
+            .or(config.validity.min_words)
+            .unwrap_or(defaults.min_words),
+        min_chars: args
+            .min_chars
+            .or(config.validity.min_chars)
+            .unwrap_or(defaults.min_chars),
+        max_words: args.max_words.or(config.validity.max_words),
+        max_chars: args.max_chars.or(config.validity.max_chars),
+        max_digit_ratio: args.max_digit_ratio.or(config.validity.max_digit_ratio),
+        reject_number_units: args
+            .reject_number_units
+            .or(config.validity.reject_number_units)
+            .unwrap_or(defaults.reject_number_units),
+    };
+    let segmenter_kind = match (args.segmenter, &config.segmentation.backend) {
+        (Some(kind), _) => kind,
+        (None, Some(backend)) => backend.parse::<SegmenterKind>()?,
+        (None, None) => SegmenterKind::Rules,
+    };
+    let unicode_form = match (args.unicode_form, &config.cleaning.unicode_form) {
+        (Some(form), _) => form,
+        (None, Some(form)) => form.parse::<UnicodeForm>()?,
+        (None, None) => UnicodeForm::None,
+    };
+    let abbrev_file = args
+        .abbrev_file
+        .as_deref()
+        .or(config.segmentation.abbrev_file.as_deref());
+    let srx_file = args
+        .srx_file
+        .as_deref()
+        .or(config.segmentation.srx_file.as_deref());
+    let srx_language = args
+        .srx_language
+        .as_deref()
+        .or(config.segmentation.srx_language.as_deref())
+        .unwrap_or("en");
+    let backend: Arc<dyn SegmentationBackend> = match (srx_file, segmenter_kind, abbrev_file) {
+        (Some(path), _, _) => Arc::new(SrxBackend::from_file(path, srx_language)?),
+        (None, SegmenterKind::Rules, Some(path)) => {
+            Arc::new(RuleBackend::default().with_abbreviation_file(path)?)
+        }
+        (None, SegmenterKind::Cjk, Some(path)) => Arc::new(CjkBackend::new(
+            RuleBackend::default().with_abbreviation_file(path)?,
+        )),
+        (None, kind, _) => kind.backend(),
+    };
+    let hyphenation_dict = args
+        .hyphenation_dict
+        .as_deref()
+        .or(config.cleaning.hyphenation_dict.as_deref());
+    let dehyphenation = match (args.keep_hyphens, hyphenation_dict) {
+        (true, _) => None,
+        (false, Some(path)) => Some(Dehyphenator::default().with_dictionary_file(path)?),
+        (false, None) => Some(Dehyphenator::default()),
+    };
+    let cleaning =
+        if !args.cleaning_regexes.is_empty() {
+            remove_patterns(&args.cleaning_regexes)?
+        } else if let Some(preset) = args.cleaning_preset {
+            preset.pipeline()
+        } else {
+            let cleaning = &config.cleaning;
+            match (&cleaning.preset, &cleaning.steps, &cleaning.regexes) {
+                (Some(preset), None, None) => preset.parse::<CleaningPreset>()?.pipeline(),
+                (None, Some(steps), None) => CleaningPipeline::new(
+                    steps
+                        .iter()
+                        .filter(|step| step.enabled)
+                        .map(|step| {
+                            CleaningStep::from_config(
+                                &step.step,
+                                step.pattern.as_deref(),
+                                &step.replacement,
+                            )
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+                (None, None, Some(regexes)) => remove_patterns(regexes)?,
+                (None, None, None) => CleaningPipeline::default(),
+                _ => return Err(
+                    "only one of cleaning.preset, cleaning.steps and cleaning.regexes can be set"
+                        .into(),
+                ),
             }
-        } else if c == '?' || c == '!' {
-            // Check if it's not part of a quotation
-            if chars.peek().map_or(true, |&(_, next_c)| {
-                next_c.is_whitespace() || next_c.is_uppercase()
-            }) {
-                if is_valid_sentence(&current_sentence) {
-                    sentences.push(current_sentence.trim().to_string());
-                    current_sentence.clear();
+        };
+    let redact = if !args.redact.is_empty() {
+        args.redact.clone()
+    } else {
+        config
+            .cleaning
+            .redact
+            .iter()
+            .flatten()
+            .map(|kind| kind.parse::<PiiKind>())
+            .collect::<Result<_, _>>()?
+    };
+    let redaction = (!redact.is_empty()).then(|| Redactor::new(&redact));
+    let lemmatizer = if args.lemmas || args.lemma_table.is_some() {
+        let mut lemmatizer = Lemmatizer::english();
+        if let Some(path) = &args.lemma_table {
+            lemmatizer.load(path)?;
+        }
+        Some(lemmatizer)
+    } else {
+        None
+    };
+    let pos_tagger: Option<Arc<dyn PosTagger>> = match (&args.pos_url, &args.pos_lexicon) {
+        (Some(url), _) => Some(Arc::new(
+            HttpTagger::new(url).with_retry(retry_policy(args)),
+        )),
+        (None, Some(path)) => {
+            let mut tagger = LexiconTagger::default();
+            tagger.load(path)?;
+            Some(Arc::new(tagger))
+        }
+        (None, None) if args.pos => Some(Arc::new(LexiconTagger::default())),
+        (None, None) => None,
+    };
+    let language = if args.detect_language || !args.languages.is_empty() {
+        Some(LanguageFilter::from_codes(&args.languages)?)
+    } else {
+        None
+    };
+    let line_metadata = match &args.line_metadata_regex {
+        Some(pattern) => Some(LineMetadata::new(pattern, &args.line_text_group)?),
+        None => None,
+    };
+    let file_name_template = args
+        .file_name_template
+        .as_deref()
+        .map(FileNameTemplate::new)
+        .transpose()?;
+
+    let xml_metadata = args
+        .xml_metadata
+        .iter()
+        .map(|field| {
+            let (name, selector) = field
+                .split_once('=')
+                .ok_or_else(|| format!("--xml-meta '{}' should be name=selector", field))?;
+            Ok((name.to_string(), selector.parse()?))
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+
+    let checkpoint = match (&args.checkpoint, args.resume) {
+        (Some(path), true) => Some(Checkpoint::load(path)?),
+        (Some(path), false) => Some(Checkpoint::new(path)),
+        (None, true) => return Err("--resume needs --checkpoint <path>".into()),
+        (None, false) => None,
+    };
+
+    let (sink, manifest, report) = open_sink(args, target, &config.routing).await?;
+    let tally = args.dry_run.then(Tally::default);
+    let sink: Box<dyn SentenceSink> = match &tally {
+        Some(tally) => Box::new(TallySink::new(sink, tally.clone())),
+        None => sink,
+    };
+
+    let options = IngestOptions {
+        invisible_chars: args.invisible_chars,
+        unicode_form,
+        fold_quotes: args.fold_quotes,
+        cleaning,
+        redaction,
+        language,
+        seen: args.dedup_in_memory.map(SeenFilter::new),
+        near_duplicates: args
+            .near_duplicates
+            .map(|action| NearDuplicates::new(action, args.near_duplicate_threshold)),
+        segmenter: Segmenter::with_backend(validity_rules, backend),
+        two_pass: args.two_pass,
+        max_line_bytes: args.max_line_bytes as usize,
+        batch_size: args.batch_size.or(config.batch_size).unwrap_or(1000),
+        flush_interval: args
+            .flush_interval_ms
+            .or(config.flush_interval_ms)
+            .map(Duration::from_millis),
+        max_punctuation_run: args
+            .max_punctuation_run
+            .or(config.cleaning.max_punctuation_run),
+        line_metadata,
+        file_name_template,
+        strip_headers: args.strip_headers,
+        ingested_at: DateTime::now(),
+        tokenizer: args.tokenizer,
+        store_tokens: args.store_tokens,
+        lemmatizer,
+        pos_tagger,
+        quality_scores: args.quality_scores,
+        join_lines: !args.keep_lines,
+        dehyphenation,
+        format: args.format,
+        format_options: FormatOptions {
+            docx_extras: args.docx_extras,
+            markdown_skip_code: args.markdown_skip_code,
+            text_field: args.text_field.clone(),
+            xml_text: args.xml_text.clone(),
+            xml_metadata,
+        },
+        encoding: args.encoding,
+        checkpoint,
+        manifest,
+        force: args.force,
+        replace: args.replace,
+        // Shown once the sources, and so the input's size, are known
+        status: StatusLine::default(),
+        errors: ErrorSummary::default(),
+        shutdown: Shutdown::on_signals(),
+        phase_times: args.bench.then(PhaseTimes::default),
+        file_reports: FileReports::default(),
+        sample: args.sample,
+        limit: args.limit,
+    };
+    Ok(PreparedIngest {
+        options,
+        sink,
+        report,
+        tally,
+    })
+}
+
+async fn run_ingest(
+    args: IngestArgs,
+    target: &MongoTarget,
+    config: &FileConfig,
+) -> Result<(), BoxError> {
+    let input_options = InputOptions {
+        stdin_name: args.stdin_name.clone(),
+        retry: retry_policy(&args),
+    };
+    let sources = expand_input(args.input(), &input_options)?;
+    let PreparedIngest {
+        mut options,
+        sink,
+        report,
+        tally,
+    } = prepare_ingest(&args, target, config).await?;
+    let total_bytes = sources
+        .iter()
+        .map(|source| Some(std::fs::metadata(source.path()?).ok()?.len()))
+        .sum();
+    options.status = StatusLine::new(Visibility::for_flags(args.quiet), total_bytes);
+    let status = options.status.clone();
+    let errors = options.errors.clone();
+    let file_reports = options.file_reports.clone();
+    let phase_times = options.phase_times.clone();
+    let started = Instant::now();
+    status.start();
+    // More workers than files would just sit idle
+    let workers = args.workers.clamp(1, sources.len().max(1));
+    let writer = WriterSink::spawn(sink, args.write_queue, args.inserts_in_flight);
+    let result = ingest_all(
+        sources,
+        Arc::new(options),
+        fan_out(Box::new(writer), workers),
+    )
+    .await;
+    status.finish();
+    print_reports(
+        &args,
+        report.as_ref(),
+        tally.as_ref(),
+        &errors,
+        &file_reports,
+    );
+    let sentence_count = result?;
+
+    eprintln!("Total processed sentences: {}", sentence_count);
+    if let Some(times) = phase_times {
+        for line in times.report(status.bytes(), sentence_count as u64, started.elapsed()) {
+            eprintln!("{}", line);
+        }
+    }
+
+    Ok(())
+}
+
+/// Ingests each file dropped into the directory once it's complete, one at
+/// a time, archiving it if asked, until interrupted. A file that fails is
+/// reported and left where it is, to be tried again if it changes; a sink
+/// that fails stops the watch.
+async fn run_watch(
+    args: WatchArgs,
+    target: &MongoTarget,
+    config: &FileConfig,
+) -> Result<(), BoxError> {
+    let ingest = &args.ingest;
+    let input_options = InputOptions {
+        stdin_name: ingest.stdin_name.clone(),
+        retry: retry_policy(ingest),
+    };
+    let mut drop_dir = DropDirectory::new(ingest.input(), args.archive.as_deref().map(Path::new))?;
+    let PreparedIngest {
+        options,
+        sink,
+        report,
+        tally,
+    } = prepare_ingest(ingest, target, config).await?;
+    let mut options = Arc::new(options);
+    let writer = WriterSink::spawn(sink, ingest.write_queue, ingest.inserts_in_flight);
+    let interval = Duration::from_millis(args.poll_interval_ms);
+
+    log::info!("Watching {} for new files", ingest.input());
+    let mut sentence_count = 0;
+    'watch: while !options.shutdown.is_requested() {
+        for path in drop_dir.poll()? {
+            let name = path.to_string_lossy();
+            if let Some(options) = Arc::get_mut(&mut options) {
+                options.ingested_at = DateTime::now();
+            }
+            let source = source_for(&name, &input_options);
+            let sinks: Vec<Box<dyn SentenceSink>> = vec![Box::new(writer.clone())];
+            match ingest_all(vec![source], options.clone(), sinks).await {
+                Ok(count) => sentence_count += count,
+                Err(IngestError::Interrupted { .. }) => break 'watch,
+                Err(e) if e.is_per_source() => {
+                    log::error!(file = name; "Error ingesting {}: {}", name, e);
+                    continue;
                 }
+                Err(e) => {
+                    print_reports(
+                        ingest,
+                        report.as_ref(),
+                        tally.as_ref(),
+                        &options.errors,
+                        &options.file_reports,
+                    );
+                    return Err(e.into());
+                }
+            }
+            if let Some(archived) = drop_dir.archive(&path)? {
+                log::info!(file = name; "Archived {} to {}", name, archived.display());
             }
         }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = options.shutdown.requested() => {}
+        }
     }
+    print_reports(
+        ingest,
+        report.as_ref(),
+        tally.as_ref(),
+        &options.errors,
+        &options.file_reports,
+    );
+    eprintln!("Total processed sentences: {}", sentence_count);
+    Ok(())
+}
+
+/// A pipeline removing each of `patterns` in turn.
+fn remove_patterns(patterns: &[String]) -> Result<CleaningPipeline, BoxError> {
+    let steps = patterns
+        .iter()
+        .map(|pattern| CleaningStep::remove(pattern))
+        .collect::<Result<_, _>>()?;
+    Ok(CleaningPipeline::new(steps))
+}
+
+async fn run_search(args: SearchArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    if let Some(path) = &args.sqlite {
+        let sink = SqliteSink::open(path)?;
+        for (score, file_name, line_number, text) in
+            sink.search(&args.text, args.filter.file.as_deref(), args.limit)?
+        {
+            match args.format {
+                SearchFormat::Table => {
+                    println!("{:.2}\t{}:{}\t{}", score, file_name, line_number, text)
+                }
+                SearchFormat::Json => println!(
+                    "{}",
+                    serde_json::json!({
+                        "score": score,
+                        "fileName": file_name,
+                        "lineNumber": line_number,
+                        "text": text,
+                    })
+                ),
+            }
+        }
+        return Ok(());
+    }
+    let collection = connect(target).await?;
+
+    let field = |name| target.schema.field(name);
+    let mut filter = doc! { "$text": { "$search": &args.text } };
+    filter.extend(sentence_filter(&args.filter, &target.schema));
 
-    // Handle the last sentence
-    if !current_sentence.is_empty() && is_valid_sentence(&current_sentence) {
-        sentences.push(current_sentence.trim().to_string());
-    } else if let Some(last) = sentences.last_mut() {
-        // If the last part isn't a valid sentence, append it to the previous one
-        last.push(' ');
-        last.push_str(current_sentence.trim());
+    let options = FindOptions::builder()
+        .projection(doc! { "score": { "$meta": "textScore" } })
+        .sort(doc! { "score": { "$meta": "textScore" } })
+        .limit(args.limit)
+        .build();
+    let mut cursor = collection.find(filter, options).await?;
+
+    // Context sentences are printed without a score
+    let row = |score: &str, sentence: &Document| {
+        format!(
+            "{}\t{}:{}\t{}",
+            score,
+            sentence.get_str(field("fileName")).unwrap_or("?"),
+            sentence.get_i32(field("lineNumber")).unwrap_or_default(),
+            sentence.get_str(field("text")).unwrap_or_default()
+        )
+    };
+    let mut first = true;
+    while let Some(mut document) = cursor.try_next().await? {
+        let (before, after) =
+            context_window(&collection, &target.schema, &document, args.context).await?;
+        match args.format {
+            SearchFormat::Table => {
+                // Matches with context are set apart as grep does
+                if args.context > 0 && !std::mem::replace(&mut first, false) {
+                    println!("--");
+                }
+                let score = format!("{:.2}", document.get_f64("score").unwrap_or_default());
+                for near in &before {
+                    println!("{}", row("", near));
+                }
+                println!("{}", row(&score, &document));
+                for near in &after {
+                    println!("{}", row("", near));
+                }
+            }
+            SearchFormat::Json => {
+                if args.context > 0 {
+                    document.insert("before", before);
+                    document.insert("after", after);
+                }
+                println!("{}", Bson::Document(document).into_relaxed_extjson())
+            }
+        }
     }
 
-    sentences
+    Ok(())
+}
+
+/// Where `serve` keeps the sentences it ingests and searches.
+enum ServedCorpus {
+    Sqlite(String),
+    Mongo(Collection<Document>),
+}
+
+/// What `serve` answers requests with.
+struct ServeState {
+    corpus: ServedCorpus,
+    schema: Schema,
+    segmenter: Segmenter,
+    /// Ingestion takes one request at a time.
+    ingest: tokio::sync::Mutex<(IngestOptions, Box<dyn SentenceSink>)>,
 }
 
-struct SentenceWriter {
-    writer: BufWriter<File>,
+/// Answers `POST /segment` with the sentences of the text posted, `POST
+/// /ingest?file=name` by storing them, with the default ingestion options,
+/// and `GET /search?q=text&limit=n&context=n` with the best matches, as
+/// `search` finds them, and `GET /metrics` with the process's metrics.
+async fn run_serve(args: ServeArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let (corpus, sink): (_, Box<dyn SentenceSink>) = match &args.sqlite {
+        Some(path) => (
+            ServedCorpus::Sqlite(path.clone()),
+            Box::new(SqliteSink::open(path)?),
+        ),
+        None => {
+            let collection = connect(target).await?;
+            let sink =
+                MongoSink::new(collection.clone(), TextIndex::Text, target.schema.clone()).await?;
+            (ServedCorpus::Mongo(collection), Box::new(sink))
+        }
+    };
+    let state = Arc::new(ServeState {
+        corpus,
+        schema: target.schema.clone(),
+        segmenter: Segmenter::default(),
+        ingest: tokio::sync::Mutex::new((IngestOptions::default(), sink)),
+    });
+
+    let listener = tokio::net::TcpListener::bind(&args.listen)
+        .await
+        .map_err(|e| format!("Error listening on {}: {}", args.listen, e))?;
+    log::info!("Listening on http://{}", listener.local_addr()?);
+    server::serve(listener, move |request| {
+        let state = state.clone();
+        async move {
+            answer_request(&state, request)
+                .await
+                .unwrap_or_else(|e| Response::error(500, e))
+        }
+    })
+    .await?;
+    Ok(())
 }
 
-impl SentenceWriter {
-    fn new(filename: &str) -> std::io::Result<Self> {
-        let file = File::create(filename)?;
-        let writer = BufWriter::new(file);
-        Ok(SentenceWriter { writer })
+async fn answer_request(state: &ServeState, request: Request) -> Result<Response, BoxError> {
+    let response = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/segment") => {
+            let text = String::from_utf8_lossy(&request.body);
+            Response::json(serde_json::json!({ "sentences": state.segmenter.split(&text) }))
+        }
+        ("POST", "/ingest") => {
+            let name = request.query.get("file").map_or("api", String::as_str);
+            let mut ingest = state.ingest.lock().await;
+            let (options, sink) = &mut *ingest;
+            options.ingested_at = DateTime::now();
+            let reader = std::io::Cursor::new(&request.body);
+            let count = ingest_reader(reader, name, None, options, sink.as_mut()).await?;
+            Response::json(serde_json::json!({ "fileName": name, "sentences": count }))
+        }
+        ("GET", "/search") => {
+            let Some(text) = request.query.get("q") else {
+                return Ok(Response::error(400, "search needs the text to find as q"));
+            };
+            let limit = match request.query.get("limit").map(|limit| limit.parse()) {
+                None => 20,
+                Some(Ok(limit)) => limit,
+                Some(Err(_)) => return Ok(Response::error(400, "limit should be a number")),
+            };
+            let context = match request.query.get("context").map(|context| context.parse()) {
+                None => 0,
+                Some(Ok(context)) => context,
+                Some(Err(_)) => return Ok(Response::error(400, "context should be a number")),
+            };
+            if context > 0 && matches!(state.corpus, ServedCorpus::Sqlite(_)) {
+                return Ok(Response::error(
+                    400,
+                    "context needs sentences stored in MongoDB",
+                ));
+            }
+            let results = search_corpus(state, text, limit, context).await?;
+            Response::json(serde_json::json!({ "results": results }))
+        }
+        ("GET", "/metrics") => Response::text(METRICS.render()),
+        (_, "/segment" | "/ingest" | "/search" | "/metrics") => {
+            Response::error(405, "method not allowed")
+        }
+        _ => Response::error(404, "no such endpoint"),
+    };
+    Ok(response)
+}
+
+/// The `limit` sentences best matching `text`, best first, as JSON, each
+/// with the `context` sentences before and after it in MongoDB.
+async fn search_corpus(
+    state: &ServeState,
+    text: &str,
+    limit: i64,
+    context: i32,
+) -> Result<Vec<serde_json::Value>, BoxError> {
+    let collection = match &state.corpus {
+        ServedCorpus::Sqlite(path) => {
+            let results = SqliteSink::open(path)?.search(text, None, limit)?;
+            return Ok(results
+                .into_iter()
+                .map(|(score, file_name, line_number, text)| {
+                    serde_json::json!({
+                        "score": score,
+                        "fileName": file_name,
+                        "lineNumber": line_number,
+                        "text": text,
+                    })
+                })
+                .collect());
+        }
+        ServedCorpus::Mongo(collection) => collection,
+    };
+    let field = |name| state.schema.field(name);
+    let options = FindOptions::builder()
+        .projection(doc! { "score": { "$meta": "textScore" } })
+        .sort(doc! { "score": { "$meta": "textScore" } })
+        .limit(limit)
+        .build();
+    let mut cursor = collection
+        .find(doc! { "$text": { "$search": text } }, options)
+        .await?;
+    let sentence = |document: &Document| {
+        serde_json::json!({
+            "fileName": document.get_str(field("fileName")).unwrap_or_default(),
+            "lineNumber": document.get_i32(field("lineNumber")).unwrap_or_default(),
+            "text": document.get_str(field("text")).unwrap_or_default(),
+        })
+    };
+    let mut results = Vec::new();
+    while let Some(document) = cursor.try_next().await? {
+        let mut result = sentence(&document);
+        result["score"] = document.get_f64("score").unwrap_or_default().into();
+        if context > 0 {
+            let (before, after) =
+                context_window(collection, &state.schema, &document, context).await?;
+            result["before"] = before.iter().map(sentence).collect();
+            result["after"] = after.iter().map(sentence).collect();
+        }
+        results.push(result);
     }
+    Ok(results)
+}
+
+/// A regular expression matching the names `file` matches, where `*`
+/// matches anything.
+fn file_pattern(file: &str) -> String {
+    regex::escape(file).replace(r"\*", ".*")
+}
+
+/// The query selecting the sentences `args` asks for.
+fn sentence_filter(args: &FilterArgs, schema: &Schema) -> Document {
+    let mut filter = Document::new();
+    if let Some(genre) = &args.genre {
+        filter.insert(schema.field("genre"), genre);
+    }
+    let mut years = Document::new();
+    if let Some(from) = args.year_from {
+        years.insert("$gte", from);
+    }
+    if let Some(to) = args.year_to {
+        years.insert("$lte", to);
+    }
+    if !years.is_empty() {
+        filter.insert(schema.field("year"), years);
+    }
+    let mut ingested = Document::new();
+    if let Some(after) = args.ingested_after {
+        ingested.insert("$gte", after);
+    }
+    if let Some(before) = args.ingested_before {
+        ingested.insert("$lt", before);
+    }
+    if !ingested.is_empty() {
+        filter.insert(schema.field("ingestedAt"), ingested);
+    }
+    if let Some(file) = &args.file {
+        let pattern = format!("^{}$", file_pattern(file));
+        filter.insert(
+            schema.field("fileName"),
+            Regex {
+                pattern,
+                options: String::new(),
+            },
+        );
+    }
+    filter
+}
 
-    fn write_sentence(&mut self, sentence: &str) -> std::io::Result<()> {
-        writeln!(self.writer, "{}", sentence)
+async fn run_kwic(args: KwicArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let collection = connect(target).await?;
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    let field = |name| target.schema.field(name);
+    let options = FindOptions::builder()
+        .sort(doc! { "score": { "$meta": "textScore" } })
+        .limit(args.limit)
+        .build();
+    let mut matches = collection
+        .find(doc! { "$text": { "$search": &args.term } }, options)
+        .await?;
+    let mut lines = 0;
+    while let Some(sentence) = matches.try_next().await? {
+        let text = sentence.get_str(field("text")).unwrap_or_default();
+        let Some(keyword) = find_keyword(text, &args.term) else {
+            continue;
+        };
+        let (mut left, mut right) = (
+            text[..keyword.start].to_string(),
+            text[keyword.end..].to_string(),
+        );
+
+        let (before, after) =
+            context_window(&collection, &target.schema, &sentence, args.context).await?;
+        if !before.is_empty() || !after.is_empty() {
+            let texts = |sentences: &[Document]| {
+                sentences
+                    .iter()
+                    .map(|near| near.get_str(field("text")).unwrap_or_default().to_string() + " ")
+                    .collect::<String>()
+            };
+            left = texts(&before) + &left;
+            right = right + " " + &texts(&after);
+        }
+
+        writeln!(
+            output,
+            "{}",
+            concordance_line(&left, &text[keyword], &right, args.width)
+        )?;
+        lines += 1;
     }
+    output.flush()?;
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        self.writer.flush()
+    if let Some(path) = &args.output {
+        println!("Wrote {} lines to {}", lines, path);
     }
+
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok();
+/// The stored sentences up to `window` before and after `sentence` in its
+/// document, in order, found by their `sentenceIndex` within its `docId`;
+/// none for a sentence stored without them.
+async fn context_window(
+    collection: &Collection<Document>,
+    schema: &Schema,
+    sentence: &Document,
+    window: i32,
+) -> Result<(Vec<Document>, Vec<Document>), BoxError> {
+    let field = |name| schema.field(name);
+    let (Ok(doc_id), Ok(index)) = (
+        sentence.get_str(field("docId")),
+        sentence.get_i32(field("sentenceIndex")),
+    ) else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    if window <= 0 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let around = doc! {
+        field("docId"): doc_id,
+        field("sentenceIndex"): {
+            "$gte": index - window,
+            "$lte": index + window,
+            "$ne": index,
+        },
+    };
+    let options = FindOptions::builder()
+        .sort(doc! { field("sentenceIndex"): 1 })
+        .build();
+    let near: Vec<Document> = collection
+        .find(around, options)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(near
+        .into_iter()
+        .partition(|near| near.get_i32(field("sentenceIndex")).unwrap_or_default() < index))
+}
 
-    let file_path = std::env::var("INPUT_FILE_PATH").expect("INPUT_FILE_PATH must be set");
-    let mongodb_uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
-    let output_file_path = std::env::var("OUTPUT_FILE_PATH").expect("OUTPUT_FILE_PATH must be set");
+/// Sentences and tokens for each value of `group`, or for the whole
+/// collection when it's `None`.
+async fn count_by(
+    collection: &Collection<Document>,
+    target: &MongoTarget,
+    group: Option<&str>,
+) -> Result<Vec<(String, i64, i64)>, BoxError> {
+    let field = |name| format!("${}", target.schema.field(name));
+    let id = group.map_or(Bson::Null, |name| Bson::String(field(name)));
+    let pipeline = [
+        doc! { "$group": {
+            "_id": id,
+            "sentences": { "$sum": 1 },
+            // Sentences stored before tokenCount was have wordCount
+            "tokens": { "$sum": { "$ifNull": [field("tokenCount"), field("wordCount")] } },
+        } },
+        doc! { "$sort": { "_id": 1 } },
+    ];
+    let options = AggregateOptions::builder().allow_disk_use(true).build();
+    let mut cursor = collection.aggregate(pipeline, options).await?;
+    let mut counts = Vec::new();
+    while let Some(group) = cursor.try_next().await? {
+        let name = match group.get("_id") {
+            Some(Bson::String(name)) => name.clone(),
+            Some(Bson::Null) | None => "(none)".to_string(),
+            Some(other) => other.to_string(),
+        };
+        counts.push((
+            name,
+            count_in(&group, "sentences"),
+            count_in(&group, "tokens"),
+        ));
+    }
+    Ok(counts)
+}
 
-    let client_options = ClientOptions::parse(&mongodb_uri).await?;
-    let client = Client::with_options(client_options)?;
+/// The count an aggregation put under `key`, which the server gives as
+/// whichever number type it fits, or 0 without one.
+fn count_in(document: &Document, key: &str) -> i64 {
+    match document.get(key) {
+        Some(Bson::Int32(n)) => *n as i64,
+        Some(Bson::Int64(n)) => *n,
+        Some(Bson::Double(n)) => *n as i64,
+        _ => 0,
+    }
+}
 
-    let db = client.database("coca_like_db");
-    let collection: mongodb::Collection<mongodb::bson::Document> = db.collection("corpus");
+/// Distinct lowercased tokens, if sentences were stored with theirs.
+async fn count_types(
+    collection: &Collection<Document>,
+    target: &MongoTarget,
+) -> Result<Option<i64>, BoxError> {
+    let tokens = target.schema.field("tokens");
+    let stored = doc! { tokens: { "$exists": true } };
+    if collection.find_one(stored, None).await?.is_none() {
+        return Ok(None);
+    }
+    let pipeline = [
+        doc! { "$unwind": format!("${}", tokens) },
+        doc! { "$group": { "_id": { "$toLower": format!("${}", tokens) } } },
+        doc! { "$count": "types" },
+    ];
+    let options = AggregateOptions::builder().allow_disk_use(true).build();
+    let mut cursor = collection.aggregate(pipeline, options).await?;
+    let types = cursor.try_next().await?;
+    Ok(Some(types.map_or(0, |t| count_in(&t, "types"))))
+}
 
-    // Create a text index for efficient searching
-    let index_model = IndexModel::builder().keys(doc! { "text": "text" }).build();
-    collection.create_index(index_model, None).await?;
+async fn run_stats(args: StatsArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let collection = connect(target).await?;
 
-    let file = File::open(&file_path)?;
-    let reader = BufReader::new(file);
-    let mut sentence_writer = SentenceWriter::new(&output_file_path)?;
+    let (sentences, tokens) = count_by(&collection, target, None)
+        .await?
+        .first()
+        .map_or((0, 0), |(_, sentences, tokens)| (*sentences, *tokens));
+    let files = collection
+        .distinct(target.schema.field("fileName"), None, None)
+        .await?;
 
-    // Compile regex once
-    let re = Regex::new(r"[^a-zA-Z0-9\s.!?]").unwrap();
+    println!("Sentences: {}", sentences);
+    println!("Files: {}", files.len());
+    println!("Tokens: {}", tokens);
+    match count_types(&collection, target).await? {
+        Some(types) => {
+            println!("Types: {}", types);
+            if tokens > 0 {
+                println!("Type/token ratio: {:.4}", types as f64 / tokens as f64);
+            }
+        }
+        None => println!("Types: unknown, ingest with --store-tokens to count them"),
+    }
+    if sentences > 0 {
+        println!(
+            "Average sentence length: {:.1} tokens",
+            tokens as f64 / sentences as f64
+        );
+    }
 
-    let mut batch = vec![];
-    let batch_size = 1000; // Adjust as needed
-    let mut sentence_count = 0;
+    let breakdowns = [
+        (args.by_file, "fileName", "File"),
+        (args.by_genre, args.genre_field.as_str(), "Genre"),
+    ];
+    for (_, group, heading) in breakdowns.into_iter().filter(|(wanted, ..)| *wanted) {
+        println!();
+        println!("{}\tsentences\ttokens\taverage length", heading);
+        for (name, sentences, tokens) in count_by(&collection, target, Some(group)).await? {
+            println!(
+                "{}\t{}\t{}\t{:.1}",
+                name,
+                sentences,
+                tokens,
+                tokens as f64 / sentences.max(1) as f64
+            );
+        }
+    }
 
-    for (line_number, line_result) in reader.lines().enumerate() {
-        let line =
-            line_result.map_err(|e| format!("Error reading line {}: {}", line_number + 1, e))?;
-        println!("Processing line {}: {}", line_number + 1, line);
+    Ok(())
+}
 
-        // Remove unwanted characters
-        let cleaned_line = re.replace_all(&line, "").trim().to_string();
+async fn run_export(args: ExportArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let collection = connect(target).await?;
+    let format = args
+        .format
+        .unwrap_or_else(|| ExportFormat::from_path(&args.output));
+    let fields: Vec<String> = args
+        .fields
+        .iter()
+        .map(|name| target.schema.field(name).to_string())
+        .collect();
+    let columns = if fields.is_empty() {
+        ["fileName", "lineNumber", "text"]
+            .map(|name| target.schema.field(name).to_string())
+            .to_vec()
+    } else {
+        fields.clone()
+    };
+    let output = create_output(&args.output, args.compress)
+        .map_err(|e| format!("Error creating {}: {}", args.output, e))?;
+    let mut sink: Box<dyn SentenceSink> = match format {
+        ExportFormat::Text => {
+            Box::new(TextSink::new(output).with_field(target.schema.field("text")))
+        }
+        ExportFormat::Jsonl if fields.is_empty() => Box::new(JsonlSink::new(output)),
+        ExportFormat::Jsonl => Box::new(JsonlSink::new(output).with_fields(fields)),
+        ExportFormat::Csv => Box::new(CsvSink::new(output, columns, b',')),
+        ExportFormat::Tsv => Box::new(CsvSink::new(output, columns, b'\t')),
+    };
 
-        // Split the cleaned line into sentences
-        let sentences = split_into_sentences(&cleaned_line);
+    let mut exported = 0;
+    let filter = sentence_filter(&args.filter, &target.schema);
+    let mut cursor = collection.find(filter, None).await?;
+    let mut batch = Vec::with_capacity(EXPORT_BATCH);
+    while let Some(document) = cursor.try_next().await? {
+        batch.push(document);
+        exported += 1;
+        if batch.len() == EXPORT_BATCH {
+            sink.write_batch(std::mem::take(&mut batch)).await?;
+        }
+    }
+    sink.write_batch(batch).await?;
+    sink.flush().await?;
 
-        for sentence in sentences {
-            if !sentence.is_empty() {
-                batch.push(doc! {
-                    "text": sentence.clone(),
-                    "fileName": file_path.clone(),
-                    "lineNumber": (line_number + 1) as i32
-                });
+    println!("Exported {} sentences to {}", exported, args.output);
 
-                println!("Processed sentence: {}", sentence);
+    Ok(())
+}
 
-                // sentence_writer.write_sentence(&sentence)?;
+/// The manifest of ingested files, in the collection the config names or
+/// the default one.
+async fn open_manifest(database: &Database, target: &MongoTarget) -> Result<Manifest, BoxError> {
+    let collection = target
+        .config
+        .manifest_collection
+        .as_deref()
+        .unwrap_or(MANIFEST_COLLECTION);
+    Manifest::new(database.collection(collection)).await
+}
 
-                if batch.len() >= batch_size {
-                    collection.insert_many(batch.clone(), None).await?;
-                    batch.clear();
-                    println!("Uploaded {} sentences", sentence_count + batch_size);
+async fn run_purge(args: PurgeArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let database = connect_database(target).await?;
+    let sentences: Collection<Document> = database.collection(&target.collection);
+    let file_name = target.schema.field("fileName");
+    let pattern = args.file.as_deref().map(file_pattern);
 
-                    // Flush the writer periodically
-                    sentence + _writer.flush()?;
-                }
+    let mut filter = Document::new();
+    if let Some(pattern) = &pattern {
+        let pattern = format!("^{}$", pattern);
+        filter.insert(
+            file_name,
+            Regex {
+                pattern,
+                options: String::new(),
+            },
+        );
+    }
+    if let Some(before) = args.before {
+        filter.insert(target.schema.field("ingestedAt"), doc! { "$lt": before });
+    }
+    let purged = sentences.delete_many(filter, None).await?.deleted_count;
+    println!("Purged {} sentences", purged);
 
-                sentence_count += 1;
+    // Weights are kept per file, so the files' own can go; the other
+    // tables count the whole corpus and have to be rebuilt
+    if let Some(pattern) = &pattern {
+        let weights: Collection<Document> = database.collection(&args.tfidf_collection);
+        let pattern = format!("^{}$", pattern);
+        let filter = doc! { file_name: Regex { pattern, options: String::new() } };
+        let removed = weights.delete_many(filter, None).await?.deleted_count;
+        if removed > 0 {
+            println!(
+                "Removed {} TF-IDF weights from {}",
+                removed, args.tfidf_collection
+            );
+        }
+    }
+    let manifest = open_manifest(&database, target).await?;
+    let forgotten = manifest.forget(pattern.as_deref(), args.before).await?;
+    if forgotten > 0 {
+        println!(
+            "Forgot {} ingested files, so they're ingested again",
+            forgotten
+        );
+    }
+    if purged > 0 {
+        for (name, command) in [
+            (WORD_FREQ_COLLECTION, "word-freq"),
+            (NGRAMS_COLLECTION, "ngrams"),
+            (args.tfidf_collection.as_str(), "tfidf"),
+        ] {
+            let collection: Collection<Document> = database.collection(name);
+            if collection.estimated_document_count(None).await? > 0 {
+                println!(
+                    "{} still counts them; rebuild it with `rustparser {}`",
+                    name, command
+                );
             }
         }
     }
 
-    // Handle any remaining items in the batch
-    if !batch.is_empty() {
-        collection.insert_many(batch.clone(), None).await?;
-        println!("Uploaded final {} sentences", batch.len());
+    Ok(())
+}
+
+async fn run_migrate(target: &MongoTarget) -> Result<(), BoxError> {
+    let collection = connect(target).await?;
+    let version = target.schema.field("schemaVersion");
+    let outdated = doc! {
+        "$or": [
+            { version: { "$exists": false } },
+            { version: { "$lt": SCHEMA_VERSION } },
+        ]
+    };
+
+    let mut migrated = 0;
+    let mut cursor = collection.find(outdated, None).await?;
+    while let Some(mut document) = cursor.try_next().await? {
+        if !migrate(&mut document, &target.schema) {
+            continue;
+        }
+        let id = document
+            .get("_id")
+            .cloned()
+            .ok_or("sentence without an _id")?;
+        collection
+            .replace_one(doc! { "_id": id }, document, None)
+            .await?;
+        migrated += 1;
+    }
+
+    println!(
+        "Migrated {} sentences to schema version {}",
+        migrated, SCHEMA_VERSION
+    );
+    for migration in MIGRATIONS {
+        println!("  {}: {}", migration.to, migration.description);
     }
 
-    // Final flush to ensure all data is written
-    sentence_writer.flush()?;
+    Ok(())
+}
+
+fn stopwords(args: &CountArgs) -> Result<Stopwords, BoxError> {
+    let mut stopwords = Stopwords::new();
+    for code in &args.stopwords {
+        stopwords.add_language(code)?;
+    }
+    if let Some(path) = &args.stopword_file {
+        stopwords.load(path)?;
+    }
+    Ok(stopwords)
+}
+
+async fn run_word_freq(args: WordFreqArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let database = connect_database(target).await?;
+    let sentences = database.collection(&target.collection);
+
+    let counting = &args.counting;
+    let mut table = FrequencyTable::new().with_stopwords(stopwords(counting)?);
+    let text = target
+        .schema
+        .field(if args.lemmas { "lemmas" } else { "text" });
+    let genre = target.schema.field(&args.genre_field);
+    let lang = target.schema.field("lang");
+    freq::scan(&sentences, &[text, genre, lang], |sentence| {
+        let (genre, lang) = (sentence.get_str(genre).ok(), sentence.get_str(lang).ok());
+        if args.lemmas {
+            let lemmas = sentence.get_array(text).into_iter().flatten();
+            table.add_words(lemmas.filter_map(|lemma| lemma.as_str()), genre, lang);
+        } else if let Ok(sentence_text) = sentence.get_str(text) {
+            table.add_sentence(sentence_text, counting.tokenizer, genre, lang);
+        }
+    })
+    .await?;
+    let written = freq::store_words(&table, &database.collection(&args.collection)).await?;
+
+    println!(
+        "Counted {} tokens of {} words into {}",
+        table.tokens(),
+        written,
+        args.collection
+    );
+
+    Ok(())
+}
+
+async fn run_ngrams(args: NgramsArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let database = connect_database(target).await?;
+    let sentences = database.collection(&target.collection);
+
+    let counting = &args.counting;
+    let mut counts =
+        NgramCounts::new(args.min_n..=args.max_n)?.with_stopwords(stopwords(counting)?);
+    let text = target.schema.field("text");
+    let genre = target.schema.field(&args.genre_field);
+    let lang = target.schema.field("lang");
+    freq::scan(&sentences, &[text, genre, lang], |sentence| {
+        if let Ok(sentence_text) = sentence.get_str(text) {
+            let (genre, lang) = (sentence.get_str(genre).ok(), sentence.get_str(lang).ok());
+            counts.add_sentence(sentence_text, counting.tokenizer, genre, lang);
+        }
+    })
+    .await?;
+    let collection = database.collection(&args.collection);
+    let written = freq::store_ngrams(&counts, &collection, args.min_count).await?;
+
+    println!(
+        "Counted {} n-grams, {} of them distinct and seen at least {} times, into {}",
+        counts.tokens(),
+        written,
+        args.min_count,
+        args.collection
+    );
+
+    Ok(())
+}
+
+async fn run_tfidf(args: TfidfArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let database = connect_database(target).await?;
+    let sentences = database.collection(&target.collection);
+
+    let counting = &args.counting;
+    let mut weights = TermWeights::new().with_stopwords(stopwords(counting)?);
+    let text = target.schema.field("text");
+    let file = target.schema.field("fileName");
+    let lang = target.schema.field("lang");
+    freq::scan(&sentences, &[text, file, lang], |sentence| {
+        if let (Ok(sentence_text), Ok(file)) = (sentence.get_str(text), sentence.get_str(file)) {
+            let lang = sentence.get_str(lang).ok();
+            weights.add_sentence(file, sentence_text, counting.tokenizer, lang);
+        }
+    })
+    .await?;
+    let collection = database.collection(&args.collection);
+    let written = tfidf::store(&weights, &collection, file, args.top).await?;
 
-    println!("Total processed sentences: {}", sentence_count);
+    println!(
+        "Weighed {} words of {} files into {}",
+        written,
+        weights.files(),
+        args.collection
+    );
 
     Ok(())
 }
+
+async fn run_collocates(args: CollocatesArgs, target: &MongoTarget) -> Result<(), BoxError> {
+    let collection = connect(target).await?;
+
+    let mut collocates = Collocates::new(&args.word, args.window, args.tokenizer);
+    let text = target.schema.field("text");
+    freq::scan(&collection, &[text], |sentence| {
+        if let Ok(sentence) = sentence.get_str(text) {
+            collocates.add_sentence(sentence);
+        }
+    })
+    .await?;
+    if collocates.node_count() == 0 {
+        return Err(format!("'{}' isn't in any stored sentence", args.word).into());
+    }
+
+    println!(
+        "Collocates of '{}' ({} times), within {} words:",
+        args.word,
+        collocates.node_count(),
+        args.window
+    );
+    println!("word\tobserved\tfrequency\texpected\tMI\tt-score\tlog-likelihood");
+    for association in collocates
+        .associations(args.measure, args.min_count)
+        .into_iter()
+        .take(args.limit)
+    {
+        println!(
+            "{}\t{}\t{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}",
+            association.word,
+            association.observed,
+            association.frequency,
+            association.expected,
+            association.mi,
+            association.t_score,
+            association.log_likelihood
+        );
+    }
+
+    Ok(())
+}
+
+/// The log levels asked for: `--log-level` if given, and otherwise warn for
+/// a quiet run, or info raised by each `--verbose`.
+fn log_filter(cli: &Cli) -> LogFilter {
+    if let Some(filter) = &cli.log_level {
+        return filter.clone();
+    }
+    let quiet = match &cli.command {
+        Command::Ingest(args) => args.quiet,
+        Command::Watch(args) => args.ingest.quiet,
+        _ => false,
+    };
+    LogFilter::new(match (quiet, cli.verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Load .env before parsing so it can supply flag defaults
+    dotenv().ok();
+    let cli = Cli::parse();
+    if let Err(e) = logging::init(log_filter(&cli), cli.log_format) {
+        eprintln!("Error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            log::error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), BoxError> {
+    let config = FileConfig::load(cli.config.as_deref())?;
+    let target = MongoTarget::resolve(&cli, &config)?;
+
+    match cli.command {
+        Command::Ingest(args) => run_ingest(*args, &target, &config).await,
+        Command::Watch(args) => run_watch(*args, &target, &config).await,
+        Command::Serve(args) => run_serve(args, &target).await,
+        Command::Search(args) => run_search(args, &target).await,
+        Command::Stats(args) => run_stats(args, &target).await,
+        Command::Export(args) => run_export(args, &target).await,
+        Command::Purge(args) => run_purge(args, &target).await,
+        Command::Migrate => run_migrate(&target).await,
+        Command::WordFreq(args) => run_word_freq(args, &target).await,
+        Command::Ngrams(args) => run_ngrams(args, &target).await,
+        Command::Collocates(args) => run_collocates(args, &target).await,
+        Command::Tfidf(args) => run_tfidf(args, &target).await,
+        Command::Kwic(args) => run_kwic(args, &target).await,
+    }
+}
@@ -0,0 +1,152 @@
+//! A record of the files already ingested, so unchanged ones are skipped
+//! when a growing corpus directory is ingested again.
+
+use mongodb::bson::{doc, DateTime, Document, Regex};
+use mongodb::options::{IndexOptions, UpdateOptions};
+use mongodb::{Collection, IndexModel};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::BoxError;
+
+/// Collection the manifest is kept in, next to the sentences.
+pub const MANIFEST_COLLECTION: &str = "files";
+
+/// What identifies a version of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileState {
+    pub size: u64,
+    pub modified: DateTime,
+}
+
+impl FileState {
+    pub fn of(path: &Path) -> Result<Self, BoxError> {
+        let metadata = std::fs::metadata(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        Ok(FileState {
+            size: metadata.len(),
+            modified: DateTime::from_system_time(metadata.modified()?),
+        })
+    }
+}
+
+/// The hex SHA-256 of the file at `path`.
+pub fn checksum(path: &Path) -> Result<String, BoxError> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| format!("Error opening {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// One document per ingested file in the `files` collection, holding its
+/// `path`, `size`, `modified` time and `checksum` as of its last complete
+/// ingestion.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    collection: Collection<Document>,
+}
+
+impl Manifest {
+    pub async fn new(collection: Collection<Document>) -> Result<Self, BoxError> {
+        let index_model = IndexModel::builder()
+            .keys(doc! { "path": 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        collection.create_index(index_model, None).await?;
+        Ok(Manifest { collection })
+    }
+
+    /// Whether `path` was ingested as it is now. A file with a new size or
+    /// modification time is only taken to have changed if its checksum did
+    /// too; a file that was merely touched is recorded with its new time.
+    pub async fn is_unchanged(&self, path: &Path) -> Result<bool, BoxError> {
+        let key = path.to_string_lossy();
+        let Some(record) = self
+            .collection
+            .find_one(doc! { "path": &*key }, None)
+            .await?
+        else {
+            return Ok(false);
+        };
+        let state = FileState::of(path)?;
+        if record.get_i64("size").ok() == Some(state.size as i64)
+            && record.get_datetime("modified").ok() == Some(&state.modified)
+        {
+            return Ok(true);
+        }
+        if record.get_str("checksum").ok() != Some(checksum(path)?.as_str()) {
+            return Ok(false);
+        }
+        self.collection
+            .update_one(
+                doc! { "path": &*key },
+                doc! { "$set": { "size": state.size as i64, "modified": state.modified } },
+                None,
+            )
+            .await?;
+        Ok(true)
+    }
+
+    /// Records that `path` was ingested completely, giving `sentences`.
+    pub async fn record(&self, path: &Path, sentences: usize) -> Result<(), BoxError> {
+        let key = path.to_string_lossy();
+        let state = FileState::of(path)?;
+        let update = doc! { "$set": {
+            "size": state.size as i64,
+            "modified": state.modified,
+            "checksum": checksum(path)?,
+            "sentences": sentences as i64,
+            "ingestedAt": DateTime::now(),
+        } };
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.collection
+            .update_one(doc! { "path": &*key }, update, options)
+            .await?;
+        Ok(())
+    }
+
+    /// Drops the records of the files whose name matches the regular
+    /// expression `name`, in any directory, and that were last ingested
+    /// before `before`, so they're ingested again next time. Returns how
+    /// many were dropped.
+    pub async fn forget(
+        &self,
+        name: Option<&str>,
+        before: Option<DateTime>,
+    ) -> Result<u64, BoxError> {
+        let mut filter = Document::new();
+        if let Some(name) = name {
+            let pattern = format!("(^|/){}$", name);
+            filter.insert(
+                "path",
+                Regex {
+                    pattern,
+                    options: String::new(),
+                },
+            );
+        }
+        if let Some(before) = before {
+            filter.insert("ingestedAt", doc! { "$lt": before });
+        }
+        let result = self.collection.delete_many(filter, None).await?;
+        Ok(result.deleted_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_file_contents() {
+        let path = std::env::temp_dir().join(format!("manifest-{}.txt", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            checksum(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(FileState::of(&path).unwrap().size, 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
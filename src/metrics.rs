@@ -0,0 +1,160 @@
+//! Counters and gauges of the run, kept for the whole process and rendered
+//! in the Prometheus text format for `/metrics`, so that a job ingesting for
+//! days can be watched in Grafana.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the insert latency histogram's buckets.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The process's metrics, counted into by ingestion and the sinks.
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Metrics {
+    files: AtomicU64,
+    sentences: AtomicU64,
+    batches: AtomicU64,
+    retries: AtomicU64,
+    queue_depth: AtomicU64,
+    /// Inserts at or under each of [`LATENCY_BUCKETS`].
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_micros: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Metrics {
+            files: ZERO,
+            sentences: ZERO,
+            batches: ZERO,
+            retries: ZERO,
+            queue_depth: ZERO,
+            latency_buckets: [ZERO; LATENCY_BUCKETS.len()],
+            latency_micros: ZERO,
+        }
+    }
+
+    pub fn add_file(&self) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_sentences(&self, sentences: u64) {
+        self.sentences.fetch_add(sentences, Ordering::Relaxed);
+    }
+
+    pub fn add_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a batch inserted into the destination, taking `latency`.
+    pub fn add_insert(&self, latency: Duration) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        let seconds = latency.as_secs_f64();
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Sets how many batches wait to be written.
+    pub fn set_queue_depth(&self, batches: usize) {
+        self.queue_depth.store(batches as u64, Ordering::Relaxed);
+    }
+
+    /// The metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed);
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            text.push_str(&format!(
+                "# HELP {0} {1}\n# TYPE {0} {2}\n{0} {3}\n",
+                name, help, kind, value
+            ));
+        };
+        metric(
+            "rustparser_files_ingested_total",
+            "counter",
+            "Files ingested.",
+            load(&self.files),
+        );
+        metric(
+            "rustparser_sentences_processed_total",
+            "counter",
+            "Sentences segmented and handed to the sink.",
+            load(&self.sentences),
+        );
+        metric(
+            "rustparser_batches_inserted_total",
+            "counter",
+            "Batches written to the destination.",
+            load(&self.batches),
+        );
+        metric(
+            "rustparser_retries_total",
+            "counter",
+            "Failed attempts retried.",
+            load(&self.retries),
+        );
+        metric(
+            "rustparser_write_queue_depth",
+            "gauge",
+            "Batches waiting to be written.",
+            load(&self.queue_depth),
+        );
+
+        let name = "rustparser_insert_latency_seconds";
+        text.push_str(&format!(
+            "# HELP {0} Time taken to write a batch to the destination.\n# TYPE {0} histogram\n",
+            name
+        ));
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets) {
+            text.push_str(&format!(
+                "{}_bucket{{le=\"{}\"}} {}\n",
+                name,
+                bound,
+                load(count)
+            ));
+        }
+        let inserts = load(&self.batches);
+        text.push_str(&format!(
+            "{0}_bucket{{le=\"+Inf\"}} {1}\n{0}_sum {2}\n{0}_count {1}\n",
+            name,
+            inserts,
+            load(&self.latency_micros) as f64 / 1e6
+        ));
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_text_format() {
+        let metrics = Metrics::new();
+        metrics.add_sentences(1000);
+        metrics.add_insert(Duration::from_millis(40));
+        metrics.add_insert(Duration::from_secs(3));
+        metrics.set_queue_depth(2);
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "# TYPE rustparser_sentences_processed_total counter\nrustparser_sentences_processed_total 1000\n"
+        ));
+        assert!(text.contains("rustparser_write_queue_depth 2\n"));
+        assert!(text.contains("rustparser_insert_latency_seconds_bucket{le=\"0.025\"} 0\n"));
+        assert!(text.contains("rustparser_insert_latency_seconds_bucket{le=\"0.05\"} 1\n"));
+        assert!(text.contains("rustparser_insert_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("rustparser_insert_latency_seconds_sum 3.04\n"));
+        assert!(text.contains("rustparser_insert_latency_seconds_count 2\n"));
+    }
+}
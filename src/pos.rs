@@ -0,0 +1,288 @@
+//! Part-of-speech tags stored with each sentence as `pos`, parallel to its
+//! tokens, so patterns such as an adjective before a noun can be queried.
+//! Tags are the Universal Dependencies ones: `NOUN`, `VERB`, `ADJ` and so on.
+
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(feature = "ingest")]
+use std::time::Duration;
+
+#[cfg(feature = "ingest")]
+use crate::sink::{http_is_transient, RetryPolicy};
+use crate::BoxError;
+
+/// Assigns a tag to every token of a sentence.
+pub trait PosTagger: std::fmt::Debug + Send + Sync {
+    /// One tag per token of `tokens`, in order.
+    fn tag(&self, tokens: &[&str]) -> Result<Vec<String>, BoxError>;
+
+    /// The tags of each of `sentences`, given as their tokens; taggers
+    /// asked over a network take them all in one request.
+    fn tag_all(&self, sentences: &[Vec<String>]) -> Result<Vec<Vec<String>>, BoxError> {
+        sentences
+            .iter()
+            .map(|tokens| self.tag(&tokens.iter().map(String::as_str).collect::<Vec<_>>()))
+            .collect()
+    }
+}
+
+/// Closed-class English words, which a lexicon can list in full.
+const CLOSED_CLASSES: &[(&str, &[&str])] = &[
+    (
+        "DET",
+        &[
+            "a", "an", "the", "this", "that", "these", "those", "some", "any", "no", "every",
+            "each", "all", "both", "either", "neither", "another", "such", "what", "which",
+        ],
+    ),
+    (
+        "PRON",
+        &[
+            "i", "me", "my", "mine", "myself", "you", "your", "yours", "yourself", "he", "him",
+            "his", "himself", "she", "her", "hers", "herself", "it", "its", "itself", "we", "us",
+            "our", "ours", "they", "them", "their", "theirs", "who", "whom", "whose", "someone",
+            "anyone", "anything", "everyone", "nobody", "nothing",
+        ],
+    ),
+    ("PRON", &["themselves", "something", "everything"]),
+    (
+        "ADP",
+        &[
+            "of", "in", "on", "at", "by", "for", "with", "from", "to", "into", "onto", "about",
+            "over", "under", "after", "before", "between", "among", "through", "during", "without",
+            "within", "against", "across", "behind", "beyond", "near", "toward", "towards", "upon",
+            "around", "along",
+        ],
+    ),
+    ("CCONJ", &["and", "or", "but", "nor", "yet", "so"]),
+    (
+        "SCONJ",
+        &[
+            "if", "because", "although", "though", "while", "whether", "unless", "since", "until",
+            "whereas", "when", "where", "than",
+        ],
+    ),
+    (
+        "AUX",
+        &[
+            "be", "am", "is", "are", "was", "were", "been", "being", "have", "has", "had", "do",
+            "does", "did", "will", "would", "shall", "should", "can", "could", "may", "might",
+            "must", "'s", "'re", "'ve", "'ll", "'d", "'m",
+        ],
+    ),
+    ("PART", &["not", "n't"]),
+    (
+        "ADV",
+        &[
+            "very", "too", "also", "just", "only", "even", "still", "never", "always", "often",
+            "here", "there", "then", "now", "again", "soon", "already", "almost", "quite",
+        ],
+    ),
+    ("INTJ", &["oh", "yes", "hello", "wow", "alas", "hey"]),
+];
+
+/// Endings of open-class words and the tag they suggest, longest first.
+const SUFFIXES: &[(&str, &str)] = &[
+    ("ness", "NOUN"),
+    ("ment", "NOUN"),
+    ("tion", "NOUN"),
+    ("sion", "NOUN"),
+    ("ship", "NOUN"),
+    ("ity", "NOUN"),
+    ("ism", "NOUN"),
+    ("ist", "NOUN"),
+    ("able", "ADJ"),
+    ("ible", "ADJ"),
+    ("less", "ADJ"),
+    ("ful", "ADJ"),
+    ("ous", "ADJ"),
+    ("ive", "ADJ"),
+    ("ish", "ADJ"),
+    ("ical", "ADJ"),
+    ("ing", "VERB"),
+    ("ize", "VERB"),
+    ("ise", "VERB"),
+    ("ed", "VERB"),
+    ("ly", "ADV"),
+];
+
+/// Tags words found in a lexicon, and others by their shape and ending. It
+/// knows the closed-class English words; a lexicon loaded with
+/// [`load`](Self::load) covers the rest.
+#[derive(Debug, Clone)]
+pub struct LexiconTagger {
+    lexicon: HashMap<String, String>,
+}
+
+impl Default for LexiconTagger {
+    fn default() -> Self {
+        let mut lexicon = HashMap::new();
+        for (tag, words) in CLOSED_CLASSES {
+            for word in *words {
+                lexicon
+                    .entry(word.to_string())
+                    .or_insert_with(|| tag.to_string());
+            }
+        }
+        LexiconTagger { lexicon }
+    }
+}
+
+impl LexiconTagger {
+    /// Adds the words listed in the file at `path`, one `word<TAB>TAG` per
+    /// line, over the ones already known. Blank lines and lines starting
+    /// with `#` are skipped.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), BoxError> {
+        let path = path.as_ref();
+        let lexicon = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading POS lexicon {}: {}", path.display(), e))?;
+        for (i, line) in lexicon.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((word, tag)) = line.split_once('\t') else {
+                return Err(format!(
+                    "{} line {}: expected a word and its tag separated by a tab",
+                    path.display(),
+                    i + 1
+                )
+                .into());
+            };
+            self.lexicon
+                .insert(word.trim().to_lowercase(), tag.trim().to_uppercase());
+        }
+        Ok(())
+    }
+
+    fn tag_one(&self, token: &str, first: bool) -> String {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'');
+        if word.is_empty() {
+            return "PUNCT".to_string();
+        }
+        if word.replace([',', '.'], "").parse::<f64>().is_ok() {
+            return "NUM".to_string();
+        }
+        let lower = word.to_lowercase();
+        if let Some(tag) = self.lexicon.get(&lower) {
+            return tag.clone();
+        }
+        if !first && word.starts_with(char::is_uppercase) {
+            return "PROPN".to_string();
+        }
+        let tag = SUFFIXES
+            .iter()
+            .find(|(suffix, _)| lower.len() > suffix.len() + 2 && lower.ends_with(suffix))
+            .map_or("NOUN", |(_, tag)| tag);
+        tag.to_string()
+    }
+}
+
+impl PosTagger for LexiconTagger {
+    fn tag(&self, tokens: &[&str]) -> Result<Vec<String>, BoxError> {
+        Ok(tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| self.tag_one(token, i == 0))
+            .collect())
+    }
+}
+
+/// How long a tagging service has to answer a request.
+#[cfg(feature = "ingest")]
+const TAG_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Sends sentences' tokens to a tagging service, a batch at a time, as a
+/// JSON body `{"sentences": [[...], ...]}`, and takes the JSON array of
+/// each sentence's tags it answers with, or the `tags` array of the object
+/// it answers with. Requests that time out or fail along the way are
+/// retried.
+#[cfg(feature = "ingest")]
+#[derive(Debug, Clone)]
+pub struct HttpTagger {
+    url: String,
+    agent: ureq::Agent,
+    retry: RetryPolicy,
+}
+
+#[cfg(feature = "ingest")]
+impl HttpTagger {
+    pub fn new(url: impl Into<String>) -> Self {
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(TAG_TIMEOUT))
+            .build();
+        HttpTagger {
+            url: url.into(),
+            agent: ureq::Agent::new_with_config(config),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[cfg(feature = "ingest")]
+impl PosTagger for HttpTagger {
+    fn tag(&self, tokens: &[&str]) -> Result<Vec<String>, BoxError> {
+        let sentences = [tokens.iter().map(|token| token.to_string()).collect()];
+        Ok(self.tag_all(&sentences)?.remove(0))
+    }
+
+    fn tag_all(&self, sentences: &[Vec<String>]) -> Result<Vec<Vec<String>>, BoxError> {
+        let body = serde_json::json!({ "sentences": sentences }).to_string();
+        let what = format!("tag {} sentences", sentences.len());
+        let answer = self
+            .retry
+            .run_blocking(&what, http_is_transient, |_| {
+                self.agent
+                    .post(&self.url)
+                    .content_type("application/json")
+                    .send(&body)
+                    .and_then(|response| response.into_body().read_to_string())
+            })
+            .map_err(|e| format!("Error tagging with {}: {}", self.url, e))?;
+        let answer: serde_json::Value = serde_json::from_str(&answer)
+            .map_err(|e| format!("Error reading tags from {}: {}", self.url, e))?;
+        let tags = answer.get("tags").unwrap_or(&answer);
+        let tags: Vec<Vec<String>> = serde_json::from_value(tags.clone())
+            .map_err(|e| format!("Error reading tags from {}: {}", self.url, e))?;
+        let counts = |lists: &[Vec<String>]| lists.iter().map(Vec::len).collect::<Vec<_>>();
+        if counts(&tags) != counts(sentences) {
+            return Err(format!(
+                "{} gave tags that don't match the tokens of {} sentences",
+                self.url,
+                sentences.len()
+            )
+            .into());
+        }
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_by_lexicon_and_word_shape() {
+        let tagger = LexiconTagger::default();
+        let tokens = [
+            "The", "careful", "driver", "quickly", "passed", "Boston", "at", "90",
+        ];
+        assert_eq!(
+            tagger.tag(&tokens).unwrap(),
+            ["DET", "ADJ", "NOUN", "ADV", "VERB", "PROPN", "ADP", "NUM"]
+        );
+        assert_eq!(tagger.tag(&["Hello", "!"]).unwrap(), ["INTJ", "PUNCT"]);
+
+        let mut tagger = tagger;
+        let path = std::env::temp_dir().join(format!("pos-{}.tsv", std::process::id()));
+        std::fs::write(&path, "# verbs\ndriver\tNOUN\nrun\tverb\n").unwrap();
+        tagger.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(tagger.tag(&["They", "run"]).unwrap(), ["PRON", "VERB"]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Readability and quality scores stored with each sentence as `quality`,
+//! so OCR junk and tables can be filtered out by query rather than being
+//! dropped for good at ingestion.
+
+use mongodb::bson::{doc, Document};
+
+/// Scores of one sentence.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quality {
+    /// Flesch-Kincaid grade level: the US school grade the sentence reads at.
+    pub grade: f64,
+    /// Flesch reading ease, highest for the easiest text.
+    pub reading_ease: f64,
+    /// Mean letters and digits per word.
+    pub word_length: f64,
+    /// Share of the non-whitespace characters that are digits.
+    pub digit_ratio: f64,
+    /// Share of the letters that are capitals.
+    pub uppercase_ratio: f64,
+    /// Share of the non-whitespace characters that are neither letters nor
+    /// digits.
+    pub symbol_ratio: f64,
+}
+
+impl Quality {
+    /// Scores `sentence`, split into `words`.
+    pub fn of(sentence: &str, words: &[&str]) -> Self {
+        let (mut chars, mut letters, mut digits, mut uppercase) = (0, 0, 0, 0);
+        for c in sentence.chars().filter(|c| !c.is_whitespace()) {
+            chars += 1;
+            if c.is_alphabetic() {
+                letters += 1;
+                uppercase += c.is_uppercase() as usize;
+            } else if c.is_numeric() {
+                digits += 1;
+            }
+        }
+        let ratio = |part: usize, whole: usize| {
+            if whole == 0 {
+                0.0
+            } else {
+                part as f64 / whole as f64
+            }
+        };
+
+        let mut quality = Quality {
+            digit_ratio: ratio(digits, chars),
+            uppercase_ratio: ratio(uppercase, letters),
+            symbol_ratio: ratio(chars - letters - digits, chars),
+            ..Quality::default()
+        };
+        if !words.is_empty() {
+            let count = words.len() as f64;
+            let length: usize = words
+                .iter()
+                .map(|word| word.chars().filter(|c| c.is_alphanumeric()).count())
+                .sum();
+            let syllables = words.iter().map(|word| syllables(word)).sum::<usize>() as f64;
+            quality.word_length = length as f64 / count;
+            quality.grade = 0.39 * count + 11.8 * syllables / count - 15.59;
+            quality.reading_ease = 206.835 - 1.015 * count - 84.6 * syllables / count;
+        }
+        quality
+    }
+
+    pub fn to_document(&self) -> Document {
+        doc! {
+            "fleschKincaidGrade": self.grade,
+            "fleschReadingEase": self.reading_ease,
+            "avgWordLength": self.word_length,
+            "digitRatio": self.digit_ratio,
+            "uppercaseRatio": self.uppercase_ratio,
+            "symbolRatio": self.symbol_ratio,
+        }
+    }
+}
+
+/// Syllables in an English `word`, estimated as its groups of vowels, not
+/// counting a silent final "e".
+fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    if word.ends_with('e') && !word.ends_with("le") && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_prose_and_junk() {
+        assert_eq!(
+            ["cat", "table", "make", "reading", "beautiful"].map(syllables),
+            [1, 2, 1, 2, 3]
+        );
+
+        let sentence = "The cat sat on the mat.";
+        let words: Vec<_> = sentence.split_whitespace().collect();
+        let prose = Quality::of(sentence, &words);
+        assert!((prose.grade - (0.39 * 6.0 + 11.8 - 15.59)).abs() < 1e-9);
+        assert!(prose.reading_ease > 100.0);
+        assert!((prose.word_length - 17.0 / 6.0).abs() < 1e-9);
+        assert_eq!(prose.digit_ratio, 0.0);
+
+        let junk = Quality::of("T4BLE 1: 0.35 | 0.72", &["T4BLE", "1", "0.35", "0.72"]);
+        assert!(junk.digit_ratio > 0.4);
+        assert_eq!(junk.uppercase_ratio, 1.0);
+        assert!(junk.symbol_ratio > 0.2);
+        assert_eq!(prose.to_document().get_f64("digitRatio"), Ok(0.0));
+    }
+}
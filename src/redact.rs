@@ -0,0 +1,129 @@
+//! Replacement of personal information with placeholder tokens, for corpora
+//! that are shared.
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// A kind of personal information that can be redacted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PiiKind {
+    /// `http(s)://` and `www.` addresses, replaced with `<URL>`.
+    Url,
+    /// Email addresses, replaced with `<EMAIL>`.
+    Email,
+    /// Phone numbers in common national and international layouts, such as
+    /// "(555) 123-4567" or "+44 20 7946 0958", replaced with `<PHONE>`.
+    Phone,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 3] = [PiiKind::Url, PiiKind::Email, PiiKind::Phone];
+
+    pub fn placeholder(self) -> &'static str {
+        match self {
+            PiiKind::Url => "<URL>",
+            PiiKind::Email => "<EMAIL>",
+            PiiKind::Phone => "<PHONE>",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            PiiKind::Url => r#"\b(?:https?://|www\.)[^\s<>"]*[^\s<>".,;:!?)\]']"#,
+            PiiKind::Email => r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+            PiiKind::Phone => {
+                r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)[\s.-]?|\b\d{2,4}[\s.-])\d{3,4}[\s.-]\d{4}\b"
+            }
+        }
+    }
+}
+
+impl FromStr for PiiKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "url" | "urls" => Ok(PiiKind::Url),
+            "email" | "emails" => Ok(PiiKind::Email),
+            "phone" | "phones" => Ok(PiiKind::Phone),
+            other => Err(format!(
+                "invalid redaction '{}' (expected url, email or phone)",
+                other
+            )),
+        }
+    }
+}
+
+/// Replaces the chosen kinds of personal information with placeholders.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    patterns: Vec<(PiiKind, Regex)>,
+}
+
+impl Redactor {
+    /// Redacts `kinds`. URLs always go first, so an address inside one is
+    /// taken as part of the URL.
+    pub fn new(kinds: &[PiiKind]) -> Self {
+        let mut kinds = kinds.to_vec();
+        kinds.sort();
+        kinds.dedup();
+        let patterns = kinds
+            .into_iter()
+            .map(|kind| (kind, Regex::new(kind.pattern()).expect("valid regex")))
+            .collect();
+        Redactor { patterns }
+    }
+
+    /// The placeholders this redactor puts in, which cleaning has to leave
+    /// alone.
+    pub fn placeholders(&self) -> Vec<&'static str> {
+        self.patterns
+            .iter()
+            .map(|(kind, _)| kind.placeholder())
+            .collect()
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut redacted = Cow::Borrowed(text);
+        for (kind, regex) in &self.patterns {
+            if let Cow::Owned(replaced) = regex.replace_all(&redacted, kind.placeholder()) {
+                redacted = Cow::Owned(replaced);
+            }
+        }
+        redacted
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Redactor::new(&PiiKind::ALL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_each_kind_with_its_placeholder() {
+        let text = "Mail jane.doe@example.org or see https://example.org/contact?id=3. \
+                    Call (555) 123-4567 or +44 20 7946 0958 between 1990-2000.";
+        assert_eq!(
+            Redactor::default().redact(text),
+            "Mail <EMAIL> or see <URL>. Call <PHONE> or <PHONE> between 1990-2000."
+        );
+    }
+
+    #[test]
+    fn only_redacts_chosen_kinds() {
+        let redactor = Redactor::new(&[PiiKind::Email]);
+        assert_eq!(
+            redactor.redact("Write to a@b.co via www.b.co today."),
+            "Write to <EMAIL> via www.b.co today."
+        );
+        assert!(matches!(redactor.redact("Nothing here."), Cow::Borrowed(_)));
+        assert_eq!("URLs".parse(), Ok(PiiKind::Url));
+        assert!("names".parse::<PiiKind>().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! What ingestion did with each file, for curators auditing a run: lines
+//! read, sentences kept, fragments rejected by each validity rule, bytes
+//! and time taken, logged as each file finishes and optionally written out
+//! as a JSON report with the run's totals.
+
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::segment::Rejections;
+use crate::BoxError;
+
+/// The summary of one file, or member of an archive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileReport {
+    pub file: String,
+    pub lines: u64,
+    /// Bytes of its lines, line breaks included, after decompression and
+    /// decoding.
+    pub bytes: u64,
+    /// Sentences handed to the sink.
+    pub sentences: u64,
+    pub rejections: Rejections,
+    pub elapsed: Duration,
+}
+
+impl FileReport {
+    fn add(&mut self, other: &FileReport) {
+        self.lines += other.lines;
+        self.bytes += other.bytes;
+        self.sentences += other.sentences;
+        self.rejections += other.rejections;
+        self.elapsed += other.elapsed;
+    }
+
+    fn to_json(&self) -> Value {
+        let rejections = &self.rejections;
+        json!({
+            "file": self.file,
+            "lines": self.lines,
+            "bytes": self.bytes,
+            "sentences": self.sentences,
+            "rejected": {
+                "total": rejections.total(),
+                "tooShort": rejections.too_short,
+                "tooLong": rejections.too_long,
+                "tooManyDigits": rejections.too_many_digits,
+                "otherLanguage": rejections.other_language,
+                "duplicate": rejections.duplicate,
+                "nearDuplicate": rejections.near_duplicate,
+            },
+            "seconds": self.elapsed.as_secs_f64(),
+        })
+    }
+}
+
+/// The reports of the files a run ingested, in the order they finished.
+/// Clones add to the same reports.
+#[derive(Debug, Clone, Default)]
+pub struct FileReports {
+    files: Arc<Mutex<Vec<FileReport>>>,
+}
+
+impl FileReports {
+    pub fn add(&self, report: FileReport) {
+        self.files.lock().unwrap().push(report);
+    }
+
+    pub fn files(&self) -> Vec<FileReport> {
+        self.files.lock().unwrap().clone()
+    }
+
+    /// The files' reports added up, with the time summed over them.
+    pub fn total(&self) -> FileReport {
+        let mut total = FileReport::default();
+        for report in self.files.lock().unwrap().iter() {
+            total.add(report);
+        }
+        total
+    }
+
+    /// A line with the totals, such as `3 files: 1200 lines, 2.1 MB, 3400
+    /// sentences, 25 fragments rejected`, or nothing if no file finished.
+    pub fn summary(&self) -> Option<String> {
+        let files = self.files.lock().unwrap().len();
+        if files == 0 {
+            return None;
+        }
+        let total = self.total();
+        Some(format!(
+            "{} files: {} lines, {:.1} MB, {} sentences, {} fragments rejected",
+            files,
+            total.lines,
+            total.bytes as f64 / 1e6,
+            total.sentences,
+            total.rejections.total()
+        ))
+    }
+
+    /// The reports as JSON: `{"files": [...], "total": {...}}`.
+    pub fn to_json(&self) -> Value {
+        let mut total = self.total().to_json();
+        total["file"] = json!(null);
+        total["files"] = json!(self.files.lock().unwrap().len());
+        json!({
+            "files": self.files().iter().map(FileReport::to_json).collect::<Vec<_>>(),
+            "total": total,
+        })
+    }
+
+    /// Writes the [JSON](Self::to_json) reports to `path`.
+    pub fn write(&self, path: &str) -> Result<(), BoxError> {
+        let json = serde_json::to_string_pretty(&self.to_json())?;
+        std::fs::write(path, json + "\n")
+            .map_err(|e| format!("Error writing the report to {}: {}", path, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_the_files() {
+        let reports = FileReports::default();
+        assert_eq!(reports.summary(), None);
+        let file = |name: &str, lines, too_short| FileReport {
+            file: name.to_string(),
+            lines,
+            bytes: lines * 5000,
+            sentences: lines * 2,
+            rejections: Rejections {
+                too_short,
+                ..Rejections::default()
+            },
+            elapsed: Duration::from_millis(500),
+        };
+        reports.add(file("a.txt", 100, 3));
+        reports.clone().add(file("b.txt", 300, 1));
+
+        assert_eq!(
+            reports.summary().unwrap(),
+            "2 files: 400 lines, 2.0 MB, 800 sentences, 4 fragments rejected"
+        );
+        let json = reports.to_json();
+        assert_eq!(json["files"][1]["file"], "b.txt");
+        assert_eq!(json["files"][0]["rejected"]["tooShort"], 3);
+        assert_eq!(json["total"]["files"], 2);
+        assert_eq!(json["total"]["sentences"], 800);
+        assert_eq!(json["total"]["seconds"], 1.0);
+    }
+}
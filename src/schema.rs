@@ -0,0 +1,172 @@
+//! Field names of stored sentences, for loading into a collection laid out
+//! differently from the one this tool creates, and the [`MIGRATIONS`] that
+//! bring sentences stored by older versions up to date.
+
+use mongodb::bson::{Bson, Document};
+use std::collections::HashMap;
+
+use crate::ingest::text_hash;
+use crate::token::Tokenizer;
+
+/// The `schemaVersion` of sentences stored now. Sentences without one were
+/// stored before it was recorded, and count as version 1.
+pub const SCHEMA_VERSION: i32 = 3;
+
+/// A change to the layout of stored sentences, bringing one from the
+/// version before `to` up to `to`.
+pub struct Migration {
+    pub to: i32,
+    pub description: &'static str,
+    /// Updates a sentence in place, with field names mapped by the schema.
+    pub apply: fn(&mut Document, &Schema),
+}
+
+/// Every migration, oldest first.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        to: 2,
+        description: "add textHash and wordCount where missing",
+        apply: |document, schema| {
+            let Ok(text) = document.get_str(schema.field("text")).map(str::to_string) else {
+                return;
+            };
+            let hash = schema.field("textHash");
+            if !document.contains_key(hash) {
+                document.insert(hash, text_hash(&text));
+            }
+            let words = schema.field("wordCount");
+            if !document.contains_key(words) {
+                document.insert(words, text.split_whitespace().count() as i32);
+            }
+        },
+    },
+    Migration {
+        to: 3,
+        description: "add tokenCount where missing",
+        apply: |document, schema| {
+            let Ok(text) = document.get_str(schema.field("text")) else {
+                return;
+            };
+            let count = Tokenizer::default().tokens(text).len() as i32;
+            let tokens = schema.field("tokenCount");
+            if !document.contains_key(tokens) {
+                document.insert(tokens, count);
+            }
+        },
+    },
+];
+
+/// The schema version `document` is at.
+pub fn schema_version(document: &Document, schema: &Schema) -> i32 {
+    match document.get(schema.field("schemaVersion")) {
+        Some(Bson::Int32(version)) => *version,
+        Some(Bson::Int64(version)) => *version as i32,
+        _ => 1,
+    }
+}
+
+/// Runs the migrations `document` hasn't had and stamps it with
+/// [`SCHEMA_VERSION`]. Returns whether anything was due.
+pub fn migrate(document: &mut Document, schema: &Schema) -> bool {
+    let version = schema_version(document, schema);
+    if version >= SCHEMA_VERSION {
+        return false;
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.to > version) {
+        (migration.apply)(document, schema);
+    }
+    document.insert(schema.field("schemaVersion"), SCHEMA_VERSION);
+    true
+}
+
+/// Renames the top-level fields of sentence documents and adds fixed ones.
+/// Names are given as the pipeline produces them, such as `text` or
+/// `fileName`.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    renames: HashMap<String, String>,
+    /// Added to every document, unless it already has a field by that name.
+    fields: Document,
+}
+
+impl Schema {
+    pub fn new(renames: HashMap<String, String>, fields: Document) -> Self {
+        Schema { renames, fields }
+    }
+
+    /// What the field the pipeline calls `name` is stored as.
+    pub fn field<'a>(&'a self, name: &'a str) -> &'a str {
+        self.renames.get(name).map_or(name, String::as_str)
+    }
+
+    /// Whether documents are stored as the pipeline produces them.
+    pub fn is_identity(&self) -> bool {
+        self.renames.is_empty() && self.fields.is_empty()
+    }
+
+    /// Renames the fields of `document` and adds the fixed ones.
+    pub fn apply(&self, document: Document) -> Document {
+        if self.is_identity() {
+            return document;
+        }
+        let mut mapped: Document = document
+            .into_iter()
+            .map(|(key, value)| match self.renames.get(&key) {
+                Some(renamed) => (renamed.clone(), value),
+                None => (key, value),
+            })
+            .collect();
+        for (key, value) in &self.fields {
+            if !mapped.contains_key(key) {
+                mapped.insert(key.clone(), Bson::clone(value));
+            }
+        }
+        mapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn renames_and_adds_fields() {
+        let renames = [("text", "sentence"), ("fileName", "source")]
+            .into_iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+        let schema = Schema::new(renames, doc! { "corpus": "news", "source": "unused" });
+        assert_eq!(schema.field("text"), "sentence");
+        assert_eq!(schema.field("lineNumber"), "lineNumber");
+
+        let document = doc! { "text": "It rained.", "fileName": "a.txt", "lineNumber": 3 };
+        assert_eq!(
+            schema.apply(document.clone()),
+            doc! {
+                "sentence": "It rained.",
+                "source": "a.txt",
+                "lineNumber": 3,
+                "corpus": "news",
+            }
+        );
+        assert_eq!(Schema::default().apply(document.clone()), document);
+    }
+
+    #[test]
+    fn migrates_unversioned_sentences() {
+        let schema = Schema::default();
+        let mut document = doc! { "text": "It rained all day." };
+        assert_eq!(schema_version(&document, &schema), 1);
+        assert!(migrate(&mut document, &schema));
+        assert_eq!(document.get_i32("wordCount"), Ok(4));
+        assert_eq!(document.get_i32("tokenCount"), Ok(4));
+        assert_eq!(
+            document.get_str("textHash"),
+            Ok(text_hash("It rained all day.").as_str())
+        );
+        assert_eq!(schema_version(&document, &schema), SCHEMA_VERSION);
+        assert!(!migrate(&mut document, &schema));
+        assert_eq!(MIGRATIONS.last().map(|m| m.to), Some(SCHEMA_VERSION));
+    }
+}
@@ -0,0 +1,857 @@
+//! Sentence boundary detection and validity rules.
+
+pub mod srx;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::BoxError;
+
+/// Whether the `.` at byte offset `pos` of `text` ends a sentence. Only the
+/// characters next to it and the whitespace after it are looked at, so
+/// checking every period of a text stays linear.
+pub fn is_sentence_boundary(text: &str, pos: usize) -> bool {
+    let (Some(before), Some(after)) = (text.get(..pos), text.get(pos + 1..)) else {
+        return false;
+    };
+    let (Some(prev_char), Some(next_char)) = (before.chars().next_back(), after.chars().next())
+    else {
+        return false;
+    };
+
+    // Check if it's not part of an abbreviation, number, or ellipsis
+    if prev_char.is_alphabetic() && next_char.is_alphabetic() {
+        return false;
+    }
+
+    if prev_char.is_numeric() && next_char.is_numeric() {
+        return false;
+    }
+
+    if next_char == '.' {
+        return false; // Likely an ellipsis
+    }
+
+    // Check if it's followed by a space and a capital letter or number
+    next_char.is_whitespace()
+        && after
+            .trim_start()
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_uppercase() || c.is_numeric())
+}
+
+/// Units recognised after a number when `reject_number_units` is set.
+const NUMBER_UNITS: &[&str] = &[
+    "%", "kg", "g", "mg", "lb", "lbs", "oz", "km", "m", "cm", "mm", "mi", "ft", "l", "ml", "s",
+    "ms", "min", "h", "hrs", "kb", "mb", "gb",
+];
+
+/// Tunable parts of the sentence validity check. Fragments below the
+/// minimums are merged with the text after them; sentences past a maximum
+/// are dropped.
+#[derive(Debug, Clone)]
+pub struct ValidityRules {
+    pub min_words: usize,
+    pub min_chars: usize,
+    pub max_words: Option<usize>,
+    pub max_chars: Option<usize>,
+    /// Drop sentences in which more than this share of the non-space
+    /// characters are digits, such as tables of figures.
+    pub max_digit_ratio: Option<f64>,
+    /// Treat a number followed by a unit ("42 kg", "100 %") like a bare number.
+    pub reject_number_units: bool,
+}
+
+impl Default for ValidityRules {
+    fn default() -> Self {
+        ValidityRules {
+            min_words: 3,
+            min_chars: 10,
+            max_words: None,
+            max_chars: None,
+            max_digit_ratio: None,
+            reject_number_units: false,
+        }
+    }
+}
+
+/// Why text was left out of the sentences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Below the minimums, or a bare number, with nothing to merge it into.
+    TooShort,
+    TooLong,
+    TooManyDigits,
+    /// Reliably in a language that isn't wanted.
+    OtherLanguage,
+    /// The same as a sentence stored earlier in the run.
+    Duplicate,
+    /// Nearly the same as a sentence stored earlier in the run.
+    NearDuplicate,
+}
+
+/// Counts of [`Rejection`]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rejections {
+    pub too_short: usize,
+    pub too_long: usize,
+    pub too_many_digits: usize,
+    pub other_language: usize,
+    pub duplicate: usize,
+    pub near_duplicate: usize,
+}
+
+impl Rejections {
+    pub fn record(&mut self, rejection: Rejection) {
+        match rejection {
+            Rejection::TooShort => self.too_short += 1,
+            Rejection::TooLong => self.too_long += 1,
+            Rejection::TooManyDigits => self.too_many_digits += 1,
+            Rejection::OtherLanguage => self.other_language += 1,
+            Rejection::Duplicate => self.duplicate += 1,
+            Rejection::NearDuplicate => self.near_duplicate += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.too_short
+            + self.too_long
+            + self.too_many_digits
+            + self.other_language
+            + self.duplicate
+            + self.near_duplicate
+    }
+}
+
+impl std::ops::AddAssign for Rejections {
+    fn add_assign(&mut self, other: Self) {
+        self.too_short += other.too_short;
+        self.too_long += other.too_long;
+        self.too_many_digits += other.too_many_digits;
+        self.other_language += other.other_language;
+        self.duplicate += other.duplicate;
+        self.near_duplicate += other.near_duplicate;
+    }
+}
+
+fn is_numeric_only(text: &str, rules: &ValidityRules) -> bool {
+    if text.parse::<f64>().is_ok() {
+        return true;
+    }
+    if !rules.reject_number_units {
+        return false;
+    }
+
+    let text = text.trim_end_matches(['.', '!', '?']);
+    let (number, unit) = match text.split_once(char::is_whitespace) {
+        Some((number, unit)) => (number, unit.trim()),
+        // Also catch the unit glued to the number, as in "42kg"
+        None => text.split_at(
+            text.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                .unwrap_or(text.len()),
+        ),
+    };
+    number.parse::<f64>().is_ok() && NUMBER_UNITS.contains(&unit.to_lowercase().as_str())
+}
+
+/// Whether `c` belongs to a script written without spaces between words:
+/// Chinese, Japanese, Thai, Lao, Khmer or Burmese.
+fn is_spaceless_char(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{3400}'..='\u{4DBF}' // CJK ideographs
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF66}'..='\u{FF9F}' // Half-width Katakana
+        | '\u{20000}'..='\u{2FA1F}'
+        | '\u{0E00}'..='\u{0EFF}' // Thai and Lao
+        | '\u{1000}'..='\u{109F}' // Myanmar
+        | '\u{1780}'..='\u{17FF}' // Khmer
+    )
+}
+
+/// Whether most of the letters in `text` are in a script without spaces
+/// between words, where counting words by whitespace means nothing.
+pub fn is_spaceless(text: &str) -> bool {
+    let (spaceless, letters) = text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .fold((0, 0), |(spaceless, letters), c| {
+            (spaceless + is_spaceless_char(c) as usize, letters + 1)
+        });
+    spaceless * 2 > letters
+}
+
+/// Whether `sentence` meets the minimums and isn't just a number, so it can
+/// stand on its own rather than be merged with the text after it. The word
+/// minimum doesn't apply to [space-less](is_spaceless) text.
+fn is_complete(sentence: &str, rules: &ValidityRules) -> bool {
+    let trimmed = sentence.trim();
+    if trimmed.is_empty() {
+        return false;
+    }
+
+    // A valid sentence should have enough words and not be just a number
+    let word_count = trimmed.split_whitespace().count();
+    (word_count >= rules.min_words || is_spaceless(trimmed))
+        && !is_numeric_only(trimmed, rules)
+        && trimmed.len() >= rules.min_chars
+}
+
+/// Checks a complete sentence against the maximums.
+fn check_limits(sentence: &str, rules: &ValidityRules) -> Result<(), Rejection> {
+    let too_long = rules
+        .max_words
+        .is_some_and(|max| sentence.split_whitespace().count() > max)
+        || rules
+            .max_chars
+            .is_some_and(|max| sentence.chars().count() > max);
+    if too_long {
+        return Err(Rejection::TooLong);
+    }
+    if let Some(max_ratio) = rules.max_digit_ratio {
+        let (digits, chars) = sentence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .fold((0, 0), |(digits, chars), c| {
+                (digits + c.is_numeric() as usize, chars + 1)
+            });
+        if chars > 0 && digits as f64 > chars as f64 * max_ratio {
+            return Err(Rejection::TooManyDigits);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `sentence` is long enough, short enough and prose-like enough to
+/// keep.
+pub fn is_valid_sentence(sentence: &str, rules: &ValidityRules) -> bool {
+    is_complete(sentence, rules) && check_limits(sentence.trim(), rules).is_ok()
+}
+
+/// A way of finding candidate sentence boundaries. Backends only propose
+/// where sentences might end; [`Segmenter`] then applies the validity rules,
+/// merging fragments that are too short to stand alone.
+pub trait SegmentationBackend: std::fmt::Debug + Send + Sync {
+    /// Splits `text` at every candidate boundary. The fragments, concatenated,
+    /// give back `text`.
+    fn fragments<'a>(&self, text: &'a str) -> Vec<&'a str>;
+}
+
+/// Abbreviations that don't end a sentence when followed by a capital,
+/// lowercase and without their final period. Ones that are also ordinary
+/// words ("no", "art", "sat") or that usually end a sentence ("etc") are
+/// left out.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "mx", "dr", "prof", "rev", "fr", "sr", "jr", "st", "mt", "gen", "col",
+    "capt", "lt", "sgt", "gov", "sen", "rep", "pres", "hon", "messrs", "mme", "mlle", "vs", "e.g",
+    "i.e", "cf", "viz", "al", "approx", "ca", "vol", "vols", "pp", "dept", "inc", "ltd", "corp",
+    "bros", "u.s", "u.k", "u.s.a", "e.u", "a.m", "p.m", "jan", "feb", "apr", "jun", "jul", "aug",
+    "sep", "sept", "oct", "nov",
+];
+
+static DEFAULT_ABBREVIATIONS: LazyLock<Arc<HashSet<String>>> =
+    LazyLock::new(|| Arc::new(ABBREVIATIONS.iter().map(|a| a.to_string()).collect()));
+
+/// The built-in heuristics: `.` ends a sentence when followed by whitespace
+/// and a capital or digit, and isn't part of a number, an ellipsis, an
+/// initial or a known abbreviation; `?` and `!` end one unless they're
+/// followed by more text. Nothing inside a quotation ends a sentence; a
+/// quotation closed by terminal punctuation does when a new sentence follows
+/// it. Runs in a single forward pass over the text.
+#[derive(Debug, Clone)]
+pub struct RuleBackend {
+    abbreviations: Arc<HashSet<String>>,
+}
+
+impl Default for RuleBackend {
+    /// Uses the built-in English abbreviation list.
+    fn default() -> Self {
+        RuleBackend {
+            abbreviations: Arc::clone(&DEFAULT_ABBREVIATIONS),
+        }
+    }
+}
+
+impl RuleBackend {
+    /// Adds abbreviations to the list, given with or without their final
+    /// period ("Dr.", "approx", "z.B.").
+    pub fn with_abbreviations<I, S>(mut self, abbreviations: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let set = Arc::make_mut(&mut self.abbreviations);
+        for abbreviation in abbreviations {
+            let abbreviation = abbreviation.as_ref().trim().trim_end_matches('.');
+            if !abbreviation.is_empty() {
+                set.insert(abbreviation.to_lowercase());
+            }
+        }
+        self
+    }
+
+    /// Adds the abbreviations in `path`, one per line. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn with_abbreviation_file(self, path: &str) -> Result<Self, BoxError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading abbreviation file {}: {}", path, e))?;
+        Ok(self.with_abbreviations(
+            contents
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#')),
+        ))
+    }
+
+    /// Whether the word before the `.` at `pos` is an abbreviation or an
+    /// initial such as the "J" in "J. Smith".
+    fn is_abbreviation(&self, text: &str, pos: usize) -> bool {
+        let word = text[..pos]
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches(|c: char| !c.is_alphanumeric());
+        let mut chars = word.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if c.is_uppercase() {
+                return true;
+            }
+        }
+        !word.is_empty() && self.abbreviations.contains(&word.to_lowercase())
+    }
+}
+
+impl SegmentationBackend for RuleBackend {
+    fn fragments<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let mut fragments = Vec::new();
+        let mut start = 0;
+        let mut quotes = QuoteState::default();
+        let mut prev_char = None;
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            let next_char = chars.peek().map(|&(_, next_c)| next_c);
+            let boundary = match c {
+                // `"Stop!" She ran.` ends after the quote; `"Stop!" she
+                // said.` keeps its attribution
+                _ if quotes.update(c, prev_char, next_char) => {
+                    !quotes.is_open()
+                        && prev_char.is_some_and(|p| matches!(p, '.' | '?' | '!'))
+                        && starts_new_sentence(&text[i + c.len_utf8()..])
+                }
+                // Terminal punctuation inside a quotation ends the quoted
+                // sentence, not the one around it
+                _ if quotes.is_open() => false,
+                '.' => is_sentence_boundary(text, i) && !self.is_abbreviation(text, i),
+                // Check if it's not part of a quotation
+                '?' | '!' => {
+                    next_char.is_none_or(|next_c| next_c.is_whitespace() || next_c.is_uppercase())
+                }
+                _ => false,
+            };
+            if boundary {
+                let end = i + c.len_utf8();
+                fragments.push(&text[start..end]);
+                start = end;
+            }
+            prev_char = Some(c);
+        }
+        if start < text.len() {
+            fragments.push(&text[start..]);
+        }
+        fragments
+    }
+}
+
+/// Whether `rest`, the text after a closing quote, begins a new sentence:
+/// nothing, or whitespace and then a capital or digit.
+fn starts_new_sentence(rest: &str) -> bool {
+    rest.is_empty()
+        || rest.starts_with(char::is_whitespace)
+            && rest
+                .trim_start()
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_uppercase() || c.is_numeric())
+}
+
+/// Nesting of the quotations open at some point in a text. Straight double
+/// quotes alternate between opening and closing; curly quotes nest. A `’`
+/// between letters is an apostrophe, not a closing quote.
+#[derive(Debug, Default)]
+struct QuoteState {
+    straight: bool,
+    curly_double: usize,
+    curly_single: usize,
+}
+
+impl QuoteState {
+    fn is_open(&self) -> bool {
+        self.straight || self.curly_double > 0 || self.curly_single > 0
+    }
+
+    /// Updates the state for `c`, returning whether it was a closing quote.
+    fn update(&mut self, c: char, prev: Option<char>, next: Option<char>) -> bool {
+        match c {
+            '"' => {
+                self.straight = !self.straight;
+                !self.straight
+            }
+            '\u{201C}' => {
+                self.curly_double += 1;
+                false
+            }
+            '\u{201D}' if self.curly_double > 0 => {
+                self.curly_double -= 1;
+                true
+            }
+            '\u{2018}' if prev.is_none_or(|p| !p.is_alphanumeric()) => {
+                self.curly_single += 1;
+                false
+            }
+            '\u{2019}' if self.curly_single > 0 && !next.is_some_and(char::is_alphabetic) => {
+                self.curly_single -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Sentence boundaries from the Unicode text segmentation algorithm
+/// (UAX #29), which knows about non-Latin terminators such as `。` and `।`
+/// but not about abbreviations.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnicodeBackend;
+
+impl SegmentationBackend for UnicodeBackend {
+    fn fragments<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.split_sentence_bounds().collect()
+    }
+}
+
+/// Full-width sentence terminators used in Chinese and Japanese.
+const CJK_TERMINATORS: &[char] = &['。', '！', '？', '．', '｡'];
+
+/// Closing brackets and quotes that stay with the terminator before them.
+const CJK_CLOSERS: &[char] = &[
+    '」', '』', '）', '】', '〕', '》', '〉', '"', '\u{201D}', '\u{2019}',
+];
+
+/// The built-in rules, plus breaks after the full-width terminators `。`,
+/// `！` and `？`, which need no space or capital after them. Like the rules
+/// backend, nothing inside `「…」` or `『…』` ends a sentence.
+#[derive(Debug, Clone, Default)]
+pub struct CjkBackend {
+    rules: RuleBackend,
+}
+
+impl CjkBackend {
+    /// Uses `rules` for the text outside of CJK sentence ends.
+    pub fn new(rules: RuleBackend) -> Self {
+        CjkBackend { rules }
+    }
+}
+
+impl SegmentationBackend for CjkBackend {
+    fn fragments<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        let mut fragments = Vec::new();
+        for fragment in self.rules.fragments(text) {
+            let mut start = 0;
+            let mut depth = 0usize;
+            let mut chars = fragment.char_indices().peekable();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '「' | '『' => depth += 1,
+                    '」' | '』' => depth = depth.saturating_sub(1),
+                    _ if depth == 0 && CJK_TERMINATORS.contains(&c) => {
+                        let mut end = i + c.len_utf8();
+                        // Keep "！？", "。」" and the like together
+                        while let Some(&(j, next)) = chars.peek() {
+                            if !CJK_TERMINATORS.contains(&next) && !CJK_CLOSERS.contains(&next) {
+                                break;
+                            }
+                            end = j + next.len_utf8();
+                            chars.next();
+                        }
+                        fragments.push(&fragment[start..end]);
+                        start = end;
+                    }
+                    _ => {}
+                }
+            }
+            if start < fragment.len() {
+                fragments.push(&fragment[start..]);
+            }
+        }
+        fragments
+    }
+}
+
+/// The segmentation backends that can be picked at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmenterKind {
+    Rules,
+    Unicode,
+    /// The rules plus Chinese and Japanese terminators.
+    Cjk,
+}
+
+impl SegmenterKind {
+    pub fn backend(self) -> Arc<dyn SegmentationBackend> {
+        match self {
+            SegmenterKind::Rules => Arc::new(RuleBackend::default()),
+            SegmenterKind::Unicode => Arc::new(UnicodeBackend),
+            SegmenterKind::Cjk => Arc::new(CjkBackend::default()),
+        }
+    }
+}
+
+impl FromStr for SegmenterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rules" => Ok(SegmenterKind::Rules),
+            "unicode" | "uax29" => Ok(SegmenterKind::Unicode),
+            "cjk" => Ok(SegmenterKind::Cjk),
+            other => Err(format!(
+                "invalid segmenter '{}' (expected rules, unicode or cjk)",
+                other
+            )),
+        }
+    }
+}
+
+/// Joins `fragments` into sentences, carrying fragments that fail the
+/// validity check over into the next one. A trailing fragment that is still
+/// too short is appended to the last sentence.
+fn merge_fragments(fragments: Vec<&str>, rules: &ValidityRules) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current_sentence = String::new();
+
+    for fragment in fragments {
+        current_sentence.push_str(fragment);
+        if is_complete(&current_sentence, rules) {
+            sentences.push(current_sentence.trim().to_string());
+            current_sentence.clear();
+        }
+    }
+
+    let rest = current_sentence.trim();
+    if let Some(last) = sentences.last_mut().filter(|_| !rest.is_empty()) {
+        last.push(' ');
+        last.push_str(rest);
+    }
+    sentences
+}
+
+/// Splits `text` into sentences with the built-in rules. Fragments that fail
+/// the validity check are merged into the sentence around them.
+pub fn split_into_sentences(text: &str, rules: &ValidityRules) -> Vec<String> {
+    Segmenter::new(rules.clone()).split(text)
+}
+
+/// Splits text into sentences under a fixed set of [`ValidityRules`], using
+/// the built-in rules unless another [`SegmentationBackend`] is plugged in.
+///
+/// ```
+/// use rustparser::segment::SegmenterKind;
+/// use rustparser::{Segmenter, ValidityRules};
+///
+/// let segmenter = Segmenter::default();
+/// assert_eq!(
+///     segmenter.split("The cat sat down. Then it slept all day."),
+///     vec!["The cat sat down.", "Then it slept all day."]
+/// );
+///
+/// let unicode = Segmenter::with_backend(ValidityRules::default(), SegmenterKind::Unicode.backend());
+/// assert_eq!(
+///     unicode.split("The cat sat down. Then it slept all day."),
+///     segmenter.split("The cat sat down. Then it slept all day.")
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Segmenter {
+    rules: ValidityRules,
+    backend: Arc<dyn SegmentationBackend>,
+}
+
+impl Default for Segmenter {
+    fn default() -> Self {
+        Segmenter::new(ValidityRules::default())
+    }
+}
+
+impl Segmenter {
+    pub fn new(rules: ValidityRules) -> Self {
+        Segmenter::with_backend(rules, Arc::new(RuleBackend::default()))
+    }
+
+    pub fn with_backend(rules: ValidityRules, backend: Arc<dyn SegmentationBackend>) -> Self {
+        Segmenter { rules, backend }
+    }
+
+    pub fn rules(&self) -> &ValidityRules {
+        &self.rules
+    }
+
+    pub fn backend(&self) -> &dyn SegmentationBackend {
+        self.backend.as_ref()
+    }
+
+    /// Replaces the backend, keeping the validity rules.
+    pub fn set_backend(&mut self, backend: Arc<dyn SegmentationBackend>) {
+        self.backend = backend;
+    }
+
+    pub fn split(&self, text: &str) -> Vec<String> {
+        let mut sentences = self.candidates(text);
+        sentences.retain(|sentence| self.check(sentence).is_ok());
+        sentences
+    }
+
+    /// Splits `text` with fragments merged until they meet the minimums, but
+    /// without dropping anything past the maximums yet; see
+    /// [`Segmenter::check`]. Text too short to make up a single sentence
+    /// gives nothing.
+    pub fn candidates(&self, text: &str) -> Vec<String> {
+        merge_fragments(self.backend.fragments(text), &self.rules)
+    }
+
+    /// Checks a sentence from [`Segmenter::candidates`] against the maximums.
+    pub fn check(&self, sentence: &str) -> Result<(), Rejection> {
+        check_limits(sentence, &self.rules)
+    }
+
+    pub fn is_valid_sentence(&self, sentence: &str) -> bool {
+        is_valid_sentence(sentence, &self.rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_numbers_are_rejected() {
+        let rules = ValidityRules::default();
+        assert!(is_numeric_only("42", &rules));
+        assert!(!is_valid_sentence("42", &rules));
+        assert!(!is_valid_sentence("   3.14159265358979   ", &rules));
+    }
+
+    #[test]
+    fn numbers_in_prose_are_kept() {
+        let strict = ValidityRules {
+            reject_number_units: true,
+            ..ValidityRules::default()
+        };
+        for rules in [ValidityRules::default(), strict] {
+            assert!(!is_numeric_only("42 kilograms of flour were used", &rules));
+            assert!(is_valid_sentence("42 kilograms of flour were used", &rules));
+        }
+    }
+
+    #[test]
+    fn number_with_unit_is_only_rejected_when_configured() {
+        let lenient = ValidityRules::default();
+        let strict = ValidityRules {
+            reject_number_units: true,
+            ..ValidityRules::default()
+        };
+
+        for fragment in ["42 kg", "100 %", "42kg", "2.5 KM."] {
+            assert!(!is_numeric_only(fragment, &lenient), "{}", fragment);
+            assert!(is_numeric_only(fragment, &strict), "{}", fragment);
+        }
+        assert!(!is_numeric_only("42 cats", &strict));
+    }
+
+    #[test]
+    fn validity_thresholds_are_configurable() {
+        let headline = "Storm hits";
+        assert!(!is_valid_sentence(headline, &ValidityRules::default()));
+
+        let lenient = ValidityRules {
+            min_words: 2,
+            min_chars: 5,
+            ..ValidityRules::default()
+        };
+        assert!(is_valid_sentence(headline, &lenient));
+    }
+
+    #[test]
+    fn maximums_drop_sentences_instead_of_merging() {
+        let rules = ValidityRules {
+            max_words: Some(6),
+            max_digit_ratio: Some(0.5),
+            ..ValidityRules::default()
+        };
+        let segmenter = Segmenter::new(rules);
+        let text = "This one is fine. This one goes on for far too many words. \
+                    Totals 1234 5678 9012 3456. Short again here.";
+
+        assert_eq!(segmenter.candidates(text).len(), 4);
+        assert_eq!(
+            segmenter.split(text),
+            ["This one is fine.", "Short again here."]
+        );
+        assert_eq!(
+            segmenter.check("Totals 1234 5678 9012 3456."),
+            Err(Rejection::TooManyDigits)
+        );
+        assert_eq!(
+            segmenter.check("This one goes on for far too many words."),
+            Err(Rejection::TooLong)
+        );
+    }
+
+    #[test]
+    fn backends_share_the_validity_rules() {
+        let text = "Dr. Smith arrived late. He sat. Then everyone left the room.";
+        let rules = Segmenter::default();
+        let unicode = Segmenter::with_backend(ValidityRules::default(), Arc::new(UnicodeBackend));
+
+        assert_eq!(
+            rules.split(text),
+            [
+                "Dr. Smith arrived late.",
+                "He sat. Then everyone left the room."
+            ]
+        );
+        // UAX #29 breaks after "Dr." too, but the two-word fragment is merged
+        assert_eq!(unicode.split(text), rules.split(text));
+    }
+
+    #[test]
+    fn abbreviations_and_initials_do_not_end_sentences() {
+        let rules = ValidityRules {
+            min_words: 1,
+            min_chars: 1,
+            ..ValidityRules::default()
+        };
+        let segmenter = Segmenter::new(rules.clone());
+        assert_eq!(
+            segmenter.split("Dr. Smith met J. R. Jones in the U.S. Army. They talked."),
+            [
+                "Dr. Smith met J. R. Jones in the U.S. Army.",
+                "They talked."
+            ]
+        );
+        assert_eq!(
+            segmenter.split("Bring tools, e.g. Hammers. Then go."),
+            ["Bring tools, e.g. Hammers.", "Then go."]
+        );
+
+        let german = Segmenter::with_backend(
+            rules,
+            Arc::new(RuleBackend::default().with_abbreviations(["z.B.", "Nr"])),
+        );
+        assert_eq!(
+            german.split("Siehe Nr. 5 und z.B. Kapitel drei. Danke."),
+            ["Siehe Nr. 5 und z.B. Kapitel drei.", "Danke."]
+        );
+    }
+
+    #[test]
+    fn no_trailing_space_after_final_terminator() {
+        assert_eq!(
+            Segmenter::default().split("Is this the last sentence?"),
+            ["Is this the last sentence?"]
+        );
+        assert!("uax29".parse::<SegmenterKind>() == Ok(SegmenterKind::Unicode));
+    }
+
+    #[test]
+    fn cjk_terminators_end_sentences() {
+        let segmenter =
+            Segmenter::with_backend(ValidityRules::default(), SegmenterKind::Cjk.backend());
+        assert_eq!(
+            segmenter.split(
+                "今日は雨が降っています。「行こう。」と彼は言った！本当ですか？Yes it is. We all went home."
+            ),
+            [
+                "今日は雨が降っています。",
+                "「行こう。」と彼は言った！",
+                "本当ですか？",
+                "Yes it is.",
+                "We all went home."
+            ]
+        );
+        assert_eq!(
+            segmenter.split("我们明天见。你好吗？"),
+            ["我们明天见。", "你好吗？"]
+        );
+        assert!(is_spaceless("我们明天见。"));
+        assert!(!is_spaceless("Tokyo 東京 is big"));
+    }
+
+    #[test]
+    fn handles_multibyte_text() {
+        assert_eq!(
+            split_into_sentences(
+                "Café au lait is tasty. Naïve people like it. Ça va très bien.",
+                &ValidityRules::default()
+            ),
+            [
+                "Café au lait is tasty.",
+                "Naïve people like it.",
+                "Ça va très bien."
+            ]
+        );
+        assert!(!is_sentence_boundary("é.", 2));
+        assert!(!is_sentence_boundary("", 0));
+        // Not a char boundary
+        assert!(!is_sentence_boundary("é. X", 1));
+    }
+
+    #[test]
+    fn long_lines_split_in_linear_time() {
+        let text = "Ünïcödé sentence number one is here. ".repeat(50_000);
+        let sentences = split_into_sentences(&text, &ValidityRules::default());
+        assert_eq!(sentences.len(), 50_000);
+        assert!(sentences
+            .iter()
+            .all(|s| s == "Ünïcödé sentence number one is here."));
+    }
+
+    #[test]
+    fn quotations_stay_whole() {
+        let rules = ValidityRules {
+            min_words: 1,
+            min_chars: 1,
+            ..ValidityRules::default()
+        };
+        let segmenter = Segmenter::new(rules);
+        let cases: &[(&str, &[&str])] = &[
+            (
+                r#""Stop!" she said. Then she left."#,
+                &[r#""Stop!" she said."#, "Then she left."],
+            ),
+            (r#""Stop!" She ran."#, &[r#""Stop!""#, "She ran."]),
+            (
+                r#""Wait. Please stop," he begged. Nobody did."#,
+                &[r#""Wait. Please stop," he begged."#, "Nobody did."],
+            ),
+            (
+                "He said \u{201C}Go home. Now.\u{201D} Then he left.",
+                &["He said \u{201C}Go home. Now.\u{201D}", "Then he left."],
+            ),
+            (
+                "\u{2018}Don\u{2019}t go. It\u{2019}s late,\u{2019} I said. We stayed.",
+                &[
+                    "\u{2018}Don\u{2019}t go. It\u{2019}s late,\u{2019} I said.",
+                    "We stayed.",
+                ],
+            ),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(segmenter.split(text), *expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Segmentation rules read from SRX (Segmentation Rules eXchange) files.
+//!
+//! An SRX file lists, per language, ordered rules made of a regex for the
+//! text before a possible break and one for the text after it. The first
+//! rule matching at a position decides whether it is a break. Only the lazy
+//! subset of ICU regex syntax the `regex` crate shares is supported; rules
+//! with lookaround are rejected when the file is loaded.
+
+use regex::Regex;
+
+use super::SegmentationBackend;
+use crate::BoxError;
+
+/// How far back a rule's `beforebreak` pattern may look, in bytes.
+const BEFORE_WINDOW: usize = 128;
+
+#[derive(Debug, Clone)]
+struct Rule {
+    is_break: bool,
+    /// `beforebreak`, anchored to the end of the text before the position.
+    before: Option<Regex>,
+    /// `beforebreak` on its own, to find where the position could be.
+    before_search: Option<Regex>,
+    /// `afterbreak`, anchored to the start of the text after the position.
+    after: Option<Regex>,
+}
+
+impl Rule {
+    fn matches(&self, text: &str, pos: usize) -> bool {
+        let mut start = pos.saturating_sub(BEFORE_WINDOW);
+        while !text.is_char_boundary(start) {
+            start += 1;
+        }
+        self.before
+            .as_ref()
+            .is_none_or(|before| before.is_match(&text[start..pos]))
+            && self
+                .after
+                .as_ref()
+                .is_none_or(|after| after.is_match(&text[pos..]))
+    }
+}
+
+/// A [`SegmentationBackend`] applying the rules an SRX file gives for one
+/// language.
+#[derive(Debug, Clone)]
+pub struct SrxBackend {
+    rules: Vec<Rule>,
+}
+
+impl SrxBackend {
+    /// Reads the rules for `language`, matched against each
+    /// `languagemap`'s `languagepattern` (such as `DE.*`), ignoring case. With
+    /// `cascade="yes"` the rules of every matching map apply, in order;
+    /// otherwise only the first map's do.
+    pub fn from_file(path: &str, language: &str) -> Result<Self, BoxError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading SRX file {}: {}", path, e))?;
+        Self::parse(&contents, language)
+            .map_err(|e| format!("Error loading SRX file {}: {}", path, e).into())
+    }
+
+    pub fn parse(srx: &str, language: &str) -> Result<Self, BoxError> {
+        let document = roxmltree::Document::parse(srx)?;
+        let cascade = document
+            .descendants()
+            .find(|n| n.has_tag_name_local("header"))
+            .and_then(|header| header.attribute("cascade"))
+            .is_some_and(|cascade| cascade.eq_ignore_ascii_case("yes"));
+
+        let mut rule_names = Vec::new();
+        for map in document
+            .descendants()
+            .filter(|n| n.has_tag_name_local("languagemap"))
+        {
+            let pattern = map.attribute("languagepattern").unwrap_or_default();
+            let pattern = Regex::new(&format!("(?i)^(?:{})$", pattern))?;
+            if pattern.is_match(language) {
+                rule_names.extend(map.attribute("languagerulename"));
+                if !cascade {
+                    break;
+                }
+            }
+        }
+        if rule_names.is_empty() {
+            return Err(format!("no language rules for '{}'", language).into());
+        }
+
+        let mut rules = Vec::new();
+        for name in rule_names {
+            let language_rule = document
+                .descendants()
+                .find(|n| {
+                    n.has_tag_name_local("languagerule")
+                        && n.attribute("languagerulename") == Some(name)
+                })
+                .ok_or_else(|| format!("missing languagerule '{}'", name))?;
+            for rule in language_rule
+                .children()
+                .filter(|n| n.has_tag_name_local("rule"))
+            {
+                rules.push(parse_rule(rule)?);
+            }
+        }
+        Ok(SrxBackend { rules })
+    }
+
+    fn is_break(&self, text: &str, pos: usize) -> bool {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(text, pos))
+            .is_some_and(|rule| rule.is_break)
+    }
+}
+
+fn parse_rule(rule: roxmltree::Node) -> Result<Rule, BoxError> {
+    let pattern = |name: &str| {
+        rule.children()
+            .find(|n| n.has_tag_name_local(name))
+            .and_then(|n| n.text())
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
+    };
+    let compile = |pattern: String| {
+        Regex::new(&pattern).map_err(|e| format!("invalid rule pattern '{}': {}", pattern, e))
+    };
+    let before = pattern("beforebreak");
+    let after = pattern("afterbreak");
+    Ok(Rule {
+        is_break: rule.attribute("break") != Some("no"),
+        before: before
+            .clone()
+            .map(|p| compile(format!("(?:{})$", p)))
+            .transpose()?,
+        before_search: before.map(compile).transpose()?,
+        after: after.map(|p| compile(format!("^(?:{})", p))).transpose()?,
+    })
+}
+
+trait LocalName {
+    fn has_tag_name_local(&self, name: &str) -> bool;
+}
+
+impl LocalName for roxmltree::Node<'_, '_> {
+    fn has_tag_name_local(&self, name: &str) -> bool {
+        self.is_element() && self.tag_name().name() == name
+    }
+}
+
+impl SegmentationBackend for SrxBackend {
+    fn fragments<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        // Breaks can only fall where some break rule's before pattern ends
+        let mut positions: Vec<usize> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.is_break)
+            .filter_map(|rule| rule.before_search.as_ref())
+            .flat_map(|before| before.find_iter(text).map(|m| m.end()))
+            .filter(|&pos| pos > 0 && pos < text.len())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let mut fragments = Vec::new();
+        let mut start = 0;
+        for pos in positions {
+            if self.is_break(text, pos) {
+                fragments.push(&text[start..pos]);
+                start = pos;
+            }
+        }
+        fragments.push(&text[start..]);
+        fragments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<srx xmlns="http://www.lisa.org/srx20" version="2.0">
+  <header segmentsubflows="yes" cascade="yes"/>
+  <body>
+    <languagerules>
+      <languagerule languagerulename="German">
+        <rule break="no"><beforebreak>\b(?:z\.B|usw|Nr)\.</beforebreak><afterbreak>\s</afterbreak></rule>
+        <rule break="no"><beforebreak>\d\.</beforebreak><afterbreak>\s</afterbreak></rule>
+      </languagerule>
+      <languagerule languagerulename="Default">
+        <rule break="yes"><beforebreak>[.?!]+</beforebreak><afterbreak>\s</afterbreak></rule>
+      </languagerule>
+    </languagerules>
+    <maprules>
+      <languagemap languagepattern="DE.*" languagerulename="German"/>
+      <languagemap languagepattern=".*" languagerulename="Default"/>
+    </maprules>
+  </body>
+</srx>"#;
+
+    #[test]
+    fn applies_the_first_matching_rule() {
+        let german = SrxBackend::parse(SRX, "de-AT").unwrap();
+        let text = "Wir fahren z.B. am 3. Mai los. Kommst du mit?";
+        assert_eq!(
+            german.fragments(text),
+            ["Wir fahren z.B. am 3. Mai los.", " Kommst du mit?"]
+        );
+
+        // Only the default rules apply to English
+        let english = SrxBackend::parse(SRX, "en").unwrap();
+        assert_eq!(english.fragments(text).len(), 4);
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        let lookahead = SRX.replace(r"[.?!]+", r"\.(?=\s)");
+        assert!(SrxBackend::parse(&lookahead, "EN").is_err());
+        assert!(SrxBackend::parse(SRX, "").is_ok());
+    }
+}
@@ -0,0 +1,273 @@
+//! A small HTTP/1.1 server for the JSON API `serve` exposes and the
+//! `/metrics` endpoint of long runs.
+//!
+//! Each connection carries one request, read whole, and is closed after the
+//! answer, which is all a JSON API called by other services needs.
+//!
+//! It's written on tokio with `httparse` rather than on axum and hyper: the
+//! API is a handful of routes matched on the path, with no keep-alive,
+//! streaming or TLS. tokio is in the build for the rest of ingestion and
+//! `httparse` has no dependencies, where axum would bring hyper, tower and
+//! theirs for the little the server does. Clients get a bounded head and
+//! body and a deadline for sending them, so a slow or stuck client only
+//! holds its own task, and not for long.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head read before giving up on a client.
+const MAX_HEAD: usize = 64 * 1024;
+/// Largest request body accepted.
+const MAX_BODY: usize = 64 * 1024 * 1024;
+/// How long a client has to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request, with its path and decoded query parameters apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// An answer with its status code and the type of its body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(body: Value) -> Self {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    /// A plain text answer, such as metrics for Prometheus.
+    pub fn text(body: String) -> Self {
+        Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body,
+        }
+    }
+
+    /// An answer of `status` carrying `message` as its `error`.
+    pub fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Response {
+            status,
+            ..Response::json(json!({ "error": message.to_string() }))
+        }
+    }
+}
+
+/// Answers the requests on `listener` with `handler`, each connection on a
+/// task of its own, until the listener fails.
+pub async fn serve<H, F>(listener: TcpListener, handler: H) -> std::io::Result<()>
+where
+    H: Fn(Request) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = Response> + Send,
+{
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, handler).await {
+                log::error!("Error answering a request: {}", e);
+            }
+        });
+    }
+}
+
+async fn answer<H, F>(mut stream: TcpStream, handler: H) -> std::io::Result<()>
+where
+    H: Fn(Request) -> F,
+    F: Future<Output = Response>,
+{
+    let response = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(request) => match request? {
+            Ok(request) => handler(request).await,
+            Err(response) => response,
+        },
+        Err(_) => Response::error(408, "request not received in time"),
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Reads a request off `stream`, or the answer to send a client whose
+/// request can't be read.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Result<Request, Response>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 8192];
+    loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(Err(Response::error(400, "incomplete request")));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut parsed = httparse::Request::new(&mut headers);
+        let head_length = match parsed.parse(&buffer) {
+            Ok(httparse::Status::Complete(length)) => length,
+            Ok(httparse::Status::Partial) if buffer.len() < MAX_HEAD => continue,
+            Ok(httparse::Status::Partial) => {
+                return Ok(Err(Response::error(431, "request head too large")))
+            }
+            Err(e) => return Ok(Err(Response::error(400, e))),
+        };
+        let content_length = parsed
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("content-length"))
+            .map(|header| {
+                String::from_utf8_lossy(header.value)
+                    .trim()
+                    .parse::<usize>()
+            });
+        let content_length = match content_length {
+            None => 0,
+            Some(Ok(length)) if length <= MAX_BODY => length,
+            Some(Ok(_)) => return Ok(Err(Response::error(413, "request body too large"))),
+            Some(Err(_)) => return Ok(Err(Response::error(400, "bad Content-Length"))),
+        };
+        let target = parsed.path.unwrap_or("/");
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Request {
+            method: parsed.method.unwrap_or("GET").to_string(),
+            path: percent_decode(path),
+            query: parse_query(query),
+            body: buffer.split_off(head_length),
+        };
+
+        while request.body.len() < content_length {
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(Err(Response::error(400, "incomplete request body")));
+            }
+            request.body.extend_from_slice(&chunk[..read]);
+        }
+        request.body.truncate(content_length);
+        return Ok(Ok(request));
+    }
+}
+
+/// The parameters of a query string, decoded, the last winning when one is
+/// repeated.
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                percent_decode(&name.replace('+', " ")),
+                percent_decode(&value.replace('+', " ")),
+            )
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_query_strings() {
+        let query = parse_query("q=%22it+rained%22&limit=5&flag");
+        assert_eq!(query["q"], "\"it rained\"");
+        assert_eq!(query["limit"], "5");
+        assert_eq!(query["flag"], "");
+        assert_eq!(percent_decode("caf%C3%A9%2"), "café%2");
+    }
+
+    #[tokio::test]
+    async fn answers_requests_with_the_handler() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, |request: Request| async move {
+            match request.path.as_str() {
+                "/echo" => Response::json(json!({
+                    "method": request.method,
+                    "name": request.query.get("name"),
+                    "body": String::from_utf8_lossy(&request.body),
+                })),
+                _ => Response::error(404, "no such endpoint"),
+            }
+        }));
+
+        let (echo, missing) = tokio::task::spawn_blocking(move || {
+            let echo = ureq::post(format!("{}/echo?name=a%20b", url))
+                .send("It rained.")
+                .unwrap()
+                .into_body()
+                .read_to_string()
+                .unwrap();
+            let missing = ureq::get(format!("{}/nowhere", url)).call().unwrap_err();
+            (echo, missing)
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            echo,
+            r#"{"method":"POST","name":"a b","body":"It rained."}"#
+        );
+        assert!(matches!(missing, ureq::Error::StatusCode(404)));
+    }
+}
@@ -0,0 +1,70 @@
+//! Stopping a run cleanly on Ctrl-C or SIGTERM: reading stops, the
+//! sentences batched so far are written and the checkpoint is saved, so an
+//! interrupted run loses nothing and resumes where it stopped.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Whether the run has been asked to stop. Clones share the request.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown {
+            requested: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl Shutdown {
+    /// A shutdown requested by the first Ctrl-C or SIGTERM the process gets.
+    /// A second one quits at once.
+    pub fn on_signals() -> Self {
+        let shutdown = Shutdown::default();
+        let requested = shutdown.clone();
+        tokio::spawn(async move {
+            next_signal().await;
+            log::warn!(
+                "Stopping after writing the sentences read so far; interrupt again to quit now"
+            );
+            requested.request();
+            next_signal().await;
+            std::process::exit(130);
+        });
+        shutdown
+    }
+
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Waits until the shutdown is requested.
+    pub async fn requested(&self) {
+        let mut receiver = self.requested.subscribe();
+        let _ = receiver.wait_for(|requested| *requested).await;
+    }
+}
+
+/// Waits for Ctrl-C or, on Unix, SIGTERM.
+async fn next_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
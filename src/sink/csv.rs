@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::io::Write;
+
+use super::SentenceSink;
+use crate::decompress::create_output;
+use crate::BoxError;
+
+/// Writes chosen fields of each document as a row of delimiter-separated
+/// values, quoted where needed, under a header row naming them. Arrays are
+/// written as their items separated by spaces, and embedded documents as
+/// JSON.
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+    columns: Vec<String>,
+    header_written: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Writes `columns` of each document, separated by `delimiter`.
+    pub fn new(writer: W, columns: Vec<String>, delimiter: u8) -> Self {
+        CsvSink {
+            writer: csv::WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(writer),
+            columns,
+            header_written: false,
+        }
+    }
+
+    /// Leaves out the header row, for adding rows to a file that has it.
+    pub fn without_header(mut self) -> Self {
+        self.header_written = true;
+        self
+    }
+
+    pub fn into_inner(self) -> Result<W, BoxError> {
+        self.writer
+            .into_inner()
+            .map_err(|e| e.error().to_string().into())
+    }
+}
+
+impl CsvSink<Box<dyn Write + Send>> {
+    /// Creates the file at `path`, compressed if its extension says so.
+    pub fn create(path: &str, columns: Vec<String>, delimiter: u8) -> std::io::Result<Self> {
+        Ok(CsvSink::new(create_output(path, None)?, columns, delimiter))
+    }
+}
+
+/// `value` as the text of one cell.
+pub(super) fn cell(value: Option<&Bson>) -> String {
+    match value {
+        None | Some(Bson::Null) => String::new(),
+        Some(Bson::String(s)) => s.clone(),
+        Some(Bson::Array(items)) => items
+            .iter()
+            .map(|item| cell(Some(item)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Some(Bson::DateTime(date)) => date
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| date.to_string()),
+        Some(Bson::ObjectId(id)) => id.to_hex(),
+        Some(document @ Bson::Document(_)) => document.clone().into_relaxed_extjson().to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[async_trait]
+impl<W: Write + Send> SentenceSink for CsvSink<W> {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        if !self.header_written {
+            self.writer.write_record(&self.columns)?;
+            self.header_written = true;
+        }
+        for document in &batch {
+            let row = self.columns.iter().map(|column| cell(document.get(column)));
+            self.writer.write_record(row)?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        if !self.header_written {
+            self.writer.write_record(&self.columns)?;
+            self.header_written = true;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[tokio::test]
+    async fn writes_quoted_rows_under_a_header() {
+        let columns = vec![
+            "text".to_string(),
+            "lineNumber".to_string(),
+            "pos".to_string(),
+        ];
+        let mut sink = CsvSink::new(Vec::new(), columns, b',');
+        sink.write_batch(vec![
+            doc! { "text": "Well, \"yes\".", "lineNumber": 3, "pos": ["INTJ", "INTJ"] },
+            doc! { "text": "No." },
+        ])
+        .await
+        .unwrap();
+        sink.flush().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.into_inner().unwrap()).unwrap(),
+            "text,lineNumber,pos\n\"Well, \"\"yes\"\".\",3,INTJ INTJ\nNo.,,\n"
+        );
+    }
+}
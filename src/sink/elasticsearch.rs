@@ -0,0 +1,279 @@
+use async_trait::async_trait;
+use mongodb::bson::{oid::ObjectId, Bson, Document};
+use serde_json::{json, Value};
+
+use super::{http_is_transient, RetryPolicy, SentenceSink};
+use crate::logging::Span;
+use crate::BoxError;
+
+/// Writes sentences into an Elasticsearch or OpenSearch index through the
+/// bulk API. Unless an index template is installed instead, a missing index
+/// is created with a mapping that analyzes `text` and keeps the other
+/// strings as keywords, for filtering and aggregations.
+#[derive(Debug, Clone)]
+pub struct ElasticsearchSink {
+    url: String,
+    index: String,
+    api_key: Option<String>,
+    retry: RetryPolicy,
+}
+
+impl ElasticsearchSink {
+    /// Connects to the cluster at `url` and readies `index`: installs
+    /// `template`, an index template body, under the index's name if given,
+    /// and otherwise creates the index if it doesn't exist.
+    pub fn connect(
+        url: &str,
+        index: &str,
+        api_key: Option<&str>,
+        template: Option<&str>,
+    ) -> Result<Self, BoxError> {
+        let sink = ElasticsearchSink {
+            url: url.trim_end_matches('/').to_string(),
+            index: index.to_string(),
+            api_key: api_key.map(str::to_string),
+            retry: RetryPolicy::default(),
+        };
+        match template {
+            Some(template) => {
+                let template: Value = serde_json::from_str(template)
+                    .map_err(|e| format!("Error reading the index template: {}", e))?;
+                sink.request(
+                    "PUT",
+                    &format!("_index_template/{}", index),
+                    &template.to_string(),
+                )?;
+            }
+            None if !sink.index_exists()? => {
+                sink.request("PUT", index, &default_mapping().to_string())?;
+            }
+            None => {}
+        }
+        Ok(sink)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn index_exists(&self) -> Result<bool, BoxError> {
+        let mut request = ureq::head(format!("{}/{}", self.url, self.index));
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", format!("ApiKey {}", key));
+        }
+        match request.call() {
+            Ok(_) => Ok(true),
+            Err(ureq::Error::StatusCode(404)) => Ok(false),
+            Err(e) => Err(self.error(e)),
+        }
+    }
+
+    /// Sends `body` with `method` to `path` under the cluster's URL and
+    /// returns the answer.
+    fn request(&self, method: &str, path: &str, body: &str) -> Result<Value, BoxError> {
+        self.send(method, path, "application/json", body)
+            .map_err(|e| self.error(e))
+    }
+
+    fn send(
+        &self,
+        method: &str,
+        path: &str,
+        content_type: &str,
+        body: &str,
+    ) -> Result<Value, ureq::Error> {
+        let url = format!("{}/{}", self.url, path);
+        let mut request = match method {
+            "PUT" => ureq::put(url),
+            _ => ureq::post(url),
+        };
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", format!("ApiKey {}", key));
+        }
+        let answer = request
+            .content_type(content_type)
+            .send(body)?
+            .into_body()
+            .read_to_string()?;
+        Ok(serde_json::from_str(&answer).unwrap_or(Value::Null))
+    }
+
+    fn error(&self, e: ureq::Error) -> BoxError {
+        format!("Error writing to Elasticsearch at {}: {}", self.url, e).into()
+    }
+}
+
+/// The index created when there's no template: `text` analyzed for full-text
+/// search, counts and dates typed, and other strings kept whole.
+fn default_mapping() -> Value {
+    json!({
+        "mappings": {
+            "dynamic_templates": [
+                { "strings": { "match_mapping_type": "string", "mapping": { "type": "keyword" } } }
+            ],
+            "properties": {
+                "text": { "type": "text" },
+                "rawText": { "type": "text" },
+                "lineNumber": { "type": "integer" },
+                "wordCount": { "type": "integer" },
+                "tokenCount": { "type": "integer" },
+                "ingestedAt": { "type": "date" }
+            }
+        }
+    })
+}
+
+/// `value` as plain JSON, with dates as RFC 3339 strings and ids as hex,
+/// which Elasticsearch maps without help.
+pub(super) fn to_json(value: Bson) -> Value {
+    match value {
+        Bson::DateTime(date) => match date.try_to_rfc3339_string() {
+            Ok(date) => Value::String(date),
+            Err(_) => Value::from(date.timestamp_millis()),
+        },
+        Bson::ObjectId(id) => Value::String(id.to_hex()),
+        Bson::Document(document) => Value::Object(
+            document
+                .into_iter()
+                .map(|(key, value)| (key, to_json(value)))
+                .collect(),
+        ),
+        Bson::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        other => other.into_relaxed_extjson(),
+    }
+}
+
+/// The id to index `document` under: the one it was given, or else its
+/// `docId` and `sentenceIndex`, so indexing it again, as a retried bulk
+/// request does after a timeout, replaces it instead of adding a copy.
+fn document_id(document: &mut Document) -> String {
+    if let Some(id) = document.remove("_id") {
+        return match to_json(id) {
+            Value::String(id) => id,
+            other => other.to_string(),
+        };
+    }
+    match (document.get_str("docId"), document.get("sentenceIndex")) {
+        (Ok(doc_id), Some(index)) => format!("{}-{}", doc_id, index),
+        _ => ObjectId::new().to_hex(),
+    }
+}
+
+/// `batch` as the body of a bulk request indexing each document in
+/// `index`.
+fn bulk_body(index: &str, batch: Vec<Document>) -> String {
+    let mut body = String::new();
+    for mut document in batch {
+        let id = document_id(&mut document);
+        let action = json!({ "index": { "_index": index, "_id": id } }).to_string();
+        body.push_str(&action);
+        body.push('\n');
+        body.push_str(&to_json(Bson::Document(document)).to_string());
+        body.push('\n');
+    }
+    body
+}
+
+#[async_trait]
+impl SentenceSink for ElasticsearchSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let what = format!("index batch of {} sentences", batch.len());
+        let body = bulk_body(&self.index, batch);
+        let sink = self.clone();
+        let span = Span::current();
+        // ureq blocks, through its retries too, so off the runtime's workers
+        let answer = tokio::task::spawn_blocking(move || {
+            span.enter(|| {
+                sink.retry.run_blocking(&what, http_is_transient, |_| {
+                    sink.send("POST", "_bulk", "application/x-ndjson", &body)
+                })
+            })
+        })
+        .await?
+        .map_err(|e| self.error(e))?;
+
+        if answer["errors"].as_bool() == Some(true) {
+            let items = answer["items"].as_array().cloned().unwrap_or_default();
+            let failed: Vec<_> = items
+                .iter()
+                .filter_map(|item| item["index"]["error"].as_object())
+                .collect();
+            let reason = failed
+                .first()
+                .and_then(|error| error.get("reason"))
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(format!(
+                "Elasticsearch rejected {} of {} sentences: {}",
+                failed.len(),
+                items.len(),
+                reason
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let query = json!({ "query": { "term": { "fileName": file_name } } });
+        let path = format!("{}/_delete_by_query?refresh=true", self.index);
+        let sink = self.clone();
+        let answer =
+            tokio::task::spawn_blocking(move || sink.request("POST", &path, &query.to_string()))
+                .await??;
+        Ok(answer["deleted"].as_u64().unwrap_or(0))
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::test_server::serve;
+    use mongodb::bson::{doc, DateTime};
+
+    #[tokio::test]
+    async fn creates_the_index_and_bulk_indexes_sentences() {
+        let (url, server) = serve(vec![
+            "",
+            r#"{"acknowledged":true}"#,
+            r#"{"errors":false,"items":[{"index":{"status":201}}]}"#,
+            r#"{"errors":true,"items":[{"index":{"status":400,"error":{"reason":"bad field"}}}]}"#,
+        ]);
+        let mut sink = ElasticsearchSink::connect(&url, "corpus", None, None).unwrap();
+        let date = DateTime::from_millis(0);
+        sink.write_batch(vec![doc! {
+            "docId": "01J0",
+            "sentenceIndex": 4,
+            "text": "It rained.",
+            "lineNumber": 3,
+            "ingestedAt": date,
+        }])
+        .await
+        .unwrap();
+        let error = sink
+            .write_batch(vec![doc! { "text": "Odd." }])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Elasticsearch rejected 1 of 1 sentences: bad field"
+        );
+
+        let received = server.join().unwrap();
+        assert_eq!(received[0], "HEAD /corpus HTTP/1.1\n");
+        assert!(received[1].starts_with("PUT /corpus HTTP/1.1\n{\"mappings\""));
+        assert_eq!(
+            received[2],
+            "POST /_bulk HTTP/1.1\n{\"index\":{\"_index\":\"corpus\",\"_id\":\"01J0-4\"}}\n\
+             {\"docId\":\"01J0\",\"sentenceIndex\":4,\"text\":\"It rained.\",\"lineNumber\":3,\"ingestedAt\":\"1970-01-01T00:00:00Z\"}\n"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::io::Write;
+
+use super::SentenceSink;
+use crate::decompress::create_output;
+use crate::BoxError;
+
+/// Writes each document as a JSON object on its own line, in MongoDB's
+/// relaxed extended JSON so dates and ids stay readable.
+pub struct JsonlSink<W: Write> {
+    writer: W,
+    /// Fields written, in order; every field but `_id` when unset.
+    fields: Option<Vec<String>>,
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlSink {
+            writer,
+            fields: None,
+        }
+    }
+
+    /// Writes only `fields`, in that order, leaving out the ones a document
+    /// doesn't have.
+    pub fn with_fields(mut self, fields: Vec<String>) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn select(&self, mut document: Document) -> Document {
+        match &self.fields {
+            Some(fields) => fields
+                .iter()
+                .filter_map(|field| Some((field.clone(), document.remove(field)?)))
+                .collect(),
+            None => {
+                document.remove("_id");
+                document
+            }
+        }
+    }
+}
+
+impl JsonlSink<Box<dyn Write + Send>> {
+    /// Creates the file at `path`, compressed if its extension says so.
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        Ok(JsonlSink::new(create_output(path, None)?))
+    }
+}
+
+#[async_trait]
+impl<W: Write + Send> SentenceSink for JsonlSink<W> {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        for document in batch {
+            let json = Bson::Document(self.select(document)).into_relaxed_extjson();
+            writeln!(self.writer, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{doc, oid::ObjectId};
+
+    #[tokio::test]
+    async fn writes_one_object_per_line() {
+        let mut sink = JsonlSink::new(Vec::new());
+        sink.write_batch(vec![
+            doc! { "_id": ObjectId::new(), "text": "It rained.", "lineNumber": 3 },
+            doc! { "text": "Then \"it\" stopped.", "tokens": ["Then", "it", "stopped"] },
+        ])
+        .await
+        .unwrap();
+        let mut selected =
+            JsonlSink::new(Vec::new()).with_fields(vec!["lineNumber".into(), "text".into()]);
+        selected
+            .write_batch(vec![
+                doc! { "text": "It rained.", "fileName": "a.txt", "lineNumber": 3 },
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "{\"text\":\"It rained.\",\"lineNumber\":3}\n\
+             {\"text\":\"Then \\\"it\\\" stopped.\",\"tokens\":[\"Then\",\"it\",\"stopped\"]}\n"
+        );
+        assert_eq!(
+            String::from_utf8(selected.into_inner()).unwrap(),
+            "{\"lineNumber\":3,\"text\":\"It rained.\"}\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use serde_json::{json, Value};
+
+use super::elasticsearch::to_json;
+use super::{http_is_transient, RetryPolicy, SentenceSink};
+use crate::logging::Span;
+use crate::BoxError;
+
+/// Publishes each sentence document to a Kafka topic through a Kafka REST
+/// proxy, a batch per request, keyed by its `docId` so a document's
+/// sentences land in one partition, in order.
+#[derive(Debug, Clone)]
+pub struct KafkaSink {
+    url: String,
+    topic: String,
+    retry: RetryPolicy,
+}
+
+impl KafkaSink {
+    /// Publishes to `topic` through the REST proxy at `url`.
+    pub fn new(url: &str, topic: &str) -> Self {
+        KafkaSink {
+            url: url.trim_end_matches('/').to_string(),
+            topic: topic.to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn publish(&self, body: &str) -> Result<Value, ureq::Error> {
+        let answer = ureq::post(format!("{}/topics/{}", self.url, self.topic))
+            .content_type("application/vnd.kafka.json.v2+json")
+            .header("Accept", "application/vnd.kafka.v2+json")
+            .send(body)?
+            .into_body()
+            .read_to_string()?;
+        Ok(serde_json::from_str(&answer).unwrap_or(Value::Null))
+    }
+}
+
+/// `batch` as the records of a produce request.
+fn records(batch: Vec<Document>) -> Value {
+    let records: Vec<_> = batch
+        .into_iter()
+        .map(|mut document| {
+            document.remove("_id");
+            let key = document.get_str("docId").ok().map(str::to_string);
+            json!({ "key": key, "value": to_json(Bson::Document(document)) })
+        })
+        .collect();
+    json!({ "records": records })
+}
+
+#[async_trait]
+impl SentenceSink for KafkaSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let what = format!("publish batch of {} sentences", batch.len());
+        let body = records(batch).to_string();
+        let sink = self.clone();
+        let span = Span::current();
+        // ureq blocks, through its retries too, so off the runtime's workers
+        let answer = tokio::task::spawn_blocking(move || {
+            span.enter(|| {
+                sink.retry
+                    .run_blocking(&what, http_is_transient, |_| sink.publish(&body))
+            })
+        })
+        .await?
+        .map_err(|e| format!("Error publishing to Kafka at {}: {}", self.url, e))?;
+
+        let offsets = answer["offsets"].as_array().cloned().unwrap_or_default();
+        let failed: Vec<_> = offsets
+            .iter()
+            .filter_map(|offset| offset["error"].as_str())
+            .collect();
+        if let Some(error) = failed.first() {
+            return Err(format!(
+                "Kafka rejected {} of {} sentences: {}",
+                failed.len(),
+                offsets.len(),
+                error
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::test_server::serve;
+    use mongodb::bson::doc;
+
+    #[tokio::test]
+    async fn publishes_records_keyed_by_document() {
+        let (url, server) = serve(vec![
+            r#"{"offsets":[{"partition":0,"offset":7,"error":null}]}"#,
+            r#"{"offsets":[{"partition":0,"offset":null,"error":"too large"}]}"#,
+        ]);
+        let mut sink = KafkaSink::new(&url, "sentences");
+        sink.write_batch(vec![doc! { "text": "It rained.", "docId": "01J" }])
+            .await
+            .unwrap();
+        let error = sink
+            .write_batch(vec![doc! { "text": "Then it stopped." }])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Kafka rejected 1 of 1 sentences: too large"
+        );
+
+        let received = server.join().unwrap();
+        assert_eq!(
+            received[0],
+            "POST /topics/sentences HTTP/1.1\n\
+             {\"records\":[{\"key\":\"01J\",\"value\":{\"text\":\"It rained.\",\"docId\":\"01J\"}}]}"
+        );
+        assert!(received[1]
+            .ends_with("{\"records\":[{\"key\":null,\"value\":{\"text\":\"Then it stopped.\"}}]}"));
+    }
+}
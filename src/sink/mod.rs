@@ -0,0 +1,204 @@
+//! Destinations for sentence documents.
+//!
+//! The ingestion loop only talks to [`SentenceSink`]; adding a backend means
+//! adding an implementation here and a [`SinkKind`] to select it.
+//! [`WriterSink`] moves writing any of them to a task of its own.
+
+mod csv;
+mod elasticsearch;
+mod jsonl;
+mod kafka;
+mod mongo;
+mod null;
+mod parquet;
+mod partition;
+mod postgres;
+mod retry;
+mod route;
+mod sqlite;
+mod tally;
+mod tee;
+#[cfg(test)]
+pub(crate) mod test_server;
+mod text;
+mod writer;
+
+use async_trait::async_trait;
+use mongodb::bson::Document;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::BoxError;
+
+pub use self::csv::CsvSink;
+pub use elasticsearch::ElasticsearchSink;
+pub use jsonl::JsonlSink;
+pub use kafka::KafkaSink;
+pub use mongo::{MongoSink, TextIndex, WriteReport};
+pub use null::NullSink;
+pub use parquet::ParquetSink;
+pub use partition::{Partition, PartitionedSink};
+pub use postgres::PostgresSink;
+pub(crate) use retry::http_is_transient;
+pub use retry::RetryPolicy;
+pub use route::{Route, RoutedSink};
+pub use sqlite::SqliteSink;
+pub use tally::{Tally, TallySink};
+pub use tee::{TeePolicy, TeeSink};
+pub use text::{Numbering, TextSink};
+pub use writer::WriterSink;
+
+/// A destination for batches of sentence documents.
+#[async_trait]
+pub trait SentenceSink: Send {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError>;
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        Ok(())
+    }
+
+    /// Deletes the sentences stored earlier from `file_name`, so it can be
+    /// ingested again without leaving stale ones behind. Returns how many
+    /// were deleted. Sinks that only ever append can't.
+    async fn remove_source(&mut self, _file_name: &str) -> Result<u64, BoxError> {
+        Err("this sink can't remove stored sentences".into())
+    }
+
+    /// Returns an independent handle to the same destination, for sinks that
+    /// can be written from several workers at once.
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        None
+    }
+}
+
+/// A sink shared between workers, which take turns writing whole batches.
+#[derive(Clone)]
+pub struct SharedSink {
+    inner: Arc<Mutex<Box<dyn SentenceSink>>>,
+}
+
+impl SharedSink {
+    pub fn new(sink: Box<dyn SentenceSink>) -> Self {
+        SharedSink {
+            inner: Arc::new(Mutex::new(sink)),
+        }
+    }
+}
+
+#[async_trait]
+impl SentenceSink for SharedSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        self.inner.lock().await.write_batch(batch).await
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        self.inner.lock().await.flush().await
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        self.inner.lock().await.remove_source(file_name).await
+    }
+}
+
+/// Gives each of `workers` its own handle to `sink`: independent clones when
+/// the sink supports them, otherwise turns on a [`SharedSink`].
+pub fn fan_out(sink: Box<dyn SentenceSink>, workers: usize) -> Vec<Box<dyn SentenceSink>> {
+    let workers = workers.max(1);
+    if sink.try_clone().is_some() {
+        let mut sinks: Vec<_> = (1..workers).filter_map(|_| sink.try_clone()).collect();
+        sinks.push(sink);
+        return sinks;
+    }
+
+    let shared = SharedSink::new(sink);
+    (0..workers)
+        .map(|_| Box::new(shared.clone()) as Box<dyn SentenceSink>)
+        .collect()
+}
+
+/// The sink implementations that can be picked at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    Mongo,
+    Sqlite,
+    /// Plain text file, one sentence per line.
+    File,
+    /// Standard output, one sentence per line.
+    Stdout,
+    /// JSON Lines file, one sentence document per line.
+    Jsonl,
+    /// Comma-separated values, a sentence per row.
+    Csv,
+    /// Tab-separated values, a sentence per row.
+    Tsv,
+    /// Apache Parquet file, a sentence per row.
+    Parquet,
+    Postgres,
+    /// Elasticsearch or OpenSearch index.
+    Elasticsearch,
+    /// Kafka topic, through a Kafka REST proxy.
+    Kafka,
+    /// Nowhere: the sentences are thrown away.
+    Null,
+}
+
+impl SinkKind {
+    /// The name `--sink` takes it by.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SinkKind::Mongo => "mongodb",
+            SinkKind::Sqlite => "sqlite",
+            SinkKind::File => "file",
+            SinkKind::Stdout => "stdout",
+            SinkKind::Jsonl => "jsonl",
+            SinkKind::Csv => "csv",
+            SinkKind::Tsv => "tsv",
+            SinkKind::Parquet => "parquet",
+            SinkKind::Postgres => "postgres",
+            SinkKind::Elasticsearch => "elasticsearch",
+            SinkKind::Kafka => "kafka",
+            SinkKind::Null => "null",
+        }
+    }
+}
+
+impl FromStr for SinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mongo" | "mongodb" => Ok(SinkKind::Mongo),
+            "sqlite" => Ok(SinkKind::Sqlite),
+            "file" => Ok(SinkKind::File),
+            "stdout" => Ok(SinkKind::Stdout),
+            "jsonl" | "ndjson" => Ok(SinkKind::Jsonl),
+            "csv" => Ok(SinkKind::Csv),
+            "tsv" => Ok(SinkKind::Tsv),
+            "parquet" => Ok(SinkKind::Parquet),
+            "postgres" | "postgresql" => Ok(SinkKind::Postgres),
+            "elasticsearch" | "opensearch" => Ok(SinkKind::Elasticsearch),
+            "kafka" => Ok(SinkKind::Kafka),
+            "null" => Ok(SinkKind::Null),
+            other => Err(format!(
+                "invalid sink '{}' (expected mongodb, sqlite, postgres, elasticsearch, kafka, file, stdout, jsonl, csv, tsv, parquet or null)",
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sink_kind() {
+        assert_eq!("MongoDB".parse(), Ok(SinkKind::Mongo));
+        assert_eq!("stdout".parse(), Ok(SinkKind::Stdout));
+        assert_eq!("JSONL".parse(), Ok(SinkKind::Jsonl));
+        assert_eq!("null".parse(), Ok(SinkKind::Null));
+        assert_eq!("Parquet".parse(), Ok(SinkKind::Parquet));
+        assert!("redis".parse::<SinkKind>().is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use async_trait::async_trait;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, Document};
+use mongodb::error::{Error, ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+use mongodb::options::{IndexOptions, InsertManyOptions};
+use mongodb::{Collection, IndexModel};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use super::{RetryPolicy, SentenceSink};
+use crate::schema::Schema;
+use crate::BoxError;
+
+/// Which field of each sentence the text index covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextIndex {
+    /// The cleaned `text`.
+    #[default]
+    Text,
+    /// The original `rawText`, with its punctuation and accents.
+    RawText,
+}
+
+impl TextIndex {
+    pub fn field(self) -> &'static str {
+        match self {
+            TextIndex::Text => "text",
+            TextIndex::RawText => "rawText",
+        }
+    }
+}
+
+impl FromStr for TextIndex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(TextIndex::Text),
+            "raw" | "rawtext" => Ok(TextIndex::RawText),
+            other => Err(format!(
+                "invalid text index '{}' (expected text or raw)",
+                other
+            )),
+        }
+    }
+}
+
+/// The documents of a run that unordered inserts left out, shared by every
+/// clone of a [`MongoSink`] so it can be summed up once at the end.
+#[derive(Debug, Clone, Default)]
+pub struct WriteReport {
+    tally: Arc<Mutex<Tally>>,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    duplicates: usize,
+    /// Count and first message of the failures with each error code.
+    failures: BTreeMap<i32, (usize, String)>,
+}
+
+impl WriteReport {
+    fn tally(&self) -> std::sync::MutexGuard<'_, Tally> {
+        self.tally.lock().expect("write report poisoned")
+    }
+
+    fn record_failure(&self, code: i32, message: &str) {
+        let mut tally = self.tally();
+        let (count, _) = tally
+            .failures
+            .entry(code)
+            .or_insert_with(|| (0, message.to_string()));
+        *count += 1;
+    }
+
+    /// Sentences skipped as already stored.
+    pub fn duplicates(&self) -> usize {
+        self.tally().duplicates
+    }
+
+    /// Sentences the server refused for any other reason.
+    pub fn failed(&self) -> usize {
+        self.tally().failures.values().map(|(count, _)| count).sum()
+    }
+
+    /// What was left out, a line for duplicates and one for each kind of
+    /// failure with its first message. Empty if everything went in.
+    pub fn summary(&self) -> Vec<String> {
+        let tally = self.tally();
+        let mut lines = Vec::new();
+        if tally.duplicates > 0 {
+            lines.push(format!("Skipped {} duplicate sentences", tally.duplicates));
+        }
+        for (code, (count, message)) in &tally.failures {
+            lines.push(format!(
+                "Failed to write {} sentences with error {}: {}",
+                count, code, message
+            ));
+        }
+        lines
+    }
+}
+
+/// Inserts batches into a MongoDB collection with a text index over `text`
+/// or `rawText`, and an index on `docId` and `sentenceIndex`. Clones share
+/// the underlying client and its connection pool.
+///
+/// Batches are inserted unordered, so a document the server refuses, say
+/// for breaking a unique index, doesn't keep the rest of its batch out. Such
+/// documents are logged and tallied in the sink's [`WriteReport`] instead of
+/// failing the ingestion.
+#[derive(Clone)]
+pub struct MongoSink {
+    collection: Collection<Document>,
+    /// Count sentences already stored as skipped rather than failed.
+    dedup: bool,
+    retry: RetryPolicy,
+    report: WriteReport,
+    schema: Arc<Schema>,
+}
+
+impl MongoSink {
+    /// Stores documents with their fields renamed by `schema`, which the
+    /// indexes are created over as well.
+    pub async fn new(
+        collection: Collection<Document>,
+        index: TextIndex,
+        schema: Schema,
+    ) -> Result<Self, BoxError> {
+        // Create a text index for efficient searching
+        let index_model = IndexModel::builder()
+            .keys(doc! { schema.field(index.field()): "text" })
+            .build();
+        collection.create_index(index_model, None).await?;
+        // Looks up the sentences around one for context windows
+        let context_model = IndexModel::builder()
+            .keys(doc! { schema.field("docId"): 1, schema.field("sentenceIndex"): 1 })
+            .build();
+        collection.create_index(context_model, None).await?;
+        Ok(MongoSink {
+            collection,
+            dedup: false,
+            retry: RetryPolicy::default(),
+            report: WriteReport::default(),
+            schema: Arc::new(schema),
+        })
+    }
+
+    /// Adds a unique index over `textHash`, so the collection holds each
+    /// sentence at most once. A sentence already there is then reported as a
+    /// failed write. Fails itself if the collection already has duplicates.
+    pub async fn with_unique_hash(self) -> Result<Self, BoxError> {
+        let index_model = IndexModel::builder()
+            .keys(doc! { self.schema.field("textHash"): 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.collection.create_index(index_model, None).await?;
+        Ok(self)
+    }
+
+    /// Like [`MongoSink::with_unique_hash`], but sentences already stored
+    /// are expected and counted as skipped duplicates rather than failures.
+    /// Re-ingesting overlapping files then leaves one copy of each sentence.
+    pub async fn with_dedup(self) -> Result<Self, BoxError> {
+        let mut sink = self.with_unique_hash().await?;
+        sink.dedup = true;
+        Ok(sink)
+    }
+
+    /// Sets how batches that fail for transient reasons are retried.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The tally of documents left out, shared with every clone.
+    pub fn report(&self) -> WriteReport {
+        self.report.clone()
+    }
+
+    /// Tallies documents left out in `report`, to share it with sinks for
+    /// other collections.
+    pub fn with_report(mut self, report: WriteReport) -> Self {
+        self.report = report;
+        self
+    }
+
+    /// One attempt at inserting `batch`. Errors for single documents are
+    /// tallied rather than returned. A retry can't tell which documents the
+    /// failed attempt got in, so it takes clashes on `_id` for documents
+    /// already written.
+    async fn insert(&self, batch: &[Document], retrying: bool) -> Result<(), Error> {
+        let len = batch.len();
+        let options = InsertManyOptions::builder().ordered(false).build();
+        let error = match self.collection.insert_many(batch, options).await {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        let write_errors = match error.kind.as_ref() {
+            ErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => {
+                failure.write_errors.as_deref().unwrap_or_default()
+            }
+            _ => return Err(error),
+        };
+
+        let (mut duplicates, mut failed) = (0, Vec::new());
+        for e in write_errors {
+            if e.code == DUPLICATE_KEY && retrying && e.message.contains("index: _id_") {
+                continue;
+            }
+            if e.code == DUPLICATE_KEY && self.dedup {
+                duplicates += 1;
+            } else {
+                failed.push(e);
+                self.report.record_failure(e.code, &e.message);
+            }
+        }
+        self.report.tally().duplicates += duplicates;
+        if duplicates > 0 {
+            log::info!(duplicates; "Skipped {} duplicate sentences", duplicates);
+        }
+        if let Some(first) = failed.first() {
+            log::error!(
+                failed = failed.len(), code = first.code;
+                "Failed to write {} of {} sentences in the batch, first with error {}: {}",
+                failed.len(),
+                len,
+                first.code,
+                first.message
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Whether `error` may pass if the write is tried again: the server or
+/// driver says so, or the connection to the server was lost.
+fn is_transient(error: &Error) -> bool {
+    error.contains_label(RETRYABLE_WRITE_ERROR)
+        || error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+        || matches!(
+            error.kind.as_ref(),
+            ErrorKind::Io(_)
+                | ErrorKind::ServerSelection { .. }
+                | ErrorKind::ConnectionPoolCleared { .. }
+        )
+}
+
+/// Server error code for a unique index violation.
+const DUPLICATE_KEY: i32 = 11000;
+
+#[async_trait]
+impl SentenceSink for MongoSink {
+    async fn write_batch(&mut self, mut batch: Vec<Document>) -> Result<(), BoxError> {
+        if !self.schema.is_identity() {
+            batch = batch
+                .into_iter()
+                .map(|document| self.schema.apply(document))
+                .collect();
+        }
+        // Ids assigned up front stay the same across attempts, so a retry
+        // recognises the documents an earlier attempt got in
+        for document in &mut batch {
+            if !document.contains_key("_id") {
+                document.insert("_id", ObjectId::new());
+            }
+        }
+        let sink = &*self;
+        let what = format!("insert batch of {} sentences", batch.len());
+        self.retry
+            .run(&what, is_transient, |attempt| {
+                sink.insert(&batch, attempt > 1)
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let result = self
+            .collection
+            .delete_many(doc! { self.schema.field("fileName"): file_name }, None)
+            .await?;
+        Ok(result.deleted_count)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_sums_up_left_out_documents() {
+        let report = WriteReport::default();
+        assert!(report.summary().is_empty());
+        let shared = report.clone();
+        shared.tally().duplicates += 2;
+        shared.record_failure(121, "Document failed validation");
+        shared.record_failure(121, "Document failed validation again");
+        shared.record_failure(17419, "Document too large");
+        assert_eq!(report.duplicates(), 2);
+        assert_eq!(report.failed(), 3);
+        assert_eq!(
+            report.summary(),
+            [
+                "Skipped 2 duplicate sentences",
+                "Failed to write 2 sentences with error 121: Document failed validation",
+                "Failed to write 1 sentences with error 17419: Document too large",
+            ]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// Throws the sentences away, for timing the pipeline without a
+/// destination's cost.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullSink;
+
+#[async_trait]
+impl SentenceSink for NullSink {
+    async fn write_batch(&mut self, _batch: Vec<Document>) -> Result<(), BoxError> {
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, _file_name: &str) -> Result<u64, BoxError> {
+        Ok(0)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(NullSink))
+    }
+}
@@ -0,0 +1,673 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use super::csv::cell;
+use super::SentenceSink;
+use crate::decompress::Compression;
+use crate::BoxError;
+
+/// Rows held before they're written out as a row group.
+const ROW_GROUP_ROWS: usize = 64 * 1024;
+
+/// What a Parquet file starts and ends with.
+const MAGIC: &[u8] = b"PAR1";
+
+/// Writes chosen fields of each document as a row of an Apache Parquet
+/// file, for querying the corpus with DuckDB, Spark or pandas. A column's
+/// type is that of its first value: booleans, integers, doubles and dates
+/// are stored as such, and anything else as text the way [`CsvSink`]
+/// writes it. Every value is optional and plainly encoded, in pages
+/// compressed with gzip or zstd if asked.
+///
+/// Rows are written out in row groups, and the footer describing them is
+/// rewritten at every flush, so the file can be read as soon as a source
+/// is done.
+///
+/// [`CsvSink`]: super::CsvSink
+pub struct ParquetSink<W: Write + Seek> {
+    writer: W,
+    columns: Vec<String>,
+    codec: Codec,
+    /// The columns' types, once the first row group settled them.
+    types: Option<Vec<ColumnType>>,
+    /// The values of each column held for the next row group.
+    values: Vec<Vec<Bson>>,
+    rows: usize,
+    row_groups: Vec<RowGroup>,
+    /// Where the row groups written end, and the footer starts.
+    data_end: u64,
+}
+
+impl<W: Write + Seek> ParquetSink<W> {
+    /// Writes `columns` of each document, with pages compressed with
+    /// `compression`, which can be gzip or zstd.
+    pub fn new(
+        mut writer: W,
+        columns: Vec<String>,
+        compression: Option<Compression>,
+    ) -> Result<Self, BoxError> {
+        let codec = match compression {
+            None => Codec::Uncompressed,
+            Some(Compression::Gzip) => Codec::Gzip,
+            Some(Compression::Zstd) => Codec::Zstd,
+            Some(Compression::Bzip2) => {
+                return Err(
+                    "Parquet pages can't be compressed with bzip2 (expected gzip or zstd)".into(),
+                )
+            }
+        };
+        writer.write_all(MAGIC)?;
+        Ok(ParquetSink {
+            writer,
+            values: vec![Vec::new(); columns.len()],
+            columns,
+            codec,
+            types: None,
+            rows: 0,
+            row_groups: Vec::new(),
+            data_end: MAGIC.len() as u64,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes the rows held as a row group after the last one. Nothing is
+    /// written if a value doesn't fit its column, so the file stays as it
+    /// was at the last flush.
+    fn write_row_group(&mut self) -> Result<(), BoxError> {
+        let values = &self.values;
+        let types = self.types.get_or_insert_with(|| {
+            values
+                .iter()
+                .map(|values| {
+                    values
+                        .iter()
+                        .find_map(ColumnType::of)
+                        .unwrap_or(ColumnType::Text)
+                })
+                .collect()
+        });
+        let pages = self
+            .columns
+            .iter()
+            .zip(types.iter())
+            .zip(values)
+            .map(|((name, column_type), values)| encode_page(name, *column_type, values))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.writer.seek(SeekFrom::Start(self.data_end))?;
+        let mut chunks = Vec::new();
+        for page in pages {
+            let compressed = self.codec.compress(&page)?;
+            let mut header = Compact::default();
+            header.i32(1, 0); // DATA_PAGE
+            header.i32(2, page.len() as i32);
+            header.i32(3, compressed.len() as i32);
+            header.begin(Some(5));
+            header.i32(1, self.rows as i32);
+            header.i32(2, PLAIN);
+            header.i32(3, RLE);
+            header.i32(4, RLE);
+            header.end();
+            header.end();
+            self.writer.write_all(&header.bytes)?;
+            self.writer.write_all(&compressed)?;
+            chunks.push(ColumnChunk {
+                offset: self.data_end as i64,
+                values: self.rows as i64,
+                uncompressed: (header.bytes.len() + page.len()) as i64,
+                compressed: (header.bytes.len() + compressed.len()) as i64,
+            });
+            self.data_end += (header.bytes.len() + compressed.len()) as u64;
+        }
+        for values in &mut self.values {
+            values.clear();
+        }
+        self.row_groups.push(RowGroup {
+            rows: self.rows as i64,
+            chunks,
+        });
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the footer after the last row group.
+    fn write_footer(&mut self) -> Result<(), BoxError> {
+        let types = match &self.types {
+            Some(types) => types.clone(),
+            None => vec![ColumnType::Text; self.columns.len()],
+        };
+        let mut footer = Compact::default();
+        footer.i32(1, 1);
+        footer.list(2, STRUCT, self.columns.len() + 1);
+        footer.begin(None);
+        footer.binary(4, b"schema");
+        footer.i32(5, self.columns.len() as i32);
+        footer.end();
+        for (name, column_type) in self.columns.iter().zip(&types) {
+            footer.begin(None);
+            footer.i32(1, column_type.physical());
+            footer.i32(3, 1); // OPTIONAL
+            footer.binary(4, name.as_bytes());
+            if let Some(converted) = column_type.converted() {
+                footer.i32(6, converted);
+            }
+            footer.end();
+        }
+        let rows: i64 = self.row_groups.iter().map(|group| group.rows).sum();
+        footer.i64(3, rows);
+        footer.list(4, STRUCT, self.row_groups.len());
+        for group in &self.row_groups {
+            footer.begin(None);
+            footer.list(1, STRUCT, group.chunks.len());
+            for ((chunk, name), column_type) in group.chunks.iter().zip(&self.columns).zip(&types) {
+                footer.begin(None);
+                footer.i64(2, chunk.offset);
+                footer.begin(Some(3));
+                footer.i32(1, column_type.physical());
+                footer.list(2, I32, 2);
+                footer.raw_i32(PLAIN);
+                footer.raw_i32(RLE);
+                footer.list(3, BINARY, 1);
+                footer.raw_binary(name.as_bytes());
+                footer.i32(4, self.codec.id());
+                footer.i64(5, chunk.values);
+                footer.i64(6, chunk.uncompressed);
+                footer.i64(7, chunk.compressed);
+                footer.i64(9, chunk.offset);
+                footer.end();
+                footer.end();
+            }
+            footer.i64(2, group.chunks.iter().map(|chunk| chunk.uncompressed).sum());
+            footer.i64(3, group.rows);
+            footer.end();
+        }
+        footer.binary(6, b"rustparser");
+        footer.end();
+
+        self.writer.seek(SeekFrom::Start(self.data_end))?;
+        self.writer.write_all(&footer.bytes)?;
+        self.writer
+            .write_all(&(footer.bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(MAGIC)?;
+        Ok(())
+    }
+}
+
+impl ParquetSink<BufWriter<File>> {
+    /// Creates the file at `path`.
+    pub fn create(
+        path: &str,
+        columns: Vec<String>,
+        compression: Option<Compression>,
+    ) -> Result<Self, BoxError> {
+        let file = File::create(path).map_err(|e| format!("Error creating {}: {}", path, e))?;
+        ParquetSink::new(BufWriter::new(file), columns, compression)
+    }
+}
+
+#[async_trait]
+impl<W: Write + Seek + Send> SentenceSink for ParquetSink<W> {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        for document in &batch {
+            for (column, values) in self.columns.iter().zip(&mut self.values) {
+                values.push(document.get(column).cloned().unwrap_or(Bson::Null));
+            }
+            self.rows += 1;
+            if self.rows >= ROW_GROUP_ROWS {
+                self.write_row_group()?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        if self.rows > 0 {
+            self.write_row_group()?;
+        }
+        self.write_footer()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// How a column's values are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    Boolean,
+    Int64,
+    Double,
+    /// Milliseconds since the epoch.
+    Timestamp,
+    Text,
+}
+
+impl ColumnType {
+    /// The type a column starting with `value` gets, if it's not null.
+    fn of(value: &Bson) -> Option<Self> {
+        Some(match value {
+            Bson::Null => return None,
+            Bson::Boolean(_) => ColumnType::Boolean,
+            Bson::Int32(_) | Bson::Int64(_) => ColumnType::Int64,
+            Bson::Double(_) => ColumnType::Double,
+            Bson::DateTime(_) => ColumnType::Timestamp,
+            _ => ColumnType::Text,
+        })
+    }
+
+    /// Its Parquet physical type.
+    fn physical(self) -> i32 {
+        match self {
+            ColumnType::Boolean => 0,
+            ColumnType::Int64 | ColumnType::Timestamp => 2,
+            ColumnType::Double => 5,
+            ColumnType::Text => 6,
+        }
+    }
+
+    /// Its Parquet converted type, for those that have one.
+    fn converted(self) -> Option<i32> {
+        match self {
+            ColumnType::Text => Some(0),      // UTF8
+            ColumnType::Timestamp => Some(9), // TIMESTAMP_MILLIS
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "booleans",
+            ColumnType::Int64 => "integers",
+            ColumnType::Double => "numbers",
+            ColumnType::Timestamp => "dates",
+            ColumnType::Text => "text",
+        }
+    }
+}
+
+/// Codecs the pages can be compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    fn id(self) -> i32 {
+        match self {
+            Codec::Uncompressed => 0,
+            Codec::Gzip => 2,
+            Codec::Zstd => 6,
+        }
+    }
+
+    fn compress(self, page: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Codec::Uncompressed => Ok(page.to_vec()),
+            Codec::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(page)?;
+                encoder.finish()
+            }
+            Codec::Zstd => zstd::encode_all(page, 0),
+        }
+    }
+}
+
+/// A column chunk written, as the footer describes it.
+struct ColumnChunk {
+    offset: i64,
+    values: i64,
+    uncompressed: i64,
+    compressed: i64,
+}
+
+struct RowGroup {
+    rows: i64,
+    chunks: Vec<ColumnChunk>,
+}
+
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+
+/// The body of a data page holding `values` of the `column_type` column
+/// `name`: which are defined, as runs, then the defined ones.
+fn encode_page(name: &str, column_type: ColumnType, values: &[Bson]) -> Result<Vec<u8>, BoxError> {
+    let mut levels = Vec::new();
+    let mut index = 0;
+    while index < values.len() {
+        let defined = values[index] != Bson::Null;
+        let run = values[index..]
+            .iter()
+            .take_while(|value| (**value != Bson::Null) == defined)
+            .count();
+        write_varint(&mut levels, (run as u64) << 1);
+        levels.push(defined as u8);
+        index += run;
+    }
+    let mut page = (levels.len() as u32).to_le_bytes().to_vec();
+    page.extend(levels);
+
+    let mismatch = |value: &Bson| {
+        format!(
+            "the {} column of the Parquet output holds a {:?} where its first values were {}",
+            name,
+            value.element_type(),
+            column_type.name()
+        )
+    };
+    let defined = values.iter().filter(|value| **value != Bson::Null);
+    match column_type {
+        ColumnType::Boolean => {
+            let mut bits = Vec::new();
+            for (i, value) in defined.enumerate() {
+                let Bson::Boolean(bit) = value else {
+                    return Err(mismatch(value).into());
+                };
+                if i % 8 == 0 {
+                    bits.push(0);
+                }
+                *bits.last_mut().unwrap() |= (*bit as u8) << (i % 8);
+            }
+            page.extend(bits);
+        }
+        ColumnType::Int64 => {
+            for value in defined {
+                let number = match value {
+                    Bson::Int32(n) => *n as i64,
+                    Bson::Int64(n) => *n,
+                    _ => return Err(mismatch(value).into()),
+                };
+                page.extend(number.to_le_bytes());
+            }
+        }
+        ColumnType::Double => {
+            for value in defined {
+                let number = match value {
+                    Bson::Double(n) => *n,
+                    Bson::Int32(n) => *n as f64,
+                    Bson::Int64(n) => *n as f64,
+                    _ => return Err(mismatch(value).into()),
+                };
+                page.extend(number.to_le_bytes());
+            }
+        }
+        ColumnType::Timestamp => {
+            for value in defined {
+                let Bson::DateTime(date) = value else {
+                    return Err(mismatch(value).into());
+                };
+                page.extend(date.timestamp_millis().to_le_bytes());
+            }
+        }
+        ColumnType::Text => {
+            for value in defined {
+                let text = cell(Some(value));
+                page.extend((text.len() as u32).to_le_bytes());
+                page.extend(text.as_bytes());
+            }
+        }
+    }
+    Ok(page)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+/// Writes Thrift's compact protocol, which Parquet's page headers and
+/// footer are in.
+#[derive(Default)]
+struct Compact {
+    bytes: Vec<u8>,
+    /// The id of the last field of the struct being written, and of the
+    /// structs it's in.
+    last_id: i16,
+    outer_ids: Vec<i16>,
+}
+
+impl Compact {
+    fn field(&mut self, id: i16, kind: u8) {
+        match id - self.last_id {
+            delta @ 1..=15 => self.bytes.push((delta as u8) << 4 | kind),
+            _ => {
+                self.bytes.push(kind);
+                write_varint(&mut self.bytes, zigzag(id as i64));
+            }
+        }
+        self.last_id = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.raw_i32(value);
+    }
+
+    fn raw_i32(&mut self, value: i32) {
+        write_varint(&mut self.bytes, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        write_varint(&mut self.bytes, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, BINARY);
+        self.raw_binary(value);
+    }
+
+    fn raw_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.bytes, value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    /// Starts a list of `len` items of `kind`, which follow unlabelled.
+    fn list(&mut self, id: i16, kind: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.bytes.push((len as u8) << 4 | kind);
+        } else {
+            self.bytes.push(0xF0 | kind);
+            write_varint(&mut self.bytes, len as u64);
+        }
+    }
+
+    /// Starts a struct, as field `id` of the one being written or, without
+    /// one, as an item of a list.
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, STRUCT);
+        }
+        self.outer_ids.push(self.last_id);
+        self.last_id = 0;
+    }
+
+    fn end(&mut self) {
+        self.bytes.push(0);
+        self.last_id = self.outer_ids.pop().unwrap_or(0);
+    }
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::{doc, DateTime};
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    /// A value of Thrift's compact protocol, as far as Parquet's metadata
+    /// uses it.
+    #[derive(Debug, PartialEq)]
+    enum Value {
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Value>),
+        Struct(BTreeMap<i16, Value>),
+    }
+
+    impl Value {
+        fn get(&self, path: &[i16]) -> &Value {
+            match (self, path) {
+                (value, []) => value,
+                (Value::Struct(fields), [id, rest @ ..]) => fields[id].get(rest),
+                (Value::List(items), [index, rest @ ..]) => items[*index as usize].get(rest),
+                _ => panic!("no {:?} in {:?}", path, self),
+            }
+        }
+
+        fn int(&self, path: &[i16]) -> i64 {
+            match self.get(path) {
+                Value::Int(n) => *n,
+                other => panic!("{:?} isn't an integer", other),
+            }
+        }
+    }
+
+    fn read_varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut n = 0;
+        for shift in (0..).step_by(7) {
+            let byte = bytes[*at];
+            *at += 1;
+            n |= ((byte & 0x7F) as u64) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        n
+    }
+
+    fn read_int(bytes: &[u8], at: &mut usize) -> i64 {
+        let n = read_varint(bytes, at);
+        (n >> 1) as i64 ^ -((n & 1) as i64)
+    }
+
+    fn read_value(kind: u8, bytes: &[u8], at: &mut usize) -> Value {
+        match kind {
+            I32 | I64 => Value::Int(read_int(bytes, at)),
+            BINARY => {
+                let len = read_varint(bytes, at) as usize;
+                *at += len;
+                Value::Binary(bytes[*at - len..*at].to_vec())
+            }
+            LIST => {
+                let header = bytes[*at];
+                *at += 1;
+                let len = match header >> 4 {
+                    15 => read_varint(bytes, at) as usize,
+                    len => len as usize,
+                };
+                Value::List(
+                    (0..len)
+                        .map(|_| read_value(header & 0x0F, bytes, at))
+                        .collect(),
+                )
+            }
+            STRUCT => {
+                let mut fields = BTreeMap::new();
+                let mut id = 0;
+                loop {
+                    let header = bytes[*at];
+                    *at += 1;
+                    if header == 0 {
+                        return Value::Struct(fields);
+                    }
+                    id = match header >> 4 {
+                        0 => read_int(bytes, at) as i16,
+                        delta => id + delta as i16,
+                    };
+                    fields.insert(id, read_value(header & 0x0F, bytes, at));
+                }
+            }
+            other => panic!("unexpected type {}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_row_groups_a_reader_can_find() {
+        let columns = ["text", "lineNumber", "ratio", "joined", "page"];
+        let mut sink = ParquetSink::new(
+            Cursor::new(Vec::new()),
+            columns.iter().map(|c| c.to_string()).collect(),
+            None,
+        )
+        .unwrap();
+        let joined = DateTime::from_millis(1_700_000_000_000);
+        sink.write_batch(vec![
+            doc! { "text": "It rained.", "lineNumber": 3, "ratio": 0.5, "joined": joined },
+            doc! { "text": "Then it stopped.", "ratio": 1 },
+        ])
+        .await
+        .unwrap();
+        sink.flush().await.unwrap();
+        sink.write_batch(vec![doc! { "text": "Later.", "lineNumber": 9_i64 }])
+            .await
+            .unwrap();
+        sink.flush().await.unwrap();
+        // A value of another type fails the flush but leaves the file whole
+        let wrong = doc! { "text": "Wrong.", "lineNumber": "ten" };
+        sink.write_batch(vec![wrong]).await.unwrap();
+        assert!(sink
+            .flush()
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("lineNumber"));
+
+        let file = sink.into_inner().into_inner();
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let footer_len =
+            u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap());
+        let mut at = file.len() - 8 - footer_len as usize;
+        let footer = read_value(STRUCT, &file, &mut at);
+        assert_eq!(at, file.len() - 8);
+
+        assert_eq!(footer.int(&[3]), 3);
+        let names: Vec<_> = (1..=5)
+            .map(|i| footer.get(&[2, i, 4]))
+            .map(|name| match name {
+                Value::Binary(name) => String::from_utf8(name.clone()).unwrap(),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(names, columns);
+        // lineNumber is INT64, joined a TIMESTAMP_MILLIS and page, never
+        // given, text
+        assert_eq!(footer.int(&[2, 2, 1]), 2);
+        assert_eq!(footer.int(&[2, 4, 6]), 9);
+        assert_eq!(footer.int(&[2, 5, 1]), 6);
+
+        // The first row group's lineNumber values, one of them missing
+        let chunk = footer.get(&[4, 0, 1, 1, 3]);
+        assert_eq!(chunk.int(&[5]), 2);
+        let mut at = chunk.int(&[9]) as usize;
+        let header = read_value(STRUCT, &file, &mut at);
+        assert_eq!(header.int(&[5, 1]), 2);
+        let levels_len = u32::from_le_bytes(file[at..at + 4].try_into().unwrap()) as usize;
+        // A run of one defined value, then one of one missing
+        assert_eq!(&file[at + 4..at + 4 + levels_len], [2, 1, 2, 0]);
+        let value = &file[at + 4 + levels_len..at + 12 + levels_len];
+        assert_eq!(i64::from_le_bytes(value.try_into().unwrap()), 3);
+        assert_eq!(footer.int(&[4, 1, 3]), 1);
+    }
+}
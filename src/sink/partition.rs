@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// What output files are split by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partition {
+    /// A file per source file, named after it.
+    File,
+    /// A file per `genre`, with sentences that have none under `unknown`.
+    Genre,
+}
+
+impl Partition {
+    /// The partition `document` falls in, usable as a file name.
+    pub fn key(&self, document: &Document) -> String {
+        let key = match self {
+            Partition::File => document
+                .get_str("fileName")
+                .ok()
+                .and_then(|name| Path::new(name).file_stem())
+                .map(|stem| stem.to_string_lossy().into_owned()),
+            Partition::Genre => document.get_str("genre").ok().map(str::to_string),
+        };
+        let key: String = key
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        match key.trim_start_matches('.') {
+            "" => "unknown".to_string(),
+            key => key.to_string(),
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "file" => Ok(Partition::File),
+            "genre" => Ok(Partition::Genre),
+            other => Err(format!(
+                "invalid partition '{}' (expected file or genre)",
+                other
+            )),
+        }
+    }
+}
+
+type OpenSink = Box<dyn FnMut(&str) -> Result<Box<dyn SentenceSink>, BoxError> + Send>;
+
+/// Writes each partition of the sentences to a sink of its own, opened by
+/// `open` with the partition's key the first time it's seen.
+pub struct PartitionedSink {
+    partition: Partition,
+    open: OpenSink,
+    sinks: HashMap<String, Box<dyn SentenceSink>>,
+}
+
+impl PartitionedSink {
+    pub fn new(
+        partition: Partition,
+        open: impl FnMut(&str) -> Result<Box<dyn SentenceSink>, BoxError> + Send + 'static,
+    ) -> Self {
+        PartitionedSink {
+            partition,
+            open: Box::new(open),
+            sinks: HashMap::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl SentenceSink for PartitionedSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        let mut partitions: HashMap<String, Vec<Document>> = HashMap::new();
+        for document in batch {
+            partitions
+                .entry(self.partition.key(&document))
+                .or_default()
+                .push(document);
+        }
+        for (key, documents) in partitions {
+            let sink = match self.sinks.get_mut(&key) {
+                Some(sink) => sink,
+                None => {
+                    let sink = (self.open)(&key)?;
+                    self.sinks.entry(key).or_insert(sink)
+                }
+            };
+            sink.write_batch(documents).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        for sink in self.sinks.values_mut() {
+            sink.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(String, Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl SentenceSink for Recorder {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            let mut written = self.1.lock().unwrap();
+            for document in batch {
+                written.push(format!("{}: {}", self.0, document.get_str("text")?));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn splits_sentences_by_partition() {
+        assert_eq!(
+            Partition::File.key(&doc! { "fileName": "corpus/news 1.txt" }),
+            "news_1"
+        );
+        assert_eq!(
+            Partition::Genre.key(&doc! { "text": "No genre." }),
+            "unknown"
+        );
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let opened = written.clone();
+        let mut sink = PartitionedSink::new(Partition::Genre, move |key| {
+            Ok(Box::new(Recorder(key.to_string(), opened.clone())) as Box<dyn SentenceSink>)
+        });
+        sink.write_batch(vec![doc! { "text": "It rained.", "genre": "news" }])
+            .await
+            .unwrap();
+        sink.write_batch(vec![
+            doc! { "text": "Once upon a time.", "genre": "fiction" },
+            doc! { "text": "Then it stopped.", "genre": "news" },
+        ])
+        .await
+        .unwrap();
+
+        let mut written = written.lock().unwrap().clone();
+        written.sort();
+        assert_eq!(
+            written,
+            [
+                "fiction: Once upon a time.",
+                "news: It rained.",
+                "news: Then it stopped."
+            ]
+        );
+        assert_eq!(sink.sinks.len(), 2);
+    }
+}
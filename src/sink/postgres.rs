@@ -0,0 +1,170 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// Writes sentences into a PostgreSQL table through `psql`, bulk-loading
+/// each batch with `COPY`. Besides the usual columns the table keeps the
+/// whole document as `doc`, in JSONB, and a `text_search` tsvector with a
+/// GIN index for full-text search.
+#[derive(Debug, Clone)]
+pub struct PostgresSink {
+    url: String,
+    table: String,
+}
+
+impl PostgresSink {
+    /// Connects to the database at `url` and creates `table`, and its
+    /// indexes, if it doesn't exist, with its text searched as the text
+    /// search configuration `language`, such as `english` or `simple`.
+    pub async fn connect(url: &str, table: &str, language: &str) -> Result<Self, BoxError> {
+        for name in [table, language] {
+            if !is_identifier(name) {
+                return Err(format!("'{}' isn't a plain SQL name", name).into());
+            }
+        }
+        let sink = PostgresSink {
+            url: url.to_string(),
+            table: table.to_string(),
+        };
+        sink.psql(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id BIGSERIAL PRIMARY KEY,
+                text TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                line_number INTEGER NOT NULL,
+                word_count INTEGER NOT NULL,
+                doc JSONB NOT NULL,
+                text_search TSVECTOR GENERATED ALWAYS AS (to_tsvector('{language}', text)) STORED
+            );
+            CREATE INDEX IF NOT EXISTS {table}_text_search ON {table} USING GIN (text_search);
+            CREATE INDEX IF NOT EXISTS {table}_file_name ON {table} (file_name);",
+            table = table,
+            language = language
+        ))
+        .await?;
+        Ok(sink)
+    }
+
+    /// Runs `psql` with `script` on its standard input, returning what it
+    /// prints.
+    async fn psql(&self, script: &str) -> Result<String, BoxError> {
+        self.psql_with(&[], script, "").await
+    }
+
+    /// Runs `psql` with the further `args`, and `script` followed by `data`
+    /// on its standard input.
+    async fn psql_with(&self, args: &[&str], script: &str, data: &str) -> Result<String, BoxError> {
+        let mut child = Command::new("psql")
+            .args(["--no-psqlrc", "--quiet", "--tuples-only", "--no-align"])
+            .args(["-v", "ON_ERROR_STOP=1", "-d", &self.url])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Error running psql: {}", e))?;
+        let mut stdin = child.stdin.take().ok_or("psql has no standard input")?;
+        stdin.write_all(script.as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        stdin.write_all(data.as_bytes()).await?;
+        drop(stdin);
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Error writing to PostgreSQL: {}", message.trim()).into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Whether `name` can go into SQL as it is.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `batch` as the CSV `COPY` reads: text, file name, line number, word
+/// count and the document as JSON.
+fn copy_rows(batch: &[Document]) -> Result<String, BoxError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for document in batch {
+        let json = Bson::Document(document.clone()).into_relaxed_extjson();
+        writer.write_record([
+            document.get_str("text")?.to_string(),
+            document.get_str("fileName")?.to_string(),
+            document.get_i32("lineNumber")?.to_string(),
+            document.get_i32("wordCount")?.to_string(),
+            json.to_string(),
+        ])?;
+    }
+    Ok(String::from_utf8(
+        writer.into_inner().map_err(|e| e.to_string())?,
+    )?)
+}
+
+#[async_trait]
+impl SentenceSink for PostgresSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        // The rows follow the command on psql's input, ended by `\.`
+        let copy = format!(
+            "COPY {} (text, file_name, line_number, word_count, doc) FROM STDIN WITH (FORMAT csv);",
+            self.table
+        );
+        let rows = copy_rows(&batch)?;
+        self.psql_with(&[], &copy, &format!("{}\\.\n", rows))
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let script = format!(
+            "WITH deleted AS (DELETE FROM {} WHERE file_name = :'file_name' RETURNING 1)
+             SELECT count(*) FROM deleted;",
+            self.table
+        );
+        let variable = format!("file_name={}", file_name);
+        let deleted = self.psql_with(&["-v", &variable], &script, "").await?;
+        Ok(deleted.parse()?)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn writes_copy_rows() {
+        assert!(is_identifier("sentences_2024"));
+        assert!(!is_identifier("sentences; DROP TABLE x"));
+        assert!(!is_identifier("2024"));
+
+        let rows = copy_rows(&[doc! {
+            "text": "Well, \"yes\".",
+            "fileName": "a.txt",
+            "lineNumber": 3,
+            "wordCount": 2,
+        }])
+        .unwrap();
+        assert_eq!(
+            rows,
+            "\"Well, \"\"yes\"\".\",a.txt,3,2,\"{\"\"text\"\":\"\"Well, \\\"\"yes\\\"\".\"\",\
+             \"\"fileName\"\":\"\"a.txt\"\",\"\"lineNumber\"\":3,\"\"wordCount\"\":2}\"\n"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Retrying writes and downloads that fail for reasons that may pass, such
+//! as a dropped connection or a replica set electing a new primary.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use crate::metrics::METRICS;
+
+/// How often and how patiently a failed write or download is tried again.
+/// The wait doubles after each attempt, up to `max_delay`, and is randomised
+/// between half and all of that so workers that failed together don't retry
+/// together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in all, the first included; 1 never retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// The wait before attempt `attempt + 1`, after `attempt` failed ones.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let full = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        // A fresh `RandomState` is randomly keyed, which is all the
+        // randomness jitter needs
+        let random = RandomState::new().hash_one(attempt);
+        let jitter = (random % 1000) as f64 / 1000.0;
+        full.mul_f64(0.5 + jitter / 2.0)
+    }
+
+    /// Runs `operation`, given the attempt number from 1, until it succeeds,
+    /// fails with an error `is_transient` rejects, or has been attempted
+    /// `max_attempts` times. Each retry is logged with `what` was attempted.
+    pub async fn run<T, E, F, Fut>(
+        &self,
+        what: &str,
+        is_transient: impl Fn(&E) -> bool,
+        mut operation: F,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation(attempt).await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    tokio::time::sleep(self.log_retry(attempt, what, &e)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// [`run`](Self::run) for a blocking `operation`, sleeping the thread
+    /// between attempts.
+    pub fn run_blocking<T, E>(
+        &self,
+        what: &str,
+        is_transient: impl Fn(&E) -> bool,
+        mut operation: impl FnMut(u32) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation(attempt) {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    std::thread::sleep(self.log_retry(attempt, what, &e));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Logs that attempt `attempt` to do `what` failed with `error` and
+    /// returns the wait before the next.
+    pub fn log_retry(&self, attempt: u32, what: &str, error: impl std::fmt::Display) -> Duration {
+        let delay = self.delay(attempt);
+        METRICS.add_retry();
+        log::warn!(
+            attempt;
+            "Attempt {}/{} to {} failed: {}; retrying in {:.1}s",
+            attempt,
+            self.max_attempts,
+            what,
+            error,
+            delay.as_secs_f64()
+        );
+        delay
+    }
+}
+
+/// Whether a failed HTTP request may go through if tried again.
+pub(crate) fn http_is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::StatusCode(status) => matches!(status, 429 | 502 | 503 | 504),
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::HostNotFound => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_grow_with_jitter_up_to_the_maximum() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..5 {
+            let full = Duration::from_millis(500) * (1 << (attempt - 1));
+            let delay = policy.delay(attempt);
+            assert!(delay >= full / 2 && delay <= full, "{:?}", delay);
+        }
+        assert!(policy.delay(30) <= Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn retries_only_transient_errors() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        let mut calls = 0;
+        let result: Result<u32, String> = policy
+            .run(
+                "write",
+                |e: &String| e == "blip",
+                |attempt| {
+                    calls += 1;
+                    async move {
+                        if attempt < 3 {
+                            Err("blip".to_string())
+                        } else {
+                            Ok(attempt)
+                        }
+                    }
+                },
+            )
+            .await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result: Result<(), String> = policy
+            .run(
+                "write",
+                |e: &String| e == "blip",
+                |_| {
+                    calls += 1;
+                    async { Err("duplicate".to_string()) }
+                },
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// A rule picking sentences by where they came from, as COCA keeps its
+/// registers apart. Every condition set has to hold.
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    /// Pattern the `fileName` has to match.
+    pub path: Option<glob::Pattern>,
+    /// A field, such as one parsed from line metadata, and the value it has
+    /// to have.
+    pub field: Option<(String, String)>,
+    /// Fields added to the sentences taken, such as a shard key. Fields they
+    /// already have are kept.
+    pub fields: Document,
+}
+
+impl Route {
+    pub fn matches(&self, document: &Document) -> bool {
+        let path_matches = self.path.as_ref().is_none_or(|pattern| {
+            document
+                .get_str("fileName")
+                .is_ok_and(|name| pattern.matches(name))
+        });
+        let field_matches =
+            self.field
+                .as_ref()
+                .is_none_or(|(field, value)| match document.get(field) {
+                    Some(Bson::String(s)) => s == value,
+                    Some(other) => other.to_string() == *value,
+                    None => false,
+                });
+        path_matches && field_matches
+    }
+}
+
+/// Sends each sentence to the sink of the first [`Route`] it matches, or to
+/// the default sink when it matches none. Several routes can share a sink.
+pub struct RoutedSink {
+    /// Each route with the index of its sink.
+    routes: Vec<(Route, usize)>,
+    /// The default sink first.
+    sinks: Vec<Box<dyn SentenceSink>>,
+}
+
+impl RoutedSink {
+    pub fn new(default: Box<dyn SentenceSink>) -> Self {
+        RoutedSink {
+            routes: Vec::new(),
+            sinks: vec![default],
+        }
+    }
+
+    /// Adds a sink routes can send to, returning its number. The default
+    /// sink is number 0.
+    pub fn add_sink(&mut self, sink: Box<dyn SentenceSink>) -> usize {
+        self.sinks.push(sink);
+        self.sinks.len() - 1
+    }
+
+    /// Adds a route to the sink numbered `sink`. Routes are tried in the
+    /// order they were added.
+    pub fn route(&mut self, route: Route, sink: usize) {
+        assert!(sink < self.sinks.len(), "no sink {}", sink);
+        self.routes.push((route, sink));
+    }
+}
+
+#[async_trait]
+impl SentenceSink for RoutedSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        let mut parts = vec![Vec::new(); self.sinks.len()];
+        for mut document in batch {
+            let index = match self
+                .routes
+                .iter()
+                .find(|(route, _)| route.matches(&document))
+            {
+                Some((route, index)) => {
+                    for (key, value) in &route.fields {
+                        if !document.contains_key(key) {
+                            document.insert(key.clone(), value.clone());
+                        }
+                    }
+                    *index
+                }
+                None => 0,
+            };
+            parts[index].push(document);
+        }
+        for (sink, part) in self.sinks.iter_mut().zip(parts) {
+            if !part.is_empty() {
+                sink.write_batch(part).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        for sink in &mut self.sinks {
+            sink.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let mut removed = 0;
+        for sink in &mut self.sinks {
+            removed += sink.remove_source(file_name).await?;
+        }
+        Ok(removed)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        let sinks = self
+            .sinks
+            .iter()
+            .map(|sink| sink.try_clone())
+            .collect::<Option<_>>()?;
+        Some(Box::new(RoutedSink {
+            routes: self.routes.clone(),
+            sinks,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<Document>>>);
+
+    #[async_trait]
+    impl SentenceSink for Collect {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    impl Collect {
+        fn texts(&self) -> Vec<String> {
+            let documents = self.0.lock().unwrap();
+            documents
+                .iter()
+                .map(|d| d.get_str("text").unwrap().to_string())
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_sentences_to_the_first_matching_route() {
+        let (rest, fiction, news) = (Collect::default(), Collect::default(), Collect::default());
+        let mut sink = RoutedSink::new(Box::new(rest.clone()));
+        let fiction_sink = sink.add_sink(Box::new(fiction.clone()));
+        let news_sink = sink.add_sink(Box::new(news.clone()));
+        sink.route(
+            Route {
+                path: Some(glob::Pattern::new("fiction/*").unwrap()),
+                ..Route::default()
+            },
+            fiction_sink,
+        );
+        sink.route(
+            Route {
+                field: Some(("genre".to_string(), "news".to_string())),
+                fields: doc! { "register": "news" },
+                ..Route::default()
+            },
+            news_sink,
+        );
+
+        sink.write_batch(vec![
+            doc! { "text": "a", "fileName": "fiction/novel.txt", "genre": "news" },
+            doc! { "text": "b", "fileName": "papers/one.txt", "genre": "news" },
+            doc! { "text": "c", "fileName": "papers/two.txt", "genre": "acad" },
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(fiction.texts(), ["a"]);
+        assert_eq!(news.texts(), ["b"]);
+        assert_eq!(rest.texts(), ["c"]);
+        assert_eq!(news.0.lock().unwrap()[0].get_str("register"), Ok("news"));
+        assert!(sink.try_clone().is_none());
+    }
+}
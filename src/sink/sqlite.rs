@@ -0,0 +1,305 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// Writes sentences into a local SQLite database, one transaction per batch.
+/// The `sentences_fts` FTS5 table indexes their text, kept up to date by
+/// triggers, so the database can be searched on its own.
+pub struct SqliteSink {
+    conn: rusqlite::Connection,
+}
+
+impl SqliteSink {
+    pub fn open(path: &str) -> Result<Self, BoxError> {
+        Self::with_connection(rusqlite::Connection::open(path)?)
+    }
+
+    pub fn with_connection(conn: rusqlite::Connection) -> Result<Self, BoxError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sentences (
+                id INTEGER PRIMARY KEY,
+                text TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                line_number INTEGER NOT NULL,
+                word_count INTEGER NOT NULL
+            )",
+        )?;
+        let indexed: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'sentences_fts')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS sentences_fts USING fts5 (
+                text, content = 'sentences', content_rowid = 'id'
+            );
+            CREATE TRIGGER IF NOT EXISTS sentences_fts_insert AFTER INSERT ON sentences BEGIN
+                INSERT INTO sentences_fts (rowid, text) VALUES (new.id, new.text);
+            END;
+            CREATE TRIGGER IF NOT EXISTS sentences_fts_delete AFTER DELETE ON sentences BEGIN
+                INSERT INTO sentences_fts (sentences_fts, rowid, text)
+                VALUES ('delete', old.id, old.text);
+            END;",
+        )?;
+        // Databases written before the index existed get their sentences
+        // indexed once
+        if !indexed {
+            conn.execute(
+                "INSERT INTO sentences_fts (sentences_fts) VALUES ('rebuild')",
+                [],
+            )?;
+        }
+        Ok(SqliteSink { conn })
+    }
+
+    /// The `limit` sentences best matching `query`, given as for `search`,
+    /// with their score, file name and line number, best first. With `file`
+    /// only sentences from files matching it, where `*` matches anything.
+    pub fn search(
+        &self,
+        query: &str,
+        file: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<(f64, String, i32, String)>, BoxError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT -bm25(sentences_fts), s.file_name, s.line_number, s.text
+             FROM sentences_fts JOIN sentences s ON s.id = sentences_fts.rowid
+             WHERE sentences_fts MATCH ?1 AND (?2 IS NULL OR s.file_name GLOB ?2)
+             ORDER BY bm25(sentences_fts) LIMIT ?3",
+        )?;
+        let file = file.map(glob_pattern);
+        let rows = stmt
+            .query_map(rusqlite::params![fts_query(query)?, file, limit], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(rows)
+    }
+}
+
+/// A `GLOB` pattern matching the names `file` matches, where only `*` is a
+/// wildcard.
+fn glob_pattern(file: &str) -> String {
+    file.chars()
+        .map(|c| match c {
+            '?' | '[' => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// `text`, in MongoDB's text search syntax, as an FTS5 query: sentences
+/// with any of its words and all of its "quoted phrases", and none of its
+/// -excluded words.
+fn fts_query(text: &str) -> Result<String, BoxError> {
+    let quote = |term: &str| format!("\"{}\"", term.replace('"', "\"\""));
+    let (mut words, mut phrases, mut excluded) = (Vec::new(), Vec::new(), Vec::new());
+    for (i, part) in text.split('"').enumerate() {
+        if i % 2 == 1 {
+            if !part.trim().is_empty() {
+                phrases.push(quote(part.trim()));
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            match word.strip_prefix('-') {
+                Some(word) if !word.is_empty() => excluded.push(quote(word)),
+                _ => words.push(quote(word)),
+            }
+        }
+    }
+    if !words.is_empty() {
+        phrases.insert(0, format!("({})", words.join(" OR ")));
+    }
+    if phrases.is_empty() {
+        return Err(format!("nothing to search for in '{}'", text).into());
+    }
+    let mut query = phrases.join(" AND ");
+    for word in excluded {
+        query = format!("{} NOT {}", query, word);
+    }
+    Ok(query)
+}
+
+#[async_trait]
+impl SentenceSink for SqliteSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO sentences (text, file_name, line_number, word_count)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for document in &batch {
+                stmt.execute(rusqlite::params![
+                    document.get_str("text")?,
+                    document.get_str("fileName")?,
+                    document.get_i32("lineNumber")?,
+                    document.get_i32("wordCount")?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let deleted = self
+            .conn
+            .execute("DELETE FROM sentences WHERE file_name = ?1", [file_name])?;
+        Ok(deleted as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingest::{ingest_reader, IngestOptions};
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn sqlite_sink_stores_queryable_rows() {
+        let input =
+            "The first sentence is here. The second one follows.\nA third sentence on line two.\n";
+        let options = IngestOptions {
+            batch_size: 2,
+            ..IngestOptions::default()
+        };
+        let mut sink =
+            SqliteSink::with_connection(rusqlite::Connection::open_in_memory().unwrap()).unwrap();
+
+        let count = ingest_reader(Cursor::new(input), "books/a.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let mut stmt = sink
+            .conn
+            .prepare("SELECT text, file_name, line_number, word_count FROM sentences ORDER BY id")
+            .unwrap();
+        let rows: Vec<(String, String, i32, i32)> = stmt
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "The first sentence is here.".to_string(),
+                    "books/a.txt".to_string(),
+                    1,
+                    5
+                ),
+                (
+                    "The second one follows.".to_string(),
+                    "books/a.txt".to_string(),
+                    1,
+                    4
+                ),
+                (
+                    "A third sentence on line two.".to_string(),
+                    "books/a.txt".to_string(),
+                    2,
+                    6
+                ),
+            ]
+        );
+
+        let on_line_one: i64 = sink
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM sentences WHERE line_number = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(on_line_one, 2);
+    }
+
+    #[tokio::test]
+    async fn searches_the_full_text_index() {
+        assert_eq!(
+            fts_query(r#"river flood "high water" -sea"#).unwrap(),
+            r#"("river" OR "flood") AND "high water" NOT "sea""#
+        );
+        assert!(fts_query("-sea").is_err());
+
+        let options = IngestOptions {
+            replace: true,
+            ..IngestOptions::default()
+        };
+        let mut sink =
+            SqliteSink::with_connection(rusqlite::Connection::open_in_memory().unwrap()).unwrap();
+        let input = "The river rose in the night. The sea was calm.\nA river met the sea.\n";
+        ingest_reader(Cursor::new(input), "a.txt", None, &options, &mut sink)
+            .await
+            .unwrap();
+
+        let texts = |sink: &SqliteSink, query| -> Vec<String> {
+            let rows = sink.search(query, None, 10).unwrap();
+            rows.into_iter().map(|(_, _, _, text)| text).collect()
+        };
+        assert_eq!(texts(&sink, "river -sea"), ["The river rose in the night."]);
+        assert_eq!(texts(&sink, "calm night").len(), 2);
+        assert_eq!(glob_pattern("a?[1]*.txt"), "a[?][[]1]*.txt");
+        assert_eq!(sink.search("river", Some("a.*"), 10).unwrap().len(), 2);
+        assert!(sink.search("river", Some("b*"), 10).unwrap().is_empty());
+
+        ingest_reader(
+            Cursor::new("Nothing here at all."),
+            "a.txt",
+            None,
+            &options,
+            &mut sink,
+        )
+        .await
+        .unwrap();
+        assert!(texts(&sink, "river").is_empty());
+    }
+
+    #[tokio::test]
+    async fn replacing_a_source_leaves_one_copy() {
+        let options = IngestOptions {
+            replace: true,
+            ..IngestOptions::default()
+        };
+        let mut sink =
+            SqliteSink::with_connection(rusqlite::Connection::open_in_memory().unwrap()).unwrap();
+        for input in [
+            "A first sentence is here.\n",
+            "The corrected first sentence.\n",
+        ] {
+            ingest_reader(Cursor::new(input), "a.txt", None, &options, &mut sink)
+                .await
+                .unwrap();
+        }
+        ingest_reader(
+            Cursor::new("Another file entirely."),
+            "b.txt",
+            None,
+            &options,
+            &mut sink,
+        )
+        .await
+        .unwrap();
+
+        let texts: Vec<String> = sink
+            .conn
+            .prepare("SELECT text FROM sentences ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            texts,
+            ["The corrected first sentence.", "Another file entirely."]
+        );
+    }
+}
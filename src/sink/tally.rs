@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use mongodb::bson::{Bson, Document};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// Sentences and words written from each source file, shared between the
+/// [`TallySink`]s counting them.
+#[derive(Debug, Clone, Default)]
+pub struct Tally {
+    files: Arc<Mutex<BTreeMap<String, (u64, u64)>>>,
+}
+
+impl Tally {
+    fn add(&self, document: &Document) {
+        let file = document.get_str("fileName").unwrap_or("?");
+        let words = match document.get("tokenCount").or(document.get("wordCount")) {
+            Some(Bson::Int32(n)) => *n as u64,
+            Some(Bson::Int64(n)) => *n as u64,
+            _ => 0,
+        };
+        let mut files = self.files.lock().unwrap();
+        let counts = match files.get_mut(file) {
+            Some(counts) => counts,
+            None => files.entry(file.to_string()).or_default(),
+        };
+        counts.0 += 1;
+        counts.1 += words;
+    }
+
+    /// A line per file with its counts, then one with the totals.
+    pub fn summary(&self) -> Vec<String> {
+        let files = self.files.lock().unwrap();
+        let mut lines: Vec<_> = files
+            .iter()
+            .map(|(file, (sentences, words))| {
+                format!("{}: {} sentences, {} words", file, sentences, words)
+            })
+            .collect();
+        let (sentences, words) = files.values().fold((0, 0), |total, counts| {
+            (total.0 + counts.0, total.1 + counts.1)
+        });
+        lines.push(format!(
+            "{} files: {} sentences, {} words",
+            files.len(),
+            sentences,
+            words
+        ));
+        lines
+    }
+}
+
+/// Counts the sentences passing through to another sink in a [`Tally`].
+pub struct TallySink {
+    inner: Box<dyn SentenceSink>,
+    tally: Tally,
+}
+
+impl TallySink {
+    pub fn new(inner: Box<dyn SentenceSink>, tally: Tally) -> Self {
+        TallySink { inner, tally }
+    }
+}
+
+#[async_trait]
+impl SentenceSink for TallySink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        for document in &batch {
+            self.tally.add(document);
+        }
+        self.inner.write_batch(batch).await
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        self.inner.flush().await
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        self.inner.remove_source(file_name).await
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        let inner = self.inner.try_clone()?;
+        Some(Box::new(TallySink::new(inner, self.tally.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::TextSink;
+    use mongodb::bson::doc;
+
+    #[tokio::test]
+    async fn counts_sentences_and_words_by_file() {
+        let tally = Tally::default();
+        let mut sink = TallySink::new(Box::new(TextSink::new(Vec::new())), tally.clone());
+        sink.write_batch(vec![
+            doc! { "text": "It rained.", "fileName": "b.txt", "tokenCount": 2 },
+            doc! { "text": "It poured.", "fileName": "a.txt", "wordCount": 2 },
+            doc! { "text": "Then it stopped.", "fileName": "b.txt", "tokenCount": 3 },
+        ])
+        .await
+        .unwrap();
+
+        assert_eq!(
+            tally.summary(),
+            [
+                "a.txt: 1 sentences, 2 words",
+                "b.txt: 2 sentences, 5 words",
+                "2 files: 3 sentences, 7 words"
+            ]
+        );
+    }
+}
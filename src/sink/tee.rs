@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+use std::str::FromStr;
+
+use super::SentenceSink;
+use crate::BoxError;
+
+/// Which of a [`TeeSink`]'s sinks have to keep working for a run to go on.
+/// A sink that fails without stopping the run is reported and left out from
+/// then on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TeePolicy {
+    /// Every sink: any failure stops the run.
+    #[default]
+    All,
+    /// The first sink, the main one; the others are copies.
+    First,
+    /// Any one sink: the run stops once every sink has failed.
+    Any,
+}
+
+impl FromStr for TeePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "all" => Ok(TeePolicy::All),
+            "first" => Ok(TeePolicy::First),
+            "any" => Ok(TeePolicy::Any),
+            other => Err(format!(
+                "invalid tee policy '{}' (expected all, first or any)",
+                other
+            )),
+        }
+    }
+}
+
+/// Writes every batch to each of several sinks in turn, for keeping a copy
+/// of what goes to the main one, the first.
+pub struct TeeSink {
+    /// Each sink with its name for messages, or `None` once it's failed.
+    sinks: Vec<(String, Option<Box<dyn SentenceSink>>)>,
+    policy: TeePolicy,
+}
+
+impl TeeSink {
+    pub fn new(sinks: Vec<(String, Box<dyn SentenceSink>)>) -> Self {
+        TeeSink {
+            sinks: sinks
+                .into_iter()
+                .map(|(name, sink)| (name, Some(sink)))
+                .collect(),
+            policy: TeePolicy::default(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: TeePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Stops the run over sink `index` failing with `e`, or leaves the sink
+    /// out, as the policy has it.
+    fn failed(&mut self, index: usize, e: BoxError) -> Result<(), BoxError> {
+        let name = self.sinks[index].0.clone();
+        let fatal = match self.policy {
+            TeePolicy::All => true,
+            TeePolicy::First => index == 0,
+            TeePolicy::Any => self
+                .sinks
+                .iter()
+                .enumerate()
+                .all(|(i, (_, sink))| i == index || sink.is_none()),
+        };
+        if fatal {
+            return Err(format!("{} sink: {}", name, e).into());
+        }
+        log::warn!(sink = name; "The {} sink failed and is left out: {}", name, e);
+        self.sinks[index].1 = None;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SentenceSink for TeeSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        for index in 0..self.sinks.len() {
+            let Some(sink) = self.sinks[index].1.as_mut() else {
+                continue;
+            };
+            if let Err(e) = sink.write_batch(batch.clone()).await {
+                self.failed(index, e)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        for index in 0..self.sinks.len() {
+            let Some(sink) = self.sinks[index].1.as_mut() else {
+                continue;
+            };
+            if let Err(e) = sink.flush().await {
+                self.failed(index, e)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the sentences from the first sink, the main one; copies only
+    /// ever get appended to.
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        match self.sinks.first_mut() {
+            Some((_, Some(sink))) => sink.remove_source(file_name).await,
+            _ => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use std::sync::{Arc, Mutex};
+
+    /// Keeps what's written, to be looked at after the sink is dropped, or
+    /// fails every write without one.
+    struct Recorder(Option<Arc<Mutex<Vec<Document>>>>);
+
+    #[async_trait]
+    impl SentenceSink for Recorder {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            match &self.0 {
+                Some(written) => written.lock().unwrap().extend(batch),
+                None => return Err("disk full".into()),
+            }
+            Ok(())
+        }
+    }
+
+    fn tee(sinks: Vec<Recorder>, policy: TeePolicy) -> TeeSink {
+        let sinks = sinks
+            .into_iter()
+            .enumerate()
+            .map(|(i, sink)| {
+                (
+                    format!("sink{}", i),
+                    Box::new(sink) as Box<dyn SentenceSink>,
+                )
+            })
+            .collect();
+        TeeSink::new(sinks).with_policy(policy)
+    }
+
+    #[tokio::test]
+    async fn writes_every_batch_to_every_sink() {
+        let main = Arc::new(Mutex::new(Vec::new()));
+        let copy = Arc::new(Mutex::new(Vec::new()));
+        let mut sink = tee(
+            vec![Recorder(Some(main.clone())), Recorder(Some(copy.clone()))],
+            TeePolicy::All,
+        );
+        sink.write_batch(vec![doc! { "text": "It rained." }])
+            .await
+            .unwrap();
+        sink.flush().await.unwrap();
+
+        assert_eq!(*main.lock().unwrap(), [doc! { "text": "It rained." }]);
+        assert_eq!(*copy.lock().unwrap(), *main.lock().unwrap());
+        assert!(sink.remove_source("a.txt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failures_stop_the_run_as_the_policy_says() {
+        let batch = || vec![doc! { "text": "It rained." }];
+        let written = Arc::new(Mutex::new(Vec::new()));
+
+        let mut all = tee(
+            vec![Recorder(Some(written.clone())), Recorder(None)],
+            TeePolicy::All,
+        );
+        let error = all.write_batch(batch()).await.unwrap_err();
+        assert_eq!(error.to_string(), "sink1 sink: disk full");
+
+        let mut first = tee(
+            vec![Recorder(Some(written.clone())), Recorder(None)],
+            TeePolicy::First,
+        );
+        first.write_batch(batch()).await.unwrap();
+        first.write_batch(batch()).await.unwrap();
+        let mut first = tee(
+            vec![Recorder(None), Recorder(Some(written.clone()))],
+            TeePolicy::First,
+        );
+        assert!(first.write_batch(batch()).await.is_err());
+
+        let mut any = tee(vec![Recorder(None), Recorder(None)], TeePolicy::Any);
+        assert_eq!(
+            any.write_batch(batch()).await.unwrap_err().to_string(),
+            "sink1 sink: disk full"
+        );
+        assert_eq!(written.lock().unwrap().len(), 3);
+    }
+}
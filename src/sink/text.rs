@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+use std::io::{Stdout, Write};
+use std::str::FromStr;
+
+use super::SentenceSink;
+use crate::decompress::create_output;
+use crate::BoxError;
+
+/// What each sentence written by a [`TextSink`] is numbered with, before a
+/// tab.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Numbering {
+    #[default]
+    None,
+    /// Its place in the output, from 1.
+    Sequence,
+    /// The line of its source it starts on.
+    Line,
+}
+
+impl FromStr for Numbering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Numbering::None),
+            "sequence" => Ok(Numbering::Sequence),
+            "line" => Ok(Numbering::Line),
+            other => Err(format!(
+                "invalid numbering '{}' (expected none, sequence or line)",
+                other
+            )),
+        }
+    }
+}
+
+/// Writes the `text` of each document on its own line.
+pub struct TextSink<W: Write> {
+    writer: W,
+    field: String,
+    numbering: Numbering,
+    /// Sentences written so far.
+    written: u64,
+}
+
+impl<W: Write> TextSink<W> {
+    pub fn new(writer: W) -> Self {
+        TextSink {
+            writer,
+            field: "text".to_string(),
+            numbering: Numbering::None,
+            written: 0,
+        }
+    }
+
+    /// Writes the field `name` instead of `text`.
+    pub fn with_field(mut self, name: &str) -> Self {
+        self.field = name.to_string();
+        self
+    }
+
+    pub fn with_numbering(mut self, numbering: Numbering) -> Self {
+        self.numbering = numbering;
+        self
+    }
+
+    /// Numbers the sentences in [sequence](Numbering::Sequence) on from
+    /// `written`, for adding them to a file holding that many.
+    pub fn starting_after(mut self, written: u64) -> Self {
+        self.written = written;
+        self
+    }
+
+    pub fn write_sentence(&mut self, sentence: &str) -> std::io::Result<()> {
+        writeln!(self.writer, "{}", sentence)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl TextSink<Box<dyn Write + Send>> {
+    /// Creates the file at `path`, compressed if its extension says so.
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        Ok(TextSink::new(create_output(path, None)?))
+    }
+}
+
+impl TextSink<Stdout> {
+    pub fn stdout() -> Self {
+        TextSink::new(std::io::stdout())
+    }
+}
+
+#[async_trait]
+impl<W: Write + Send> SentenceSink for TextSink<W> {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        for document in &batch {
+            let sentence = document.get_str(&self.field)?;
+            self.written += 1;
+            match self.numbering {
+                Numbering::None => self.write_sentence(sentence)?,
+                Numbering::Sequence => writeln!(self.writer, "{}\t{}", self.written, sentence)?,
+                Numbering::Line => {
+                    let line = document.get_i32("lineNumber").unwrap_or_default();
+                    writeln!(self.writer, "{}\t{}", line, sentence)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[tokio::test]
+    async fn writes_one_sentence_per_line() {
+        let mut sink = TextSink::new(Vec::new());
+        sink.write_batch(vec![
+            doc! { "text": "The first sentence is here.", "lineNumber": 1 },
+            doc! { "text": "The second one follows.", "lineNumber": 1 },
+        ])
+        .await
+        .unwrap();
+        sink.flush().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "The first sentence is here.\nThe second one follows.\n"
+        );
+
+        let mut numbered = TextSink::new(Vec::new()).with_numbering(Numbering::Sequence);
+        for line in [3, 5] {
+            numbered
+                .write_batch(vec![doc! { "text": "It rained.", "lineNumber": line }])
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(numbered.into_inner()).unwrap(),
+            "1\tIt rained.\n2\tIt rained.\n"
+        );
+        let mut by_line = TextSink::new(Vec::new()).with_numbering(Numbering::Line);
+        by_line
+            .write_batch(vec![doc! { "text": "It rained.", "lineNumber": 3 }])
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(by_line.into_inner()).unwrap(),
+            "3\tIt rained.\n"
+        );
+    }
+}
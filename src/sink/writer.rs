@@ -0,0 +1,301 @@
+use async_trait::async_trait;
+use mongodb::bson::Document;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+
+use super::SentenceSink;
+use crate::logging::Span;
+use crate::metrics::METRICS;
+use crate::BoxError;
+
+enum Command {
+    Write(Vec<Document>),
+    Flush(oneshot::Sender<Result<(), String>>),
+    RemoveSource(String, oneshot::Sender<Result<u64, String>>),
+}
+
+/// Hands batches to a writer task that owns the real sink, through a
+/// channel holding a bounded number of them. Writing a batch returns as soon
+/// as it's queued, so segmentation goes on while earlier ones are inserted,
+/// and waits when the queue is full, so a slow destination holds back
+/// segmentation instead of letting batches pile up in memory.
+///
+/// Sinks that can be cloned get up to `in_flight` batches written at once,
+/// each through its own clone, so the writer isn't idle for every network
+/// round trip; others get one at a time.
+///
+/// Each batch is written, and each flush is done, in the log [`Span`] it was
+/// handed over in, so what the sink logs is about the right file and batch.
+///
+/// The handle can be cloned for every worker; the writer starts batches in
+/// the order they were queued. A failed write is reported by the next call
+/// on any handle, and the writer drops everything after it. [`flush`]
+/// returns once every batch queued before it is written.
+///
+/// [`flush`]: SentenceSink::flush
+#[derive(Clone)]
+pub struct WriterSink {
+    commands: mpsc::Sender<(Span, Command)>,
+    /// The error the writer stopped on, if it did.
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+/// The writer task's state.
+struct Writer {
+    /// Handles to the sink not writing a batch right now.
+    idle: Vec<Box<dyn SentenceSink>>,
+    writing: JoinSet<(Box<dyn SentenceSink>, Result<(), BoxError>)>,
+    error: Option<String>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl Writer {
+    fn fail(&mut self, e: BoxError) {
+        let message = e.to_string();
+        *self.failure.lock().expect("writer failure poisoned") = Some(message.clone());
+        self.error.get_or_insert(message);
+    }
+
+    /// Waits for the next batch being written to finish.
+    async fn finish_one(&mut self) {
+        if let Some(finished) = self.writing.join_next().await {
+            match finished {
+                Ok((sink, result)) => {
+                    self.idle.push(sink);
+                    if let Err(e) = result {
+                        self.fail(e);
+                    }
+                }
+                Err(e) => self.fail(format!("a write panicked: {}", e).into()),
+            }
+        }
+    }
+
+    async fn finish_all(&mut self) {
+        while !self.writing.is_empty() {
+            self.finish_one().await;
+        }
+    }
+
+    /// The error the writer stopped on, as a result.
+    fn status(&self) -> Result<(), String> {
+        self.error.clone().map_or(Ok(()), Err)
+    }
+
+    async fn run(mut self, mut commands: mpsc::Receiver<(Span, Command)>) {
+        while let Some((span, command)) = commands.recv().await {
+            METRICS.set_queue_depth(commands.len());
+            match command {
+                Command::Write(batch) => {
+                    while self.idle.is_empty() {
+                        self.finish_one().await;
+                    }
+                    if self.error.is_some() {
+                        continue;
+                    }
+                    let mut sink = self.idle.pop().expect("an idle sink");
+                    self.writing.spawn(span.run(async move {
+                        let started = Instant::now();
+                        let result = sink.write_batch(batch).await;
+                        if result.is_ok() {
+                            METRICS.add_insert(started.elapsed());
+                        }
+                        (sink, result)
+                    }));
+                }
+                Command::Flush(reply) => {
+                    self.finish_all().await;
+                    if self.error.is_none() {
+                        let flushed = span.run(async {
+                            for sink in &mut self.idle {
+                                sink.flush().await?;
+                            }
+                            Ok(())
+                        });
+                        if let Err(e) = flushed.await {
+                            self.fail(e);
+                        }
+                    }
+                    let _ = reply.send(self.status());
+                }
+                Command::RemoveSource(file_name, reply) => {
+                    self.finish_all().await;
+                    let result = match self.status() {
+                        Ok(()) => span
+                            .run(self.idle[0].remove_source(&file_name))
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e),
+                    };
+                    let _ = reply.send(result);
+                }
+            }
+        }
+        self.finish_all().await;
+    }
+}
+
+impl WriterSink {
+    /// Starts a writer task for `sink` with room for `queue` batches, and
+    /// `in_flight` being written if `sink` can be cloned.
+    pub fn spawn(sink: Box<dyn SentenceSink>, queue: usize, in_flight: usize) -> Self {
+        let (commands, received) = mpsc::channel(queue.max(1));
+        let failure = Arc::new(Mutex::new(None));
+        let mut idle: Vec<_> = (1..in_flight.max(1))
+            .map_while(|_| sink.try_clone())
+            .collect();
+        idle.push(sink);
+        let writer = Writer {
+            idle,
+            writing: JoinSet::new(),
+            error: None,
+            failure: Arc::clone(&failure),
+        };
+        tokio::spawn(writer.run(received));
+        WriterSink { commands, failure }
+    }
+
+    fn failure(&self) -> Option<BoxError> {
+        let failure = self.failure.lock().expect("writer failure poisoned");
+        failure.as_ref().map(|e| e.clone().into())
+    }
+
+    async fn send(&self, command: Command) -> Result<(), BoxError> {
+        if let Some(e) = self.failure() {
+            return Err(e);
+        }
+        self.commands
+            .send((Span::current(), command))
+            .await
+            .map_err(|_| "the writer task stopped".into())
+    }
+}
+
+#[async_trait]
+impl SentenceSink for WriterSink {
+    async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+        self.send(Command::Write(batch)).await?;
+        METRICS.set_queue_depth(self.commands.max_capacity() - self.commands.capacity());
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), BoxError> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::Flush(reply)).await?;
+        result.await.map_err(|_| "the writer task stopped")??;
+        Ok(())
+    }
+
+    async fn remove_source(&mut self, file_name: &str) -> Result<u64, BoxError> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::RemoveSource(file_name.to_string(), reply))
+            .await?;
+        Ok(result.await.map_err(|_| "the writer task stopped")??)
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Collects documents where the test can see them, failing on a batch
+    /// holding `fail_on`.
+    struct Collect {
+        documents: Arc<Mutex<Vec<Document>>>,
+        fail_on: Option<i32>,
+    }
+
+    #[async_trait]
+    impl SentenceSink for Collect {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            if batch.iter().any(|d| d.get_i32("n").ok() == self.fail_on) {
+                return Err("disk full".into());
+            }
+            self.documents.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_queued_batches_in_order() {
+        let documents = Arc::new(Mutex::new(Vec::new()));
+        let sink = Collect {
+            documents: Arc::clone(&documents),
+            fail_on: None,
+        };
+        let mut writer = WriterSink::spawn(Box::new(sink), 1, 1);
+        let mut other = writer.try_clone().unwrap();
+        for n in 0..4 {
+            writer.write_batch(vec![doc! { "n": n }]).await.unwrap();
+            other.write_batch(vec![doc! { "n": n + 10 }]).await.unwrap();
+        }
+        other.flush().await.unwrap();
+
+        let written: Vec<_> = documents
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|d| d.get_i32("n").unwrap())
+            .collect();
+        assert_eq!(written, [0, 10, 1, 11, 2, 12, 3, 13]);
+    }
+
+    /// Counts the writes going on at once.
+    #[derive(Clone, Default)]
+    struct Slow {
+        active: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+        written: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl SentenceSink for Slow {
+        async fn write_batch(&mut self, batch: Vec<Document>) -> Result<(), BoxError> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(active, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            self.written.fetch_add(batch.len(), Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn try_clone(&self) -> Option<Box<dyn SentenceSink>> {
+            Some(Box::new(self.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_batches_concurrently_up_to_the_limit() {
+        let sink = Slow::default();
+        let mut writer = WriterSink::spawn(Box::new(sink.clone()), 8, 3);
+        for n in 0..9 {
+            writer.write_batch(vec![doc! { "n": n }]).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+        assert_eq!(sink.written.load(Ordering::SeqCst), 9);
+        assert_eq!(sink.peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reports_a_failed_write_on_the_next_call() {
+        let sink = Collect {
+            documents: Arc::default(),
+            fail_on: Some(1),
+        };
+        let mut writer = WriterSink::spawn(Box::new(sink), 4, 1);
+        writer.write_batch(vec![doc! { "n": 1 }]).await.unwrap();
+        let error = writer.flush().await.unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+        assert!(writer.write_batch(vec![doc! { "n": 2 }]).await.is_err());
+        assert!(writer.remove_source("a.txt").await.is_err());
+    }
+}
@@ -0,0 +1,423 @@
+//! Where corpus text comes from.
+//!
+//! The ingestion loop reads through [`CorpusSource`], so new kinds of input
+//! only need an implementation here and a case in [`source_for`].
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::archive::{ArchiveKind, ArchiveSource};
+use crate::object_store::{self, ObjectSource, ObjectStore, ObjectUri};
+use crate::sink::{http_is_transient, RetryPolicy};
+use crate::BoxError;
+
+/// One document inside a container source such as an archive.
+pub struct Member {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// A named stream of corpus text.
+pub trait CorpusSource: Send + Sync {
+    /// Name stored as `fileName` on every sentence from this source.
+    fn name(&self) -> &str;
+
+    /// Opens the source for reading from the start.
+    fn open(&self) -> Result<Box<dyn BufRead + Send>, BoxError>;
+
+    /// The local file read, for sources that are one.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Whether `open` can be called more than once, as two-pass mode needs.
+    fn rereadable(&self) -> bool {
+        true
+    }
+
+    /// For containers such as archives: the documents inside, each ingested
+    /// under its own name. Plain sources return `None` and are read with
+    /// `open`.
+    fn members(&self) -> Option<Box<dyn Iterator<Item = Result<Member, BoxError>> + Send>> {
+        None
+    }
+}
+
+/// A file on the local filesystem.
+pub struct FileSource {
+    path: String,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<String>) -> Self {
+        FileSource { path: path.into() }
+    }
+}
+
+impl CorpusSource for FileSource {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(Path::new(&self.path))
+    }
+
+    fn open(&self) -> Result<Box<dyn BufRead + Send>, BoxError> {
+        let file =
+            File::open(&self.path).map_err(|e| format!("Error opening {}: {}", self.path, e))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Standard input, which can only be read once.
+pub struct StdinSource {
+    name: String,
+}
+
+impl StdinSource {
+    pub fn new(name: impl Into<String>) -> Self {
+        StdinSource { name: name.into() }
+    }
+}
+
+impl CorpusSource for StdinSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn open(&self) -> Result<Box<dyn BufRead + Send>, BoxError> {
+        Ok(Box::new(BufReader::new(std::io::stdin())))
+    }
+
+    fn rereadable(&self) -> bool {
+        false
+    }
+}
+
+/// A document fetched over HTTP(S). The body is streamed, not downloaded
+/// up front, and a download that breaks off is resumed where it stopped.
+pub struct UrlSource {
+    url: String,
+    retry: RetryPolicy,
+}
+
+impl UrlSource {
+    pub fn new(url: impl Into<String>) -> Self {
+        UrlSource {
+            url: url.into(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+impl CorpusSource for UrlSource {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn open(&self) -> Result<Box<dyn BufRead + Send>, BoxError> {
+        let body = self
+            .retry
+            .run_blocking(&format!("fetch {}", self.url), http_is_transient, |_| {
+                request_from(&self.url, 0)
+            })
+            .map_err(|e| format!("Error fetching {}: {}", self.url, e))?;
+        Ok(Box::new(BufReader::new(Download {
+            url: self.url.clone(),
+            retry: self.retry,
+            body,
+            offset: 0,
+        })))
+    }
+}
+
+/// Requests `url` from byte `offset` on, skipping what's sent before it if
+/// the server ignores the range.
+fn request_from(url: &str, offset: u64) -> Result<Box<dyn Read + Send>, ureq::Error> {
+    if offset == 0 {
+        return Ok(Box::new(ureq::get(url).call()?.into_body().into_reader()));
+    }
+    let response = ureq::get(url)
+        .header("Range", format!("bytes={}-", offset))
+        .call()?;
+    let ranged = response.status() == 206;
+    let mut body = response.into_body().into_reader();
+    if !ranged {
+        std::io::copy(&mut (&mut body).take(offset), &mut std::io::sink())?;
+    }
+    Ok(Box::new(body))
+}
+
+/// A response body that reconnects when reading it fails, asking for the
+/// rest from where it broke off.
+struct Download {
+    url: String,
+    retry: RetryPolicy,
+    body: Box<dyn Read + Send>,
+    /// Bytes read so far.
+    offset: u64,
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut attempt = 1;
+        loop {
+            let error = match self.body.read(buf) {
+                Ok(read) => {
+                    self.offset += read as u64;
+                    return Ok(read);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => e,
+            };
+            if attempt >= self.retry.max_attempts {
+                return Err(error);
+            }
+            let what = format!("read {} from byte {}", self.url, self.offset);
+            std::thread::sleep(self.retry.log_retry(attempt, &what, &error));
+            attempt += 1;
+            // A reconnection that fails counts as another attempt, in the
+            // next round, on reading from the broken body again
+            if let Ok(body) = request_from(&self.url, self.offset) {
+                self.body = body;
+            }
+        }
+    }
+}
+
+/// Settings that shape how input arguments become sources.
+#[derive(Debug, Clone)]
+pub struct InputOptions {
+    /// `fileName` recorded for text read from stdin.
+    pub stdin_name: String,
+    /// How failed downloads of URLs are retried.
+    pub retry: RetryPolicy,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        InputOptions {
+            stdin_name: "stdin".to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// Picks the source for an input argument: `-` is stdin, `http://` and
+/// `https://` are fetched, `s3://` and `gs://` are objects in a bucket, tar
+/// and zip archives are read by member, and anything else is a local file.
+pub fn source_for(input: &str, options: &InputOptions) -> Box<dyn CorpusSource> {
+    if input == "-" {
+        Box::new(StdinSource::new(options.stdin_name.clone()))
+    } else if input.starts_with("http://") || input.starts_with("https://") {
+        Box::new(UrlSource::new(input).with_retry(options.retry))
+    } else if let Some(uri) = ObjectUri::parse(input) {
+        Box::new(ObjectSource::new(&uri))
+    } else {
+        local_source(input)
+    }
+}
+
+fn local_source(path: &str) -> Box<dyn CorpusSource> {
+    match ArchiveKind::from_path(path) {
+        Some(kind) => Box::new(ArchiveSource::new(path, kind)),
+        None => Box::new(FileSource::new(path)),
+    }
+}
+
+/// Expands an input argument into every source it names. Directories are
+/// walked recursively and glob patterns (`corpus/**/*.txt`) are matched
+/// against the filesystem; both yield files in sorted order. `s3://` and
+/// `gs://` URIs are listed, as [`object_store`] describes.
+pub fn expand_input(
+    input: &str,
+    options: &InputOptions,
+) -> Result<Vec<Box<dyn CorpusSource>>, BoxError> {
+    if let Some(uri) = ObjectUri::parse(input) {
+        return object_store::expand(&uri, ObjectStore::from_env(uri.service));
+    }
+    let paths = if input.contains(['*', '?', '[']) {
+        let mut paths = Vec::new();
+        for entry in glob::glob(input)? {
+            let path = entry?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        if paths.is_empty() {
+            return Err(format!("no files match {}", input).into());
+        }
+        paths
+    } else if Path::new(input).is_dir() {
+        let mut paths = Vec::new();
+        collect_files(Path::new(input), &mut paths)
+            .map_err(|e| format!("Error reading directory {}: {}", input, e))?;
+        paths
+    } else {
+        return Ok(vec![source_for(input, options)]);
+    };
+
+    Ok(paths
+        .into_iter()
+        .map(|path| local_source(&path.to_string_lossy()))
+        .collect())
+}
+
+fn collect_files(dir: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_files(&path, paths)?;
+        } else if path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn file_source_can_be_opened_repeatedly() {
+        let path = std::env::temp_dir().join("rustparser_file_source_test.txt");
+        std::fs::write(&path, "One line of text.\n").unwrap();
+        let source = FileSource::new(path.to_str().unwrap());
+
+        for _ in 0..2 {
+            let mut text = String::new();
+            source.open().unwrap().read_to_string(&mut text).unwrap();
+            assert_eq!(text, "One line of text.\n");
+        }
+        assert!(source.rereadable());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn expands_directories_and_globs() {
+        let root = std::env::temp_dir().join("rustparser_expand_input_test");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("news/2021")).unwrap();
+        for file in [
+            "a.txt",
+            "news/b.txt",
+            "news/2021/c.txt",
+            "news/2021/notes.md",
+        ] {
+            std::fs::write(root.join(file), "Some text.\n").unwrap();
+        }
+        let names = |input: &Path| -> Vec<String> {
+            expand_input(input.to_str().unwrap(), &InputOptions::default())
+                .unwrap()
+                .iter()
+                .map(|s| {
+                    s.name()
+                        .strip_prefix(root.to_str().unwrap())
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            names(&root),
+            vec![
+                "/a.txt",
+                "/news/2021/c.txt",
+                "/news/2021/notes.md",
+                "/news/b.txt"
+            ]
+        );
+        assert_eq!(
+            names(&root.join("**/*.txt")),
+            vec!["/a.txt", "/news/2021/c.txt", "/news/b.txt"]
+        );
+        assert!(expand_input(
+            root.join("*.csv").to_str().unwrap(),
+            &InputOptions::default()
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn resumes_broken_downloads() {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        // Breaks off the first answer halfway and sends the rest of the
+        // body for the range asked for next
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a.txt", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut ranges = Vec::new();
+            for answer in [
+                "200 OK\r\nContent-Length: 22\r\n\r\nIt rained.\n",
+                "206 Partial Content\r\nContent-Length: 11\r\n\r\nIt poured.\n",
+            ] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(range) = header.strip_prefix("range: ") {
+                        ranges.push(range.trim().to_string());
+                    }
+                }
+                write!(reader.get_mut(), "HTTP/1.1 {}", answer).unwrap();
+            }
+            ranges
+        });
+
+        let retry = RetryPolicy {
+            max_attempts: 2,
+            base_delay: std::time::Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let mut text = String::new();
+        UrlSource::new(url)
+            .with_retry(retry)
+            .open()
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "It rained.\nIt poured.\n");
+        assert_eq!(server.join().unwrap(), ["bytes=11-"]);
+    }
+
+    #[test]
+    fn picks_source_from_input() {
+        let options = InputOptions {
+            stdin_name: "piped.txt".to_string(),
+            ..InputOptions::default()
+        };
+        assert!(!source_for("-", &options).rereadable());
+        assert_eq!(source_for("-", &options).name(), "piped.txt");
+        assert_eq!(
+            source_for("https://example.com/a.txt", &options).name(),
+            "https://example.com/a.txt"
+        );
+        assert_eq!(
+            source_for("gs://corpus/a.txt", &options).name(),
+            "gs://corpus/a.txt"
+        );
+        assert_eq!(source_for("corpus/a.txt", &options).name(), "corpus/a.txt");
+    }
+}
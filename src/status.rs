@@ -0,0 +1,372 @@
+//! The status line an ingestion run keeps up to date on stderr in place of
+//! a message per line and sentence: bytes read, lines of those counted in
+//! two-pass mode, sentences and their rate, batches written and, when the
+//! input's size or line count is known, the time left.
+//!
+//! On a terminal the line is redrawn in place, behind a progress bar when
+//! there's a total to measure against. Log records are printed through
+//! [`print_above`], which keeps the status line under them.
+
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Characters of the progress bar drawn on a terminal.
+const BAR_WIDTH: usize = 24;
+/// How often a terminal's status line is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the status is printed as a line of its own when stderr isn't a
+/// terminal, such as a log file.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The status line being redrawn on the terminal, if one is.
+static ON_SCREEN: Mutex<Option<StatusLine>> = Mutex::new(None);
+
+/// How the status is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Visibility {
+    /// Not shown.
+    #[default]
+    Hidden,
+    /// Redrawn in place on a terminal, and printed now and then to anything
+    /// else.
+    Shown,
+}
+
+impl Visibility {
+    /// `Hidden` for a quiet run, and otherwise `Shown`.
+    pub fn for_flags(quiet: bool) -> Self {
+        if quiet {
+            Visibility::Hidden
+        } else {
+            Visibility::Shown
+        }
+    }
+}
+
+/// Prints `message` on stderr, above the status line if one is on screen.
+pub fn print_above(message: &str) {
+    let on_screen = ON_SCREEN.lock().unwrap();
+    match on_screen.as_ref() {
+        Some(status) => {
+            eprint!("\r\x1b[2K{}\n", message);
+            status.draw();
+        }
+        None => eprintln!("{}", message),
+    }
+}
+
+struct Counters {
+    visibility: Visibility,
+    started: Instant,
+    /// Bytes of input in all, if known.
+    total_bytes: Option<u64>,
+    bytes: AtomicU64,
+    /// Lines counted in two-pass mode, in the sources begun so far, and
+    /// how many of those were read.
+    total_lines: AtomicU64,
+    lines: AtomicU64,
+    sentences: AtomicU64,
+    batches: AtomicU64,
+    /// Whether the status line is on a terminal, to be cleared before
+    /// printing a log record.
+    on_terminal: bool,
+    /// Whether the drawing thread should stop.
+    finished: AtomicBool,
+    drawer: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// Counts what an ingestion run has done, shared by its workers, and shows
+/// it as its [`Visibility`] says. Clones count into the same status.
+#[derive(Clone)]
+pub struct StatusLine {
+    counters: Arc<Counters>,
+}
+
+impl Default for StatusLine {
+    fn default() -> Self {
+        StatusLine::new(Visibility::Hidden, None)
+    }
+}
+
+impl StatusLine {
+    /// A status for reading `total_bytes` of input, if known. Call
+    /// [`start`](Self::start) to have it shown.
+    pub fn new(visibility: Visibility, total_bytes: Option<u64>) -> Self {
+        StatusLine {
+            counters: Arc::new(Counters {
+                visibility,
+                started: Instant::now(),
+                total_bytes,
+                bytes: AtomicU64::new(0),
+                total_lines: AtomicU64::new(0),
+                lines: AtomicU64::new(0),
+                sentences: AtomicU64::new(0),
+                batches: AtomicU64::new(0),
+                on_terminal: visibility == Visibility::Shown && std::io::stderr().is_terminal(),
+                finished: AtomicBool::new(false),
+                drawer: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Starts showing the status on a thread of its own, if it's to be
+    /// shown, until [`finish`](Self::finish).
+    pub fn start(&self) {
+        if self.counters.visibility != Visibility::Shown {
+            return;
+        }
+        let status = self.clone();
+        let interval = if self.counters.on_terminal {
+            REDRAW_INTERVAL
+        } else {
+            LOG_INTERVAL
+        };
+        let drawer = thread::spawn(move || {
+            let mut last = Instant::now();
+            while !status.counters.finished.load(Ordering::Relaxed) {
+                thread::sleep(REDRAW_INTERVAL.min(interval));
+                if last.elapsed() >= interval {
+                    status.draw();
+                    last = Instant::now();
+                }
+            }
+        });
+        *self.counters.drawer.lock().unwrap() = Some(drawer);
+        if self.counters.on_terminal {
+            *ON_SCREEN.lock().unwrap() = Some(self.clone());
+        }
+    }
+
+    /// Stops showing the status, leaving its last state on screen.
+    pub fn finish(&self) {
+        self.counters.finished.store(true, Ordering::Relaxed);
+        let drawer = self.counters.drawer.lock().unwrap().take();
+        if let Some(drawer) = drawer {
+            let _ = drawer.join();
+            ON_SCREEN.lock().unwrap().take();
+            self.draw();
+            if self.counters.on_terminal {
+                eprintln!();
+            }
+        }
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Adds the lines two-pass mode counted in a source about to be read.
+    pub fn add_total_lines(&self, lines: u64) {
+        self.counters
+            .total_lines
+            .fetch_add(lines, Ordering::Relaxed);
+    }
+
+    /// Counts a line read of a source whose lines were counted.
+    pub fn add_line(&self) {
+        self.counters.lines.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add_sentences(&self, sentences: u64) {
+        self.counters
+            .sentences
+            .fetch_add(sentences, Ordering::Relaxed);
+    }
+
+    /// Bytes of input read so far.
+    pub fn bytes(&self) -> u64 {
+        self.counters.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Counts a batch written, returning how many there have been.
+    pub fn add_batch(&self) -> u64 {
+        self.counters.batches.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// `reader` with what's read from it counted as input bytes.
+    pub fn counting<R: BufRead>(&self, reader: R) -> CountingReader<R> {
+        CountingReader {
+            reader,
+            status: self.clone(),
+        }
+    }
+
+    fn draw(&self) {
+        let line = self.line();
+        if self.counters.on_terminal {
+            let bar = self.done().map(progress_bar).unwrap_or_default();
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[2K{}{}", bar, line);
+            let _ = stderr.flush();
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+
+    /// The status as the line shown, such as `12.0 MiB / 40.0 MiB (30%),
+    /// 51200 sentences (1280/s), 52 batches, 1m 10s left`, with the lines as
+    /// `1200 / 4000 lines (30%)` after the bytes when they were counted.
+    pub fn line(&self) -> String {
+        self.line_after(self.counters.started.elapsed())
+    }
+
+    fn line_after(&self, elapsed: Duration) -> String {
+        let counters = &self.counters;
+        let bytes = counters.bytes.load(Ordering::Relaxed);
+        let sentences = counters.sentences.load(Ordering::Relaxed);
+        let batches = counters.batches.load(Ordering::Relaxed);
+        let seconds = elapsed.as_secs_f64().max(0.001);
+
+        let mut line = match counters.total_bytes {
+            Some(total) if total > 0 => format!(
+                "{} / {} ({:.0}%)",
+                format_bytes(bytes),
+                format_bytes(total),
+                bytes.min(total) as f64 * 100.0 / total as f64
+            ),
+            _ => format_bytes(bytes),
+        };
+        let total_lines = counters.total_lines.load(Ordering::Relaxed);
+        let lines = counters.lines.load(Ordering::Relaxed).min(total_lines);
+        if total_lines > 0 {
+            line.push_str(&format!(
+                ", {} / {} lines ({:.0}%)",
+                lines,
+                total_lines,
+                lines as f64 * 100.0 / total_lines as f64
+            ));
+        }
+        line.push_str(&format!(
+            ", {} sentences ({:.0}/s), {} batches",
+            sentences,
+            sentences as f64 / seconds,
+            batches
+        ));
+        if let Some(done) = self.done().filter(|&done| done > 0.0) {
+            let left = seconds * (1.0 - done) / done;
+            line.push_str(&format!(", {} left", format_duration(left)));
+        }
+        line
+    }
+
+    /// The share of the input read, by its size or else by the lines
+    /// counted, if either is known.
+    fn done(&self) -> Option<f64> {
+        let counters = &self.counters;
+        let bytes = counters.bytes.load(Ordering::Relaxed);
+        let total_lines = counters.total_lines.load(Ordering::Relaxed);
+        let lines = counters.lines.load(Ordering::Relaxed).min(total_lines);
+        match counters.total_bytes.filter(|&total| total > 0) {
+            Some(total) => Some(bytes.min(total) as f64 / total as f64),
+            None => (total_lines > 0).then(|| lines as f64 / total_lines as f64),
+        }
+    }
+}
+
+/// A bar [`BAR_WIDTH`] wide filled to the share `done`, such as
+/// `[=========>              ] `.
+fn progress_bar(done: f64) -> String {
+    let filled = ((done.clamp(0.0, 1.0) * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+    let head = if filled < BAR_WIDTH { ">" } else { "" };
+    format!(
+        "[{}{}{}] ",
+        "=".repeat(filled),
+        head,
+        " ".repeat(BAR_WIDTH - filled - head.len())
+    )
+}
+
+/// A reader counting the bytes read through it into a [`StatusLine`].
+pub struct CountingReader<R> {
+    reader: R,
+    status: StatusLine,
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.status.add_bytes(read as u64);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.status.add_bytes(amount as u64);
+        self.reader.consume(amount);
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_rates_and_time_left() {
+        let status = StatusLine::new(Visibility::Hidden, Some(4 * 1024 * 1024));
+        let mut text = String::new();
+        status
+            .counting(&[b'x'; 1024 * 1024][..])
+            .read_to_string(&mut text)
+            .unwrap();
+        status.add_sentences(500);
+        status.add_batch();
+        assert_eq!(
+            status.line_after(Duration::from_secs(10)),
+            "1.0 MiB / 4.0 MiB (25%), 500 sentences (50/s), 1 batches, 30s left"
+        );
+
+        let unknown_size = StatusLine::new(Visibility::Hidden, None);
+        unknown_size.add_bytes(512);
+        assert_eq!(
+            unknown_size.line_after(Duration::from_secs(1)),
+            "512 B, 0 sentences (0/s), 0 batches"
+        );
+        assert_eq!(format_duration(4000.0), "1h 06m");
+        assert_eq!(
+            progress_bar(0.5),
+            format!("[{}>{}] ", "=".repeat(12), " ".repeat(11))
+        );
+        assert_eq!(progress_bar(1.0), format!("[{}] ", "=".repeat(24)));
+
+        let counted = StatusLine::new(Visibility::Hidden, None);
+        counted.add_total_lines(400);
+        for _ in 0..100 {
+            counted.add_line();
+        }
+        assert_eq!(
+            counted.line_after(Duration::from_secs(20)),
+            "0 B, 100 / 400 lines (25%), 0 sentences (0/s), 0 batches, 1m 00s left"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Stopwords left out of the derived frequency and n-gram tables, so
+//! function words don't crowd out everything else. The sentences themselves
+//! are stored whole.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::BoxError;
+
+/// The built-in lists, by ISO 639-3 code.
+const BUILT_IN: &[(&str, &str)] = &[
+    ("deu", include_str!("stopwords/deu.txt")),
+    ("eng", include_str!("stopwords/eng.txt")),
+    ("fra", include_str!("stopwords/fra.txt")),
+    ("spa", include_str!("stopwords/spa.txt")),
+];
+
+fn words(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+}
+
+/// Stopwords by language, and ones that are stopwords whatever the language.
+#[derive(Debug, Clone, Default)]
+pub struct Stopwords {
+    by_language: HashMap<String, HashSet<String>>,
+    everywhere: HashSet<String>,
+}
+
+impl Stopwords {
+    pub fn new() -> Self {
+        Stopwords::default()
+    }
+
+    /// Adds the built-in list for the language with ISO 639-3 `code`.
+    pub fn add_language(&mut self, code: &str) -> Result<(), BoxError> {
+        let code = code.to_ascii_lowercase();
+        let Some((_, list)) = BUILT_IN.iter().find(|(language, _)| *language == code) else {
+            let known: Vec<_> = BUILT_IN.iter().map(|(language, _)| *language).collect();
+            return Err(format!(
+                "no stopword list for '{}' (expected one of {})",
+                code,
+                known.join(", ")
+            )
+            .into());
+        };
+        self.by_language
+            .entry(code)
+            .or_default()
+            .extend(words(list));
+        Ok(())
+    }
+
+    /// Adds the words in the file at `path`, one per line, as stopwords in
+    /// every language. Blank lines and lines starting with `#` are skipped.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), BoxError> {
+        let path = path.as_ref();
+        let list = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading stopwords {}: {}", path.display(), e))?;
+        self.everywhere.extend(words(&list));
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_language.is_empty() && self.everywhere.is_empty()
+    }
+
+    /// Whether lowercased `word` is a stopword in a sentence in `lang`. When
+    /// the language isn't known, every list applies.
+    pub fn contains(&self, word: &str, lang: Option<&str>) -> bool {
+        if self.everywhere.contains(word) {
+            return true;
+        }
+        match lang {
+            Some(lang) => self
+                .by_language
+                .get(lang)
+                .is_some_and(|list| list.contains(word)),
+            None => self.by_language.values().any(|list| list.contains(word)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_lists_by_language() {
+        let mut stopwords = Stopwords::new();
+        stopwords.add_language("ENG").unwrap();
+        stopwords.add_language("fra").unwrap();
+        assert!(stopwords.add_language("xyz").is_err());
+
+        assert!(stopwords.contains("the", Some("eng")));
+        assert!(!stopwords.contains("the", Some("fra")));
+        assert!(stopwords.contains("les", None));
+        assert!(!stopwords.contains("river", None));
+
+        let path = std::env::temp_dir().join(format!("stopwords-{}.txt", std::process::id()));
+        std::fs::write(&path, "# corpus-specific\nSaid\n").unwrap();
+        stopwords.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(stopwords.contains("said", Some("deu")));
+    }
+}
@@ -0,0 +1,171 @@
+//! TF-IDF weights of the words of each source file, telling which words
+//! mark a file out from the rest of the corpus, for keyword extraction and
+//! comparing files.
+
+use mongodb::bson::{doc, Document};
+use mongodb::Collection;
+use std::collections::HashMap;
+
+use crate::freq::replace;
+use crate::stopword::Stopwords;
+use crate::token::Tokenizer;
+use crate::BoxError;
+
+/// Collection the weights are kept in, next to the sentences.
+pub const TFIDF_COLLECTION: &str = "tfidf";
+
+#[derive(Debug, Clone, Default)]
+struct FileCounts {
+    terms: HashMap<String, u64>,
+    total: u64,
+}
+
+/// Counts of the lowercased words of each file.
+#[derive(Debug, Clone, Default)]
+pub struct TermWeights {
+    files: HashMap<String, FileCounts>,
+    stopwords: Stopwords,
+}
+
+impl TermWeights {
+    pub fn new() -> Self {
+        TermWeights::default()
+    }
+
+    /// Leaves `stopwords` out when counting words.
+    pub fn with_stopwords(mut self, stopwords: Stopwords) -> Self {
+        self.stopwords = stopwords;
+        self
+    }
+
+    /// Counts the words of `sentence`, from `file` and in `lang`.
+    pub fn add_sentence(
+        &mut self,
+        file: &str,
+        sentence: &str,
+        tokenizer: Tokenizer,
+        lang: Option<&str>,
+    ) {
+        let counts = match self.files.get_mut(file) {
+            Some(counts) => counts,
+            None => self.files.entry(file.to_string()).or_default(),
+        };
+        for token in tokenizer.tokens(sentence) {
+            let word = token.to_lowercase();
+            if !self.stopwords.contains(&word, lang) {
+                *counts.terms.entry(word).or_default() += 1;
+                counts.total += 1;
+            }
+        }
+    }
+
+    /// How many files were counted.
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// A document per file and word, holding its name under `file_field`,
+    /// the `term`, its `count` and share `tf` of the file's words, the `df`
+    /// of files it's in and its `tfidf` weight, tf × ln(files / df). Only
+    /// the `top` weightiest words of each file are kept, if given.
+    pub fn weights(&self, file_field: &str, top: Option<usize>) -> Vec<Document> {
+        let mut df: HashMap<&str, u64> = HashMap::new();
+        for counts in self.files.values() {
+            for term in counts.terms.keys() {
+                *df.entry(term).or_default() += 1;
+            }
+        }
+        let files = self.files.len() as f64;
+
+        let mut names: Vec<_> = self.files.keys().collect();
+        names.sort();
+        let mut documents = Vec::new();
+        for name in names {
+            let counts = &self.files[name];
+            let mut weights: Vec<_> = counts
+                .terms
+                .iter()
+                .map(|(term, &count)| {
+                    let tf = count as f64 / counts.total as f64;
+                    let df = df[term.as_str()];
+                    (term, count, tf, df, tf * (files / df as f64).ln())
+                })
+                .collect();
+            weights.sort_by(|a, b| b.4.total_cmp(&a.4).then_with(|| a.0.cmp(b.0)));
+            weights.truncate(top.unwrap_or(usize::MAX));
+            documents.extend(weights.into_iter().map(|(term, count, tf, df, tfidf)| {
+                doc! {
+                    file_field: name,
+                    "term": term,
+                    "count": count as i64,
+                    "tf": tf,
+                    "df": df as i64,
+                    "tfidf": tfidf,
+                }
+            }));
+        }
+        documents
+    }
+}
+
+/// Replaces the contents of `collection` with the weights of `weights`,
+/// keeping the `top` of each file, whose name goes under `file_field`.
+/// Returns how many were written.
+pub async fn store(
+    weights: &TermWeights,
+    collection: &Collection<Document>,
+    file_field: &str,
+    top: Option<usize>,
+) -> Result<usize, BoxError> {
+    let documents = weights.weights(file_field, top);
+    let key = doc! { file_field: 1, "term": 1 };
+    replace(
+        collection,
+        &documents,
+        key,
+        doc! { file_field: 1, "tfidf": -1 },
+    )
+    .await?;
+    Ok(documents.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighs_words_by_how_few_files_have_them() {
+        let mut weights = TermWeights::new();
+        weights.add_sentence(
+            "a.txt",
+            "The river floods. The river rises.",
+            Tokenizer::Unicode,
+            None,
+        );
+        weights.add_sentence("b.txt", "The market rises.", Tokenizer::Unicode, None);
+        assert_eq!(weights.files(), 2);
+
+        let documents = weights.weights("fileName", Some(2));
+        let top: Vec<_> = documents
+            .iter()
+            .map(|d| (d.get_str("fileName").unwrap(), d.get_str("term").unwrap()))
+            .collect();
+        assert_eq!(
+            top,
+            [
+                ("a.txt", "river"),
+                ("a.txt", "floods"),
+                ("b.txt", "market"),
+                ("b.txt", "rises")
+            ]
+        );
+
+        let river = &documents[0];
+        assert_eq!(river.get_i64("count"), Ok(2));
+        assert_eq!(river.get_i64("df"), Ok(1));
+        let expected = 2.0 / 6.0 * 2.0_f64.ln();
+        assert!((river.get_f64("tfidf").unwrap() - expected).abs() < 1e-9);
+        // In every file, so no weight at all
+        assert_eq!(documents[3].get_f64("tfidf"), Ok(0.0));
+    }
+}
@@ -0,0 +1,61 @@
+//! Splitting sentences into the tokens stored with them as `tokenCount` and
+//! `tokens`, which frequency tables and length queries are built on.
+
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How a sentence is split into tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// UAX #29 word boundaries: punctuation is dropped, and contractions
+    /// such as "don't" and numbers such as "3.5" stay whole.
+    #[default]
+    Unicode,
+    /// Runs of non-whitespace, keeping punctuation attached to its word.
+    Whitespace,
+}
+
+impl FromStr for Tokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "unicode" => Ok(Tokenizer::Unicode),
+            "whitespace" => Ok(Tokenizer::Whitespace),
+            other => Err(format!(
+                "invalid tokenizer '{}' (expected unicode or whitespace)",
+                other
+            )),
+        }
+    }
+}
+
+impl Tokenizer {
+    /// The tokens of `sentence`, as they're written in it.
+    pub fn tokens<'a>(&self, sentence: &'a str) -> Vec<&'a str> {
+        match self {
+            Tokenizer::Unicode => sentence.unicode_words().collect(),
+            Tokenizer::Whitespace => sentence.split_whitespace().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_word_boundaries_or_whitespace() {
+        let sentence = "\"Don't go,\" she said, 3.5 miles away.";
+        assert_eq!(
+            Tokenizer::Unicode.tokens(sentence),
+            ["Don't", "go", "she", "said", "3.5", "miles", "away"]
+        );
+        assert_eq!(
+            Tokenizer::Whitespace.tokens(sentence),
+            ["\"Don't", "go,\"", "she", "said,", "3.5", "miles", "away."]
+        );
+        assert_eq!("WHITESPACE".parse(), Ok(Tokenizer::Whitespace));
+        assert!("spaces".parse::<Tokenizer>().is_err());
+    }
+}
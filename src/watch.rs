@@ -0,0 +1,169 @@
+//! Watching a drop directory for files to ingest as they arrive.
+//!
+//! The directory is polled rather than subscribed to with `notify`. Drop
+//! directories are often NFS or SMB mounts, where inotify and its kin,
+//! which `notify` watches with by default, see nothing of files written by
+//! other hosts, so they have to be polled regardless. A scan every
+//! `--poll-interval-ms` of a directory that is mostly empty costs little,
+//! and keeps the build free of a watcher crate and its per-platform
+//! backends.
+//!
+//! The wait between polls is also the debounce: a file is picked up once
+//! its size and modification time have stayed the same over two polls, so
+//! one still being copied in isn't read halfway, however many writes the
+//! copy takes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use mongodb::bson::DateTime;
+
+use crate::manifest::FileState;
+use crate::BoxError;
+
+/// A directory files are dropped into, with the files seen in it so far.
+pub struct DropDirectory {
+    dir: PathBuf,
+    /// Where files are moved once ingested, if they are.
+    archive: Option<PathBuf>,
+    /// Files handed out by [`poll`](Self::poll), as they were then.
+    seen: HashMap<PathBuf, FileState>,
+    /// Files new or changed at the last poll, as they were, to be handed out
+    /// if they're still the same at the next.
+    pending: HashMap<PathBuf, FileState>,
+}
+
+impl DropDirectory {
+    /// Watches `dir`, leaving out `archive`, where
+    /// [`archive`](Self::archive) moves files to, if it's inside.
+    pub fn new(dir: impl Into<PathBuf>, archive: Option<&Path>) -> Result<Self, BoxError> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(format!("{} isn't a directory", dir.display()).into());
+        }
+        if let Some(archive) = archive {
+            std::fs::create_dir_all(archive)
+                .map_err(|e| format!("Error creating {}: {}", archive.display(), e))?;
+        }
+        Ok(DropDirectory {
+            dir,
+            archive: archive.map(Path::to_path_buf),
+            seen: HashMap::new(),
+            pending: HashMap::new(),
+        })
+    }
+
+    /// The files, in sorted order, that are new or changed since they were
+    /// last handed out, and have stayed the same since the previous poll.
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>, BoxError> {
+        let archive = match &self.archive {
+            Some(archive) => Some(std::fs::canonicalize(archive)?),
+            None => None,
+        };
+        let mut files = BTreeMap::new();
+        collect_files(&self.dir, archive.as_deref(), &mut files)
+            .map_err(|e| format!("Error reading directory {}: {}", self.dir.display(), e))?;
+
+        self.seen.retain(|path, _| files.contains_key(path));
+        let mut ready = Vec::new();
+        let mut pending = HashMap::new();
+        for (path, state) in files {
+            if self.seen.get(&path) == Some(&state) {
+                continue;
+            }
+            if self.pending.get(&path) == Some(&state) {
+                self.seen.insert(path.clone(), state);
+                ready.push(path);
+            } else {
+                pending.insert(path, state);
+            }
+        }
+        self.pending = pending;
+        Ok(ready)
+    }
+
+    /// Moves `path`, a file in the directory, to the same place under the
+    /// archive directory, if there is one, and returns where it went.
+    pub fn archive(&mut self, path: &Path) -> Result<Option<PathBuf>, BoxError> {
+        let Some(archive) = &self.archive else {
+            return Ok(None);
+        };
+        let target = archive.join(path.strip_prefix(&self.dir).unwrap_or(path));
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // A rename can't cross filesystems; a copy can
+        if std::fs::rename(path, &target).is_err() {
+            std::fs::copy(path, &target)
+                .map_err(|e| format!("Error archiving {}: {}", path.display(), e))?;
+            std::fs::remove_file(path)?;
+        }
+        self.seen.remove(path);
+        Ok(Some(target))
+    }
+}
+
+/// Adds each file under `dir` with its state to `files`, skipping hidden
+/// ones, which are often uploads in progress, and the `archive` directory.
+fn collect_files(
+    dir: &Path,
+    archive: Option<&Path>,
+    files: &mut BTreeMap<PathBuf, FileState>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        // Files can go away between being listed and looked at
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if archive.is_none() || std::fs::canonicalize(&path).ok().as_deref() != archive {
+                collect_files(&path, archive, files)?;
+            }
+        } else if metadata.is_file() {
+            let state = FileState {
+                size: metadata.len(),
+                modified: DateTime::from_system_time(metadata.modified()?),
+            };
+            files.insert(path, state);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_files_once_they_stop_changing() {
+        let root = std::env::temp_dir().join("rustparser_drop_directory_test");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("news")).unwrap();
+        let archive = root.join("done");
+        let mut drop = DropDirectory::new(&root, Some(&archive)).unwrap();
+
+        let file = root.join("news/a.txt");
+        std::fs::write(&file, "It rained.\n").unwrap();
+        std::fs::write(root.join(".b.txt.part"), "Half").unwrap();
+        assert!(drop.poll().unwrap().is_empty());
+        assert_eq!(drop.poll().unwrap(), vec![file.clone()]);
+        assert!(drop.poll().unwrap().is_empty());
+
+        std::fs::write(&file, "It rained.\nIt poured.\n").unwrap();
+        assert!(drop.poll().unwrap().is_empty());
+        assert_eq!(drop.poll().unwrap(), vec![file.clone()]);
+
+        let archived = drop.archive(&file).unwrap().unwrap();
+        assert_eq!(archived, archive.join("news/a.txt"));
+        assert!(archived.is_file() && !file.exists());
+        assert!(drop.poll().unwrap().is_empty());
+        assert!(drop.poll().unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+//! The sentence splitter and line cleaning of rustparser for the browser,
+//! built on the crate without its `ingest` feature, so an annotation tool
+//! splits text exactly as ingestion did.
+//!
+//! ```js
+//! import init, { cleanLine, splitIntoSentences, Segmenter } from "./pkg/rustparser_wasm.js";
+//!
+//! await init();
+//! splitIntoSentences(cleanLine("“The cat sat down.” Then it slept all day."));
+//! // ["The cat sat down.", "Then it slept all day."]
+//!
+//! const segmenter = new Segmenter("unicode");
+//! segmenter.minWords = 2;
+//! segmenter.split("Go home. Then sleep.");
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use rustparser::clean::{
+    normalize_invisible, normalize_unicode, CleaningPreset, InvisibleChars, UnicodeForm,
+};
+use rustparser::segment::SegmenterKind;
+use rustparser::ValidityRules;
+
+/// Parses an option given as a string the way the command line does, or
+/// takes `default` when it's left out.
+fn parse<T: std::str::FromStr<Err = String>>(
+    value: Option<String>,
+    default: &str,
+) -> Result<T, JsError> {
+    value
+        .as_deref()
+        .unwrap_or(default)
+        .parse()
+        .map_err(|e: String| JsError::new(&e))
+}
+
+/// Splits `text` into sentences with the built-in rules and the default
+/// validity rules of `rustparser ingest`.
+#[wasm_bindgen(js_name = splitIntoSentences)]
+pub fn split_into_sentences(text: &str) -> Vec<String> {
+    rustparser::segment::split_into_sentences(text, &ValidityRules::default())
+}
+
+/// Cleans a line as ingestion does before splitting it: invisible
+/// characters, then Unicode normalization, then the cleaning preset's steps.
+/// The options default as they do for `rustparser ingest`.
+#[wasm_bindgen(js_name = cleanLine)]
+pub fn clean_line(
+    text: &str,
+    preset: Option<String>,
+    invisible_chars: Option<String>,
+    unicode_form: Option<String>,
+    fold_quotes: Option<bool>,
+) -> Result<String, JsError> {
+    let pipeline = parse::<CleaningPreset>(preset, "strict")?.pipeline();
+    let invisible: InvisibleChars = parse(invisible_chars, "normalize")?;
+    let form: UnicodeForm = parse(unicode_form, "none")?;
+    let fold_quotes = fold_quotes.unwrap_or(false);
+    let mut line = normalize_invisible(text, invisible);
+    if form != UnicodeForm::None || fold_quotes {
+        line = normalize_unicode(&line, form, fold_quotes);
+    }
+    Ok(pipeline.apply(&line).trim().to_string())
+}
+
+/// A segmenter with validity rules of its own, set as properties.
+#[wasm_bindgen]
+pub struct Segmenter {
+    kind: SegmenterKind,
+    rules: ValidityRules,
+}
+
+#[wasm_bindgen]
+impl Segmenter {
+    /// A segmenter of `kind` (rules, unicode or cjk; rules if left out) with
+    /// the default validity rules.
+    #[wasm_bindgen(constructor)]
+    pub fn new(kind: Option<String>) -> Result<Segmenter, JsError> {
+        Ok(Segmenter {
+            kind: parse(kind, "rules")?,
+            rules: ValidityRules::default(),
+        })
+    }
+
+    #[wasm_bindgen(setter = minWords)]
+    pub fn set_min_words(&mut self, min_words: usize) {
+        self.rules.min_words = min_words;
+    }
+
+    #[wasm_bindgen(setter = minChars)]
+    pub fn set_min_chars(&mut self, min_chars: usize) {
+        self.rules.min_chars = min_chars;
+    }
+
+    #[wasm_bindgen(setter = maxWords)]
+    pub fn set_max_words(&mut self, max_words: Option<usize>) {
+        self.rules.max_words = max_words;
+    }
+
+    #[wasm_bindgen(setter = maxChars)]
+    pub fn set_max_chars(&mut self, max_chars: Option<usize>) {
+        self.rules.max_chars = max_chars;
+    }
+
+    #[wasm_bindgen(setter = maxDigitRatio)]
+    pub fn set_max_digit_ratio(&mut self, max_digit_ratio: Option<f64>) {
+        self.rules.max_digit_ratio = max_digit_ratio;
+    }
+
+    #[wasm_bindgen(setter = rejectNumberUnits)]
+    pub fn set_reject_number_units(&mut self, reject_number_units: bool) {
+        self.rules.reject_number_units = reject_number_units;
+    }
+
+    pub fn split(&self, text: &str) -> Vec<String> {
+        rustparser::Segmenter::with_backend(self.rules.clone(), self.kind.backend()).split(text)
+    }
+}